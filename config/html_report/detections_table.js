@@ -0,0 +1,229 @@
+// Detection table of the Hayabusa HTML report (--html-interactive).
+// Reads the JSON embedded in #detections-data and renders a sortable, filterable and paginated
+// table into #detections-app. Values are only ever inserted with textContent so that field data
+// from event logs cannot inject markup into the report.
+(function () {
+  "use strict";
+
+  var PAGE_SIZE = 100;
+  var LEVEL_ORDER = ["emergency", "critical", "high", "medium", "low", "informational"];
+
+  var dataElement = document.getElementById("detections-data");
+  var app = document.getElementById("detections-app");
+  if (!dataElement || !app) {
+    return;
+  }
+  var data = JSON.parse(dataElement.textContent);
+  var columns = data.columns;
+  var rows = data.rows;
+
+  var state = {
+    sortColumn: -1,
+    sortAscending: true,
+    columnFilters: columns.map(function () {
+      return "";
+    }),
+    levels: {},
+    computers: {},
+    page: 0,
+  };
+
+  function element(tag, className, text) {
+    var e = document.createElement(tag);
+    if (className) {
+      e.className = className;
+    }
+    if (text !== undefined) {
+      e.textContent = text;
+    }
+    return e;
+  }
+
+  function countBy(key) {
+    var counts = {};
+    rows.forEach(function (row) {
+      counts[row[key]] = (counts[row[key]] || 0) + 1;
+    });
+    return counts;
+  }
+
+  function levelRank(level) {
+    var rank = LEVEL_ORDER.indexOf(level);
+    return rank === -1 ? LEVEL_ORDER.length : rank;
+  }
+
+  // A facet with nothing checked does not filter anything.
+  function facetAllows(selected, value) {
+    var keys = Object.keys(selected).filter(function (k) {
+      return selected[k];
+    });
+    return keys.length === 0 || selected[value] === true;
+  }
+
+  function filteredRows() {
+    var filters = state.columnFilters.map(function (f) {
+      return f.toLowerCase();
+    });
+    var result = rows.filter(function (row) {
+      if (!facetAllows(state.levels, row.level) || !facetAllows(state.computers, row.computer)) {
+        return false;
+      }
+      return filters.every(function (filter, i) {
+        return filter === "" || String(row.values[i]).toLowerCase().indexOf(filter) !== -1;
+      });
+    });
+    if (state.sortColumn !== -1) {
+      var i = state.sortColumn;
+      var isLevel = /level/i.test(columns[i]);
+      result.sort(function (a, b) {
+        var cmp = isLevel
+          ? levelRank(a.level) - levelRank(b.level)
+          : String(a.values[i]).localeCompare(String(b.values[i]), undefined, { numeric: true });
+        return state.sortAscending ? cmp : -cmp;
+      });
+    }
+    return result;
+  }
+
+  function renderFacet(container, title, counts, selected, order) {
+    container.appendChild(element("h4", null, title));
+    order(Object.keys(counts)).forEach(function (value) {
+      var label = element("label");
+      var checkbox = element("input");
+      checkbox.type = "checkbox";
+      checkbox.checked = selected[value] === true;
+      checkbox.addEventListener("change", function () {
+        selected[value] = checkbox.checked;
+        state.page = 0;
+        renderBody();
+      });
+      label.appendChild(checkbox);
+      label.appendChild(document.createTextNode(" " + value + " (" + counts[value] + ")"));
+      label.title = value;
+      container.appendChild(label);
+    });
+  }
+
+  function fieldInfoRow(row) {
+    var tr = element("tr", "field-info");
+    var td = element("td");
+    td.colSpan = columns.length;
+    var dl = element("dl");
+    String(row.allFieldInfo)
+      .split(" ¦ ")
+      .forEach(function (field) {
+        var separator = field.indexOf(": ");
+        dl.appendChild(element("dt", null, separator === -1 ? "" : field.slice(0, separator)));
+        dl.appendChild(element("dd", null, separator === -1 ? field : field.slice(separator + 2)));
+      });
+    td.appendChild(dl);
+    tr.appendChild(td);
+    return tr;
+  }
+
+  var tbody = element("tbody");
+  var pager = element("div", "pager");
+
+  function renderBody() {
+    var result = filteredRows();
+    var pageCount = Math.max(1, Math.ceil(result.length / PAGE_SIZE));
+    state.page = Math.min(state.page, pageCount - 1);
+    tbody.textContent = "";
+    result.slice(state.page * PAGE_SIZE, (state.page + 1) * PAGE_SIZE).forEach(function (row) {
+      var tr = element("tr", "level-" + row.level);
+      row.values.forEach(function (value) {
+        tr.appendChild(element("td", null, value));
+      });
+      var details = null;
+      tr.addEventListener("click", function () {
+        if (details) {
+          details.remove();
+          details = null;
+        } else {
+          details = fieldInfoRow(row);
+          tr.after(details);
+        }
+      });
+      tbody.appendChild(tr);
+    });
+
+    pager.textContent = "";
+    var previous = element("button", null, "<");
+    previous.disabled = state.page === 0;
+    previous.addEventListener("click", function () {
+      state.page -= 1;
+      renderBody();
+    });
+    var next = element("button", null, ">");
+    next.disabled = state.page >= pageCount - 1;
+    next.addEventListener("click", function () {
+      state.page += 1;
+      renderBody();
+    });
+    pager.appendChild(previous);
+    pager.appendChild(
+      element(
+        "span",
+        null,
+        "Page " + (state.page + 1) + " / " + pageCount + " (" + result.length + " of " + rows.length + " detections)"
+      )
+    );
+    pager.appendChild(next);
+  }
+
+  function render() {
+    var facets = element("div", "facets");
+    renderFacet(facets, "Level", countBy("level"), state.levels, function (keys) {
+      return keys.sort(function (a, b) {
+        return levelRank(a) - levelRank(b);
+      });
+    });
+    var computerCounts = countBy("computer");
+    renderFacet(facets, "Computer", computerCounts, state.computers, function (keys) {
+      return keys.sort(function (a, b) {
+        return computerCounts[b] - computerCounts[a] || a.localeCompare(b);
+      });
+    });
+
+    var table = element("table");
+    var thead = element("thead");
+    var titles = element("tr");
+    var filters = element("tr");
+    columns.forEach(function (column, i) {
+      var th = element("th", null, column);
+      th.addEventListener("click", function () {
+        state.sortAscending = state.sortColumn === i ? !state.sortAscending : true;
+        state.sortColumn = i;
+        renderBody();
+      });
+      titles.appendChild(th);
+      var filterCell = element("th");
+      var input = element("input");
+      input.type = "search";
+      input.placeholder = "Filter";
+      input.addEventListener("input", function () {
+        state.columnFilters[i] = input.value;
+        state.page = 0;
+        renderBody();
+      });
+      filterCell.appendChild(input);
+      filters.appendChild(filterCell);
+    });
+    thead.appendChild(titles);
+    thead.appendChild(filters);
+    table.appendChild(thead);
+    table.appendChild(tbody);
+
+    var tableArea = element("div", "table-area");
+    var wrapper = element("div", "table-wrapper");
+    wrapper.appendChild(table);
+    tableArea.appendChild(pager);
+    tableArea.appendChild(wrapper);
+
+    app.appendChild(facets);
+    app.appendChild(tableArea);
+    renderBody();
+  }
+
+  render();
+})();
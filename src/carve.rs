@@ -0,0 +1,365 @@
+use std::fs::File;
+use std::io::{self, ErrorKind, Read};
+use std::path::Path;
+use std::sync::Arc;
+
+use evtx::{EvtxChunkData, ParserSettings};
+use hashbrown::HashSet;
+use memchr::memmem;
+use serde_json::Value;
+
+const EVTX_CHUNK_SIGNATURE: &[u8] = b"ElfChnk\0";
+const EVTX_RECORD_SIGNATURE: &[u8] = b"**\0\0";
+const EVTX_CHUNK_SIZE: usize = 65536;
+const EVTX_CHUNK_HEADER_SIZE: usize = 512;
+// Smallest record: the 24-byte header followed by the 4-byte size copy.
+const EVTX_MIN_RECORD_SIZE: usize = 28;
+// The raw file is read in blocks of this size. The unscanned tail of a block is kept so that
+// chunks crossing two blocks are still found.
+const READ_SIZE: usize = 16 * 1024 * 1024;
+
+/// An evtx chunk carved from a raw file, with its records converted to JSON.
+#[derive(Debug, Clone)]
+pub struct CarvedChunk {
+    /// Offset of the chunk in the raw file.
+    pub offset: u64,
+    pub records: Vec<Value>,
+    /// Errors of the records in the chunk that could not be parsed.
+    pub errors: Vec<String>,
+    /// The checksum of the chunk is wrong, so only the records following each other from the
+    /// chunk header were parsed.
+    pub damaged: bool,
+}
+
+/// What was found while carving a raw file.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CarveStats {
+    pub chunks: usize,
+    /// Chunks with a wrong header checksum (or event data checksum with -V) whose records were
+    /// still parsed. These chunks are also counted in `chunks`.
+    pub damaged_chunks: usize,
+    /// Records of the damaged chunks that could not be parsed.
+    pub unparsed_records: usize,
+    /// Chunk signatures with a wrong checksum and no records after the header, or cut off at
+    /// the end of the file.
+    pub invalid_chunks: usize,
+    /// Chunks with the same header as a chunk carved before, such as a page that is both in
+    /// memory and in the pagefile.
+    pub duplicate_chunks: usize,
+    /// Record signatures outside of the carved chunks. These records cannot be parsed as their
+    /// strings and templates are in the chunk they came from.
+    pub orphan_records: usize,
+}
+
+enum Carved {
+    /// The chunk and the number of bytes of the raw file it takes up.
+    Chunk(CarvedChunk, usize),
+    Duplicate,
+    Invalid,
+}
+
+/// Scans a raw file (disk image, unallocated space, pagefile, hiberfil, memory dump) for evtx
+/// chunk signatures and returns the records of each chunk, in file order.
+pub struct ChunkCarver {
+    file: File,
+    buf: Vec<u8>,
+    // Offset in the raw file of the first byte of `buf`.
+    buf_offset: u64,
+    // Position in `buf` up to which the data has been scanned.
+    pos: usize,
+    eof: bool,
+    settings: Arc<ParserSettings>,
+    validate_checksums: bool,
+    seen_headers: HashSet<Vec<u8>>,
+    pub stats: CarveStats,
+}
+
+impl ChunkCarver {
+    pub fn open(
+        path: &Path,
+        settings: ParserSettings,
+        validate_checksums: bool,
+    ) -> io::Result<ChunkCarver> {
+        Ok(ChunkCarver {
+            file: File::open(path)?,
+            buf: vec![],
+            buf_offset: 0,
+            pos: 0,
+            eof: false,
+            settings: Arc::new(settings),
+            validate_checksums,
+            seen_headers: HashSet::new(),
+            stats: CarveStats::default(),
+        })
+    }
+
+    /// Drops the buffer before `keep` and appends the next block of the file.
+    fn refill(&mut self, keep: usize) -> io::Result<()> {
+        self.buf.drain(..keep);
+        self.buf_offset += keep as u64;
+        self.pos -= keep;
+        let mut filled = self.buf.len();
+        self.buf.resize(filled + READ_SIZE, 0);
+        while filled < self.buf.len() {
+            match self.file.read(&mut self.buf[filled..]) {
+                Ok(0) => {
+                    self.eof = true;
+                    break;
+                }
+                Ok(n) => filled += n,
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => {
+                    self.buf.truncate(filled);
+                    return Err(err);
+                }
+            }
+        }
+        self.buf.truncate(filled);
+        Ok(())
+    }
+
+    /// Counts the record signatures starting between `from` and `to` in the buffer.
+    fn count_orphan_records(&mut self, from: usize, to: usize) {
+        let end = (to + EVTX_RECORD_SIGNATURE.len() - 1).min(self.buf.len());
+        self.stats.orphan_records += memmem::find_iter(&self.buf[from..end], EVTX_RECORD_SIGNATURE)
+            .map(|idx| from + idx)
+            .filter(|&start| start < to && record_size(&self.buf, start).is_some())
+            .count();
+    }
+
+    fn carve_chunk(&mut self, start: usize) -> Carved {
+        let data = self.buf[start..start + EVTX_CHUNK_SIZE].to_vec();
+        let Ok(mut chunk_data) = EvtxChunkData::new(data, false) else {
+            return Carved::Invalid;
+        };
+        let damaged = !chunk_data.validate_header_checksum()
+            || (self.validate_checksums && !chunk_data.validate_data_checksum());
+        let (record_cnt, len) = if damaged {
+            // The header cannot be trusted, so the records are read up to the first one whose
+            // size is not repeated at its end, and the rest of the chunk is scanned again.
+            let (record_cnt, records_end) =
+                consistent_records(&self.buf[start..start + EVTX_CHUNK_SIZE]);
+            if record_cnt == 0 {
+                return Carved::Invalid;
+            }
+            chunk_data.header.free_space_offset = records_end as u32;
+            (record_cnt, records_end)
+        } else {
+            (0, EVTX_CHUNK_SIZE)
+        };
+        if !self
+            .seen_headers
+            .insert(self.buf[start..start + EVTX_CHUNK_HEADER_SIZE].to_vec())
+        {
+            return Carved::Duplicate;
+        }
+        let mut chunk = CarvedChunk {
+            offset: self.buf_offset + start as u64,
+            records: vec![],
+            errors: vec![],
+            damaged,
+        };
+        match chunk_data.parse(Arc::clone(&self.settings)) {
+            Ok(mut parsed) => {
+                for record in parsed.iter() {
+                    match record.and_then(|record| record.into_json_value()) {
+                        Ok(record) => chunk.records.push(record.data),
+                        Err(err) => chunk.errors.push(err.to_string()),
+                    }
+                }
+            }
+            Err(err) if damaged => chunk.errors.push(err.to_string()),
+            Err(_) => return Carved::Invalid,
+        }
+        if damaged && chunk.records.len() < record_cnt {
+            let unparsed = record_cnt - chunk.records.len();
+            self.stats.unparsed_records += unparsed;
+            chunk.errors.push(format!(
+                "{unparsed} of the {record_cnt} records of the damaged chunk could not be parsed."
+            ));
+        }
+        Carved::Chunk(chunk, len)
+    }
+}
+
+impl Iterator for ChunkCarver {
+    type Item = io::Result<CarvedChunk>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let found =
+                memmem::find(&self.buf[self.pos..], EVTX_CHUNK_SIGNATURE).map(|idx| self.pos + idx);
+            let keep = match found {
+                Some(start) if start + EVTX_CHUNK_SIZE <= self.buf.len() => {
+                    self.count_orphan_records(self.pos, start);
+                    match self.carve_chunk(start) {
+                        Carved::Chunk(chunk, len) => {
+                            self.stats.chunks += 1;
+                            if chunk.damaged {
+                                self.stats.damaged_chunks += 1;
+                            }
+                            self.pos = start + len;
+                            return Some(Ok(chunk));
+                        }
+                        Carved::Duplicate => {
+                            self.stats.duplicate_chunks += 1;
+                            self.pos = start + EVTX_CHUNK_SIZE;
+                        }
+                        Carved::Invalid => {
+                            self.stats.invalid_chunks += 1;
+                            self.pos = start + 1;
+                        }
+                    }
+                    continue;
+                }
+                Some(start) if self.eof => {
+                    // The chunk was cut off by the end of the file.
+                    self.count_orphan_records(self.pos, start);
+                    self.stats.invalid_chunks += 1;
+                    self.pos = start + 1;
+                    continue;
+                }
+                // The rest of the chunk is in the next block.
+                Some(start) => start,
+                None if self.eof => {
+                    self.count_orphan_records(self.pos, self.buf.len());
+                    self.pos = self.buf.len();
+                    return None;
+                }
+                // Keep the bytes that may be the start of a signature.
+                None => self
+                    .buf
+                    .len()
+                    .saturating_sub(EVTX_CHUNK_SIGNATURE.len() - 1)
+                    .max(self.pos),
+            };
+            self.count_orphan_records(self.pos, keep);
+            self.pos = keep;
+            if let Err(err) = self.refill(keep) {
+                self.eof = true;
+                return Some(Err(err));
+            }
+        }
+    }
+}
+
+/// Returns the size of the record at `start` when its size is repeated at the end of the record.
+fn record_size(buf: &[u8], start: usize) -> Option<usize> {
+    let read_u32 = |offset: usize| {
+        buf.get(offset..offset + 4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()) as usize)
+    };
+    let size = read_u32(start + 4)?;
+    ((EVTX_MIN_RECORD_SIZE..=EVTX_CHUNK_SIZE - EVTX_CHUNK_HEADER_SIZE).contains(&size)
+        && read_u32(start + size - 4) == Some(size))
+    .then_some(size)
+}
+
+/// Returns the number of records that follow each other from the end of the chunk header, and
+/// the offset in the chunk where the last one ends.
+fn consistent_records(chunk: &[u8]) -> (usize, usize) {
+    let mut record_cnt = 0;
+    let mut end = EVTX_CHUNK_HEADER_SIZE;
+    while chunk[end..].starts_with(EVTX_RECORD_SIGNATURE) {
+        let Some(size) = record_size(chunk, end) else {
+            break;
+        };
+        record_cnt += 1;
+        end += size;
+    }
+    (record_cnt, end)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    fn record(size: usize) -> Vec<u8> {
+        let mut rec = EVTX_RECORD_SIGNATURE.to_vec();
+        rec.extend((size as u32).to_le_bytes());
+        rec.resize(size - 4, 0xAA);
+        rec.extend((size as u32).to_le_bytes());
+        rec
+    }
+
+    #[test]
+    fn test_carve_invalid_chunks_and_orphan_records() {
+        let mut raw = vec![0x11; 1000];
+        raw.extend(record(64));
+        // A record signature without a matching size copy is not counted.
+        raw.extend(EVTX_RECORD_SIGNATURE);
+        raw.extend([0xFF; 100]);
+        // A chunk signature with a zeroed header does not pass the checksum.
+        raw.extend(EVTX_CHUNK_SIGNATURE);
+        raw.resize(raw.len() + EVTX_CHUNK_SIZE, 0);
+        raw.extend(record(128));
+        // A chunk cut off by the end of the file.
+        raw.extend(EVTX_CHUNK_SIGNATURE);
+        raw.extend([0; 100]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("raw.img");
+        File::create(&path).unwrap().write_all(&raw).unwrap();
+        let mut carver = ChunkCarver::open(&path, ParserSettings::default(), false).unwrap();
+        assert!(carver.next().is_none());
+        assert_eq!(carver.stats.chunks, 0);
+        assert_eq!(carver.stats.invalid_chunks, 2);
+        assert_eq!(carver.stats.duplicate_chunks, 0);
+        assert_eq!(carver.stats.orphan_records, 2);
+    }
+
+    #[test]
+    fn test_carve_damaged_chunk() {
+        // A chunk with a zeroed header followed by two records, then an orphan record.
+        let mut raw = EVTX_CHUNK_SIGNATURE.to_vec();
+        raw.resize(EVTX_CHUNK_HEADER_SIZE, 0);
+        raw.extend(record(64));
+        raw.extend(record(96));
+        raw.resize(EVTX_CHUNK_SIZE, 0);
+        raw.extend(record(128));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("raw.img");
+        File::create(&path).unwrap().write_all(&raw).unwrap();
+        let mut carver = ChunkCarver::open(&path, ParserSettings::default(), false).unwrap();
+        let chunk = carver.next().unwrap().unwrap();
+        assert_eq!(chunk.offset, 0);
+        assert!(chunk.damaged);
+        // The record data is not binary XML, so the records are reported as unparsed.
+        assert!(chunk.records.is_empty());
+        assert!(!chunk.errors.is_empty());
+        assert!(carver.next().is_none());
+        assert_eq!(carver.stats.chunks, 1);
+        assert_eq!(carver.stats.damaged_chunks, 1);
+        assert_eq!(carver.stats.unparsed_records, 2);
+        assert_eq!(carver.stats.invalid_chunks, 0);
+        assert_eq!(carver.stats.orphan_records, 1);
+    }
+
+    #[test]
+    fn test_record_size() {
+        let rec = record(EVTX_MIN_RECORD_SIZE);
+        assert_eq!(record_size(&rec, 0), Some(EVTX_MIN_RECORD_SIZE));
+        assert_eq!(record_size(&rec[..rec.len() - 1], 0), None);
+        assert_eq!(record_size(&record(64)[..8], 0), None);
+    }
+
+    #[test]
+    fn test_consistent_records() {
+        let mut chunk = vec![0; EVTX_CHUNK_HEADER_SIZE];
+        chunk.extend(record(64));
+        chunk.extend(record(32));
+        // The size copy of the third record is wrong.
+        let mut broken = record(48);
+        broken[44] = 0;
+        chunk.extend(broken);
+        chunk.extend(record(64));
+        assert_eq!(consistent_records(&chunk), (2, EVTX_CHUNK_HEADER_SIZE + 96));
+        assert_eq!(
+            consistent_records(&[0; EVTX_CHUNK_HEADER_SIZE]),
+            (0, EVTX_CHUNK_HEADER_SIZE)
+        );
+    }
+}
@@ -4,10 +4,16 @@ use crate::detections::field_data_map::{FieldDataMap, create_field_data_map};
 use crate::detections::message::AlertMessage;
 use crate::detections::utils;
 use crate::level::LEVEL;
+use crate::options::attack_navigator::AttackNavigatorMap;
 use crate::options::geoip_search::GeoIPSearch;
 use crate::options::htmlreport;
+use crate::options::identity_snapshot::IdentitySnapshot;
+use crate::options::message_templates::MessageTemplates;
 use crate::options::pivot::PivotKeywordMap;
 use crate::options::profile::{Profile, load_profile};
+use crate::options::risk_score::{RiskScoreMap, RiskWeights};
+use crate::timeline::logon_sessions::LogonSessions;
+use crate::timeline::process_tree::ProcessTree;
 use aho_corasick::{AhoCorasick, AhoCorasickBuilder, MatchKind};
 use chrono::{DateTime, Days, Duration, Local, Months, Utc};
 use clap::{
@@ -125,6 +131,22 @@ pub struct StoredStatic {
     pub geo_ip_db_yaml: Option<HashMap<CompactString, Yaml>>,
     /// The geoip filter config (channel/EID conditions), set only when `--geo-ip` is used.
     pub geo_ip_filter: Option<Vec<Yaml>>,
+    /// Accounts loaded from `--identity-snapshot` for the `%Identity*%` profile columns.
+    pub identity_snapshot: Option<Arc<IdentitySnapshot>>,
+    /// Message templates loaded from `--message-db`, used for `%RenderedMessage%` when a record
+    /// has no `RenderingInfo`.
+    pub message_templates: Option<Arc<MessageTemplates>>,
+    /// Process tree for the `%ProcessAncestry%` profile column, set only when the profile uses
+    /// it. Filled batch by batch before the rules run, so a detection's ancestry covers the
+    /// process events scanned up to that point. Shared with the per-scan snapshot like
+    /// `pivot_keyword`.
+    pub process_tree: Option<Arc<RwLock<ProcessTree>>>,
+    /// Logon sessions for the `%Session*%` profile columns, set only when the profile uses them.
+    /// Filled in the same way as `process_tree`.
+    pub logon_sessions: Option<Arc<RwLock<LogonSessions>>>,
+    /// Whether alerts involving privileged snapshot accounts are raised one level
+    /// (`--escalate-privileged`).
+    pub escalate_privileged: bool,
     pub channel_abbr_config: HashMap<CompactString, CompactString>,
     pub generic_abbr_matcher: AhoCorasick,
     pub generic_abbr_values: Vec<CompactString>,
@@ -136,6 +158,12 @@ pub struct StoredStatic {
     pub search_flag: bool,
     pub computer_metrics_flag: bool,
     pub log_metrics_flag: bool,
+    pub event_anomalies_flag: bool,
+    pub rare_artifacts_flag: bool,
+    pub process_tree_flag: bool,
+    pub sessions_flag: bool,
+    pub coverage_flag: bool,
+    pub convert_flag: bool,
     pub extract_base64_flag: bool,
     pub search_option: Option<SearchOption>,
     pub output_option: Option<OutputOption>,
@@ -160,14 +188,28 @@ pub struct StoredStatic {
     /// alert count from its total. `Arc<DashSet<..>>` for the same reason as `computer_mitre_attck_map`;
     /// replaces the former `COMPUTER_MITRE_ATTCK_UNIQUE_KEYS` global.
     pub computer_mitre_attck_unique_keys: Arc<DashSet<CompactString>>,
+    /// Detection counts and highest levels per ATT&CK technique for `--attack-navigator`, counted
+    /// when the results are output so that removed duplicates are not included. Only filled when
+    /// `attack_navigator_flag` is set.
+    pub attack_navigator_map: Arc<AttackNavigatorMap>,
+    pub attack_navigator_flag: bool,
+    /// Risk points per computer and account for `--risk-scoring`, added together with
+    /// `attack_navigator_map` when the results are output. Only filled when `risk_weights` is set.
+    pub risk_score_map: Arc<RiskScoreMap>,
+    /// Weights loaded from risk_scoring.yaml, set only when risk scoring is enabled.
+    pub risk_weights: Option<Arc<RiskWeights>>,
     pub default_details: HashMap<CompactString, CompactString>,
     pub html_report_flag: bool,
+    /// Whether the detections are embedded in the HTML report (`--html-interactive`). Detections
+    /// then keep their AllFieldInfo even when the profile does not output it.
+    pub html_interactive_flag: bool,
     pub profiles: Option<Vec<(CompactString, Profile)>>,
     pub event_timeline_config: EventInfoConfig,
     pub target_eventids: TargetIds,
     pub target_ruleids: TargetIds,
     pub thread_number: Option<usize>,
     pub json_input_flag: bool,
+    pub carve_flag: bool,
     pub output_path: Option<PathBuf>,
     pub common_options: CommonOptions,
     pub multiline_flag: bool,
@@ -235,6 +277,12 @@ impl StoredStatic {
             Some(Action::Search(opt)) => opt.quiet_errors,
             Some(Action::ComputerMetrics(opt)) => opt.quiet_errors,
             Some(Action::LogMetrics(opt)) => opt.detect_common_options.quiet_errors,
+            Some(Action::EventAnomalies(opt)) => opt.detect_common_options.quiet_errors,
+            Some(Action::RareArtifacts(opt)) => opt.detect_common_options.quiet_errors,
+            Some(Action::ProcessTree(opt)) => opt.detect_common_options.quiet_errors,
+            Some(Action::Sessions(opt)) => opt.detect_common_options.quiet_errors,
+            Some(Action::Coverage(opt)) => opt.detect_common_options.quiet_errors,
+            Some(Action::Convert(opt)) => opt.detect_common_options.quiet_errors,
             _ => false,
         };
         let common_options = match action {
@@ -250,8 +298,15 @@ impl StoredStatic {
             Some(Action::Search(opt)) => opt.common_options,
             Some(Action::ComputerMetrics(opt)) => opt.common_options,
             Some(Action::LogMetrics(opt)) => opt.common_options,
+            Some(Action::EventAnomalies(opt)) => opt.common_options,
+            Some(Action::RareArtifacts(opt)) => opt.common_options,
+            Some(Action::ProcessTree(opt)) => opt.common_options,
+            Some(Action::Sessions(opt)) => opt.common_options,
+            Some(Action::Coverage(opt)) => opt.common_options,
+            Some(Action::Convert(opt)) => opt.common_options,
             Some(Action::ExpandList(opt)) => opt.common_options,
             Some(Action::ConfigCriticalSystems(opt)) => opt.common_options,
+            Some(Action::DiffResults(opt)) => opt.common_options,
             None => CommonOptions {
                 no_color: false,
                 quiet: false,
@@ -268,6 +323,12 @@ impl StoredStatic {
             Some(Action::Search(opt)) => &opt.config,
             Some(Action::ComputerMetrics(opt)) => &opt.config,
             Some(Action::LogMetrics(opt)) => &opt.detect_common_options.config,
+            Some(Action::EventAnomalies(opt)) => &opt.detect_common_options.config,
+            Some(Action::RareArtifacts(opt)) => &opt.detect_common_options.config,
+            Some(Action::ProcessTree(opt)) => &opt.detect_common_options.config,
+            Some(Action::Sessions(opt)) => &opt.detect_common_options.config,
+            Some(Action::Coverage(opt)) => &opt.detect_common_options.config,
+            Some(Action::Convert(opt)) => &opt.detect_common_options.config,
             _ => &binding,
         };
         let verbose_flag = match action {
@@ -279,6 +340,12 @@ impl StoredStatic {
             Some(Action::Search(opt)) => opt.verbose,
             Some(Action::ComputerMetrics(opt)) => opt.verbose,
             Some(Action::LogMetrics(opt)) => opt.detect_common_options.verbose,
+            Some(Action::EventAnomalies(opt)) => opt.detect_common_options.verbose,
+            Some(Action::RareArtifacts(opt)) => opt.detect_common_options.verbose,
+            Some(Action::ProcessTree(opt)) => opt.detect_common_options.verbose,
+            Some(Action::Sessions(opt)) => opt.detect_common_options.verbose,
+            Some(Action::Coverage(opt)) => opt.detect_common_options.verbose,
+            Some(Action::Convert(opt)) => opt.detect_common_options.verbose,
             _ => false,
         };
         let json_input_flag = match action {
@@ -289,6 +356,12 @@ impl StoredStatic {
             Some(Action::PivotKeywordsList(opt)) => opt.detect_common_options.json_input,
             Some(Action::ComputerMetrics(opt)) => opt.json_input,
             Some(Action::LogMetrics(opt)) => opt.detect_common_options.json_input,
+            Some(Action::EventAnomalies(opt)) => opt.detect_common_options.json_input,
+            Some(Action::RareArtifacts(opt)) => opt.detect_common_options.json_input,
+            Some(Action::ProcessTree(opt)) => opt.detect_common_options.json_input,
+            Some(Action::Sessions(opt)) => opt.detect_common_options.json_input,
+            Some(Action::Coverage(opt)) => opt.detect_common_options.json_input,
+            Some(Action::Convert(opt)) => opt.detect_common_options.json_input,
             _ => false,
         };
         let is_valid_min_level = match action {
@@ -399,6 +472,38 @@ impl StoredStatic {
             }
             geo_ip_db_yaml = Some(static_geoip_conf);
         };
+        let identity_snapshot = match action {
+            Some(Action::DfirTimeline(opt)) => opt.identity_snapshot.as_ref(),
+            _ => None,
+        }
+        .map(|path| match IdentitySnapshot::load(path) {
+            Ok(snapshot) => Arc::new(snapshot),
+            Err(e) => {
+                AlertMessage::alert(&format!("Failed to load the identity snapshot. {e}")).ok();
+                process::exit(1);
+            }
+        });
+        let message_templates = match action {
+            Some(Action::DfirTimeline(opt)) => opt.message_db.as_ref(),
+            _ => None,
+        }
+        .map(|path| match MessageTemplates::load(path) {
+            Ok(templates) => Arc::new(templates),
+            Err(e) => {
+                AlertMessage::alert(&format!("Failed to load the message database. {e}")).ok();
+                process::exit(1);
+            }
+        });
+        let escalate_privileged =
+            matches!(action, Some(Action::DfirTimeline(opt)) if opt.escalate_privileged);
+        let risk_weights = match action {
+            Some(Action::DfirTimeline(opt))
+                if opt.output_options.risk_scoring || opt.output_options.risk_output.is_some() =>
+            {
+                Some(Arc::new(RiskWeights::load(config_path)))
+            }
+            _ => None,
+        };
         let output_path = match action {
             Some(Action::DfirTimeline(opt)) => opt.output.as_ref(),
             Some(Action::EidMetrics(opt)) => opt.output.as_ref(),
@@ -408,6 +513,14 @@ impl StoredStatic {
             Some(Action::Search(opt)) => opt.output.as_ref(),
             Some(Action::ComputerMetrics(opt)) => opt.output.as_ref(),
             Some(Action::LogMetrics(opt)) => opt.output.as_ref(),
+            Some(Action::EventAnomalies(opt)) => opt.output.as_ref(),
+            Some(Action::RareArtifacts(opt)) => opt.output.as_ref(),
+            Some(Action::ProcessTree(opt)) => opt.output.as_ref(),
+            Some(Action::Sessions(opt)) => opt.output.as_ref(),
+            Some(Action::Coverage(opt)) => opt.output.as_ref(),
+            Some(Action::Convert(opt)) => Some(&opt.output),
+            Some(Action::ConfigCriticalSystems(opt)) => opt.output.as_ref(),
+            Some(Action::DiffResults(opt)) => opt.output.as_ref(),
             _ => None,
         };
         let disable_abbreviation = match action {
@@ -471,6 +584,24 @@ impl StoredStatic {
             Some(Action::LogMetrics(opt)) => {
                 compact_string_set(opt.detect_common_options.include_computer.as_ref())
             }
+            Some(Action::EventAnomalies(opt)) => {
+                compact_string_set(opt.detect_common_options.include_computer.as_ref())
+            }
+            Some(Action::RareArtifacts(opt)) => {
+                compact_string_set(opt.detect_common_options.include_computer.as_ref())
+            }
+            Some(Action::ProcessTree(opt)) => {
+                compact_string_set(opt.detect_common_options.include_computer.as_ref())
+            }
+            Some(Action::Sessions(opt)) => {
+                compact_string_set(opt.detect_common_options.include_computer.as_ref())
+            }
+            Some(Action::Coverage(opt)) => {
+                compact_string_set(opt.detect_common_options.include_computer.as_ref())
+            }
+            Some(Action::Convert(opt)) => {
+                compact_string_set(opt.detect_common_options.include_computer.as_ref())
+            }
             _ => HashSet::default(),
         };
         let exclude_computer: HashSet<CompactString> = match action {
@@ -495,6 +626,24 @@ impl StoredStatic {
             Some(Action::LogMetrics(opt)) => {
                 compact_string_set(opt.detect_common_options.exclude_computer.as_ref())
             }
+            Some(Action::EventAnomalies(opt)) => {
+                compact_string_set(opt.detect_common_options.exclude_computer.as_ref())
+            }
+            Some(Action::RareArtifacts(opt)) => {
+                compact_string_set(opt.detect_common_options.exclude_computer.as_ref())
+            }
+            Some(Action::ProcessTree(opt)) => {
+                compact_string_set(opt.detect_common_options.exclude_computer.as_ref())
+            }
+            Some(Action::Sessions(opt)) => {
+                compact_string_set(opt.detect_common_options.exclude_computer.as_ref())
+            }
+            Some(Action::Coverage(opt)) => {
+                compact_string_set(opt.detect_common_options.exclude_computer.as_ref())
+            }
+            Some(Action::Convert(opt)) => {
+                compact_string_set(opt.detect_common_options.exclude_computer.as_ref())
+            }
             _ => HashSet::default(),
         };
         let include_eid: HashSet<CompactString> = match action {
@@ -502,6 +651,7 @@ impl StoredStatic {
                 compact_string_set(opt.output_options.include_eid.as_ref())
             }
             Some(Action::PivotKeywordsList(opt)) => compact_string_set(opt.include_eid.as_ref()),
+            Some(Action::Convert(opt)) => compact_string_set(opt.include_eid.as_ref()),
             _ => HashSet::default(),
         };
         let exclude_eid: HashSet<CompactString> = match action {
@@ -509,10 +659,13 @@ impl StoredStatic {
                 compact_string_set(opt.output_options.exclude_eid.as_ref())
             }
             Some(Action::PivotKeywordsList(opt)) => compact_string_set(opt.exclude_eid.as_ref()),
+            Some(Action::Convert(opt)) => compact_string_set(opt.exclude_eid.as_ref()),
             _ => HashSet::default(),
         };
         let no_field_data_mapping_flag = match action {
             Some(Action::DfirTimeline(opt)) => opt.output_options.no_field,
+            // Raw events are converted as they are unless asked otherwise.
+            Some(Action::Convert(opt)) => !opt.field_data_mapping,
             _ => false,
         };
         let field_data_map = if no_field_data_mapping_flag {
@@ -538,6 +691,12 @@ impl StoredStatic {
             Some(Action::PivotKeywordsList(opt)) => opt.input_args.recover_records,
             Some(Action::Search(opt)) => opt.input_args.recover_records,
             Some(Action::LogMetrics(opt)) => opt.input_args.recover_records,
+            Some(Action::EventAnomalies(opt)) => opt.input_args.recover_records,
+            Some(Action::RareArtifacts(opt)) => opt.input_args.recover_records,
+            Some(Action::ProcessTree(opt)) => opt.input_args.recover_records,
+            Some(Action::Sessions(opt)) => opt.input_args.recover_records,
+            Some(Action::Coverage(opt)) => opt.input_args.recover_records,
+            Some(Action::Convert(opt)) => opt.input_args.recover_records,
             _ => false,
         };
         let time_offset = match action {
@@ -549,6 +708,12 @@ impl StoredStatic {
             Some(Action::Search(opt)) => opt.input_args.time_offset.clone(),
             Some(Action::ComputerMetrics(opt)) => opt.input_args.time_offset.clone(),
             Some(Action::LogMetrics(opt)) => opt.input_args.time_offset.clone(),
+            Some(Action::EventAnomalies(opt)) => opt.input_args.time_offset.clone(),
+            Some(Action::RareArtifacts(opt)) => opt.input_args.time_offset.clone(),
+            Some(Action::ProcessTree(opt)) => opt.input_args.time_offset.clone(),
+            Some(Action::Sessions(opt)) => opt.input_args.time_offset.clone(),
+            Some(Action::Coverage(opt)) => opt.input_args.time_offset.clone(),
+            Some(Action::Convert(opt)) => opt.input_args.time_offset.clone(),
             _ => None,
         };
         let include_status: HashSet<CompactString> = match action {
@@ -567,6 +732,13 @@ impl StoredStatic {
                 .iter()
                 .map(|x| x.into())
                 .collect(),
+            Some(Action::Coverage(opt)) => opt
+                .include_status
+                .as_ref()
+                .unwrap_or(&vec![])
+                .iter()
+                .map(|x| x.into())
+                .collect(),
             _ => HashSet::default(),
         };
         let is_low_memory = match action {
@@ -597,8 +769,18 @@ impl StoredStatic {
             Some(Action::Search(opt)) => opt.validate_checksums,
             Some(Action::ComputerMetrics(opt)) => opt.validate_checksums,
             Some(Action::LogMetrics(opt)) => opt.detect_common_options.validate_checksums,
+            Some(Action::EventAnomalies(opt)) => opt.detect_common_options.validate_checksums,
+            Some(Action::RareArtifacts(opt)) => opt.detect_common_options.validate_checksums,
+            Some(Action::ProcessTree(opt)) => opt.detect_common_options.validate_checksums,
+            Some(Action::Sessions(opt)) => opt.detect_common_options.validate_checksums,
+            Some(Action::Coverage(opt)) => opt.detect_common_options.validate_checksums,
+            Some(Action::Convert(opt)) => opt.detect_common_options.validate_checksums,
             _ => false,
         };
+        let output_option = extract_output_options(&config);
+        let carve_flag = output_option
+            .as_ref()
+            .is_some_and(|opt| opt.input_args.carve.is_some());
         let mut ret = StoredStatic {
             config: config.to_owned(),
             config_path: config_path.to_path_buf(),
@@ -639,23 +821,42 @@ impl StoredStatic {
             geo_ip_search,
             geo_ip_db_yaml,
             geo_ip_filter,
+            identity_snapshot,
+            message_templates,
+            process_tree: None,
+            logon_sessions: None,
+            escalate_privileged,
             // The numeric ids compared below come from Action::to_usize.
             logon_summary_flag: action_id == 2,
             metrics_flag: action_id == 3,
             search_flag: action_id == 10,
             computer_metrics_flag: action_id == 11,
             log_metrics_flag: action_id == 12,
+            event_anomalies_flag: action_id == 17,
+            rare_artifacts_flag: action_id == 18,
+            process_tree_flag: action_id == 19,
+            sessions_flag: action_id == 20,
+            coverage_flag: action_id == 21,
+            convert_flag: action_id == 22,
             extract_base64_flag: action_id == 13,
             search_option: extract_search_options(&config),
-            output_option: extract_output_options(&config),
+            output_option,
             pivot_keyword_list_flag: action_id == 4,
             pivot_keyword: Arc::new(RwLock::new(PivotKeywordMap::new())),
             error_log_stack: Arc::new(Mutex::new(Nested::<String>::new())),
             computer_mitre_attck_map: Arc::new(DashMap::new()),
             computer_mitre_attck_unique_keys: Arc::new(DashSet::new()),
+            attack_navigator_map: Arc::new(DashMap::new()),
+            attack_navigator_flag: matches!(
+                &config.action,
+                Some(Action::DfirTimeline(opt)) if opt.output_options.attack_navigator.is_some()
+            ),
+            risk_score_map: Arc::new(DashMap::new()),
+            risk_weights,
             quiet_errors_flag,
             verbose_flag,
             html_report_flag: htmlreport::check_html_flag(&config),
+            html_interactive_flag: htmlreport::check_html_interactive_flag(&config),
             profiles: None,
             thread_number: check_thread_number(&config),
             event_timeline_config: load_eventcode_info(
@@ -670,6 +871,7 @@ impl StoredStatic {
             ),
             target_ruleids,
             json_input_flag,
+            carve_flag,
             output_path: output_path.cloned(),
             common_options,
             multiline_flag,
@@ -680,7 +882,8 @@ impl StoredStatic {
             exclude_eid,
             field_data_map,
             no_pwsh_field_extraction: no_pwsh_field_extraction_flag,
-            enable_recover_records,
+            // Carved records are all recovered records.
+            enable_recover_records: enable_recover_records || carve_flag,
             time_offset,
             include_status,
             is_low_memory,
@@ -709,6 +912,26 @@ impl StoredStatic {
             .unwrap(),
             Some(&ret),
         );
+        if ret.profiles.as_ref().is_some_and(|profiles| {
+            profiles
+                .iter()
+                .any(|(_, profile)| matches!(profile, Profile::ProcessAncestry(_)))
+        }) {
+            ret.process_tree = Some(Arc::new(RwLock::new(ProcessTree::default())));
+        }
+        if ret.profiles.as_ref().is_some_and(|profiles| {
+            profiles.iter().any(|(_, profile)| {
+                matches!(
+                    profile,
+                    Profile::SessionLogonType(_)
+                        | Profile::SessionSourceIP(_)
+                        | Profile::SessionLogonTime(_)
+                        | Profile::SessionDuration(_)
+                )
+            })
+        }) {
+            ret.logon_sessions = Some(Arc::new(RwLock::new(LogonSessions::default())));
+        }
         ret
     }
     /// Reads the default `details` output templates (per provider and EID) from
@@ -789,6 +1012,12 @@ fn check_thread_number(config: &Config) -> Option<usize> {
         Action::ExtractBase64(opt) => opt.detect_common_options.thread_number,
         Action::PivotKeywordsList(opt) => opt.detect_common_options.thread_number,
         Action::LogMetrics(opt) => opt.detect_common_options.thread_number,
+        Action::EventAnomalies(opt) => opt.detect_common_options.thread_number,
+        Action::RareArtifacts(opt) => opt.detect_common_options.thread_number,
+        Action::ProcessTree(opt) => opt.detect_common_options.thread_number,
+        Action::Sessions(opt) => opt.detect_common_options.thread_number,
+        Action::Coverage(opt) => opt.detect_common_options.thread_number,
+        Action::Convert(opt) => opt.detect_common_options.thread_number,
         Action::Search(opt) => opt.thread_number,
         _ => None,
     }
@@ -855,6 +1084,16 @@ pub enum Action {
     /// Output event ID metrics
     EidMetrics(EidMetricsOption),
 
+    #[clap(
+        author = "Yamato Security (https://github.com/Yamato-Security/hayabusa - @SecurityYamato)",
+        help_template = help_banner("\n{author-with-newline}\n{usage-heading}\n  hayabusa.exe event-anomalies <INPUT> [OPTIONS]\n\n{all-args}"),
+        term_width = 400,
+        display_order = 310,
+        disable_help_flag = true
+    )]
+    /// Output unusual spikes and silences in event rates
+    EventAnomalies(EventAnomaliesOption),
+
     #[clap(
         author = "Yamato Security (https://github.com/Yamato-Security/hayabusa - @SecurityYamato)",
         help_template = help_banner("\n{author-with-newline}\n{usage-heading}\n  hayabusa.exe expand-list [OPTIONS]\n\n{all-args}"),
@@ -885,6 +1124,26 @@ pub enum Action {
     /// Create a list of pivot keywords
     PivotKeywordsList(PivotKeywordOption),
 
+    #[clap(
+        author = "Yamato Security (https://github.com/Yamato-Security/hayabusa - @SecurityYamato)",
+        help_template = help_banner("\n{author-with-newline}\n{usage-heading}\n  hayabusa.exe process-tree <INPUT> [OPTIONS]\n\n{all-args}"),
+        term_width = 400,
+        display_order = 425,
+        disable_help_flag = true
+    )]
+    /// Rebuild process trees from process creation and termination events
+    ProcessTree(ProcessTreeOption),
+
+    #[clap(
+        author = "Yamato Security (https://github.com/Yamato-Security/hayabusa - @SecurityYamato)",
+        help_template = help_banner("\n{author-with-newline}\n{usage-heading}\n  hayabusa.exe rare-artifacts <INPUT> [OPTIONS]\n\n{all-args}"),
+        term_width = 400,
+        display_order = 430,
+        disable_help_flag = true
+    )]
+    /// Output processes, services, tasks and drivers sorted by how rare they are across computers
+    RareArtifacts(RareArtifactsOption),
+
     #[clap(
         author = "Yamato Security (https://github.com/Yamato-Security/hayabusa - @SecurityYamato)",
         help_template = help_banner("\n{author-with-newline}\n{usage-heading}\n  hayabusa.exe search <INPUT> <--keywords \"<KEYWORDS>\" OR --regex \"<REGEX>\"> [OPTIONS]\n\n{all-args}"),
@@ -895,6 +1154,16 @@ pub enum Action {
     /// Search all events by keyword(s) or regular expression
     Search(SearchOption),
 
+    #[clap(
+        author = "Yamato Security (https://github.com/Yamato-Security/hayabusa - @SecurityYamato)",
+        help_template = help_banner("\n{author-with-newline}\n{usage-heading}\n  hayabusa.exe sessions <INPUT> [OPTIONS]\n\n{all-args}"),
+        term_width = 400,
+        display_order = 460,
+        disable_help_flag = true
+    )]
+    /// Print logon sessions with their source, duration and activity
+    Sessions(SessionsOption),
+
     #[clap(
         author = "Yamato Security (https://github.com/Yamato-Security/hayabusa - @SecurityYamato)",
         help_template = help_banner("\n{author-with-newline}\n{usage-heading}\n  hayabusa.exe update-rules [OPTIONS]\n\n{all-args}"),
@@ -962,8 +1231,38 @@ pub enum Action {
         display_order = 291,
         disable_help_flag = true
     )]
-    /// Find critical systems like domain controllers and file servers.
+    /// Find critical systems like domain controllers, file servers and other server roles.
     ConfigCriticalSystems(ConfigCriticalSystemsOption),
+
+    #[clap(
+        author = "Yamato Security (https://github.com/Yamato-Security/hayabusa - @SecurityYamato)",
+        help_template = help_banner("\n{author-with-newline}\n{usage-heading}\n  hayabusa.exe coverage <INPUT> [OPTIONS]\n\n{all-args}"),
+        term_width = 400,
+        display_order = 291,
+        disable_help_flag = true
+    )]
+    /// Find the rules and ATT&CK techniques that cannot fire on each computer
+    Coverage(CoverageOption),
+
+    #[clap(
+        author = "Yamato Security (https://github.com/Yamato-Security/hayabusa - @SecurityYamato)",
+        help_template = help_banner("\n{author-with-newline}\n{usage-heading}\n  hayabusa.exe convert <INPUT> -o <DIR> [OPTIONS]\n\n{all-args}"),
+        term_width = 400,
+        display_order = 291,
+        disable_help_flag = true
+    )]
+    /// Convert events to JSONL or XML files without running rules
+    Convert(ConvertOption),
+
+    #[clap(
+        author = "Yamato Security (https://github.com/Yamato-Security/hayabusa - @SecurityYamato)",
+        help_template = help_banner("\n{author-with-newline}\n{usage-heading}\n  hayabusa.exe diff-results --old <FILE> --new <FILE> [OPTIONS]\n\n{all-args}"),
+        term_width = 400,
+        display_order = 293,
+        disable_help_flag = true
+    )]
+    /// Compare two dfir-timeline results
+    DiffResults(DiffResultsOption),
 }
 
 impl Action {
@@ -987,6 +1286,13 @@ impl Action {
                 Action::ExtractBase64(_) => 13,
                 Action::ExpandList(_) => 14,
                 Action::ConfigCriticalSystems(_) => 15,
+                Action::DiffResults(_) => 16,
+                Action::EventAnomalies(_) => 17,
+                Action::RareArtifacts(_) => 18,
+                Action::ProcessTree(_) => 19,
+                Action::Sessions(_) => 20,
+                Action::Coverage(_) => 21,
+                Action::Convert(_) => 22,
             }
         } else {
             100
@@ -1012,6 +1318,13 @@ impl Action {
                 Action::ExtractBase64(_) => "extract-base64",
                 Action::ExpandList(_) => "expand-list",
                 Action::ConfigCriticalSystems(_) => "config-critical-systems",
+                Action::DiffResults(_) => "diff-results",
+                Action::EventAnomalies(_) => "event-anomalies",
+                Action::RareArtifacts(_) => "rare-artifacts",
+                Action::ProcessTree(_) => "process-tree",
+                Action::Sessions(_) => "sessions",
+                Action::Coverage(_) => "coverage",
+                Action::Convert(_) => "convert",
             }
         } else {
             ""
@@ -1134,6 +1447,26 @@ pub struct TimeFormatOptions {
     /// Output time in UTC format (default: local time)
     #[arg(help_heading = Some("Time Format"), short = 'U', long = "utc", display_order = 210)]
     pub utc: bool,
+
+    /// Output timestamp in an IANA time zone (ex: Asia/Tokyo)
+    #[arg(
+        help_heading = Some("Time Format"),
+        long = "timezone",
+        value_name = "TZ",
+        conflicts_with_all = ["utc", "iso_8601"],
+        display_order = 201,
+    )]
+    pub timezone: Option<String>,
+
+    /// Output timestamp with a strftime pattern (ex: "%Y/%m/%d %H:%M:%S %Z")
+    #[arg(
+        help_heading = Some("Time Format"),
+        long = "time-format",
+        value_name = "FORMAT",
+        conflicts_with_all = ["european_time", "iso_8601", "rfc_2822", "rfc_3339", "us_military_time", "us_time"],
+        display_order = 200,
+    )]
+    pub time_format: Option<String>,
 }
 
 #[derive(Args, Clone, Debug, Default)]
@@ -1341,8 +1674,326 @@ pub struct EidMetricsOption {
     pub clobber_opt: ClobberOption,
 }
 
+/// Baseline that `event-anomalies` scores each time bucket against.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum BaselineMethod {
+    /// Median and median absolute deviation of all buckets.
+    #[default]
+    Mad,
+    /// Exponentially weighted moving average of the previous buckets.
+    Ewma,
+}
+
+#[derive(Args, Clone, Debug, Default)]
+pub struct EventAnomaliesOption {
+    #[clap(flatten)]
+    pub input_args: InputOption,
+
+    /// Save the anomalies in CSV format (ex: anomalies.csv)
+    #[arg(help_heading = Some("Output"), short = 'o', long, value_name = "FILE", display_order = 410)]
+    pub output: Option<PathBuf>,
+
+    /// Baseline to compare each time bucket against: mad (default) or ewma
+    #[arg(
+        help_heading = Some("Output"),
+        long = "baseline",
+        value_enum,
+        value_name = "METHOD",
+        ignore_case = true,
+        default_value = "mad",
+        hide_default_value = true,
+        hide_possible_values = true,
+        display_order = 280
+    )]
+    pub baseline: BaselineMethod,
+
+    /// Length of the time buckets events are counted in (ex: 30m, 1h, 1d) (default: 1h)
+    #[arg(help_heading = Some("Output"), long = "interval", value_name = "INTERVAL", display_order = 360)]
+    pub interval: Option<String>,
+
+    /// Minimum events in a spike, or in the baseline of a silence (default: 10)
+    #[arg(help_heading = Some("Output"), long = "min-count", value_name = "NUMBER", display_order = 390)]
+    pub min_count: Option<u64>,
+
+    /// Score above which a bucket is reported as a spike or silence (default: 3.5)
+    #[arg(help_heading = Some("Output"), long = "threshold", value_name = "SCORE", display_order = 490)]
+    pub threshold: Option<f64>,
+
+    #[clap(flatten)]
+    pub common_options: CommonOptions,
+
+    #[clap(flatten)]
+    pub detect_common_options: DetectCommonOption,
+
+    #[clap(flatten)]
+    pub time_format_options: TimeFormatOptions,
+
+    #[clap(flatten)]
+    pub clobber_opt: ClobberOption,
+}
+
+#[derive(Args, Clone, Debug, Default)]
+pub struct RareArtifactsOption {
+    #[clap(flatten)]
+    pub input_args: InputOption,
+
+    /// Save the artifacts in CSV format (ex: rare-artifacts.csv)
+    #[arg(help_heading = Some("Output"), short = 'o', long, value_name = "FILE", display_order = 410)]
+    pub output: Option<PathBuf>,
+
+    /// Only output artifacts seen on this many computers or fewer
+    #[arg(help_heading = Some("Output"), long = "max-hosts", value_name = "NUMBER", display_order = 390)]
+    pub max_hosts: Option<usize>,
+
+    #[clap(flatten)]
+    pub common_options: CommonOptions,
+
+    #[clap(flatten)]
+    pub detect_common_options: DetectCommonOption,
+
+    #[clap(flatten)]
+    pub time_format_options: TimeFormatOptions,
+
+    #[clap(flatten)]
+    pub clobber_opt: ClobberOption,
+}
+
+/// Output format for `convert`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ConvertFormat {
+    /// One JSON object per event in the same layout as the JSON input of the other commands.
+    #[default]
+    Jsonl,
+    /// The event XML under a single `<Events>` element, like a `wevtutil qe` export.
+    Xml,
+}
+
+#[derive(Args, Clone, Debug, Default)]
+pub struct ConvertOption {
+    #[clap(flatten)]
+    pub input_args: InputOption,
+
+    /// Directory to save one converted file per .evtx file to (ex: converted)
+    #[arg(help_heading = Some("Output"), short = 'o', long, value_name = "DIR", display_order = 410)]
+    pub output: PathBuf,
+
+    /// Output format: jsonl (default) or xml
+    #[arg(
+        help_heading = Some("Output"),
+        short = 't',
+        long = "output-type",
+        value_enum,
+        value_name = "OUTPUT_FORMAT",
+        ignore_case = true,
+        default_value = "jsonl",
+        hide_default_value = true,
+        hide_possible_values = true,
+        display_order = 411
+    )]
+    pub output_type: ConvertFormat,
+
+    /// Rewrite field data with the data_mapping rules (ex: %%1833 -> Yes) (JSONL output only)
+    #[arg(help_heading = Some("Output"), long = "field-data-mapping", display_order = 320)]
+    pub field_data_mapping: bool,
+
+    #[clap(flatten)]
+    pub common_options: CommonOptions,
+
+    #[clap(flatten)]
+    pub detect_common_options: DetectCommonOption,
+
+    #[clap(flatten)]
+    pub time_range: TimeRangeOption,
+
+    /// Convert only common EIDs (./rules/config/target_event_IDs.txt)
+    #[arg(help_heading = Some("Filtering"), short = 'E', long = "eid-filter", display_order = 50)]
+    pub eid_filter: bool,
+
+    /// Convert only specified EIDs (ex: 1) (ex: 1,4688)
+    #[arg(help_heading = Some("Filtering"), long = "include-eid", value_name = "EID...", conflicts_with_all = ["eid_filter", "exclude_eid"], use_value_delimiter = true, value_delimiter = ',', display_order = 352)]
+    pub include_eid: Option<Vec<String>>,
+
+    /// Do not convert specified EIDs (ex: 1) (ex: 1,4688)
+    #[arg(help_heading = Some("Filtering"), long = "exclude-eid", value_name = "EID...", conflicts_with_all = ["eid_filter", "include_eid"], use_value_delimiter = true, value_delimiter = ',', display_order = 315)]
+    pub exclude_eid: Option<Vec<String>>,
+
+    /// Convert only specified channels (ex: Security) (ex: Security,System)
+    #[arg(help_heading = Some("Filtering"), long = "include-channel", value_name = "CHANNEL...", conflicts_with = "exclude_channel", use_value_delimiter = true, value_delimiter = ',', display_order = 355)]
+    pub include_channel: Option<Vec<String>>,
+
+    /// Do not convert specified channels (ex: Security) (ex: Security,System)
+    #[arg(help_heading = Some("Filtering"), long = "exclude-channel", value_name = "CHANNEL...", conflicts_with = "include_channel", use_value_delimiter = true, value_delimiter = ',', display_order = 315)]
+    pub exclude_channel: Option<Vec<String>>,
+
+    #[clap(flatten)]
+    pub clobber_opt: ClobberOption,
+}
+
+#[derive(Args, Clone, Debug, Default)]
+pub struct CoverageOption {
+    #[clap(flatten)]
+    pub input_args: InputOption,
+
+    /// Save the coverage report to two CSV files (ex: -o coverage)
+    #[arg(help_heading = Some("Output"), short = 'o', long, value_name = "FILENAME-PREFIX", display_order = 410)]
+    pub output: Option<PathBuf>,
+
+    /// Specify a custom rule directory or file (default: ./rules)
+    #[arg(
+        help_heading = Some("General Options"),
+        short = 'r',
+        long,
+        default_value = "./rules",
+        hide_default_value = true,
+        value_name = "DIR/FILE",
+        display_order = 441
+    )]
+    pub rules: PathBuf,
+
+    #[clap(flatten)]
+    pub common_options: CommonOptions,
+
+    /// Enable rules with a status of deprecated
+    #[arg(help_heading = Some("Filtering"), short = 'D', long = "enable-deprecated-rules", display_order = 310)]
+    pub enable_deprecated_rules: bool,
+
+    /// Enable rules with a status of unsupported
+    #[arg(help_heading = Some("Filtering"), short = 'u', long = "enable-unsupported-rules", display_order = 312)]
+    pub enable_unsupported_rules: bool,
+
+    /// Enable rules set to noisy (./rules/config/noisy_rules.txt)
+    #[arg(help_heading = Some("Filtering"), short = 'n', long = "enable-noisy-rules", display_order = 311)]
+    pub enable_noisy_rules: bool,
+
+    /// Do not load rules according to status (ex: experimental) (ex: stable,test)
+    #[arg(help_heading = Some("Filtering"), long = "exclude-status", value_name = "STATUS...", conflicts_with = "include_status", use_value_delimiter = true, value_delimiter = ',', display_order = 316)]
+    pub exclude_status: Option<Vec<String>>,
+
+    /// Only load rules with specific status (ex: experimental) (ex: stable,test)
+    #[arg(help_heading = Some("Filtering"), long = "include-status", value_name = "STATUS...", conflicts_with = "exclude_status", use_value_delimiter = true, value_delimiter = ',', display_order = 353)]
+    pub include_status: Option<Vec<String>>,
+
+    /// Only load rules with specific tags (ex: attack.execution,attack.discovery)
+    #[arg(help_heading = Some("Filtering"), long = "include-tag", value_name = "TAG...", conflicts_with = "exclude_tag", use_value_delimiter = true, value_delimiter = ',', display_order = 354)]
+    pub include_tag: Option<Vec<String>>,
+
+    /// Do not load rules with specific tags (ex: sysmon)
+    #[arg(help_heading = Some("Filtering"), long = "exclude-tag", value_name = "TAG...", conflicts_with = "include_tag", use_value_delimiter = true, value_delimiter = ',', display_order = 316)]
+    pub exclude_tag: Option<Vec<String>>,
+
+    /// Minimum level for rules to load (default: informational)
+    #[arg(
+        help_heading = Some("Filtering"),
+        short = 'm',
+        long = "min-level",
+        default_value = "informational",
+        hide_default_value = true,
+        value_name = "LEVEL",
+        conflicts_with = "exact_level",
+        display_order = 390
+    )]
+    pub min_level: String,
+
+    /// Only load rules with a specific level (informational, low, medium, high, critical)
+    #[arg(
+        help_heading = Some("Filtering"),
+        short = 'e',
+        long = "exact-level",
+        value_name = "LEVEL",
+        conflicts_with = "min_level",
+        display_order = 313
+    )]
+    pub exact_level: Option<String>,
+
+    #[clap(flatten)]
+    pub detect_common_options: DetectCommonOption,
+
+    #[clap(flatten)]
+    pub clobber_opt: ClobberOption,
+}
+
+#[derive(Args, Clone, Debug, Default)]
+pub struct SessionsOption {
+    #[clap(flatten)]
+    pub input_args: InputOption,
+
+    /// Save the sessions in CSV format (ex: sessions.csv)
+    #[arg(help_heading = Some("Output"), short = 'o', long, value_name = "FILE", display_order = 410)]
+    pub output: Option<PathBuf>,
+
+    #[clap(flatten)]
+    pub common_options: CommonOptions,
+
+    #[clap(flatten)]
+    pub detect_common_options: DetectCommonOption,
+
+    #[clap(flatten)]
+    pub time_format_options: TimeFormatOptions,
+
+    #[clap(flatten)]
+    pub clobber_opt: ClobberOption,
+}
+
+/// Output format for `computer-metrics`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ComputerMetricsFormat {
+    /// A table, or CSV with -o. Lists such as the users are summarized in a single cell.
+    #[default]
+    Csv,
+    /// One object per computer with the full inventory, including the services and the
+    /// retention span of each channel.
+    Json,
+}
+
+/// Output format for `process-tree`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ProcessTreeFormat {
+    /// Indented trees, one block per computer.
+    #[default]
+    Text,
+    /// Nested JSON objects with the children of each process under `Children`.
+    Json,
+}
+
 #[derive(Args, Clone, Debug, Default)]
-#[clap(group(ArgGroup::new("input_filtering").args(["directory", "filepath", "live_analysis"]).required(true)))]
+pub struct ProcessTreeOption {
+    #[clap(flatten)]
+    pub input_args: InputOption,
+
+    /// Save the process trees to a file (ex: process-tree.txt)
+    #[arg(help_heading = Some("Output"), short = 'o', long, value_name = "FILE", display_order = 410)]
+    pub output: Option<PathBuf>,
+
+    /// Output format: text (default) or json
+    #[arg(
+        help_heading = Some("Output"),
+        short = 't',
+        long = "output-type",
+        value_enum,
+        value_name = "OUTPUT_FORMAT",
+        ignore_case = true,
+        default_value = "text",
+        hide_default_value = true,
+        hide_possible_values = true,
+        display_order = 411
+    )]
+    pub output_type: ProcessTreeFormat,
+
+    #[clap(flatten)]
+    pub common_options: CommonOptions,
+
+    #[clap(flatten)]
+    pub detect_common_options: DetectCommonOption,
+
+    #[clap(flatten)]
+    pub time_format_options: TimeFormatOptions,
+
+    #[clap(flatten)]
+    pub clobber_opt: ClobberOption,
+}
+
+#[derive(Args, Clone, Debug, Default)]
+#[clap(group(ArgGroup::new("input_filtering").args(["directory", "filepath", "live_analysis", "carve"]).required(true)))]
 #[clap(group(ArgGroup::new("level_rule_filtering").args(["min_level", "exact_level"]).multiple(false)))]
 pub struct PivotKeywordOption {
     #[clap(flatten)]
@@ -1440,7 +2091,7 @@ pub struct LogonSummaryOption {
     #[clap(flatten)]
     pub input_args: InputOption,
 
-    /// Save the logon summary to two CSV files (ex: -o logon-summary)
+    /// Save the logon summary to three CSV files (ex: -o logon-summary)
     #[arg(help_heading = Some("Output"), short = 'o', long, value_name = "FILENAME-PREFIX", display_order = 410)]
     pub output: Option<PathBuf>,
 
@@ -1462,6 +2113,38 @@ pub struct LogonSummaryOption {
 
     #[clap(flatten)]
     pub time_range: TimeRangeOption,
+
+    /// Save a lateral movement graph of the logons (ex: lateral-movement.graphml)
+    #[arg(help_heading = Some("Output"), long = "graph-output", value_name = "FILE", display_order = 330)]
+    pub graph_output: Option<PathBuf>,
+
+    /// Graph format: graphml (default), dot, or json
+    #[arg(
+        help_heading = Some("Output"),
+        long = "graph-format",
+        value_enum,
+        value_name = "GRAPH_FORMAT",
+        ignore_case = true,
+        default_value = "graphml",
+        hide_default_value = true,
+        hide_possible_values = true,
+        requires = "graph_output",
+        display_order = 331
+    )]
+    pub graph_format: GraphFormat,
+
+    /// Report accounts requesting service tickets for at least this many services (default: 10)
+    #[arg(help_heading = Some("General Options"), long = "spn-threshold", value_name = "NUMBER", display_order = 452)]
+    pub spn_threshold: Option<usize>,
+}
+
+/// File format of the lateral movement graph written by `logon-summary --graph-output`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum GraphFormat {
+    #[default]
+    Graphml,
+    Dot,
+    Json,
 }
 
 /// Options that can be set when outputting results (flattened into csv-timeline/json-timeline)
@@ -1595,6 +2278,43 @@ pub struct OutputOption {
     #[arg(help_heading = Some("Output"), short = 'H', long="html-report", conflicts_with = "no_summary", value_name = "FILE", display_order = 80, requires = "output")]
     pub html_report: Option<PathBuf>,
 
+    /// Embed a sortable and filterable table of the detections in the HTML report
+    #[arg(help_heading = Some("Output"), long = "html-interactive", requires = "html_report", display_order = 340)]
+    pub html_interactive: bool,
+
+    /// Save an ATT&CK Navigator layer of the detected techniques (ex: layer.json)
+    #[arg(help_heading = Some("Output"), long = "attack-navigator", value_name = "FILE", display_order = 270)]
+    pub attack_navigator: Option<PathBuf>,
+
+    /// Also save one ATT&CK Navigator layer per computer (ex: layer-PC01.json)
+    #[arg(help_heading = Some("Output"), long = "attack-navigator-per-computer", requires = "attack_navigator", display_order = 271)]
+    pub attack_navigator_per_computer: bool,
+
+    /// Number of computers with the most detections to show in the attack story of the HTML report (default: 5)
+    #[arg(help_heading = Some("Output"), long = "attack-story-hosts", value_name = "NUMBER", requires = "html_report", display_order = 272)]
+    pub attack_story_hosts: Option<usize>,
+
+    /// Rank computers and accounts by risk score in the Results Summary (./config/risk_scoring.yaml)
+    #[arg(help_heading = Some("Output"), long = "risk-scoring", display_order = 442)]
+    pub risk_scoring: bool,
+
+    /// Save the risk scores of all computers and accounts in JSON format (ex: risk.json)
+    #[arg(help_heading = Some("Output"), long = "risk-output", value_name = "FILE", display_order = 443)]
+    pub risk_output: Option<PathBuf>,
+
+    /// Save one file per computer, UTC date or level in the output directory: computer, date or level
+    #[arg(
+        help_heading = Some("Output"),
+        long = "split-by",
+        value_enum,
+        value_name = "KEY",
+        ignore_case = true,
+        requires = "output",
+        hide_possible_values = true,
+        display_order = 445
+    )]
+    pub split_by: Option<SplitBy>,
+
     /// Do not display Results Summary for faster speed
     #[arg(help_heading = Some("Display Settings"), short = 'N', long = "no-summary", conflicts_with = "html_report", display_order = 401)]
     pub no_summary: bool,
@@ -1614,6 +2334,14 @@ pub struct OutputOption {
     #[arg(help_heading = Some("Output"), short = 'X', long = "remove-duplicate-detections", requires = "sort_events", display_order = 441)]
     pub remove_duplicate_detections: bool,
 
+    /// Cluster detections with the same keys within a time window into one row (ex: 30m, 1h) (sort required)
+    #[arg(help_heading = Some("Output"), long = "cluster-window", value_name = "DURATION", requires = "sort_events", display_order = 293)]
+    pub cluster_window: Option<String>,
+
+    /// Columns or field names to cluster detections by (default: RuleTitle,Computer)
+    #[arg(help_heading = Some("Output"), long = "cluster-keys", value_name = "KEY...", requires = "cluster_window", use_value_delimiter = true, value_delimiter = ',', display_order = 292)]
+    pub cluster_keys: Option<Vec<String>>,
+
     /// Do not ask questions. Scan for all events and alerts.
     #[arg(help_heading = Some("General Options"), short = 'w', long = "no-wizard", display_order = 400)]
     pub no_wizard: bool,
@@ -1655,7 +2383,7 @@ pub struct CommonOptions {
 }
 
 #[derive(Args, Clone, Debug, Default)]
-#[clap(group(ArgGroup::new("input_filtering").args(["directory", "filepath", "live_analysis"]).required(true)))]
+#[clap(group(ArgGroup::new("input_filtering").args(["directory", "filepath", "live_analysis", "carve"]).required(true)))]
 pub struct InputOption {
     /// Directory of multiple .evtx files
     #[arg(help_heading = Some("Input"), short = 'd', long, value_name = "DIR", conflicts_with_all = ["filepath", "live_analysis"], display_order = 300)]
@@ -1669,6 +2397,10 @@ pub struct InputOption {
     #[arg(help_heading = Some("Input"), short = 'l', long = "live-analysis", conflicts_with_all = ["filepath", "directory", "json_input"], display_order = 380)]
     pub live_analysis: bool,
 
+    /// Carve evtx chunks from a raw file (ex: disk image, pagefile, memory dump)
+    #[arg(help_heading = Some("Input"), long = "carve", value_name = "FILE", conflicts_with_all = ["directory", "filepath", "live_analysis", "json_input", "recover_records"], display_order = 310)]
+    pub carve: Option<PathBuf>,
+
     /// Carve evtx records from slack space (default: disabled)
     #[arg(help_heading = Some("General Options"), short = 'x', long = "recover-records", conflicts_with = "json_input", display_order = 440)]
     pub recover_records: bool,
@@ -1686,6 +2418,19 @@ pub enum OutputType {
     Csv,
     Json,
     Jsonl,
+    /// JSONL events for Timesketch imports.
+    Timesketch,
+    /// JSONL documents in Elastic Common Schema.
+    Ecs,
+}
+
+/// How `--split-by` shards the `dfir-timeline` output files.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SplitBy {
+    Computer,
+    /// The UTC date of the detection.
+    Date,
+    Level,
 }
 
 #[derive(Args, Clone, Debug, Default)]
@@ -1693,7 +2438,7 @@ pub struct DfirTimelineOption {
     #[clap(flatten)]
     pub output_options: OutputOption,
 
-    /// Output format: csv (default), json, or jsonl
+    /// Output format: csv (default), json, jsonl, timesketch or ecs
     #[arg(
         help_heading = Some("Output"),
         short = 't',
@@ -1728,6 +2473,33 @@ pub struct DfirTimelineOption {
     )]
     pub geo_ip: Option<PathBuf>,
 
+    /// Add account info from an offline AD snapshot (CSV/JSON export or SharpHound JSON files)
+    #[arg(
+        help_heading = Some("Output"),
+        long = "identity-snapshot",
+        value_name = "FILE-OR-DIR",
+        display_order = 350
+    )]
+    pub identity_snapshot: Option<PathBuf>,
+
+    /// Raise the level of alerts involving privileged accounts by one (requires --identity-snapshot)
+    #[arg(
+        help_heading = Some("Output"),
+        long = "escalate-privileged",
+        requires = "identity_snapshot",
+        display_order = 310
+    )]
+    pub escalate_privileged: bool,
+
+    /// Render %RenderedMessage% from an offline message template database (JSON/JSONL)
+    #[arg(
+        help_heading = Some("Output"),
+        long = "message-db",
+        value_name = "FILE-OR-DIR",
+        display_order = 390
+    )]
+    pub message_db: Option<PathBuf>,
+
     /// Save the timeline to a file (ex: results.csv)
     #[arg(help_heading = Some("Output"), short = 'o', long, value_name = "FILE", display_order = 410)]
     pub output: Option<PathBuf>,
@@ -1741,10 +2513,25 @@ pub struct ComputerMetricsOption {
     #[clap(flatten)]
     pub input_args: InputOption,
 
-    /// Save the results in CSV format (ex: computer-metrics.csv)
+    /// Save the results in CSV or JSON format (ex: computer-metrics.csv)
     #[arg(help_heading = Some("Output"), short = 'o', long, value_name = "FILE", display_order = 410)]
     pub output: Option<PathBuf>,
 
+    /// Output format: csv (default) or json
+    #[arg(
+        help_heading = Some("Output"),
+        short = 't',
+        long = "output-type",
+        value_enum,
+        value_name = "OUTPUT_FORMAT",
+        ignore_case = true,
+        default_value = "csv",
+        hide_default_value = true,
+        hide_possible_values = true,
+        display_order = 411
+    )]
+    pub output_type: ComputerMetricsFormat,
+
     #[clap(flatten)]
     pub common_options: CommonOptions,
 
@@ -1810,6 +2597,14 @@ pub struct LogMetricsOption {
     #[arg(help_heading = Some("Output"), short = 'S', long="tab-separator", conflicts_with = "multiline", display_order = 490)]
     pub tab_separator: bool,
 
+    /// Check the logs for record ID gaps, time gaps, log clears, audit policy changes and missing chunks
+    #[arg(help_heading = Some("Output"), long = "integrity", display_order = 360)]
+    pub integrity: bool,
+
+    /// Hours without events to report as a time gap (default: 24)
+    #[arg(help_heading = Some("Output"), long = "gap-hours", value_name = "HOURS", requires = "integrity", display_order = 320)]
+    pub gap_hours: Option<u64>,
+
     #[clap(flatten)]
     pub clobber_opt: ClobberOption,
 
@@ -1885,6 +2680,38 @@ pub struct ConfigCriticalSystemsOption {
     #[arg(help_heading = Some("Input"), short = 'f', long = "file", value_name = "FILE", conflicts_with_all = ["directory"], display_order = 320)]
     pub filepath: Option<PathBuf>,
 
+    /// Save the results in JSON format without asking questions (ex: critical-systems.json)
+    #[arg(help_heading = Some("Output"), short = 'o', long, value_name = "FILE", display_order = 410)]
+    pub output: Option<PathBuf>,
+
+    /// Add computers with a role of at least this confidence (0-100) to critical_systems.txt without asking questions (ex: 90)
+    #[arg(help_heading = Some("Output"), long = "min-confidence", value_name = "PERCENT", value_parser = clap::value_parser!(u8).range(0..=100), display_order = 380)]
+    pub min_confidence: Option<u8>,
+
+    #[clap(flatten)]
+    pub clobber_opt: ClobberOption,
+
+    #[clap(flatten)]
+    pub common_options: CommonOptions,
+}
+
+#[derive(Args, Clone, Debug, Default)]
+pub struct DiffResultsOption {
+    /// Results of the earlier scan (CSV, JSON or JSONL)
+    #[arg(help_heading = Some("Input"), long = "old", value_name = "FILE", display_order = 410)]
+    pub old: PathBuf,
+
+    /// Results of the later scan (CSV, JSON or JSONL)
+    #[arg(help_heading = Some("Input"), long = "new", value_name = "FILE", display_order = 400)]
+    pub new: PathBuf,
+
+    /// Save the differences in CSV format (ex: diff.csv)
+    #[arg(help_heading = Some("Output"), short = 'o', long, value_name = "FILE", display_order = 410)]
+    pub output: Option<PathBuf>,
+
+    #[clap(flatten)]
+    pub clobber_opt: ClobberOption,
+
     #[clap(flatten)]
     pub common_options: CommonOptions,
 }
@@ -2194,9 +3021,35 @@ impl TargetEventTime {
                 );
                 Self::set(parse_success_flag, start_time, end_time)
             }
+            Action::Convert(option) => {
+                let start_time = if time_offset.is_some() {
+                    get_time(
+                        time_offset.as_ref(),
+                        "Invalid timeline offset. Please use one of the following formats: 1y, 3M, 30d, 24h, 30m",
+                        &mut parse_success_flag,
+                    )
+                } else {
+                    get_time(
+                        option.time_range.start_timeline.as_ref(),
+                        "start-timeline field: the timestamp format is not correct.",
+                        &mut parse_success_flag,
+                    )
+                };
+                let end_time = get_time(
+                    option.time_range.end_timeline.as_ref(),
+                    "end-timeline field: the timestamp format is not correct.",
+                    &mut parse_success_flag,
+                );
+                Self::set(parse_success_flag, start_time, end_time)
+            }
             Action::LogMetrics(_)
             | Action::EidMetrics(_)
+            | Action::EventAnomalies(_)
             | Action::ComputerMetrics(_)
+            | Action::RareArtifacts(_)
+            | Action::ProcessTree(_)
+            | Action::Sessions(_)
+            | Action::Coverage(_)
             | Action::ExtractBase64(_) => {
                 let start_time = if time_offset.is_some() {
                     get_time(
@@ -2439,6 +3292,73 @@ fn extract_output_options(config: &Config) -> Option<OutputOption> {
             no_wizard: true,
             ..Default::default()
         }),
+        Action::EventAnomalies(option) => Some(OutputOption {
+            input_args: option.input_args.clone(),
+            time_format_options: option.time_format_options.clone(),
+            common_options: option.common_options,
+            detect_common_options: option.detect_common_options.clone(),
+            clobber_opt: option.clobber_opt.clone(),
+            no_wizard: true,
+            ..Default::default()
+        }),
+        Action::RareArtifacts(option) => Some(OutputOption {
+            input_args: option.input_args.clone(),
+            time_format_options: option.time_format_options.clone(),
+            common_options: option.common_options,
+            detect_common_options: option.detect_common_options.clone(),
+            clobber_opt: option.clobber_opt.clone(),
+            no_wizard: true,
+            ..Default::default()
+        }),
+        Action::ProcessTree(option) => Some(OutputOption {
+            input_args: option.input_args.clone(),
+            time_format_options: option.time_format_options.clone(),
+            common_options: option.common_options,
+            detect_common_options: option.detect_common_options.clone(),
+            clobber_opt: option.clobber_opt.clone(),
+            no_wizard: true,
+            ..Default::default()
+        }),
+        Action::Coverage(option) => Some(OutputOption {
+            input_args: option.input_args.clone(),
+            enable_deprecated_rules: option.enable_deprecated_rules,
+            enable_noisy_rules: option.enable_noisy_rules,
+            enable_unsupported_rules: option.enable_unsupported_rules,
+            exclude_status: option.exclude_status.clone(),
+            include_status: option.include_status.clone(),
+            include_tag: option.include_tag.clone(),
+            exclude_tag: option.exclude_tag.clone(),
+            min_level: option.min_level.clone(),
+            exact_level: option.exact_level.clone(),
+            rules: option.rules.clone(),
+            common_options: option.common_options,
+            detect_common_options: option.detect_common_options.clone(),
+            clobber_opt: option.clobber_opt.clone(),
+            no_wizard: true,
+            ..Default::default()
+        }),
+        Action::Convert(option) => Some(OutputOption {
+            input_args: option.input_args.clone(),
+            time_range: option.time_range.clone(),
+            eid_filter: option.eid_filter,
+            common_options: option.common_options,
+            detect_common_options: option.detect_common_options.clone(),
+            clobber_opt: option.clobber_opt.clone(),
+            include_eid: option.include_eid.clone(),
+            exclude_eid: option.exclude_eid.clone(),
+            no_field: !option.field_data_mapping,
+            no_wizard: true,
+            ..Default::default()
+        }),
+        Action::Sessions(option) => Some(OutputOption {
+            input_args: option.input_args.clone(),
+            time_format_options: option.time_format_options.clone(),
+            common_options: option.common_options,
+            detect_common_options: option.detect_common_options.clone(),
+            clobber_opt: option.clobber_opt.clone(),
+            no_wizard: true,
+            ..Default::default()
+        }),
         Action::ExtractBase64(option) => Some(OutputOption {
             input_args: option.input_args.clone(),
             time_format_options: option.time_format_options.clone(),
@@ -2529,12 +3449,20 @@ fn extract_output_options(config: &Config) -> Option<OutputOption> {
                 directory: option.directory.clone(),
                 filepath: option.filepath.clone(),
                 live_analysis: false,
+                carve: None,
                 recover_records: false,
                 time_offset: None,
             },
             time_format_options: TimeFormatOptions::default(),
             common_options: option.common_options,
             detect_common_options: DetectCommonOption::default(),
+            clobber_opt: option.clobber_opt.clone(),
+            no_wizard: true,
+            ..Default::default()
+        }),
+        Action::DiffResults(option) => Some(OutputOption {
+            common_options: option.common_options,
+            clobber_opt: option.clobber_opt.clone(),
             no_wizard: true,
             ..Default::default()
         }),
@@ -7,6 +7,7 @@ use itertools::Itertools;
 use nested::Nested;
 use num_format::{Locale, ToFormattedString};
 use serde_json::Value;
+use std::borrow::Cow;
 use std::collections::HashSet;
 use std::default::Default;
 use std::fmt::Write;
@@ -23,19 +24,26 @@ use crate::detections::rule::correlation_parser::parse_correlation_rules;
 use crate::detections::rule::count::{AggRecordTimeInfo, get_sec_timeframe};
 use crate::detections::rule::{self, AggResult, CorrelationType, RuleNode};
 use crate::detections::utils::{
-    create_recordinfos, format_time, get_writable_color, write_color_buffer,
+    create_recordinfos, format_time_with_zone, get_writable_color, write_color_buffer,
 };
 use crate::detections::utils::{get_serde_number_to_string, make_ascii_titlecase};
 use crate::filter;
 use crate::level::LEVEL;
+use crate::options::asset_inventory::ASSET_INVENTORY;
 use crate::options::htmlreport;
+use crate::options::identity_snapshot::IdentitySnapshot;
 use crate::options::pivot::insert_pivot_keyword;
 use crate::options::profile::Profile::{
-    self, Channel, Computer, EventID, EvtxFile, Level, MitreTactics, MitreTags, OtherTags,
-    Provider, RecordID, RecoveredRecord, RenderedMessage, RuleAuthor, RuleCreationDate, RuleFile,
-    RuleID, RuleModifiedDate, RuleTitle, SrcASN, SrcCity, SrcCountry, Status, TgtASN, TgtCity,
-    TgtCountry, Timestamp,
+    self, AssetOwner, AssetRole, AssetTags, AssetTier, CarveOffset, Channel, Computer, EventID,
+    EvtxFile, IdentityAccount, IdentityPrivilegedGroups, IdentityStatus, IdentityType, Level,
+    MitreTactics, MitreTags, OriginalOffset, OtherTags, ProcessAncestry, Provider, RecordID,
+    RecoveredRecord, RenderedMessage, RuleAuthor, RuleCreationDate, RuleFile, RuleID,
+    RuleModifiedDate, RuleTitle, SessionDuration, SessionLogonTime, SessionLogonType,
+    SessionSourceIP, SrcASN, SrcCity, SrcCountry, Status, TgtASN, TgtCity, TgtCountry, Timestamp,
 };
+use crate::options::risk_score;
+use crate::timeline::logon_sessions::SessionKey;
+use crate::timeline::process_tree::ProcessKey;
 use crate::yaml::ParseYaml;
 
 use super::configs::{EventKeyAliasConfig, StoredStatic};
@@ -49,6 +57,7 @@ pub struct EvtxRecordInfo {
     pub data_string: String,   // Data within one record converted to a string.
     pub key_to_value: HashMap<String, String>, // Map of hierarchical keys joined by "." and their values.
     pub recovered_record: bool,                // Whether the record was recovered.
+    pub carve_offset: Option<u64>, // Offset of the chunk in the raw file given with --carve.
 }
 
 impl EvtxRecordInfo {
@@ -440,7 +449,7 @@ impl Detection {
         let time = message::get_event_time(&record_info.record, stored_static.json_input_flag)
             .unwrap_or(default_time);
         let level_str = rule.yaml["level"].as_str().unwrap_or("-");
-        let mut level = &LEVEL::from(level_str);
+        let mut level = LEVEL::from(level_str);
 
         let mut profile_converter: HashMap<&str, Profile> = HashMap::new();
         let tags_config_values: Vec<&CompactString> = TAGS_CONFIG.values().collect();
@@ -455,26 +464,69 @@ impl Detection {
                 .unwrap_or_default()
                 .replace('\"', ""),
         );
+        let identities = stored_static
+            .identity_snapshot
+            .as_ref()
+            .map(|snapshot| snapshot.find_accounts(&record_info.record))
+            .unwrap_or_default();
         let mut computer_name_to_mitre_tactics = CompactString::default();
         for (key, profile) in stored_static.profiles.as_ref().unwrap().iter() {
             match profile {
-                Timestamp(_) => {
+                Timestamp(zone) => {
+                    let time_format_options = &stored_static
+                        .output_option
+                        .as_ref()
+                        .unwrap()
+                        .time_format_options;
+                    // A column with its own zone overrides the zone chosen on the command line.
+                    let zone = if zone.is_empty() {
+                        time_format_options.timezone.as_deref()
+                    } else {
+                        Some(zone.as_ref())
+                    };
                     profile_converter.insert(
                         key.as_str(),
                         Timestamp(
-                            format_time(
-                                &time,
-                                false,
-                                &stored_static
-                                    .output_option
-                                    .as_ref()
-                                    .unwrap()
-                                    .time_format_options,
+                            format_time_with_zone(&time, false, time_format_options, zone).into(),
+                        ),
+                    );
+                }
+                OriginalOffset(_) => {
+                    profile_converter.insert(
+                        key.as_str(),
+                        OriginalOffset(
+                            message::get_event_offset(
+                                &record_info.record,
+                                stored_static.json_input_flag,
                             )
+                            .unwrap_or_else(|| "-".into())
                             .into(),
                         ),
                     );
                 }
+                AssetRole(_) | AssetOwner(_) | AssetTier(_) | AssetTags(_) => {
+                    profile_converter.insert(
+                        key.as_str(),
+                        profile.convert(
+                            &ASSET_INVENTORY.profile_value(profile, computer_name.as_str()),
+                        ),
+                    );
+                }
+                IdentityAccount(_)
+                | IdentityType(_)
+                | IdentityStatus(_)
+                | IdentityPrivilegedGroups(_) => {
+                    profile_converter.insert(
+                        key.as_str(),
+                        profile.convert(&IdentitySnapshot::profile_value(profile, &identities)),
+                    );
+                }
+                SessionLogonType(_) | SessionSourceIP(_) | SessionLogonTime(_)
+                | SessionDuration(_) => {
+                    // Filled in from the session key after the scan, once every logon and logoff
+                    // is known.
+                    profile_converter.insert(key.as_str(), profile.convert(&"-".into()));
+                }
                 Computer(_) => {
                     if stored_static.html_report_flag {
                         computer_name_to_mitre_tactics = computer_name.clone();
@@ -501,6 +553,11 @@ impl Detection {
                 }
                 Level(_) => {
                     level = level.convert(computer_name.as_str());
+                    if stored_static.escalate_privileged
+                        && identities.iter().any(|identity| identity.is_privileged())
+                    {
+                        level = level.shift(1);
+                    }
 
                     let level_str = if stored_static.disable_abbreviation {
                         level.to_full()
@@ -700,10 +757,29 @@ impl Detection {
                     profile_converter
                         .insert("RecoveredRecord", RecoveredRecord(recovered_record.into()));
                 }
+                CarveOffset(_) => {
+                    let offset = record_info
+                        .carve_offset
+                        .map_or_else(|| "-".into(), |offset| format!("0x{offset:X}").into());
+                    profile_converter.insert(key.as_str(), CarveOffset(offset));
+                }
+                ProcessAncestry(_) => {
+                    // Filled in from the process key after the scan, once every parent is known.
+                    profile_converter.insert(key.as_str(), ProcessAncestry("-".into()));
+                }
                 RenderedMessage(_) => {
-                    let convert_value = if let Some(message) =
-                        record_info.record["Event"]["RenderingInfo"]["Message"].as_str()
-                    {
+                    // Forwarded events carry the rendered message. Otherwise it is built from the
+                    // --message-db templates when they were loaded.
+                    let message =
+                        match record_info.record["Event"]["RenderingInfo"]["Message"].as_str() {
+                            Some(message) => Some(Cow::Borrowed(message)),
+                            None => stored_static
+                                .message_templates
+                                .as_ref()
+                                .and_then(|templates| templates.render(&record_info.record))
+                                .map(Cow::Owned),
+                        };
+                    let convert_value = if let Some(message) = message {
                         message
                             .replace('\t', "\\t")
                             .split("\r\n")
@@ -890,7 +966,7 @@ impl Detection {
             ruleid: CompactString::from(rule.yaml["id"].as_str().unwrap_or("-")),
             ruletitle: CompactString::from(rule.yaml["title"].as_str().unwrap_or("-")),
             ruleauthor: CompactString::from(rule.yaml["author"].as_str().unwrap_or("-")),
-            level: level.clone(),
+            level,
             computername: computer_name,
             eventid: eid,
             rec_id,
@@ -898,9 +974,27 @@ impl Detection {
             output_fields: stored_static.profiles.as_ref().unwrap().to_owned(),
             agg_result: None,
             details_convert_map: HashMap::default(),
+            channel: channel_str.clone(),
+            tags: Detection::get_raw_tags(rule)
+                .into_iter()
+                .map(CompactString::from)
+                .collect(),
+            risk_accounts: stored_static
+                .risk_weights
+                .as_ref()
+                .map(|weights| risk_score::record_accounts(&record_info.record, weights))
+                .unwrap_or_default(),
+            process_key: stored_static
+                .process_tree
+                .as_ref()
+                .and_then(|_| ProcessKey::from_record(&record_info.record, eventkey_alias)),
+            session_key: stored_static
+                .logon_sessions
+                .as_ref()
+                .and_then(|_| SessionKey::from_record(&record_info.record, eventkey_alias)),
         };
 
-        message::create_message(
+        let mut detect_info = message::create_message(
             &record_info.record,
             CompactString::new(details_fmt_str),
             detect_info,
@@ -911,7 +1005,24 @@ impl Detection {
                 &field_data_map_key,
                 &stored_static.field_data_map,
             ),
-        )
+        );
+        // The interactive HTML report shows every field of the record when a row is clicked, even
+        // when the profile has no AllFieldInfo column.
+        if stored_static.html_interactive_flag
+            && !detect_info
+                .details_convert_map
+                .contains_key("#AllFieldInfo")
+        {
+            detect_info.details_convert_map.insert(
+                "#AllFieldInfo".into(),
+                create_recordinfos(
+                    &record_info.record,
+                    &field_data_map_key,
+                    &stored_static.field_data_map,
+                ),
+            );
+        }
+        detect_info
     }
 
     /// Creates a DetectInfo detection message for one aggregation condition (count/correlation)
@@ -927,7 +1038,7 @@ impl Detection {
 
         let mut profile_converter: HashMap<&str, Profile> = HashMap::new();
         let level_str = rule.yaml["level"].as_str().unwrap_or("-");
-        let mut level = &LEVEL::from(level_str);
+        let mut level = LEVEL::from(level_str);
         let computers =
             Detection::join_agg_values(&agg_result.agg_record_time_info, |x| x.computer.clone());
         let tags_config_values: Vec<&CompactString> = TAGS_CONFIG.values().collect();
@@ -937,26 +1048,52 @@ impl Detection {
         );
         for (key, profile) in stored_static.profiles.as_ref().unwrap().iter() {
             match profile {
-                Timestamp(_) => {
+                Timestamp(zone) => {
+                    let time_format_options = &stored_static
+                        .output_option
+                        .as_ref()
+                        .unwrap()
+                        .time_format_options;
+                    let zone = if zone.is_empty() {
+                        time_format_options.timezone.as_deref()
+                    } else {
+                        Some(zone.as_ref())
+                    };
                     profile_converter.insert(
                         key.as_str(),
                         Timestamp(
-                            format_time(
+                            format_time_with_zone(
                                 &agg_result.start_datetime,
                                 false,
-                                &stored_static
-                                    .output_option
-                                    .as_ref()
-                                    .unwrap()
-                                    .time_format_options,
+                                time_format_options,
+                                zone,
                             )
                             .into(),
                         ),
                     );
                 }
+                // Aggregated results span several records, so there is no single original offset.
+                OriginalOffset(_) => {
+                    profile_converter.insert(key.as_str(), OriginalOffset("-".into()));
+                }
                 Computer(_) => {
                     profile_converter.insert(key.as_str(), Computer(computers.clone().into()));
                 }
+                AssetRole(_) | AssetOwner(_) | AssetTier(_) | AssetTags(_) => {
+                    profile_converter.insert(
+                        key.as_str(),
+                        profile
+                            .convert(&ASSET_INVENTORY.profile_value(profile, computers.as_str())),
+                    );
+                }
+                // The accounts of the individual records are not kept for aggregated results.
+                IdentityAccount(_)
+                | IdentityType(_)
+                | IdentityStatus(_)
+                | IdentityPrivilegedGroups(_) => {
+                    profile_converter
+                        .insert(key.as_str(), profile.convert(&CompactString::default()));
+                }
                 Channel(_) => {
                     profile_converter.insert(
                         key.as_str(),
@@ -1129,6 +1266,10 @@ impl Detection {
                 RecoveredRecord(_) => {
                     profile_converter.insert("RecoveredRecord", RenderedMessage("".into()));
                 }
+                ProcessAncestry(_) | CarveOffset(_) | SessionLogonType(_) | SessionSourceIP(_)
+                | SessionLogonTime(_) | SessionDuration(_) => {
+                    profile_converter.insert(key.as_str(), profile.convert(&"-".into()));
+                }
                 RenderedMessage(_) => {
                     profile_converter.insert(key.as_str(), RenderedMessage("-".into()));
                 }
@@ -1157,7 +1298,7 @@ impl Detection {
             ruleid: CompactString::from(rule.yaml["id"].as_str().unwrap_or("-")),
             ruletitle: CompactString::from(rule.yaml["title"].as_str().unwrap_or("-")),
             ruleauthor: CompactString::from(rule.yaml["author"].as_str().unwrap_or("-")),
-            level: level.clone(),
+            level,
             computername: CompactString::from("-"),
             eventid: CompactString::from("-"),
             rec_id: CompactString::from("-"),
@@ -1165,6 +1306,14 @@ impl Detection {
             output_fields: stored_static.profiles.as_ref().unwrap().to_owned(),
             agg_result: Some(agg_result),
             details_convert_map: HashMap::default(),
+            channel: CompactString::from("-"),
+            tags: Detection::get_raw_tags(rule)
+                .into_iter()
+                .map(CompactString::from)
+                .collect(),
+            risk_accounts: vec![],
+            process_key: None,
+            session_key: None,
         };
         let eventkey_alias = &stored_static.eventkey_alias;
 
@@ -1215,6 +1364,15 @@ impl Detection {
         )
     }
 
+    /// Returns the rule's tags as written in the rule, without the abbreviations of
+    /// `get_tag_info`.
+    fn get_raw_tags(rule: &RuleNode) -> Vec<&str> {
+        rule.yaml["tags"]
+            .as_vec()
+            .map(|tags| tags.iter().filter_map(|tag| tag.as_str()).collect())
+            .unwrap_or_default()
+    }
+
     /// Function that returns the detection output string for the count portion of the aggregation condition.
     fn create_count_output(rule: &RuleNode, agg_result: &AggResult) -> CompactString {
         let mut ret: String = "".to_string();
@@ -7,9 +7,12 @@ use crate::detections::rule::AggResult;
 use crate::detections::utils::{self, get_serde_number_to_string, write_color_buffer};
 use crate::level::LEVEL;
 use crate::options::profile::Profile::{
-    self, AllFieldInfo, Details, ExtraFieldInfo, Literal, SrcASN, SrcCity, SrcCountry, TgtASN,
-    TgtCity, TgtCountry,
+    self, AllFieldInfo, Details, ExtraFieldInfo, Literal, SrcASN, SrcCity, SrcCountry, Template,
+    TgtASN, TgtCity, TgtCountry,
 };
+use crate::options::profile_template::ProfileTemplate;
+use crate::timeline::logon_sessions::SessionKey;
+use crate::timeline::process_tree::ProcessKey;
 use chrono::{DateTime, Local, Utc};
 use compact_str::CompactString;
 use hashbrown::HashMap;
@@ -49,6 +52,21 @@ pub struct DetectInfo {
     // Per-field values keyed by "#Details" / "#AllFieldInfo" / "#ExtraFieldInfo", used by the JSON
     // output writers to expand those profile fields themselves.
     pub details_convert_map: HashMap<CompactString, Vec<CompactString>>,
+    // Channel of the matched record as written in the log, without the abbreviations of the
+    // Channel profile column. "-" for aggregation results.
+    pub channel: CompactString,
+    // Tags of the matched rule as written in the rule (ex: attack.execution, attack.t1059.001), so
+    // that the output does not depend on the MitreTactics/MitreTags profile columns.
+    pub tags: Vec<CompactString>,
+    // Accounts of the matched record that --risk-scoring adds the risk points to. Empty without
+    // the option and for aggregation results.
+    pub risk_accounts: Vec<CompactString>,
+    // Process that the matched record was logged for, used to fill in the ProcessAncestry column
+    // after the scan. Set only when the profile has that column.
+    pub process_key: Option<ProcessKey>,
+    // Logon session that the matched record was logged in, used to fill in the Session* columns
+    // after the scan. Set only when the profile has one of those columns.
+    pub session_key: Option<SessionKey>,
 }
 
 /// Namespace for console error/warning output and for writing the error log file.
@@ -289,6 +307,23 @@ pub fn create_message(
                     profile_converter.get(key.as_str()).unwrap().to_owned(),
                 ))
             }
+            Template(source) => {
+                let rendered = ProfileTemplate::cached(source)
+                    .map(|template| {
+                        template.render(|name| {
+                            resolve_template_value(
+                                name,
+                                event_record,
+                                profile_converter,
+                                eventkey_alias,
+                                field_data_map_key,
+                                field_data_map,
+                            )
+                        })
+                    })
+                    .unwrap_or_default();
+                replaced_profiles.push((key.to_owned(), Template(rendered.into())));
+            }
             _ => {
                 if let Some(converter_profile) = profile_converter.get(key.as_str()) {
                     let (parsed_message, _) = &parse_message(
@@ -313,6 +348,43 @@ pub fn create_message(
     detect_info
 }
 
+/// Returns the value of a `%name%` placeholder of a profile template. Built-in aliases such as
+/// `Computer` or `RuleTitle` reuse the value already computed for that column of the profile, a
+/// dotted name is read from that path of the record, and any other name is resolved like the
+/// `details` of rules.
+fn resolve_template_value(
+    name: &str,
+    event_record: &Value,
+    profile_converter: &HashMap<&str, Profile>,
+    eventkey_alias: &EventKeyAliasConfig,
+    field_data_map_key: &FieldDataMapKey,
+    field_data_map: &Option<FieldDataMap>,
+) -> Option<String> {
+    let alias = Profile::from(format!("%{name}%").as_str());
+    if !matches!(alias, Literal(_) | Template(_))
+        && let Some(profile) = profile_converter
+            .values()
+            .find(|p| std::mem::discriminant(*p) == std::mem::discriminant(&alias))
+    {
+        return Some(profile.to_value());
+    }
+    if name.contains('.') {
+        let value = name
+            .split('.')
+            .try_fold(event_record, |value, key| value.get(key))?;
+        return get_serde_number_to_string(value, false).map(|v| v.split_whitespace().join(" "));
+    }
+    let (value, _) = parse_message(
+        event_record,
+        &CompactString::from(format!("%{name}%")),
+        eventkey_alias,
+        false,
+        field_data_map_key,
+        field_data_map,
+    );
+    (value != "n/a").then(|| value.to_string())
+}
+
 /// Treats each %...% section in `output` as an alias and replaces it with the corresponding value
 /// looked up in the event record (via eventkey_alias.txt, falling back to Event.EventData.<name>).
 /// Returns the replaced message together with the "key: value" pairs that make up the details.
@@ -440,6 +512,19 @@ pub fn get_event_time(event_record: &Value, json_input_flag: bool) -> Option<Dat
     utils::str_time_to_datetime(system_time.as_str().unwrap_or(""))
 }
 
+/// Returns the UTC offset written in the @timestamp of a JSON record (ex: "+09:00"). evtx files
+/// always store SystemTime in UTC, so None is returned for them, as well as for JSON timestamps
+/// without an offset.
+pub fn get_event_offset(event_record: &Value, json_input_flag: bool) -> Option<CompactString> {
+    if !json_input_flag {
+        return None;
+    }
+    let system_time = event_record["Event"]["System"]["@timestamp"].as_str()?;
+    DateTime::parse_from_rfc3339(system_time)
+        .ok()
+        .map(|time| time.offset().to_string().into())
+}
+
 impl AlertMessage {
     /// Writes all errors accumulated in the error log stack to ./logs/errorlog-<timestamp>.log
     /// (creating the logs directory if needed and recording the command line that was run first),
@@ -514,8 +599,12 @@ impl AlertMessage {
 mod tests {
     use crate::detections::configs::{CURRENT_EXE_PATH, StoredStatic, load_eventkey_alias};
     use crate::detections::field_data_map::FieldDataMapKey;
-    use crate::detections::message::{AlertMessage, parse_message};
+    use crate::detections::message::{
+        AlertMessage, get_event_offset, parse_message, resolve_template_value,
+    };
     use crate::detections::utils;
+    use crate::options::profile::Profile;
+    use crate::options::profile_template::ProfileTemplate;
 
     use compact_str::CompactString;
     use hashbrown::HashMap;
@@ -535,6 +624,30 @@ mod tests {
         AlertMessage::warn(input).expect("[WARN] TESTWarn!");
     }
 
+    #[test]
+    fn test_get_event_offset() {
+        let record = |time: &str| -> Value {
+            serde_json::from_str(&format!(
+                r#"{{"Event": {{"System": {{"@timestamp": "{time}", "TimeCreated_attributes": {{"SystemTime": "{time}"}}}}}}}}"#
+            ))
+            .unwrap()
+        };
+        assert_eq!(
+            get_event_offset(&record("2025-01-02T03:04:05.000+09:00"), true),
+            Some("+09:00".into())
+        );
+        assert_eq!(
+            get_event_offset(&record("2025-01-02T03:04:05.000Z"), true),
+            Some("+00:00".into())
+        );
+        assert_eq!(get_event_offset(&record("2025-01-02 03:04:05"), true), None);
+        // evtx SystemTime is always UTC, so there is no original offset to report.
+        assert_eq!(
+            get_event_offset(&record("2025-01-02T03:04:05.000Z"), false),
+            None
+        );
+    }
+
     #[test]
     /// Verifies that %alias% keys in output (defined in eventkey_alias.txt) are replaced with the
     /// corresponding values from the target record.
@@ -842,6 +955,43 @@ mod tests {
             expected,
         );
     }
+    #[test]
+    /// Verifies that a template falls back to its default for a SessionDuration column, which is
+    /// only filled in after the scan.
+    fn test_template_with_session_duration() {
+        let event_record: Value = serde_json::from_str(
+            r#"{"Event": {"System": {"Computer": "PC01"}, "EventData": {"LogonType": 3}}}"#,
+        )
+        .unwrap();
+        let eventkey_alias = load_eventkey_alias(
+            utils::check_setting_path(
+                &CURRENT_EXE_PATH.to_path_buf(),
+                "rules/config/eventkey_alias.txt",
+                true,
+            )
+            .unwrap()
+            .to_str()
+            .unwrap(),
+        );
+        let profile_converter: HashMap<&str, Profile> = HashMap::from([
+            ("Computer", Profile::Computer("PC01".into())),
+            ("SessionDuration", Profile::SessionDuration("-".into())),
+        ]);
+        let rendered = ProfileTemplate::parse("%Computer% (%SessionDuration|default:n/a%)")
+            .unwrap()
+            .render(|name| {
+                resolve_template_value(
+                    name,
+                    &event_record,
+                    &profile_converter,
+                    &eventkey_alias,
+                    &FieldDataMapKey::default(),
+                    &None,
+                )
+            });
+        assert_eq!(rendered, "PC01 (n/a)");
+    }
+
     #[test]
     /// Loading test for the output filter config in mitre_tactics.txt.
     fn test_load_mitre_tactics_log() {
@@ -15,6 +15,7 @@ use std::vec;
 use std::{fs, io};
 
 use chrono::Local;
+use chrono::format::{Item, StrftimeItems};
 use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
 use compact_str::{CompactString, ToCompactString};
 use hashbrown::{HashMap, HashSet};
@@ -420,6 +421,7 @@ pub fn create_rec_info(
         data_string: data_str,
         key_to_value: flat_key_to_value,
         recovered_record: *recovered_record,
+        carve_offset: None,
     }
 }
 
@@ -678,13 +680,44 @@ pub fn format_time(
     date_only: bool,
     output_option: &TimeFormatOptions,
 ) -> CompactString {
-    if !(output_option.utc || output_option.iso_8601) {
+    format_time_with_zone(
+        time,
+        date_only,
+        output_option,
+        output_option.timezone.as_deref(),
+    )
+}
+
+/// Same as `format_time`, but converts the time to the given IANA time zone (ex: "Asia/Tokyo")
+/// instead of the one selected on the command line. Used by profile columns with their own zone.
+/// ISO 8601 output is always UTC, so the zone is ignored there.
+pub fn format_time_with_zone(
+    time: &DateTime<Utc>,
+    date_only: bool,
+    output_option: &TimeFormatOptions,
+    zone: Option<&str>,
+) -> CompactString {
+    if let Some(tz) = zone.and_then(|zone| zone.parse::<chrono_tz::Tz>().ok())
+        && !output_option.iso_8601
+    {
+        format_rfc(&time.with_timezone(&tz), date_only, output_option)
+    } else if !(output_option.utc || output_option.iso_8601) {
         format_rfc(&time.with_timezone(&Local), date_only, output_option)
     } else {
         format_rfc(time, date_only, output_option)
     }
 }
 
+/// Checks that the string is a known IANA time zone name.
+pub fn is_valid_timezone(zone: &str) -> bool {
+    zone.parse::<chrono_tz::Tz>().is_ok()
+}
+
+/// Checks that the string is a strftime pattern chrono can format.
+pub fn is_valid_time_format(pattern: &str) -> bool {
+    !StrftimeItems::new(pattern).any(|item| matches!(item, Item::Error))
+}
+
 /// Formats the time according to the selected time format option (RFC 2822, RFC 3339, US,
 /// US military, European or ISO 8601 style, defaulting to "YYYY-MM-DD hh:mm:ss.fff +-hh:mm").
 fn format_rfc<Tz: TimeZone>(
@@ -695,7 +728,11 @@ fn format_rfc<Tz: TimeZone>(
 where
     Tz::Offset: std::fmt::Display,
 {
-    if time_args.rfc_2822 {
+    if let Some(pattern) = time_args.time_format.as_deref()
+        && !date_only
+    {
+        time.format(pattern).to_compact_string()
+    } else if time_args.rfc_2822 {
         if date_only {
             time.format("%a, %e %b %Y").to_compact_string()
         } else {
@@ -998,6 +1035,49 @@ mod tests {
         assert!(check_setting_path(dir.path(), "geoip_field_mapping", false).is_none());
     }
 
+    #[test]
+    fn test_format_time_with_timezone_and_pattern() {
+        use crate::detections::configs::TimeFormatOptions;
+        use chrono::{TimeZone, Utc};
+        let time = Utc.with_ymd_and_hms(2022, 2, 22, 12, 0, 0).unwrap();
+        let tokyo = TimeFormatOptions {
+            timezone: Some("Asia/Tokyo".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            super::format_time(&time, false, &tokyo),
+            "2022-02-22 21:00:00.000 +09:00"
+        );
+        // A column zone wins over the command line zone, and ISO 8601 output stays UTC.
+        assert_eq!(
+            super::format_time_with_zone(&time, false, &tokyo, Some("America/New_York")),
+            "2022-02-22 07:00:00.000 -05:00"
+        );
+        let iso = TimeFormatOptions {
+            iso_8601: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            super::format_time_with_zone(&time, false, &iso, Some("Asia/Tokyo")),
+            "2022-02-22T12:00:00Z"
+        );
+        let pattern = TimeFormatOptions {
+            timezone: Some("Europe/Berlin".to_string()),
+            time_format: Some("%Y/%m/%d %H:%M %Z".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            super::format_time(&time, false, &pattern),
+            "2022/02/22 13:00 CET"
+        );
+        // Date only output keeps the date format.
+        assert_eq!(super::format_time(&time, true, &pattern), "2022-02-22");
+        assert!(super::is_valid_timezone("Asia/Tokyo"));
+        assert!(!super::is_valid_timezone("Asia/Tokio"));
+        assert!(super::is_valid_time_format("%d.%m.%Y %H:%M:%S%.3f"));
+        assert!(!super::is_valid_time_format("%Y-%m-%d %Q"));
+    }
+
     #[test]
     /// #1820: `parse_evtx_timestamp` must apply the Splunk-JSON UTC offset instead of discarding it
     /// (used by log-metrics and the eid-metrics/logon-summary time-range aggregators).
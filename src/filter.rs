@@ -151,60 +151,61 @@ fn peek_channel_from_evtx_first_record(
     channels
 }
 
-/// Matches the Channel values referenced by each rule against the channels actually present in
-/// the loaded evtx files. Returns the paths of the rules whose Channel matches at least one evtx
-/// channel, together with the matched channel names.
-fn extract_channel_from_rules(
-    rule_files: &Vec<RuleNode>,
-    evtx_channels: &HashSet<String>,
-) -> (Vec<String>, Vec<String>) {
-    // Recursively walks a rule's YAML tree and records every evtx channel matched by any
-    // "Channel:" value found in it.
-    fn visit_value(
-        key: &str,
-        value: &Yaml,
-        evtx_channels: &HashSet<String>,
-        intersection_channels: &mut Vec<String>,
-    ) {
+/// Returns the values of every "Channel:" field in a rule's YAML tree. The values can have leading
+/// and trailing wildcards (see channel_matches()).
+pub fn rule_channels(rule: &Yaml) -> Vec<&str> {
+    // Recursively walks the YAML tree and records the "Channel:" values found in it.
+    fn visit_value<'a>(key: &str, value: &'a Yaml, rule_channels: &mut Vec<&'a str>) {
         match *value {
-            Yaml::String(ref s) if key == "Channel" => {
-                if s.contains('*') {
-                    // The rule uses a wildcard in its Channel value: strip the leading/trailing
-                    // wildcards and treat the remainder as a substring match against each evtx
-                    // channel name.
-                    for ch in evtx_channels {
-                        if ch.contains(s.trim_matches('*')) {
-                            intersection_channels.push(ch.to_string());
-                        }
-                    }
-                } else if evtx_channels.contains(s) {
-                    intersection_channels.push(s.clone());
-                }
-            }
+            Yaml::String(ref s) if key == "Channel" => rule_channels.push(s),
             Yaml::Hash(ref map) => {
                 for (entry_key, entry_value) in map {
-                    visit_value(
-                        entry_key.as_str().unwrap(),
-                        entry_value,
-                        evtx_channels,
-                        intersection_channels,
-                    );
+                    visit_value(entry_key.as_str().unwrap(), entry_value, rule_channels);
                 }
             }
             Yaml::Array(ref seq) => {
                 for element in seq {
-                    visit_value(key, element, evtx_channels, intersection_channels);
+                    visit_value(key, element, rule_channels);
                 }
             }
             _ => {}
         }
     }
+    let mut channels = vec![];
+    visit_value("", rule, &mut channels);
+    channels
+}
+
+/// Returns true when the Channel value of a rule matches a channel found in the logs. A value
+/// with a wildcard has the leading/trailing wildcards stripped and is matched as a substring.
+pub fn channel_matches(rule_channel: &str, channel: &str) -> bool {
+    if rule_channel.contains('*') {
+        channel.contains(rule_channel.trim_matches('*'))
+    } else {
+        rule_channel == channel
+    }
+}
+
+/// Matches the Channel values referenced by each rule against the channels actually present in
+/// the loaded evtx files. Returns the paths of the rules whose Channel matches at least one evtx
+/// channel, together with the matched channel names.
+fn extract_channel_from_rules(
+    rule_files: &Vec<RuleNode>,
+    evtx_channels: &HashSet<String>,
+) -> (Vec<String>, Vec<String>) {
     let mut intersection_channels = vec![];
     let mut filtered_rule_paths = vec![];
     for rule in rule_files {
         let before_visit_len = intersection_channels.len();
-        visit_value("", &rule.yaml, evtx_channels, &mut intersection_channels);
-        // If the visit added any channel, this rule targets at least one loaded evtx channel.
+        for rule_channel in rule_channels(&rule.yaml) {
+            intersection_channels.extend(
+                evtx_channels
+                    .iter()
+                    .filter(|ch| channel_matches(rule_channel, ch))
+                    .cloned(),
+            );
+        }
+        // If any channel matched, this rule targets at least one loaded evtx channel.
         if before_visit_len < intersection_channels.len() {
             filtered_rule_paths.push(rule.rule_path.to_string());
         }
@@ -2,26 +2,12 @@ use crate::detections::configs::CURRENT_EXE_PATH;
 use crate::detections::message::AlertMessage;
 use crate::detections::utils;
 use crate::detections::utils::parse_csv;
+use crate::options::asset_inventory::ASSET_INVENTORY;
 use crate::results::Colors;
-use hashbrown::{HashMap, HashSet};
-use lazy_static::lazy_static;
+use hashbrown::HashMap;
 use rust_embed::Embed;
-lazy_static! {
-    // Computer names listed in config/critical_systems.txt. Alerts for these hosts get their
-    // level raised by one step (see LEVEL::convert). Missing file means an empty set.
-    static ref CRITICAL_SYSTEM: HashSet<String> = {
-        let current = CURRENT_EXE_PATH.to_path_buf();
-        let path = current.join("config/critical_systems.txt");
-        let content = fs::read_to_string(path).unwrap_or("".to_string());
-        content
-            .lines()
-            .map(|line| line.trim().to_string())
-            .collect()
-    };
-}
-use std::fs;
 use std::path::Path;
-use strum::EnumIter;
+use strum::{EnumIter, IntoEnumIterator};
 
 use termcolor::Color;
 
@@ -100,28 +86,28 @@ impl LEVEL {
         }
     }
 
-    /// Raises the level by one step when any of the computer names is listed in
-    /// config/critical_systems.txt; otherwise returns the level unchanged. `computer` may
-    /// contain several names joined with " ¦ " (count/correlation results combine the computer
-    /// names of all contributing records).
-    /// INFORMATIONAL stays INFORMATIONAL and EMERGENCY is already the maximum.
-    pub fn convert(&self, computer: &str) -> &LEVEL {
-        // If the computer is included in CRITICAL_SYSTEM, raise the level.
-        let computers = computer.split(" ¦ ");
-        for computer_name in computers {
-            if CRITICAL_SYSTEM.contains(computer_name) {
-                return match self {
-                    LEVEL::INFORMATIONAL => &LEVEL::INFORMATIONAL,
-                    LEVEL::LOW => &LEVEL::MEDIUM,
-                    LEVEL::MEDIUM => &LEVEL::HIGH,
-                    LEVEL::HIGH => &LEVEL::CRITICAL,
-                    LEVEL::CRITICAL => &LEVEL::EMERGENCY,
-                    LEVEL::EMERGENCY => &LEVEL::EMERGENCY,
-                    _ => &LEVEL::UNDEFINED,
-                };
-            }
+    /// Moves the level `steps` steps up (positive) or down (negative), stopping at INFORMATIONAL
+    /// and EMERGENCY. INFORMATIONAL alerts are never raised and UNDEFINED is left unchanged.
+    pub fn shift(&self, steps: i32) -> LEVEL {
+        if *self == LEVEL::UNDEFINED || (*self == LEVEL::INFORMATIONAL && steps > 0) {
+            return self.clone();
         }
-        self
+        let index = (self.index() as i32 + steps).clamp(
+            LEVEL::INFORMATIONAL.index() as i32,
+            LEVEL::EMERGENCY.index() as i32,
+        );
+        LEVEL::iter()
+            .find(|level| level.index() as i32 == index)
+            .unwrap_or_default()
+    }
+
+    /// Adjusts the level by the tier rule of the computer's asset (see
+    /// config/asset_inventory.csv and config/asset_tier_rules.txt). Hosts listed in
+    /// config/critical_systems.txt are raised by one step unless the tier rules say otherwise.
+    /// `computer` may contain several names joined with " ¦ " (count/correlation results combine
+    /// the computer names of all contributing records).
+    pub fn convert(&self, computer: &str) -> LEVEL {
+        self.shift(ASSET_INVENTORY.level_adjustment(computer))
     }
 }
 
@@ -319,8 +305,9 @@ mod tests {
 
     #[test]
     fn test_level_convert_returns_self_when_no_critical_system_match() {
-        // The default config/critical_systems.txt is empty, so no host name will match
-        // and convert() should return the original level for every variant.
+        // The default config/critical_systems.txt and config/asset_inventory.csv are empty, so
+        // no host name will match and convert() should return the original level for every
+        // variant.
         for level in [
             LEVEL::INFORMATIONAL,
             LEVEL::LOW,
@@ -330,19 +317,30 @@ mod tests {
             LEVEL::EMERGENCY,
             LEVEL::UNDEFINED,
         ] {
-            assert_eq!(level.convert("UNKNOWN-HOST"), &level);
+            assert_eq!(level.convert("UNKNOWN-HOST"), level);
         }
     }
 
     #[test]
     fn test_level_convert_handles_pipe_separated_computers() {
-        // convert() splits on " ¦ "; when none of the names match CRITICAL_SYSTEM
+        // convert() splits on " ¦ "; when none of the names are known assets
         // it should still return the original level rather than panic.
-        assert_eq!(
-            LEVEL::HIGH.convert("HOST-A ¦ HOST-B ¦ HOST-C"),
-            &LEVEL::HIGH
-        );
-        assert_eq!(LEVEL::LOW.convert(""), &LEVEL::LOW);
+        assert_eq!(LEVEL::HIGH.convert("HOST-A ¦ HOST-B ¦ HOST-C"), LEVEL::HIGH);
+        assert_eq!(LEVEL::LOW.convert(""), LEVEL::LOW);
+    }
+
+    #[test]
+    fn test_level_shift() {
+        assert_eq!(LEVEL::LOW.shift(1), LEVEL::MEDIUM);
+        assert_eq!(LEVEL::MEDIUM.shift(2), LEVEL::CRITICAL);
+        assert_eq!(LEVEL::CRITICAL.shift(3), LEVEL::EMERGENCY);
+        assert_eq!(LEVEL::HIGH.shift(-1), LEVEL::MEDIUM);
+        assert_eq!(LEVEL::LOW.shift(-5), LEVEL::INFORMATIONAL);
+        assert_eq!(LEVEL::HIGH.shift(0), LEVEL::HIGH);
+        // Informational alerts are never raised and undefined levels are never moved.
+        assert_eq!(LEVEL::INFORMATIONAL.shift(2), LEVEL::INFORMATIONAL);
+        assert_eq!(LEVEL::UNDEFINED.shift(1), LEVEL::UNDEFINED);
+        assert_eq!(LEVEL::UNDEFINED.shift(-1), LEVEL::UNDEFINED);
     }
 
     #[test]
@@ -1,3 +1,4 @@
+pub mod carve;
 pub mod debug;
 pub mod detections;
 pub mod filter;
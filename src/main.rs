@@ -14,10 +14,11 @@ use dialoguer::Confirm;
 use dialoguer::{Select, theme::ColorfulTheme};
 use evtx::{EvtxParser, ParserSettings, RecordAllocation};
 use hashbrown::{HashMap, HashSet};
+use hayabusa::carve::{CarveStats, ChunkCarver};
 use hayabusa::debug::checkpoint_process_timer::CheckPointProcessTimer;
 use hayabusa::detections::configs::{
-    Action, CURRENT_EXE_PATH, ConfigReader, EventKeyAliasConfig, ONE_CONFIG_MAP, OutputType,
-    StoredStatic, TargetEventTime, TargetIds, load_pivot_keywords, resolve_config_file,
+    Action, CURRENT_EXE_PATH, ConfigReader, ConvertFormat, EventKeyAliasConfig, ONE_CONFIG_MAP,
+    OutputType, StoredStatic, TargetEventTime, TargetIds, load_pivot_keywords, resolve_config_file,
 };
 use hayabusa::detections::detection::{self, EvtxRecordInfo};
 use hayabusa::detections::message::{AlertMessage, DetectInfo, get_event_time};
@@ -29,12 +30,19 @@ use hayabusa::detections::utils::{
 };
 use hayabusa::filter::{create_channel_filter, filter_evtx_files};
 use hayabusa::level::LEVEL;
+use hayabusa::options::attack_navigator;
+use hayabusa::options::diff_results;
 use hayabusa::options::htmlreport::{self, HtmlReporter};
 use hayabusa::options::pivot::create_output;
 use hayabusa::options::profile::set_default_profile;
+use hayabusa::options::risk_score;
 use hayabusa::options::{expand_list::expand_list, level_tuning::LevelTuning, update::Update};
 use hayabusa::results::{self, OutputWriter, ResultOutputState};
 use hayabusa::timeline::computer_metrics::countup_event_by_computer;
+use hayabusa::timeline::config_critical_systems::CRITICAL_SYSTEMS_CHANNELS;
+use hayabusa::timeline::convert::{ConvertedFile, map_field_data, strip_xml_declaration};
+use hayabusa::timeline::coverage::RuleCoverage;
+use hayabusa::timeline::log_integrity::integrity_output_path;
 use hayabusa::{detections::configs, timeline::timelines::Timeline};
 use hayabusa::{detections::utils::write_color_buffer, filter};
 use hayabusa::{options, yaml};
@@ -181,6 +189,21 @@ fn emit_or_buffer(
     result_state: &mut ResultOutputState,
 ) {
     if stored_static.is_low_memory {
+        // Only the logons, logoffs and processes scanned so far are known when streaming, so
+        // later ones leave "-".
+        if let Some(logon_sessions) = &stored_static.logon_sessions {
+            logon_sessions.read().unwrap().resolve_sessions(
+                log_records,
+                &stored_static
+                    .output_option
+                    .as_ref()
+                    .unwrap()
+                    .time_format_options,
+            );
+        }
+        if let Some(process_tree) = &stored_static.process_tree {
+            process_tree.read().unwrap().resolve_ancestries(log_records);
+        }
         let empty_ids = HashSet::new();
         results::emit_csv(
             log_records,
@@ -316,12 +339,16 @@ fn calculate_wizard_rule_count(
 
 /// Apply the ad-hoc channel filters used by the non-timeline subcommands: `logon-summary` and
 /// `config-critical-systems` each narrow `evtx_files` to those containing their required
-/// channels, and `log-metrics` applies its include/exclude channel/filename filters.
+/// channels, `log-metrics` applies its include/exclude channel/filename filters and `convert` its
+/// include/exclude channel filters.
 fn apply_channel_filters(
     mut evtx_files: Vec<PathBuf>,
     stored_static: &StoredStatic,
 ) -> Vec<PathBuf> {
-    if stored_static.logon_summary_flag && !stored_static.json_input_flag {
+    if stored_static.logon_summary_flag
+        && !stored_static.json_input_flag
+        && !stored_static.carve_flag
+    {
         // Create a channel filter for the logon summary.
         let yaml_str = r#"
             detection:
@@ -351,13 +378,16 @@ fn apply_channel_filters(
         stored_static.config.action.as_ref().unwrap(),
         Action::ConfigCriticalSystems(_)
     ) {
-        // Create a channel filter for config-critical-systems.
-        let yaml_str = r#"
-            detection:
-                selection:
-                    Channel: Security
-            "#;
-        let yaml_data = YamlLoader::load_from_str(yaml_str);
+        // Create a channel filter for config-critical-systems from the channels that contain
+        // role evidence.
+        let yaml_str = format!(
+            "detection:\n    selection:\n        Channel:\n{}",
+            CRITICAL_SYSTEMS_CHANNELS
+                .iter()
+                .map(|ch| format!("            - '{ch}'\n"))
+                .collect::<String>()
+        );
+        let yaml_data = YamlLoader::load_from_str(&yaml_str);
         let node = RuleNode::new(
             "config-critical-systems".to_string(),
             yaml_data.ok().unwrap_or_default().first().unwrap().clone(),
@@ -382,9 +412,52 @@ fn apply_channel_filters(
             &stored_static.error_log_stack,
         );
     }
+    if let Some(Action::Convert(opt)) = &stored_static.config.action {
+        evtx_files = filter_evtx_files(
+            evtx_files,
+            &opt.include_channel,
+            &None,
+            &opt.exclude_channel,
+            &None,
+            &stored_static.error_log_stack,
+        );
+    }
     evtx_files
 }
 
+/// Print what was found in the raw file given with --carve.
+fn output_carve_stats(stats: &CarveStats, stored_static: &StoredStatic) {
+    let counts = [
+        ("Carved evtx chunks: ", stats.chunks),
+        ("Invalid chunk signatures: ", stats.invalid_chunks),
+        ("Damaged chunks: ", stats.damaged_chunks),
+        ("Unparsed records: ", stats.unparsed_records),
+        ("Duplicate chunks: ", stats.duplicate_chunks),
+        ("Records outside of carved chunks: ", stats.orphan_records),
+    ];
+    println!();
+    for (label, cnt) in counts {
+        write_color_buffer(
+            &BufferWriter::stdout(ColorChoice::Always),
+            get_writable_color(
+                Some(Color::Rgb(0, 255, 0)),
+                stored_static.common_options.no_color,
+            ),
+            label,
+            false,
+        )
+        .ok();
+        write_color_buffer(
+            &BufferWriter::stdout(ColorChoice::Always),
+            None,
+            &cnt.to_formatted_string(&Locale::en),
+            true,
+        )
+        .ok();
+    }
+    println!();
+}
+
 /// Build the indicatif progress bar (spinner + bar) used for the per-file scan.
 fn build_progress_bar(stored_static: &StoredStatic, file_count: u64) -> ProgressBar {
     // Build the indicatif progress bar template. In the colored branch, the doubled braces
@@ -525,6 +598,49 @@ impl App {
         if !time_filter.is_parse_success() {
             return;
         }
+        if let Some(window) = stored_static
+            .output_option
+            .as_ref()
+            .and_then(|option| option.cluster_window.as_ref())
+            && results::parse_cluster_window(window).is_none()
+        {
+            AlertMessage::alert(
+                "Invalid cluster window. Please use one of the following formats: 1d, 1h, 30m, 90s",
+            )
+            .ok();
+            return;
+        }
+        if let Some(Action::EventAnomalies(opt)) = &stored_static.config.action
+            && let Some(interval) = opt.interval.as_ref()
+            && results::parse_cluster_window(interval).is_none()
+        {
+            AlertMessage::alert(
+                "Invalid interval. Please use one of the following formats: 1d, 1h, 30m",
+            )
+            .ok();
+            return;
+        }
+        if let Some(time_format_options) = stored_static
+            .output_option
+            .as_ref()
+            .map(|option| &option.time_format_options)
+        {
+            if let Some(zone) = time_format_options.timezone.as_ref()
+                && !utils::is_valid_timezone(zone)
+            {
+                AlertMessage::alert(&format!(
+                    "Unknown time zone: {zone}. Please specify an IANA time zone name such as Asia/Tokyo or America/New_York."
+                ))
+                .ok();
+                return;
+            }
+            if let Some(pattern) = time_format_options.time_format.as_ref()
+                && !utils::is_valid_time_format(pattern)
+            {
+                AlertMessage::alert(&format!("Invalid time format: {pattern}")).ok();
+                return;
+            }
+        }
 
         let _ = self.output_open_close_message("opening_messages.txt", stored_static);
         if let Action::ListContributors(_) = &stored_static.config.action.as_ref().unwrap() {
@@ -575,7 +691,8 @@ impl App {
         if matches!(
             stored_static.config.action.as_ref().unwrap(),
             Action::ConfigCriticalSystems(_)
-        ) {
+        ) && stored_static.output_path.is_none()
+        {
             Self::prompt_critical_systems_config(stored_static);
         }
 
@@ -613,6 +730,19 @@ impl App {
             if let Some(path) = output_path
                 && let Ok(metadata) = fs::metadata(path)
             {
+                // With --split-by the output path is a directory of result files.
+                let size = if metadata.is_dir() {
+                    fs::read_dir(path)
+                        .map(|entries| {
+                            entries
+                                .filter_map(|entry| entry.ok()?.metadata().ok())
+                                .map(|metadata| metadata.len())
+                                .sum::<u64>()
+                        })
+                        .unwrap_or_default()
+                } else {
+                    metadata.len()
+                };
                 let output_saved_str = format!("{message}:");
                 write_color_buffer(
                     &BufferWriter::stdout(ColorChoice::Always),
@@ -621,11 +751,7 @@ impl App {
                     false,
                 )
                 .ok();
-                let file_str = format!(
-                    " {} ({})",
-                    path.display(),
-                    ByteSize::b(metadata.len()).display()
-                );
+                let file_str = format!(" {} ({})", path.display(), ByteSize::b(size).display());
                 write_color_buffer(
                     &BufferWriter::stdout(ColorChoice::Always),
                     None,
@@ -691,22 +817,98 @@ impl App {
                         return;
                     }
                 }
-                if let Some(path) = &stored_static.output_path
+                if let Some(layer_path) = &stored_static
+                    .output_option
+                    .as_ref()
+                    .unwrap()
+                    .attack_navigator
                     && !stored_static
                         .output_option
                         .as_ref()
                         .unwrap()
                         .is_clobber_enabled()
                     && utils::check_file_expect_not_exist(
-                        path.as_path(),
+                        layer_path.as_path(),
                         format!(
                             " The file {} already exists. Please specify a different filename or add the -C, --clobber option to overwrite.\n",
-                            path.as_os_str().to_str().unwrap()
+                            layer_path.to_str().unwrap()
                         ),
                     )
                 {
                     return;
                 }
+                if let Some(layer_path) = &stored_static
+                    .output_option
+                    .as_ref()
+                    .unwrap()
+                    .attack_navigator
+                    && stored_static
+                        .output_option
+                        .as_ref()
+                        .unwrap()
+                        .attack_navigator_per_computer
+                    && !stored_static
+                        .output_option
+                        .as_ref()
+                        .unwrap()
+                        .is_clobber_enabled()
+                    && let Some(existing_layer) =
+                        attack_navigator::existing_computer_layers(layer_path).first()
+                    && utils::check_file_expect_not_exist(
+                        existing_layer.as_path(),
+                        format!(
+                            " The files with a base name of {} already exist. Please specify a different base filename or add the -C, --clobber option to overwrite.\n",
+                            layer_path.to_str().unwrap()
+                        ),
+                    )
+                {
+                    return;
+                }
+                if let Some(risk_path) = &stored_static.output_option.as_ref().unwrap().risk_output
+                    && !stored_static
+                        .output_option
+                        .as_ref()
+                        .unwrap()
+                        .is_clobber_enabled()
+                    && utils::check_file_expect_not_exist(
+                        risk_path.as_path(),
+                        format!(
+                            " The file {} already exists. Please specify a different filename or add the -C, --clobber option to overwrite.\n",
+                            risk_path.to_str().unwrap()
+                        ),
+                    )
+                {
+                    return;
+                }
+                if let Some(path) = &stored_static.output_path
+                    && !stored_static
+                        .output_option
+                        .as_ref()
+                        .unwrap()
+                        .is_clobber_enabled()
+                    && utils::check_file_expect_not_exist(
+                        path.as_path(),
+                        if stored_static
+                            .output_option
+                            .as_ref()
+                            .unwrap()
+                            .split_by
+                            .is_some()
+                        {
+                            format!(
+                                " The directory {} already exists. Please specify a different directory or add the -C, --clobber option to overwrite the files in it.\n",
+                                path.as_os_str().to_str().unwrap()
+                            )
+                        } else {
+                            format!(
+                                " The file {} already exists. Please specify a different filename or add the -C, --clobber option to overwrite.\n",
+                                path.as_os_str().to_str().unwrap()
+                            )
+                        },
+                    )
+                {
+                    return;
+                }
                 if stored_static.json_input_flag
                     && (stored_static.scan_all_evtx_files || stored_static.enable_all_rules)
                 {
@@ -735,10 +937,10 @@ impl App {
                     &mut html_reporter,
                 );
             }
-            Action::LogonSummary(_) => {
+            Action::LogonSummary(logon_summary_option) => {
                 let mut target_output_path = Nested::<String>::new();
                 if let Some(path) = &stored_static.output_path {
-                    for suffix in &["-successful.csv", "-failed.csv"] {
+                    for suffix in &["-successful.csv", "-failed.csv", "-authentication.csv"] {
                         let output_file = format!("{}{suffix}", path.to_str().unwrap());
                         if !stored_static
                             .output_option
@@ -758,6 +960,24 @@ impl App {
                         target_output_path.push(output_file);
                     }
                 }
+                if let Some(graph_path) = &logon_summary_option.graph_output {
+                    if !stored_static
+                        .output_option
+                        .as_ref()
+                        .unwrap()
+                        .is_clobber_enabled()
+                        && utils::check_file_expect_not_exist(
+                            graph_path.as_path(),
+                            format!(
+                                " The file {} already exists. Please specify a different filename or add the -C, --clobber option to overwrite.\n",
+                                graph_path.as_os_str().to_str().unwrap()
+                            ),
+                        )
+                    {
+                        return;
+                    }
+                    target_output_path.push(graph_path.to_str().unwrap().to_string());
+                }
                 self.analysis_start(
                     &target_extensions,
                     &time_filter,
@@ -772,6 +992,12 @@ impl App {
                     if target_path.ends_with("-failed.csv") {
                         msg = "Failed logon results"
                     }
+                    if target_path.ends_with("-authentication.csv") {
+                        msg = "Authentication findings"
+                    }
+                    if msg.is_empty() {
+                        msg = "Lateral movement graph"
+                    }
                     output_saved_file(
                         &Some(Path::new(target_path).to_path_buf()),
                         msg,
@@ -781,10 +1007,97 @@ impl App {
                 }
                 println!();
             }
+            Action::Coverage(_) => {
+                let mut target_output_path = Nested::<String>::new();
+                if let Some(path) = &stored_static.output_path {
+                    for suffix in &["-hosts.csv", "-blind-rules.csv"] {
+                        let output_file = format!("{}{suffix}", path.to_str().unwrap());
+                        if !stored_static
+                            .output_option
+                            .as_ref()
+                            .unwrap()
+                            .is_clobber_enabled()
+                            && utils::check_file_expect_not_exist(
+                                Path::new(output_file.as_str()),
+                                format!(
+                                    " The files with a base name of {} already exist. Please specify a different base filename or add the -C, --clobber option to overwrite.\n",
+                                    path.as_os_str().to_str().unwrap()
+                                ),
+                            )
+                        {
+                            return;
+                        }
+                        target_output_path.push(output_file);
+                    }
+                }
+                self.analysis_start(
+                    &target_extensions,
+                    &time_filter,
+                    stored_static,
+                    &mut html_reporter,
+                );
+                for target_path in target_output_path.iter() {
+                    let msg = if target_path.ends_with("-hosts.csv") {
+                        "Coverage by computer"
+                    } else {
+                        "Blind rules"
+                    };
+                    output_saved_file(
+                        &Some(Path::new(target_path).to_path_buf()),
+                        msg,
+                        &stored_static.html_report_flag,
+                        &mut html_reporter,
+                    );
+                }
+                println!();
+            }
+            Action::Convert(opt) => {
+                // The XML of a record cannot be rebuilt from JSON, and carved chunks are not
+                // files that can be converted one by one.
+                if stored_static.json_input_flag || stored_static.carve_flag {
+                    AlertMessage::alert(
+                        "The convert command only reads .evtx files, so -J, --json-input and --carve cannot be used.",
+                    )
+                    .ok();
+                    return;
+                }
+                // The XML is written as it was logged, so its field data cannot be rewritten.
+                if opt.field_data_mapping && opt.output_type == ConvertFormat::Xml {
+                    AlertMessage::alert(
+                        "--field-data-mapping can only be used with the JSONL output, so it cannot be used with -t xml.",
+                    )
+                    .ok();
+                    return;
+                }
+                if let Some(path) = &stored_static.output_path
+                    && let Err(err) = fs::create_dir_all(path)
+                {
+                    AlertMessage::alert(&format!("Failed to create the output directory. {err}"))
+                        .ok();
+                    self.failed.set(true);
+                    return;
+                }
+                self.analysis_start(
+                    &target_extensions,
+                    &time_filter,
+                    stored_static,
+                    &mut html_reporter,
+                );
+                output_saved_file(
+                    &stored_static.output_path,
+                    "Saved converted files",
+                    &stored_static.html_report_flag,
+                    &mut html_reporter,
+                );
+            }
             Action::EidMetrics(_)
+            | Action::EventAnomalies(_)
+            | Action::RareArtifacts(_)
+            | Action::ProcessTree(_)
             | Action::ComputerMetrics(_)
             | Action::LogMetrics(_)
             | Action::Search(_)
+            | Action::Sessions(_)
             | Action::ExtractBase64(_) => {
                 if let Some(path) = &stored_static.output_path
                     && !stored_static
@@ -802,6 +1115,28 @@ impl App {
                 {
                     return;
                 }
+                let integrity_path = match &stored_static.config.action {
+                    Some(Action::LogMetrics(opt)) if opt.integrity => {
+                        opt.output.as_deref().map(integrity_output_path)
+                    }
+                    _ => None,
+                };
+                if let Some(path) = &integrity_path
+                    && !stored_static
+                        .output_option
+                        .as_ref()
+                        .unwrap()
+                        .is_clobber_enabled()
+                    && utils::check_file_expect_not_exist(
+                        path.as_path(),
+                        format!(
+                            " The file {} already exists. Please specify a different filename or add the -C, --clobber option to overwrite.\n",
+                            path.as_os_str().to_str().unwrap()
+                        ),
+                    )
+                {
+                    return;
+                }
                 self.analysis_start(
                     &target_extensions,
                     &time_filter,
@@ -814,6 +1149,12 @@ impl App {
                     &stored_static.html_report_flag,
                     &mut html_reporter,
                 );
+                output_saved_file(
+                    &integrity_path,
+                    "Saved integrity evidence",
+                    &stored_static.html_report_flag,
+                    &mut html_reporter,
+                );
             }
             Action::PivotKeywordsList(_) => {
                 // Resolve pivot_keywords.txt through the `-c` custom config directory (falling back
@@ -886,13 +1227,61 @@ impl App {
                 self.run_expand_list(opt, stored_static);
                 return;
             }
+            Action::DiffResults(opt) => {
+                if let Some(path) = &stored_static.output_path
+                    && !stored_static
+                        .output_option
+                        .as_ref()
+                        .unwrap()
+                        .is_clobber_enabled()
+                    && utils::check_file_expect_not_exist(
+                        path.as_path(),
+                        format!(
+                            " The file {} already exists. Please specify a different filename or add the -C, --clobber option to overwrite.\n",
+                            path.as_os_str().to_str().unwrap()
+                        ),
+                    )
+                {
+                    return;
+                }
+                self.run_diff_results(opt, stored_static);
+                output_saved_file(
+                    &stored_static.output_path,
+                    "Saved file",
+                    &stored_static.html_report_flag,
+                    &mut html_reporter,
+                );
+                return;
+            }
             Action::ConfigCriticalSystems(_) => {
+                if let Some(path) = &stored_static.output_path
+                    && !stored_static
+                        .output_option
+                        .as_ref()
+                        .unwrap()
+                        .is_clobber_enabled()
+                    && utils::check_file_expect_not_exist(
+                        path.as_path(),
+                        format!(
+                            " The file {} already exists. Please specify a different filename or add the -C, --clobber option to overwrite.\n",
+                            path.as_os_str().to_str().unwrap()
+                        ),
+                    )
+                {
+                    return;
+                }
                 self.analysis_start(
                     &target_extensions,
                     &time_filter,
                     stored_static,
                     &mut html_reporter,
                 );
+                output_saved_file(
+                    &stored_static.output_path,
+                    "Saved file",
+                    &stored_static.html_report_flag,
+                    &mut html_reporter,
+                );
                 let _ = self.output_open_close_message("closing_messages.txt", stored_static);
                 return;
             }
@@ -904,8 +1293,10 @@ impl App {
 
     fn prompt_critical_systems_config(stored_static: &StoredStatic) {
         let msg = "This command tries to find critical systems like domain controllers and file servers by checking for logs that should only exist in those systems.
-It will search for Security 4768 (Kerberos TGT requested) events to determine if it is a domain controller.
-It will search for Security 5145 (Network Share File Access) events to determine if it is a file server.
+It will search for Security 4768/4769 (Kerberos tickets requested) events to determine if it is a domain controller.
+It will search for Security 5140/5145 (Network Share Access) events to determine if it is a file server.
+It will also look for Exchange, SQL Server, ADCS, ADFS, DNS, Hyper-V, RDS gateway and backup server logs.
+Each role is shown with a confidence score and the number of events that support it.
 Any hostnames added to the critical_systems.txt file will have all alerts above low increased by one level with a maximum of emergency level.";
         write_color_buffer(
             &BufferWriter::stdout(ColorChoice::Always),
@@ -1324,6 +1715,29 @@ Any hostnames added to the critical_systems.txt file will have all alerts above
         let _ = self.output_open_close_message("closing_messages.txt", stored_static);
     }
 
+    fn run_diff_results(&self, opt: &configs::DiffResultsOption, stored_static: &StoredStatic) {
+        let load = |path: &Path| match diff_results::load_results(path) {
+            Ok(rows) => Some(rows),
+            Err(e) => {
+                AlertMessage::alert(&e).ok();
+                None
+            }
+        };
+        let (Some(old), Some(new)) = (load(&opt.old), load(&opt.new)) else {
+            self.failed.set(true);
+            return;
+        };
+        println!();
+        let entries = diff_results::diff(&old, &new);
+        diff_results::print_summary(&entries, old.len(), new.len());
+        if let Some(path) = &stored_static.output_path
+            && let Err(e) = diff_results::output_csv(&entries, path)
+        {
+            AlertMessage::alert(&format!("Failed to write CSV. {e}")).ok();
+            self.failed.set(true);
+        }
+    }
+
     fn run_expand_list(&self, opt: &configs::ExpandListOption, stored_static: &StoredStatic) {
         let encoded_rule_dir = Path::new("./encoded_rules.yml");
         let rule_dir = if encoded_rule_dir.exists() {
@@ -1475,6 +1889,80 @@ Any hostnames added to the critical_systems.txt file will have all alerts above
                     stored_static.common_options.no_color,
                 )
             }
+            if let Some(layer_path) = &stored_static
+                .output_option
+                .as_ref()
+                .unwrap()
+                .attack_navigator
+            {
+                match attack_navigator::output_layers(
+                    &stored_static.attack_navigator_map,
+                    layer_path,
+                    stored_static
+                        .output_option
+                        .as_ref()
+                        .unwrap()
+                        .attack_navigator_per_computer,
+                ) {
+                    Ok(paths) => {
+                        for path in paths {
+                            write_color_buffer(
+                                &BufferWriter::stdout(ColorChoice::Always),
+                                get_writable_color(
+                                    Some(Color::Rgb(0, 255, 0)),
+                                    stored_static.common_options.no_color,
+                                ),
+                                "ATT&CK Navigator layer: ",
+                                false,
+                            )
+                            .ok();
+                            write_color_buffer(
+                                &BufferWriter::stdout(ColorChoice::Always),
+                                None,
+                                &path.display().to_string(),
+                                true,
+                            )
+                            .ok();
+                        }
+                    }
+                    Err(e) => {
+                        AlertMessage::alert(&format!(
+                            "Failed to save the ATT&CK Navigator layer. {e}"
+                        ))
+                        .ok();
+                    }
+                }
+            }
+            if let Some(risk_path) = &stored_static.output_option.as_ref().unwrap().risk_output {
+                let ranked = risk_score::ranked_entities(
+                    &stored_static.risk_score_map,
+                    stored_static.risk_weights.as_ref().unwrap(),
+                );
+                match risk_score::output_json(&ranked, risk_path) {
+                    Ok(_) => {
+                        write_color_buffer(
+                            &BufferWriter::stdout(ColorChoice::Always),
+                            get_writable_color(
+                                Some(Color::Rgb(0, 255, 0)),
+                                stored_static.common_options.no_color,
+                            ),
+                            "Risk scores: ",
+                            false,
+                        )
+                        .ok();
+                        write_color_buffer(
+                            &BufferWriter::stdout(ColorChoice::Always),
+                            None,
+                            &risk_path.display().to_string(),
+                            true,
+                        )
+                        .ok();
+                    }
+                    Err(e) => {
+                        AlertMessage::alert(&format!("Failed to save the risk scores. {e}")).ok();
+                    }
+                }
+            }
         }
 
         // If the -Q option is specified or there are no parse errors, the error stack is 0 and no error log file is generated.
@@ -1523,6 +2011,24 @@ Any hostnames added to the critical_systems.txt file will have all alerts above
                 stored_static.borrow_mut(),
                 html_reporter,
             );
+        } else if let Some(output_option) = stored_static.output_option.as_ref()
+            && let Some(carve_path) = &output_option.input_args.carve
+        {
+            if !carve_path.exists() {
+                AlertMessage::alert(&format!(
+                    " The file {} does not exist. Please specify a valid file path.",
+                    carve_path.display()
+                ))
+                .ok();
+                self.failed.set(true);
+                return;
+            }
+            self.analysis_files(
+                vec![carve_path.to_path_buf()],
+                time_filter,
+                stored_static.borrow_mut(),
+                html_reporter,
+            );
         } else if let Some(output_option) = &stored_static.output_option.as_ref()
             && let Some(directories) = &output_option.input_args.directory
         {
@@ -2168,6 +2674,18 @@ Any hostnames added to the critical_systems.txt file will have all alerts above
             wait_message = "Currently scanning for computer metrics. Please wait.";
         } else if stored_static.log_metrics_flag {
             wait_message = "Currently scanning for log metrics. Please wait.";
+        } else if stored_static.event_anomalies_flag {
+            wait_message = "Currently scanning for event anomalies. Please wait.";
+        } else if stored_static.rare_artifacts_flag {
+            wait_message = "Currently scanning for rare artifacts. Please wait.";
+        } else if stored_static.process_tree_flag {
+            wait_message = "Currently building process trees. Please wait.";
+        } else if stored_static.sessions_flag {
+            wait_message = "Currently scanning for logon sessions. Please wait.";
+        } else if stored_static.coverage_flag {
+            wait_message = "Currently checking rule coverage. Please wait.";
+        } else if stored_static.convert_flag {
+            wait_message = "Currently converting events. Please wait.";
         }
         if !wait_message.is_empty() {
             println!();
@@ -2185,9 +2703,10 @@ Any hostnames added to the critical_systems.txt file will have all alerts above
         println!();
 
         let mut rule_files = vec![];
+        let mut coverage = None;
         let need_rules = matches!(
             stored_static.config.action.as_ref().unwrap(),
-            Action::DfirTimeline(_) | Action::PivotKeywordsList(_)
+            Action::DfirTimeline(_) | Action::PivotKeywordsList(_) | Action::Coverage(_)
         );
         if need_rules {
             rule_files = detection::Detection::parse_rule_files(
@@ -2208,100 +2727,133 @@ Any hostnames added to the critical_systems.txt file will have all alerts above
                 self.failed.set(true);
                 return;
             }
-            if !stored_static.json_input_flag
-                && !stored_static.scan_all_evtx_files
-                && !stored_static.enable_all_rules
-            {
+            if stored_static.coverage_flag {
+                // The coverage command only reads the log sources of the rules, so they are not
+                // handed to the detection engine and the channel filter is skipped to keep the
+                // blind rules.
+                coverage = Some(RuleCoverage::from_rules(&std::mem::take(&mut rule_files)));
+            } else {
+                // A raw file to carve has no channels for the channel filter to check.
+                if !stored_static.json_input_flag
+                    && !stored_static.carve_flag
+                    && !stored_static.scan_all_evtx_files
+                    && !stored_static.enable_all_rules
+                {
+                    write_color_buffer(
+                        &BufferWriter::stdout(ColorChoice::Always),
+                        get_writable_color(
+                            Some(Color::Rgb(255, 175, 0)),
+                            stored_static.common_options.no_color,
+                        ),
+                        "Creating the channel filter. Please wait.",
+                        true,
+                    )
+                    .ok();
+                    println!();
+                    let mut channel_filter = create_channel_filter(
+                        &evtx_files,
+                        &rule_files,
+                        stored_static.quiet_errors_flag,
+                        &stored_static.error_log_stack,
+                    );
+                    if !stored_static.scan_all_evtx_files {
+                        evtx_files
+                            .retain(|evtx_file| channel_filter.scannable_rule_exists(evtx_file));
+                        write_color_buffer(
+                            &BufferWriter::stdout(ColorChoice::Always),
+                            get_writable_color(
+                                Some(Color::Rgb(0, 255, 0)),
+                                stored_static.common_options.no_color,
+                            ),
+                            "Evtx files loaded after channel filter: ",
+                            false,
+                        )
+                        .ok();
+                        write_color_buffer(
+                            &BufferWriter::stdout(ColorChoice::Always),
+                            None,
+                            evtx_files.len().to_formatted_string(&Locale::en).as_str(),
+                            true,
+                        )
+                        .ok();
+                    }
+                    if !stored_static.enable_all_rules {
+                        rule_files.retain(|rule| {
+                            channel_filter.rule_paths.contains(&rule.rule_path)
+                                || !rule.yaml["correlation"].is_badvalue()
+                        });
+                        let rules_after_channel_filter =
+                            rule_files.len().to_formatted_string(&Locale::en);
+                        write_color_buffer(
+                            &BufferWriter::stdout(ColorChoice::Always),
+                            get_writable_color(
+                                Some(Color::Rgb(0, 255, 0)),
+                                stored_static.common_options.no_color,
+                            ),
+                            "Detection rules enabled after channel filter: ",
+                            false,
+                        )
+                        .ok();
+                        write_color_buffer(
+                            &BufferWriter::stdout(ColorChoice::Always),
+                            None,
+                            rules_after_channel_filter.as_str(),
+                            true,
+                        )
+                        .ok();
+                        println!();
+                    }
+                }
+                output_profile_name(
+                    &stored_static.output_option,
+                    true,
+                    stored_static.common_options.no_color,
+                    html_reporter,
+                );
+                println!();
                 write_color_buffer(
                     &BufferWriter::stdout(ColorChoice::Always),
                     get_writable_color(
                         Some(Color::Rgb(255, 175, 0)),
                         stored_static.common_options.no_color,
                     ),
-                    "Creating the channel filter. Please wait.",
+                    "Scanning in progress. Please wait.",
                     true,
                 )
                 .ok();
                 println!();
-                let mut channel_filter = create_channel_filter(
-                    &evtx_files,
-                    &rule_files,
-                    stored_static.quiet_errors_flag,
-                    &stored_static.error_log_stack,
-                );
-                if !stored_static.scan_all_evtx_files {
-                    evtx_files.retain(|evtx_file| channel_filter.scannable_rule_exists(evtx_file));
-                    write_color_buffer(
-                        &BufferWriter::stdout(ColorChoice::Always),
-                        get_writable_color(
-                            Some(Color::Rgb(0, 255, 0)),
-                            stored_static.common_options.no_color,
-                        ),
-                        "Evtx files loaded after channel filter: ",
-                        false,
-                    )
-                    .ok();
-                    write_color_buffer(
-                        &BufferWriter::stdout(ColorChoice::Always),
-                        None,
-                        evtx_files.len().to_formatted_string(&Locale::en).as_str(),
-                        true,
-                    )
-                    .ok();
-                }
-                if !stored_static.enable_all_rules {
-                    rule_files.retain(|rule| {
-                        channel_filter.rule_paths.contains(&rule.rule_path)
-                            || !rule.yaml["correlation"].is_badvalue()
-                    });
-                    let rules_after_channel_filter =
-                        rule_files.len().to_formatted_string(&Locale::en);
-                    write_color_buffer(
-                        &BufferWriter::stdout(ColorChoice::Always),
-                        get_writable_color(
-                            Some(Color::Rgb(0, 255, 0)),
-                            stored_static.common_options.no_color,
-                        ),
-                        "Detection rules enabled after channel filter: ",
-                        false,
-                    )
-                    .ok();
-                    write_color_buffer(
-                        &BufferWriter::stdout(ColorChoice::Always),
-                        None,
-                        rules_after_channel_filter.as_str(),
-                        true,
-                    )
-                    .ok();
-                    println!();
-                }
             }
-            output_profile_name(
-                &stored_static.output_option,
-                true,
-                stored_static.common_options.no_color,
-                html_reporter,
-            );
-            println!();
-            write_color_buffer(
-                &BufferWriter::stdout(ColorChoice::Always),
-                get_writable_color(
-                    Some(Color::Rgb(255, 175, 0)),
-                    stored_static.common_options.no_color,
-                ),
-                "Scanning in progress. Please wait.",
-                true,
-            )
-            .ok();
-            println!();
         }
-
         evtx_files = apply_channel_filters(evtx_files, stored_static);
 
         let progress_bar = build_progress_bar(stored_static, evtx_files.len() as u64);
         self.rule_keys = self.get_all_keys(&rule_files);
         let mut detection = detection::Detection::new(rule_files);
         let mut timeline = Timeline::new();
+        if let Some(coverage) = coverage {
+            timeline.coverage = coverage;
+        }
+        // All of the converted files are named before the scan, so that nothing is written when
+        // one of them already exists.
+        if let Some(Action::Convert(opt)) = &stored_static.config.action
+            && !opt.clobber_opt.clobber
+            && evtx_files.iter().any(|evtx_file| {
+                let output_path =
+                    timeline
+                        .converter
+                        .output_path(&opt.output, evtx_file, opt.output_type);
+                utils::check_file_expect_not_exist(
+                    &output_path,
+                    format!(
+                        " The file {} already exists. Please specify a different output directory or add the -C, --clobber option to overwrite.\n",
+                        output_path.display()
+                    ),
+                )
+            })
+        {
+            self.failed.set(true);
+            return;
+        }
         // Snapshot `stored_static` once and share it with the per-rule parallel tasks in
         // `Detection::execute_rules` via cheap `Arc::clone`s. The Arc-wrapped inner fields
         // (e.g. error_log_stack, pivot_keyword) stay shared with the live `stored_static`,
@@ -2321,6 +2873,12 @@ Any hostnames added to the critical_systems.txt file will have all alerts above
                     | Action::ComputerMetrics(_)
                     | Action::LogMetrics(_)
                     | Action::EidMetrics(_)
+                    | Action::EventAnomalies(_)
+                    | Action::RareArtifacts(_)
+                    | Action::ProcessTree(_)
+                    | Action::Sessions(_)
+                    | Action::Coverage(_)
+                    | Action::Convert(_)
             );
         if is_show_progress {
             progress_bar.enable_steady_tick(Duration::from_millis(300));
@@ -2345,7 +2903,22 @@ Any hostnames added to the critical_systems.txt file will have all alerts above
             }
 
             let (detection_tmp, cnt_tmp, tl_tmp, recover_cnt_tmp, mut detect_infos) =
-                if evtx_file.extension().unwrap() == "json"
+                if stored_static.convert_flag {
+                    let (cnt, tl, recover_cnt) = self.analysis_convert_file(
+                        (evtx_file, time_filter, target_event_ids, stored_static),
+                        timeline.to_owned(),
+                    );
+                    (detection, cnt, tl, recover_cnt, vec![])
+                } else if stored_static.carve_flag {
+                    self.analysis_carved_file(
+                        (evtx_file, time_filter, target_event_ids, stored_static),
+                        &stored_static_arc,
+                        detection,
+                        timeline.to_owned(),
+                        &mut output_writer,
+                        &mut result_state,
+                    )
+                } else if evtx_file.extension().unwrap() == "json"
                     || evtx_file.extension().unwrap() == "jsonl"
                 {
                     self.analysis_json_file(
@@ -2375,6 +2948,9 @@ Any hostnames added to the critical_systems.txt file will have all alerts above
                 progress_bar.inc(1);
             }
         }
+        if stored_static.carve_flag {
+            progress_bar.suspend(|| output_carve_stats(&result_state.carve_stats, stored_static));
+        }
         let is_timeline_cmd = matches!(
             stored_static.config.action.as_ref().unwrap(),
             Action::DfirTimeline(_)
@@ -2400,12 +2976,24 @@ Any hostnames added to the critical_systems.txt file will have all alerts above
             timeline.computer_metrics_dsp_msg(stored_static)
         } else if stored_static.log_metrics_flag {
             timeline.log_metrics_dsp_msg(stored_static)
+        } else if stored_static.event_anomalies_flag {
+            timeline.event_anomalies_dsp_msg(event_timeline_config, stored_static)
+        } else if stored_static.rare_artifacts_flag {
+            timeline.rare_artifacts_dsp_msg(stored_static)
+        } else if stored_static.process_tree_flag {
+            timeline.process_tree_dsp_msg(stored_static)
+        } else if stored_static.sessions_flag {
+            timeline.sessions_dsp_msg(stored_static)
+        } else if stored_static.coverage_flag {
+            timeline.coverage_dsp_msg(stored_static)
+        } else if stored_static.convert_flag {
+            timeline.convert_dsp_msg(stored_static)
         } else if stored_static.extract_base64_flag {
             timeline.extract_base64_dsp_msg(stored_static)
         } else if let Action::ConfigCriticalSystems(_) =
             &stored_static.config.action.as_ref().unwrap()
         {
-            timeline.config_critical_systems_dsp_msg(stored_static.common_options.no_color);
+            timeline.config_critical_systems_dsp_msg(stored_static);
         }
         if is_timeline_cmd {
             let mut log_records = detection.add_aggcondition_msgs(&self.runtime, stored_static);
@@ -2470,11 +3058,19 @@ Any hostnames added to the critical_systems.txt file will have all alerts above
             }
 
             if let Some(Action::DfirTimeline(json_options)) = &stored_static.config.action
-                && matches!(json_options.output_type, OutputType::Jsonl)
-                && let Some(path) = &stored_static.output_path
-                && let Ok(mut file) = fs::OpenOptions::new().append(true).open(path)
+                && matches!(
+                    json_options.output_type,
+                    OutputType::Jsonl | OutputType::Timesketch | OutputType::Ecs
+                )
             {
-                let _ = file.write_all(b"\n");
+                let result_files = output_writer
+                    .shard_paths()
+                    .unwrap_or_else(|| stored_static.output_path.iter().cloned().collect());
+                for path in result_files {
+                    if let Ok(mut file) = fs::OpenOptions::new().append(true).open(path) {
+                        let _ = file.write_all(b"\n");
+                    }
+                }
             }
         }
         self.checkpoint.lap_checkpoint("Output Processing Time");
@@ -2569,45 +3165,9 @@ Any hostnames added to the critical_systems.txt file will have all alerts above
                     // The computer-metrics command does not perform detection, so only count and check the next record.
                     continue;
                 }
-                if !stored_static.search_flag {
-                    // Filter if the Computer name does not match include_computer or matches exclude_computer.
-                    if utils::is_filtered_by_computer_name(
-                        utils::get_event_value(
-                            "Event.System.Computer",
-                            data,
-                            &stored_static.eventkey_alias,
-                        ),
-                        (
-                            &stored_static.include_computer,
-                            &stored_static.exclude_computer,
-                        ),
-                    ) {
-                        continue;
-                    }
-
-                    // Filter if the EventID does not match include_eid, matches exclude_eid, or is not an EventID specified in target_eventids.txt.
-                    if self.is_filtered_by_eid(
-                        data,
-                        &stored_static.eventkey_alias,
-                        (&stored_static.include_eid, &stored_static.exclude_eid),
-                        stored_static.output_option.as_ref().unwrap().eid_filter,
-                        target_event_ids,
-                    ) {
-                        continue;
-                    }
-
-                    // Filter if channel is null.
-                    if !self._is_valid_channel(
-                        data,
-                        &stored_static.eventkey_alias,
-                        "Event.System.Channel",
-                    ) {
-                        continue;
-                    }
-                }
-                // Separated the time-based filtering condition branch to avoid confusion with EventID conditions.
-                let timestamp = get_event_time(data, stored_static.json_input_flag);
-                if !time_filter.is_target(&timestamp) {
+                if self
+                    .is_filtered_evtx_record((time_filter, target_event_ids, stored_static), data)
+                {
                     continue;
                 }
 
@@ -2620,7 +3180,7 @@ Any hostnames added to the critical_systems.txt file will have all alerts above
             }
 
             detection = self.process_detection_batch(
-                (records_per_detect, &path, &rec_ctx),
+                (records_per_detect, &path, None, &rec_ctx),
                 detection,
                 &mut timeline,
                 stored_static,
@@ -2642,17 +3202,266 @@ Any hostnames added to the critical_systems.txt file will have all alerts above
         )
     }
 
+    /// Carve evtx chunks out of a raw file (disk image, unallocated space, pagefile, memory dump)
+    /// and run the records of each chunk through the same filters, timeline processing and rule
+    /// detection as an .evtx file. All of the records are counted as recovered records, and the
+    /// offset of their chunk is output in the `%CarveOffset%` column.
+    fn analysis_carved_file(
+        &self,
+        (raw_filepath, time_filter, target_event_ids, stored_static): (
+            PathBuf,
+            &TargetEventTime,
+            &TargetIds,
+            &StoredStatic,
+        ),
+        stored_static_arc: &Arc<StoredStatic>,
+        mut detection: detection::Detection,
+        mut timeline: Timeline,
+        output_writer: &mut OutputWriter,
+        result_state: &mut ResultOutputState,
+    ) -> (
+        detection::Detection,
+        usize,
+        Timeline,
+        usize,
+        Vec<DetectInfo>,
+    ) {
+        let path = raw_filepath.display();
+        let mut record_cnt = 0;
+        let mut detect_infos: Vec<DetectInfo> = vec![];
+        let mut carver = match ChunkCarver::open(
+            &raw_filepath,
+            Self::parser_settings(stored_static),
+            stored_static.validate_checksum,
+        ) {
+            Ok(carver) => carver,
+            Err(e) => {
+                let errmsg =
+                    format!("Failed to open the file to carve.\nFile: {path}\nError: {e}\n");
+                AlertMessage::alert(&errmsg).ok();
+                self.failed.set(true);
+                return (detection, record_cnt, timeline, 0, detect_infos);
+            }
+        };
+        let need_rule = matches!(
+            stored_static.config.action.as_ref().unwrap(),
+            Action::DfirTimeline(_) | Action::PivotKeywordsList(_)
+        );
+        let rec_ctx = RecordBuildContext {
+            rule_keys: Arc::new(self.rule_keys.to_owned()),
+            eventkey_alias: Arc::new(stored_static.eventkey_alias.clone()),
+            no_pwsh_field_extraction: stored_static.no_pwsh_field_extraction,
+        };
+        for carved in carver.by_ref() {
+            let chunk = match carved {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    let errmsg =
+                        format!("Failed to read the file to carve.\nFile: {path}\nError: {e}\n");
+                    AlertMessage::alert(&errmsg).ok();
+                    break;
+                }
+            };
+            for e in &chunk.errors {
+                let errmsg = format!(
+                    "Failed to parse carved record.\nEventFile: {path}\nOffset: 0x{:X}\nError: {e}\n",
+                    chunk.offset
+                );
+                if stored_static.verbose_flag {
+                    AlertMessage::alert(&errmsg).ok();
+                }
+                if !stored_static.quiet_errors_flag {
+                    stored_static
+                        .error_log_stack
+                        .lock()
+                        .unwrap()
+                        .push(format!("[ERROR] {errmsg}"));
+                }
+            }
+            let mut records_per_detect = vec![];
+            for data in chunk.records {
+                record_cnt += 1;
+                if stored_static.computer_metrics_flag {
+                    countup_event_by_computer(&data, &stored_static.eventkey_alias, &mut timeline);
+                    continue;
+                }
+                if self
+                    .is_filtered_evtx_record((time_filter, target_event_ids, stored_static), &data)
+                {
+                    continue;
+                }
+                records_per_detect.push((data, true));
+            }
+            if records_per_detect.is_empty() {
+                continue;
+            }
+            detection = self.process_detection_batch(
+                (records_per_detect, &path, Some(chunk.offset), &rec_ctx),
+                detection,
+                &mut timeline,
+                stored_static,
+                stored_static_arc,
+                (&mut detect_infos, output_writer, result_state),
+                BatchPolicy {
+                    run_rules: need_rule,
+                    update_time_range: true,
+                },
+            );
+        }
+        result_state.carve_stats = carver.stats;
+        timeline.total_record_cnt += record_cnt;
+        (detection, record_cnt, timeline, record_cnt, detect_infos)
+    }
+
+    /// Write the records of an .evtx file that pass the computer, EventID, channel and time
+    /// filters to its own JSONL or XML file in the output directory of the convert command. The
+    /// records are not run through the rules. Returns the number of records read, the timeline
+    /// with the written file added, and the number of recovered records.
+    fn analysis_convert_file(
+        &self,
+        (evtx_filepath, time_filter, target_event_ids, stored_static): (
+            PathBuf,
+            &TargetEventTime,
+            &TargetIds,
+            &StoredStatic,
+        ),
+        mut timeline: Timeline,
+    ) -> (usize, Timeline, usize) {
+        let Some(Action::Convert(opt)) = &stored_static.config.action else {
+            return (0, timeline, 0);
+        };
+        let path = evtx_filepath.display();
+        let Some(mut parser) = self.evtx_to_jsons(&evtx_filepath, stored_static) else {
+            return (0, timeline, 0);
+        };
+        // The filters need the JSON of a record, so the XML is serialized by a second parser of
+        // the same file. Both parsers return the same records in the same order.
+        let mut xml_parser = None;
+        if opt.output_type == ConvertFormat::Xml {
+            xml_parser = self.evtx_to_jsons(&evtx_filepath, stored_static);
+            if xml_parser.is_none() {
+                return (0, timeline, 0);
+            }
+        }
+        let output_path =
+            timeline
+                .converter
+                .output_path(&opt.output, &evtx_filepath, opt.output_type);
+        let mut writer = match File::create(&output_path) {
+            Ok(file) => BufWriter::new(file),
+            Err(e) => {
+                AlertMessage::alert(&format!("Failed to create file. {e}")).ok();
+                self.failed.set(true);
+                return (0, timeline, 0);
+            }
+        };
+        let log_error = |errmsg: String| {
+            if stored_static.verbose_flag {
+                AlertMessage::alert(&errmsg).ok();
+            }
+            if !stored_static.quiet_errors_flag {
+                stored_static
+                    .error_log_stack
+                    .lock()
+                    .unwrap()
+                    .push(format!("[ERROR] {errmsg}"));
+            }
+        };
+        let mut xml_records = xml_parser.as_mut().map(|parser| parser.records());
+        let mut record_cnt = 0;
+        let mut recover_records_cnt = 0;
+        let mut event_cnt = 0;
+        let mut write_result = match opt.output_type {
+            ConvertFormat::Xml => {
+                writeln!(
+                    writer,
+                    "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<Events>"
+                )
+            }
+            ConvertFormat::Jsonl => Ok(()),
+        };
+        for record_result in parser.records_json_value() {
+            if write_result.is_err() {
+                break;
+            }
+            // Taken before any record is skipped to keep both parsers on the same record.
+            let xml_record = xml_records.as_mut().and_then(|records| records.next());
+            record_cnt += 1;
+            let record = match record_result {
+                Ok(record) => record,
+                Err(e) => {
+                    log_error(format!(
+                        "Failed to parse event file.\nEventFile: {path}\nError: {e}\n"
+                    ));
+                    continue;
+                }
+            };
+            if record.allocation == RecordAllocation::EmptyPage {
+                recover_records_cnt += 1;
+            }
+            if self.is_filtered_evtx_record(
+                (time_filter, target_event_ids, stored_static),
+                &record.data,
+            ) {
+                continue;
+            }
+            write_result = match xml_record {
+                Some(Ok(xml_record)) => {
+                    writeln!(writer, "{}", strip_xml_declaration(&xml_record.data))
+                }
+                Some(Err(e)) => {
+                    log_error(format!(
+                        "Failed to convert event record to XML.\nEventFile: {path}\nError: {e}\n"
+                    ));
+                    continue;
+                }
+                None => {
+                    let mut data = record.data;
+                    if let Some(field_data_map) = &stored_static.field_data_map {
+                        map_field_data(&mut data, field_data_map);
+                    }
+                    serde_json::to_writer(&mut writer, &data)
+                        .map_err(std::io::Error::from)
+                        .and_then(|_| writeln!(writer))
+                }
+            };
+            if write_result.is_ok() {
+                event_cnt += 1;
+            }
+        }
+        if opt.output_type == ConvertFormat::Xml && write_result.is_ok() {
+            write_result = writeln!(writer, "</Events>");
+        }
+        if let Err(e) = write_result.and_then(|_| writer.flush()) {
+            AlertMessage::alert(&format!(
+                "Failed to write file: {}. {e}",
+                output_path.display()
+            ))
+            .ok();
+            self.failed.set(true);
+        }
+        timeline.converter.files.push(ConvertedFile {
+            source: evtx_filepath,
+            output: output_path,
+            events: event_cnt,
+        });
+        timeline.total_record_cnt += record_cnt;
+        (record_cnt, timeline, recover_records_cnt)
+    }
+
     /// Runs one accumulated batch of records through the shared detection pipeline tail: build
-    /// EvtxRecordInfo structs, feed the timeline, and (when `run_rules`) run the rules and either
-    /// stream or buffer the results. `update_time_range` widens the results-summary detection time
-    /// span (the evtx path does this; the JSON path does not). This is the tail that was
-    /// copy-pasted at the end of both the evtx and JSON batch loops.
+    /// EvtxRecordInfo structs (with the chunk offset for carved records), feed the timeline, and
+    /// (when `run_rules`) run the rules and either stream or buffer the results. `update_time_range`
+    /// widens the results-summary detection time span (the evtx path does this; the JSON path does
+    /// not). This is the tail that was copy-pasted at the end of both the evtx and JSON batch
+    /// loops.
     #[allow(clippy::too_many_arguments)]
     fn process_detection_batch(
         &self,
-        (records_per_detect, path, rec_ctx): (
+        (records_per_detect, path, carve_offset, rec_ctx): (
             Vec<(Value, bool)>,
             &dyn Display,
+            Option<u64>,
             &RecordBuildContext,
         ),
         mut detection: detection::Detection,
@@ -2675,6 +3484,7 @@ Any hostnames added to the critical_systems.txt file will have all alerts above
         let records_per_detect = self.runtime.block_on(App::create_rec_infos(
             records_per_detect,
             path,
+            carve_offset,
             Arc::clone(&rec_ctx.rule_keys),
             Arc::clone(&rec_ctx.eventkey_alias),
             rec_ctx.no_pwsh_field_extraction,
@@ -2715,6 +3525,56 @@ Any hostnames added to the critical_systems.txt file will have all alerts above
         detection
     }
 
+    /// Apply the computer, EventID, channel, and timestamp filters to a record parsed from an evtx
+    /// file or carved chunk. The search command only filters by time. Returns true if the record
+    /// should be filtered out (skipped).
+    fn is_filtered_evtx_record(
+        &self,
+        (time_filter, target_event_ids, stored_static): (
+            &TargetEventTime,
+            &TargetIds,
+            &StoredStatic,
+        ),
+        data: &Value,
+    ) -> bool {
+        if !stored_static.search_flag {
+            // Filter if the Computer name does not match include_computer or matches exclude_computer.
+            if utils::is_filtered_by_computer_name(
+                utils::get_event_value(
+                    "Event.System.Computer",
+                    data,
+                    &stored_static.eventkey_alias,
+                ),
+                (
+                    &stored_static.include_computer,
+                    &stored_static.exclude_computer,
+                ),
+            ) {
+                return true;
+            }
+
+            // Filter if the EventID does not match include_eid, matches exclude_eid, or is not an EventID specified in target_eventids.txt.
+            if self.is_filtered_by_eid(
+                data,
+                &stored_static.eventkey_alias,
+                (&stored_static.include_eid, &stored_static.exclude_eid),
+                stored_static.output_option.as_ref().unwrap().eid_filter,
+                target_event_ids,
+            ) {
+                return true;
+            }
+
+            // Filter if channel is null.
+            if !self._is_valid_channel(data, &stored_static.eventkey_alias, "Event.System.Channel")
+            {
+                return true;
+            }
+        }
+        // Separated the time-based filtering condition branch to avoid confusion with EventID conditions.
+        let timestamp = get_event_time(data, stored_static.json_input_flag);
+        !time_filter.is_target(&timestamp)
+    }
+
     /// Apply the computer, EventID, channel, and timestamp filters to a JSON record. Returns
     /// true if the record should be filtered out (skipped).
     fn is_filtered_record(
@@ -3090,7 +3950,7 @@ Any hostnames added to the critical_systems.txt file will have all alerts above
             }
 
             detection = self.process_detection_batch(
-                (records_per_detect, &path, &rec_ctx),
+                (records_per_detect, &path, None, &rec_ctx),
                 detection,
                 &mut timeline,
                 stored_static,
@@ -3100,7 +3960,12 @@ Any hostnames added to the critical_systems.txt file will have all alerts above
                     run_rules: !(stored_static.metrics_flag
                         || stored_static.logon_summary_flag
                         || stored_static.log_metrics_flag
-                        || stored_static.search_flag),
+                        || stored_static.event_anomalies_flag
+                        || stored_static.rare_artifacts_flag
+                        || stored_static.process_tree_flag
+                        || stored_static.search_flag
+                        || stored_static.sessions_flag
+                        || stored_static.coverage_flag),
                     update_time_range: false,
                 },
             );
@@ -3120,6 +3985,7 @@ Any hostnames added to the critical_systems.txt file will have all alerts above
     async fn create_rec_infos(
         records_per_detect: Vec<(Value, bool)>,
         path: &dyn Display,
+        carve_offset: Option<u64>,
         rule_keys: Arc<Nested<String>>,
         eventkey_alias: Arc<EventKeyAliasConfig>,
         no_pwsh_field_extraction: bool,
@@ -3134,14 +4000,16 @@ Any hostnames added to the critical_systems.txt file will have all alerts above
                     let arc_no_pwsh_field_extraction = Arc::clone(&no_pwsh_field_extraction);
                     let arc_eventkey_alias = Arc::clone(&eventkey_alias);
                     spawn(async move {
-                        utils::create_rec_info(
+                        let mut rec_info = utils::create_rec_info(
                             rec,
                             arc_path.to_string(),
                             &arc_rule_keys,
                             &recovered_record_flag,
                             &arc_no_pwsh_field_extraction,
                             &arc_eventkey_alias,
-                        )
+                        );
+                        rec_info.carve_offset = carve_offset;
+                        rec_info
                     })
                 },
             );
@@ -3234,6 +4102,16 @@ Any hostnames added to the critical_systems.txt file will have all alerts above
             || (eid_filter && !self._is_target_event_id(data, target_event_ids, eventkey_alias))
     }
 
+    /// Build the evtx parser settings for JSON output from the command line options.
+    fn parser_settings(stored_static: &StoredStatic) -> ParserSettings {
+        // Change the default parser settings.
+        let mut parse_config =
+            ParserSettings::default().parse_empty_chunks(stored_static.enable_recover_records);
+        parse_config = parse_config.separate_json_attributes(true); // Set rules for converting XML attributes to JSON.
+        parse_config = parse_config.num_threads(stored_static.thread_number.unwrap_or(0));
+        parse_config.validate_checksums(stored_static.validate_checksum)
+    }
+
     /// Open an .evtx file and return an EvtxParser configured for JSON output. Returns None
     /// (after logging the error) if the file cannot be opened.
     fn evtx_to_jsons(
@@ -3243,14 +4121,8 @@ Any hostnames added to the critical_systems.txt file will have all alerts above
     ) -> Option<EvtxParser<File>> {
         match EvtxParser::from_path(evtx_filepath) {
             Ok(evtx_parser) => {
-                // Change the default parser settings.
-                let mut parse_config = ParserSettings::default()
-                    .parse_empty_chunks(stored_static.enable_recover_records);
-                parse_config = parse_config.separate_json_attributes(true); // Set rules for converting XML attributes to JSON.
-                parse_config = parse_config.num_threads(stored_static.thread_number.unwrap_or(0));
-                parse_config = parse_config.validate_checksums(stored_static.validate_checksum);
-
-                let evtx_parser = evtx_parser.with_configuration(parse_config);
+                let evtx_parser =
+                    evtx_parser.with_configuration(Self::parser_settings(stored_static));
                 Some(evtx_parser)
             }
             Err(e) => {
@@ -3407,9 +4279,15 @@ Any hostnames added to the critical_systems.txt file will have all alerts above
             Action::DfirTimeline(_)
             | Action::LogonSummary(_)
             | Action::EidMetrics(_)
+            | Action::EventAnomalies(_)
             | Action::PivotKeywordsList(_)
+            | Action::ProcessTree(_)
+            | Action::RareArtifacts(_)
             | Action::SetDefaultProfile(_)
             | Action::Search(_)
+            | Action::Sessions(_)
+            | Action::Coverage(_)
+            | Action::Convert(_)
             | Action::ComputerMetrics(_) => env::args().len() != 2,
             _ => true,
         }
@@ -3928,9 +4806,15 @@ mod tests {
         app.exec(&mut config_reader.app, &mut stored_static);
         let meta = fs::metadata(&out_overwrite_logon_clobber_successful_csv).unwrap();
         assert_ne!(meta.len(), 0);
-        // Delete the test files (LogonSummary writes both -successful and -failed).
+        // Delete the test files (LogonSummary writes -successful, -failed and -authentication).
         remove_file(&out_overwrite_logon_clobber_successful_csv).ok();
         remove_file(&out_overwrite_logon_clobber_failed_csv).ok();
+        remove_file(
+            output_tmp_dir
+                .path()
+                .join("overwrite_logon_clobber-authentication.csv"),
+        )
+        .ok();
     }
 
     #[test]
@@ -0,0 +1,300 @@
+use crate::detections::configs::CURRENT_EXE_PATH;
+use crate::detections::message::AlertMessage;
+use crate::detections::utils::parse_csv;
+use crate::options::profile::Profile;
+use cidr_utils::cidr::IpCidr;
+use compact_str::CompactString;
+use hashbrown::HashMap;
+use itertools::Itertools;
+use lazy_static::lazy_static;
+use std::fs;
+use std::net::IpAddr;
+use std::path::Path;
+use std::str::FromStr;
+
+lazy_static! {
+    // Loaded once from config/asset_inventory.csv, config/asset_tier_rules.txt and
+    // config/critical_systems.txt next to the executable. Missing files mean an empty inventory,
+    // in which case no level is adjusted and the asset columns stay empty.
+    pub static ref ASSET_INVENTORY: AssetInventory =
+        AssetInventory::load(&CURRENT_EXE_PATH.join("config"));
+}
+
+/// Tier given to hosts listed in critical_systems.txt that have no entry in the asset inventory.
+pub const CRITICAL_SYSTEMS_TIER: &str = "critical";
+
+/// One row of config/asset_inventory.csv.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Asset {
+    pub role: CompactString,
+    pub owner: CompactString,
+    pub tier: CompactString,
+    pub tags: Vec<CompactString>,
+}
+
+/// Asset inventory used for level adjustment and the `%Asset*%` profile columns. Hosts are
+/// identified by hostname, IP address or CIDR range; `tier_rules` maps each criticality tier to
+/// the number of levels its alerts are raised (positive) or lowered (negative).
+#[derive(Debug, Default)]
+pub struct AssetInventory {
+    hosts: HashMap<CompactString, Asset>,
+    ips: HashMap<IpAddr, Asset>,
+    // Kept in file order: the first range containing an address wins.
+    cidrs: Vec<(IpCidr, Asset)>,
+    tier_rules: HashMap<CompactString, i32>,
+}
+
+impl AssetInventory {
+    fn load(config_dir: &Path) -> Self {
+        let read = |name: &str| fs::read_to_string(config_dir.join(name)).unwrap_or_default();
+        let inventory = AssetInventory::from_contents(
+            &read("asset_inventory.csv"),
+            &read("critical_systems.txt"),
+            &read("asset_tier_rules.txt"),
+        );
+        if let Err(e) = &inventory {
+            AlertMessage::warn(&format!(
+                "Failed to load the asset inventory. Levels will not be adjusted. {e}"
+            ))
+            .ok();
+        }
+        inventory.unwrap_or_default()
+    }
+
+    /// Builds the inventory from the contents of asset_inventory.csv
+    /// (`Asset,Role,Owner,Tier,Tags`, tags separated by `;`), critical_systems.txt (one hostname
+    /// per line) and asset_tier_rules.txt (`Tier,Adjustment`). Hosts in critical_systems.txt get
+    /// the `critical` tier unless the inventory already describes them. When no rule is given
+    /// for the `critical` tier, it raises levels by one step as critical systems always have.
+    pub fn from_contents(
+        inventory: &str,
+        critical_systems: &str,
+        tier_rules: &str,
+    ) -> Result<Self, String> {
+        let mut ret = AssetInventory::default();
+        // Not parse_csv, which skips malformed rows: a row with the wrong number of columns is
+        // reported with its line number instead of silently leaving the host out.
+        let mut reader = csv::ReaderBuilder::new()
+            .flexible(false)
+            .from_reader(inventory.as_bytes());
+        let invalid_row = |line: u64| {
+            format!(
+                "Invalid row at line {line} of asset_inventory.csv. Rows must have 5 columns (Asset,Role,Owner,Tier,Tags)."
+            )
+        };
+        for row in reader.records() {
+            let row = row.map_err(|e| match e.kind() {
+                csv::ErrorKind::UnequalLengths { pos: Some(pos), .. } => invalid_row(pos.line()),
+                _ => format!("Failed to read asset_inventory.csv. {e}"),
+            })?;
+            if row.len() != 5 {
+                return Err(invalid_row(row.position().map_or(0, |pos| pos.line())));
+            }
+            let name = row[0].trim();
+            if name.is_empty() {
+                continue;
+            }
+            let asset = Asset {
+                role: row[1].trim().into(),
+                owner: row[2].trim().into(),
+                tier: row[3].trim().to_lowercase().into(),
+                tags: row[4]
+                    .split(';')
+                    .map(|tag| CompactString::from(tag.trim()))
+                    .filter(|tag| !tag.is_empty())
+                    .collect(),
+            };
+            if let Ok(ip) = IpAddr::from_str(name) {
+                ret.ips.insert(ip, asset);
+            } else if name.contains('/') {
+                let cidr = IpCidr::from_str(name)
+                    .map_err(|_| format!("Invalid CIDR range in asset_inventory.csv: {name}"))?;
+                ret.cidrs.push((cidr, asset));
+            } else {
+                let name = name.to_uppercase();
+                if let Some((short, _)) = name.split_once('.') {
+                    ret.hosts
+                        .entry(short.into())
+                        .or_insert_with(|| asset.clone());
+                }
+                ret.hosts.insert(name.into(), asset);
+            }
+        }
+        for host in critical_systems.lines().map(str::trim) {
+            if host.is_empty() {
+                continue;
+            }
+            ret.hosts
+                .entry(host.to_uppercase().into())
+                .or_insert_with(|| Asset {
+                    tier: CRITICAL_SYSTEMS_TIER.into(),
+                    ..Default::default()
+                });
+        }
+        ret.tier_rules.insert(CRITICAL_SYSTEMS_TIER.into(), 1);
+        for row in parse_csv(tier_rules).iter() {
+            if row.len() != 2 {
+                continue;
+            }
+            let adjustment = row[1].trim().parse::<i32>().map_err(|_| {
+                format!(
+                    "Invalid adjustment in asset_tier_rules.txt: {}. Please specify a number such as +1 or -1.",
+                    row.join(",")
+                )
+            })?;
+            ret.tier_rules
+                .insert(row[0].trim().to_lowercase().into(), adjustment);
+        }
+        Ok(ret)
+    }
+
+    /// Finds the asset for one computer name. Hostnames match case-insensitively, either as
+    /// written or by their short name (the part before the first dot). Computer names that are
+    /// IP addresses are matched against the IP and CIDR entries.
+    pub fn lookup(&self, computer: &str) -> Option<&Asset> {
+        let upper = computer.trim().to_uppercase();
+        if let Some(asset) = self.hosts.get(upper.as_str()) {
+            return Some(asset);
+        }
+        if let Some((short, _)) = upper.split_once('.')
+            && let Some(asset) = self.hosts.get(short)
+        {
+            return Some(asset);
+        }
+        let ip = IpAddr::from_str(computer.trim()).ok()?;
+        self.ips.get(&ip).or_else(|| {
+            self.cidrs
+                .iter()
+                .find(|(cidr, _)| cidr.contains(&ip))
+                .map(|(_, asset)| asset)
+        })
+    }
+
+    /// Number of levels to move alerts for `computer`, which may hold several names joined with
+    /// " ¦ " (count/correlation results). The largest adjustment among the names wins, and names
+    /// that are not in the inventory count as 0, so a result is only lowered when every host
+    /// involved belongs to a lowered tier.
+    pub fn level_adjustment(&self, computer: &str) -> i32 {
+        computer
+            .split(" ¦ ")
+            .map(|name| {
+                self.lookup(name)
+                    .and_then(|asset| self.tier_rules.get(&asset.tier))
+                    .copied()
+                    .unwrap_or(0)
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Returns the value of an `%Asset*%` profile column for `computer`. Values of several hosts
+    /// are de-duplicated and joined with " ¦ ". Hosts that are not in the inventory yield an
+    /// empty string.
+    pub fn profile_value(&self, profile: &Profile, computer: &str) -> CompactString {
+        computer
+            .split(" ¦ ")
+            .filter_map(|name| self.lookup(name))
+            .map(|asset| match profile {
+                Profile::AssetRole(_) => asset.role.to_string(),
+                Profile::AssetOwner(_) => asset.owner.to_string(),
+                Profile::AssetTier(_) => asset.tier.to_string(),
+                Profile::AssetTags(_) => asset.tags.iter().join(" ¦ "),
+                _ => String::default(),
+            })
+            .filter(|value| !value.is_empty())
+            .unique()
+            .join(" ¦ ")
+            .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AssetInventory, CRITICAL_SYSTEMS_TIER};
+    use crate::options::profile::Profile;
+
+    const INVENTORY: &str = "Asset,Role,Owner,Tier,Tags
+DC01,Domain Controller,Infra Team,tier0,ad;crown-jewel
+fs01.corp.local,File Server,Infra Team,Tier1,
+LAB-PC,Test Workstation,QA,tier3,lab
+10.0.5.20,Database,DBA,tier1,sql
+10.0.9.0/24,Kiosk,Facilities,tier3,
+";
+
+    const TIER_RULES: &str = "Tier,Adjustment
+tier0,+2
+tier1,1
+tier3,-1
+";
+
+    #[test]
+    fn test_lookup_by_hostname_ip_and_cidr() {
+        let inventory = AssetInventory::from_contents(INVENTORY, "", TIER_RULES).unwrap();
+        assert_eq!(inventory.lookup("dc01").unwrap().role, "Domain Controller");
+        assert_eq!(
+            inventory.lookup("DC01.corp.local").unwrap().owner,
+            "Infra Team"
+        );
+        assert_eq!(inventory.lookup("FS01.CORP.LOCAL").unwrap().tier, "tier1");
+        assert_eq!(inventory.lookup("10.0.5.20").unwrap().role, "Database");
+        assert_eq!(inventory.lookup("10.0.9.77").unwrap().role, "Kiosk");
+        assert!(inventory.lookup("10.0.8.1").is_none());
+        assert!(inventory.lookup("UNKNOWN-HOST").is_none());
+    }
+
+    #[test]
+    fn test_level_adjustment_per_tier() {
+        let inventory =
+            AssetInventory::from_contents(INVENTORY, "WEB01\nDC01", TIER_RULES).unwrap();
+        assert_eq!(inventory.level_adjustment("DC01"), 2);
+        assert_eq!(inventory.level_adjustment("fs01.corp.local"), 1);
+        assert_eq!(inventory.level_adjustment("LAB-PC"), -1);
+        assert_eq!(inventory.level_adjustment("UNKNOWN-HOST"), 0);
+        // critical_systems.txt hosts fall back to the built-in +1 of the critical tier, while
+        // hosts also in the inventory keep their inventory tier.
+        assert_eq!(
+            inventory.lookup("WEB01").unwrap().tier,
+            CRITICAL_SYSTEMS_TIER
+        );
+        assert_eq!(inventory.level_adjustment("WEB01"), 1);
+        // Aggregated results take the largest adjustment, with unknown hosts counting as 0.
+        assert_eq!(inventory.level_adjustment("LAB-PC ¦ DC01"), 2);
+        assert_eq!(inventory.level_adjustment("LAB-PC ¦ UNKNOWN-HOST"), 0);
+        assert_eq!(inventory.level_adjustment("LAB-PC ¦ 10.0.9.1"), -1);
+    }
+
+    #[test]
+    fn test_profile_value() {
+        let inventory = AssetInventory::from_contents(INVENTORY, "", TIER_RULES).unwrap();
+        let tags = Profile::from("%AssetTags%");
+        assert_eq!(inventory.profile_value(&tags, "DC01"), "ad ¦ crown-jewel");
+        assert_eq!(inventory.profile_value(&tags, "FS01"), "");
+        let owner = Profile::from("%AssetOwner%");
+        assert_eq!(
+            inventory.profile_value(&owner, "DC01 ¦ FS01 ¦ 10.0.5.20"),
+            "Infra Team ¦ DBA"
+        );
+        assert_eq!(inventory.profile_value(&owner, "UNKNOWN-HOST"), "");
+    }
+
+    #[test]
+    fn test_invalid_inventory() {
+        assert!(
+            AssetInventory::from_contents(
+                "Asset,Role,Owner,Tier,Tags\n10.0.0.0/99,a,b,c,d\n",
+                "",
+                ""
+            )
+            .is_err()
+        );
+        assert!(AssetInventory::from_contents("", "", "Tier,Adjustment\ntier0,up\n").is_err());
+        // A row with a missing column is reported with its line number.
+        let err = AssetInventory::from_contents(
+            "Asset,Role,Owner,Tier,Tags\nDC01,Domain Controller,Infra Team,tier0,ad\nFS01,File Server,Infra Team\n",
+            "",
+            "",
+        )
+        .unwrap_err();
+        assert!(err.contains("line 3"), "{err}");
+    }
+}
@@ -0,0 +1,316 @@
+use crate::level::LEVEL;
+use compact_str::CompactString;
+use dashmap::DashMap;
+use hashbrown::HashMap;
+use itertools::Itertools;
+use serde_json::{Value, json};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Detection count and highest level of one technique, overall or on one computer.
+#[derive(Debug, Clone, Default)]
+pub struct TechniqueScore {
+    pub count: usize,
+    pub max_level: LEVEL,
+}
+
+impl TechniqueScore {
+    fn add(&mut self, level: &LEVEL) {
+        self.count += 1;
+        if level.index() > self.max_level.index() {
+            self.max_level = level.clone();
+        }
+    }
+}
+
+/// Scores of one technique. `total` counts each detection once, even when a count/correlation
+/// result spans several computers, while `computers` counts it on each of them.
+#[derive(Debug, Clone, Default)]
+pub struct TechniqueStats {
+    pub total: TechniqueScore,
+    pub computers: HashMap<CompactString, TechniqueScore>,
+}
+
+/// Technique ID (ex: T1059.001) to its scores. Filled when the detections are output.
+pub type AttackNavigatorMap = DashMap<CompactString, TechniqueStats>;
+
+/// Returns the ATT&CK technique IDs (ex: `attack.t1059.001` -> `T1059.001`) in a rule's tags.
+/// Tactic, group and software tags are ignored.
+pub fn technique_ids<'a>(tags: impl Iterator<Item = &'a str>) -> Vec<CompactString> {
+    tags.filter_map(|tag| tag.strip_prefix("attack.t"))
+        .filter(|id| id.starts_with(|c: char| c.is_ascii_digit()))
+        .map(|id| CompactString::from(format!("T{id}").to_uppercase()))
+        .unique()
+        .collect()
+}
+
+/// Adds one detection to the scores of each of its techniques. `computer` may hold several names
+/// joined with " ¦ " (count/correlation results).
+pub fn add_detection(
+    map: &AttackNavigatorMap,
+    techniques: &[CompactString],
+    computer: &str,
+    level: &LEVEL,
+) {
+    let computers = computer
+        .split(" ¦ ")
+        .map(str::trim)
+        .filter(|name| !name.is_empty() && *name != "-")
+        .unique()
+        .collect_vec();
+    for technique in techniques {
+        let mut stats = map.entry(technique.clone()).or_default();
+        stats.total.add(level);
+        for name in &computers {
+            stats
+                .computers
+                .entry(CompactString::from(*name))
+                .or_default()
+                .add(level);
+        }
+    }
+}
+
+fn level_color(level: &LEVEL) -> &'static str {
+    match level {
+        LEVEL::EMERGENCY => "#b30000",
+        LEVEL::CRITICAL => "#ff0000",
+        LEVEL::HIGH => "#ff8c00",
+        LEVEL::MEDIUM => "#ffd700",
+        LEVEL::LOW => "#66ccff",
+        _ => "#d9d9d9",
+    }
+}
+
+/// Creates an ATT&CK Navigator layer (layer format 4.5). The score of each technique is its
+/// detection count and the color is taken from its highest level.
+pub fn create_layer<'a>(
+    name: &str,
+    scores: impl Iterator<Item = (&'a CompactString, &'a TechniqueScore)>,
+) -> Value {
+    let scores = scores.sorted_by(|a, b| a.0.cmp(b.0)).collect_vec();
+    let max_count = scores.iter().map(|(_, s)| s.count).max().unwrap_or(1);
+    let techniques = scores
+        .iter()
+        .map(|(technique, score)| {
+            json!({
+                "techniqueID": technique.as_str(),
+                "score": score.count,
+                "color": level_color(&score.max_level),
+                "comment": format!("Detections: {} / Max level: {}", score.count, score.max_level.to_full()),
+                "metadata": [
+                    {"name": "Detections", "value": score.count.to_string()},
+                    {"name": "Max level", "value": score.max_level.to_full()},
+                ],
+                "enabled": true,
+                "showSubtechniques": true,
+            })
+        })
+        .collect_vec();
+    let legend = [
+        LEVEL::EMERGENCY,
+        LEVEL::CRITICAL,
+        LEVEL::HIGH,
+        LEVEL::MEDIUM,
+        LEVEL::LOW,
+        LEVEL::INFORMATIONAL,
+    ]
+    .iter()
+    .map(|level| json!({"label": level.to_full(), "color": level_color(level)}))
+    .collect_vec();
+    json!({
+        "name": name,
+        "versions": {"attack": "16", "navigator": "5.1.0", "layer": "4.5"},
+        "domain": "enterprise-attack",
+        "description": "Techniques detected by Hayabusa. Score: detection count. Color: highest level.",
+        "sorting": 3,
+        "layout": {"layout": "side", "showAggregateScores": true, "aggregateFunction": "max"},
+        "hideDisabled": false,
+        "techniques": techniques,
+        "gradient": {"colors": ["#ffffff", "#ff6666"], "minValue": 0, "maxValue": max_count},
+        "legendItems": legend,
+        "showTacticRowBackground": false,
+        "selectTechniquesAcrossTactics": true,
+        "selectSubtechniquesWithParent": false,
+    })
+}
+
+/// Returns the per-computer layer path: `<stem>-<computer>.<ext>` next to `path`. Characters
+/// that cannot be used in file names are replaced with `_`.
+pub fn computer_layer_path(path: &Path, computer: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+    let computer = computer
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '.' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    path.with_file_name(format!("{stem}-{computer}{extension}"))
+}
+
+/// Returns the per-computer layers of a previous run next to `path` (`<stem>-*.<ext>`).
+pub fn existing_computer_layers(path: &Path) -> Vec<PathBuf> {
+    let prefix = format!(
+        "{}-",
+        path.file_stem().unwrap_or_default().to_string_lossy()
+    );
+    let extension = path.extension();
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let Ok(entries) = fs::read_dir(dir) else {
+        return vec![];
+    };
+    entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|entry| {
+            entry.is_file()
+                && entry.extension() == extension
+                && entry
+                    .file_name()
+                    .is_some_and(|name| name.to_string_lossy().starts_with(&prefix))
+        })
+        .sorted()
+        .collect()
+}
+
+/// Writes the layer of all detections to `path` and, with `per_computer`, one layer per computer
+/// next to it. Returns the written paths.
+pub fn output_layers(
+    map: &AttackNavigatorMap,
+    path: &Path,
+    per_computer: bool,
+) -> io::Result<Vec<PathBuf>> {
+    let write = |path: &Path, layer: Value| -> io::Result<()> {
+        let mut out = serde_json::to_string_pretty(&layer).unwrap();
+        out.push('\n');
+        fs::write(path, out)
+    };
+    let totals = map
+        .iter()
+        .map(|entry| (entry.key().clone(), entry.value().total.clone()))
+        .collect_vec();
+    write(
+        path,
+        create_layer("Hayabusa detections", totals.iter().map(|(t, s)| (t, s))),
+    )?;
+    let mut written = vec![path.to_path_buf()];
+    if !per_computer {
+        return Ok(written);
+    }
+    let mut by_computer: HashMap<CompactString, Vec<(CompactString, TechniqueScore)>> =
+        HashMap::new();
+    for entry in map.iter() {
+        for (computer, score) in entry.value().computers.iter() {
+            by_computer
+                .entry(computer.clone())
+                .or_default()
+                .push((entry.key().clone(), score.clone()));
+        }
+    }
+    for (computer, scores) in by_computer.iter().sorted_by(|a, b| a.0.cmp(b.0)) {
+        let computer_path = computer_layer_path(path, computer);
+        write(
+            &computer_path,
+            create_layer(
+                &format!("Hayabusa detections - {computer}"),
+                scores.iter().map(|(t, s)| (t, s)),
+            ),
+        )?;
+        written.push(computer_path);
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        AttackNavigatorMap, add_detection, computer_layer_path, create_layer,
+        existing_computer_layers, technique_ids,
+    };
+    use crate::level::LEVEL;
+    use compact_str::CompactString;
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn test_technique_ids() {
+        let tags = [
+            "attack.execution",
+            "attack.t1059.001",
+            "attack.t1003",
+            "attack.g0032",
+            "attack.s0002",
+            "attack.t1059.001",
+            "car.2016-04-005",
+        ];
+        assert_eq!(technique_ids(tags.into_iter()), vec!["T1059.001", "T1003"]);
+    }
+
+    #[test]
+    fn test_layer_scores() {
+        let map = AttackNavigatorMap::new();
+        let t1059 = technique_ids(["attack.t1059.001"].into_iter());
+        add_detection(&map, &t1059, "PC-01", &LEVEL::MEDIUM);
+        add_detection(&map, &t1059, "PC-01", &LEVEL::HIGH);
+        add_detection(&map, &t1059, "PC-02 ¦ PC-03", &LEVEL::LOW);
+
+        let stats = map.get("T1059.001").unwrap();
+        assert_eq!(stats.total.count, 3);
+        assert_eq!(stats.total.max_level, LEVEL::HIGH);
+        assert_eq!(stats.computers["PC-01"].count, 2);
+        assert_eq!(stats.computers["PC-03"].max_level, LEVEL::LOW);
+
+        let totals = vec![(CompactString::from("T1059.001"), stats.total.clone())];
+        let layer = create_layer("test", totals.iter().map(|(t, s)| (t, s)));
+        let technique = &layer["techniques"][0];
+        assert_eq!(technique["techniqueID"], "T1059.001");
+        assert_eq!(technique["score"], 3);
+        assert_eq!(technique["color"], "#ff8c00");
+        assert_eq!(layer["domain"], "enterprise-attack");
+        assert_eq!(layer["gradient"]["maxValue"], 3);
+    }
+
+    #[test]
+    fn test_computer_layer_path() {
+        assert_eq!(
+            computer_layer_path(Path::new("out/layer.json"), "DC01.corp.local"),
+            Path::new("out/layer-DC01.corp.local.json")
+        );
+        assert_eq!(
+            computer_layer_path(Path::new("layer"), "A/B:C"),
+            Path::new("layer-A_B_C")
+        );
+    }
+
+    #[test]
+    fn test_existing_computer_layers() {
+        let dir = std::env::temp_dir().join("hayabusa-test-navigator-layers");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for name in [
+            "layer.json",
+            "layer-PC01.json",
+            "layer-PC02.json",
+            "layer-PC03.txt",
+        ] {
+            fs::write(dir.join(name), "{}").unwrap();
+        }
+        assert_eq!(
+            existing_computer_layers(&dir.join("layer.json")),
+            vec![dir.join("layer-PC01.json"), dir.join("layer-PC02.json")]
+        );
+        assert!(existing_computer_layers(&dir.join("other.json")).is_empty());
+        fs::remove_dir_all(&dir).ok();
+    }
+}
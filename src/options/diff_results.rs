@@ -0,0 +1,583 @@
+use crate::level::LEVEL;
+use chrono::{DateTime, SubsecRound, Utc};
+use comfy_table::modifiers::UTF8_ROUND_CORNERS;
+use comfy_table::presets::UTF8_FULL;
+use comfy_table::{Cell, Table};
+use compact_str::CompactString;
+use csv::{QuoteStyle, ReaderBuilder, WriterBuilder};
+use hashbrown::HashMap;
+use itertools::Itertools;
+use num_format::{Locale, ToFormattedString};
+use serde_json::Value;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::Path;
+use strum::IntoEnumIterator;
+
+/// Column names of the fields used to match detections, in order of preference. The first name
+/// is the Hayabusa profile column and the others are the `-t timesketch` and `-t ecs` fields.
+const TIMESTAMP_FIELDS: [&str; 3] = ["Timestamp", "datetime", "@timestamp"];
+const RULE_ID_FIELDS: [&str; 3] = ["RuleID", "rule_id", "rule.id"];
+const RULE_TITLE_FIELDS: [&str; 3] = ["RuleTitle", "rule_title", "rule.name"];
+const COMPUTER_FIELDS: [&str; 3] = ["Computer", "computer", "host.name"];
+const RECORD_ID_FIELDS: [&str; 3] = ["RecordID", "record_id", "winlog.record_id"];
+const LEVEL_FIELDS: [&str; 3] = ["Level", "level", "log.level"];
+
+/// Timestamp formats of the default, `--rfc-3339`, `--us-time`, `--us-military-time`,
+/// `--european-time` and `--rfc-2822` output. `--iso-8601` and the `-t timesketch`/`-t ecs` fields
+/// are read as RFC 3339.
+const TIMESTAMP_FORMATS: [&str; 6] = [
+    "%Y-%m-%d %H:%M:%S%.f %:z",
+    "%Y-%m-%d %H:%M:%S%.f%:z",
+    "%m-%d-%Y %I:%M:%S%.f %p %:z",
+    "%m-%d-%Y %H:%M:%S%.f %:z",
+    "%d-%m-%Y %H:%M:%S%.f %:z",
+    "%a, %e %b %Y %H:%M:%S %:z",
+];
+
+/// One detection read from a results file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResultRow {
+    /// The timestamp as written in the results, and below converted to UTC.
+    pub timestamp: CompactString,
+    pub time: DateTime<Utc>,
+    pub rule_id: CompactString,
+    pub rule_title: CompactString,
+    pub computer: CompactString,
+    pub record_id: CompactString,
+    pub level: LEVEL,
+}
+
+impl ResultRow {
+    /// Detections are matched by rule, computer, event record ID and timestamp. Profiles without
+    /// a RuleID column (ex: minimal) fall back to the rule title. The timestamp is compared in
+    /// whole seconds, as `--rfc-2822` does not write fractions of a second.
+    fn key(&self) -> (&str, &str, &str, DateTime<Utc>) {
+        let rule = if self.rule_id.is_empty() {
+            &self.rule_title
+        } else {
+            &self.rule_id
+        };
+        (
+            rule.as_str(),
+            self.computer.as_str(),
+            self.record_id.as_str(),
+            self.time.trunc_subsecs(0),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DiffStatus {
+    New,
+    Resolved,
+    LevelChanged,
+}
+
+impl DiffStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            DiffStatus::New => "New",
+            DiffStatus::Resolved => "Resolved",
+            DiffStatus::LevelChanged => "Level changed",
+        }
+    }
+}
+
+/// A detection that is only in one of the results, or whose level differs between them. `row` is
+/// the newer detection, except for resolved detections.
+#[derive(Debug, Clone)]
+pub struct DiffEntry {
+    pub status: DiffStatus,
+    pub row: ResultRow,
+    pub old_level: Option<LEVEL>,
+}
+
+fn parse_level(level: &str) -> LEVEL {
+    let level = level.trim().to_lowercase();
+    LEVEL::iter()
+        .find(|l| *l == *level.as_str())
+        .unwrap_or_default()
+}
+
+fn parse_timestamp(timestamp: &str, format: Option<&str>) -> Option<DateTime<Utc>> {
+    match format {
+        Some(format) => DateTime::parse_from_str(timestamp, format),
+        None => DateTime::parse_from_rfc3339(timestamp),
+    }
+    .ok()
+    .map(|time| time.with_timezone(&Utc))
+}
+
+/// Converts the timestamps of the rows to UTC, so that results written with different time format
+/// and time zone options can be compared. A date such as 03-01-2026 can be month-first or
+/// day-first, so the first format that can parse every timestamp of the results is used.
+fn parse_times(rows: &mut [ResultRow]) -> Result<(), String> {
+    let formats = std::iter::once(None).chain(TIMESTAMP_FORMATS.iter().map(|f| Some(*f)));
+    let parses_all = |format: Option<&str>| {
+        rows.iter()
+            .all(|row| parse_timestamp(&row.timestamp, format).is_some())
+    };
+    let Some(format) = formats.clone().find(|format| parses_all(*format)) else {
+        let unparsed = rows
+            .iter()
+            .find(|row| {
+                formats
+                    .clone()
+                    .all(|format| parse_timestamp(&row.timestamp, format).is_none())
+            })
+            .unwrap_or(&rows[0]);
+        return Err(format!(
+            "Failed to parse the timestamp \"{}\". Results written with --time-format or with several time formats cannot be compared.",
+            unparsed.timestamp
+        ));
+    };
+    for row in rows {
+        row.time = parse_timestamp(&row.timestamp, format).unwrap_or_default();
+    }
+    Ok(())
+}
+
+fn json_field<'a>(record: &'a Value, names: &[&str]) -> Option<&'a Value> {
+    names.iter().find_map(|name| {
+        name.split('.')
+            .try_fold(record, |value, key| value.get(key))
+    })
+}
+
+fn json_string_field(record: &Value, names: &[&str]) -> CompactString {
+    match json_field(record, names) {
+        Some(Value::String(s)) => CompactString::from(s.trim()),
+        // Count/correlation results of -t ecs can have several host names.
+        Some(Value::Array(values)) => values
+            .iter()
+            .map(|v| {
+                v.as_str()
+                    .map(str::to_string)
+                    .unwrap_or_else(|| v.to_string())
+            })
+            .join(" ¦ ")
+            .into(),
+        Some(Value::Null) | None => CompactString::default(),
+        Some(value) => CompactString::from(value.to_string()),
+    }
+}
+
+/// Reads JSON (one object after another) or JSONL results.
+fn rows_from_json(contents: &str) -> Result<Vec<ResultRow>, String> {
+    let mut rows = vec![];
+    for record in serde_json::Deserializer::from_str(contents).into_iter::<Value>() {
+        let record = record.map_err(|e| format!("Failed to parse the JSON results. {e}"))?;
+        rows.push(ResultRow {
+            timestamp: json_string_field(&record, &TIMESTAMP_FIELDS),
+            time: DateTime::default(),
+            rule_id: json_string_field(&record, &RULE_ID_FIELDS),
+            rule_title: json_string_field(&record, &RULE_TITLE_FIELDS),
+            computer: json_string_field(&record, &COMPUTER_FIELDS),
+            record_id: json_string_field(&record, &RECORD_ID_FIELDS),
+            level: parse_level(json_string_field(&record, &LEVEL_FIELDS).as_str()),
+        });
+    }
+    parse_times(&mut rows)?;
+    Ok(rows)
+}
+
+fn rows_from_csv(contents: &str) -> Result<Vec<ResultRow>, String> {
+    let mut reader = ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(contents.as_bytes());
+    let headers = reader
+        .headers()
+        .map_err(|e| format!("Failed to read the CSV header. {e}"))?
+        .clone();
+    let column = |names: &[&str]| {
+        names
+            .iter()
+            .find_map(|name| headers.iter().position(|h| h.trim() == *name))
+    };
+    let (Some(timestamp), Some(rule_title), Some(computer), Some(level)) = (
+        column(&TIMESTAMP_FIELDS),
+        column(&RULE_TITLE_FIELDS),
+        column(&COMPUTER_FIELDS),
+        column(&LEVEL_FIELDS),
+    ) else {
+        return Err(
+            "The CSV results need the Timestamp, RuleTitle, Computer and Level columns."
+                .to_string(),
+        );
+    };
+    let rule_id = column(&RULE_ID_FIELDS);
+    let record_id = column(&RECORD_ID_FIELDS);
+    let mut rows = vec![];
+    for record in reader.records() {
+        let record = record.map_err(|e| format!("Failed to read the CSV results. {e}"))?;
+        let get = |i: Option<usize>| {
+            i.and_then(|i| record.get(i))
+                .map(|v| CompactString::from(v.trim()))
+                .unwrap_or_default()
+        };
+        rows.push(ResultRow {
+            timestamp: get(Some(timestamp)),
+            time: DateTime::default(),
+            rule_id: get(rule_id),
+            rule_title: get(Some(rule_title)),
+            computer: get(Some(computer)),
+            record_id: get(record_id),
+            level: parse_level(&get(Some(level))),
+        });
+    }
+    parse_times(&mut rows)?;
+    Ok(rows)
+}
+
+/// Loads a `dfir-timeline` results file. JSON and JSONL are recognized by their first character,
+/// anything else is read as CSV.
+pub fn load_results(path: &Path) -> Result<Vec<ResultRow>, String> {
+    let contents =
+        fs::read_to_string(path).map_err(|e| format!("Failed to read {}. {e}", path.display()))?;
+    let contents = contents.trim_start_matches('\u{feff}');
+    let rows = if contents.trim_start().starts_with('{') {
+        rows_from_json(contents)
+    } else {
+        rows_from_csv(contents)
+    };
+    rows.map_err(|e| format!("{e} ({})", path.display()))
+}
+
+/// Compares two results. Detections only in `new` are new, detections only in `old` are resolved
+/// and detections in both with a different level have a changed level. A key can be in a result
+/// more than once, so the detections of each key are counted: the extra detections on either side
+/// are new or resolved.
+pub fn diff(old: &[ResultRow], new: &[ResultRow]) -> Vec<DiffEntry> {
+    fn group(rows: &[ResultRow]) -> HashMap<(&str, &str, &str, DateTime<Utc>), Vec<&ResultRow>> {
+        let mut map: HashMap<_, Vec<&ResultRow>> = HashMap::new();
+        for row in rows {
+            map.entry(row.key()).or_default().push(row);
+        }
+        map
+    }
+    let mut old_map = group(old);
+    let new_map = group(new);
+    let mut entries = vec![];
+    for (key, new_rows) in new_map {
+        let mut old_rows = old_map.remove(&key).unwrap_or_default();
+        // Detections with the same level on both sides are matched first.
+        let mut unmatched = vec![];
+        for row in new_rows {
+            match old_rows
+                .iter()
+                .position(|old_row| old_row.level == row.level)
+            {
+                Some(pos) => {
+                    old_rows.remove(pos);
+                }
+                None => unmatched.push(row),
+            }
+        }
+        let mut old_rows = old_rows.into_iter();
+        for row in unmatched {
+            entries.push(match old_rows.next() {
+                Some(old_row) => DiffEntry {
+                    status: DiffStatus::LevelChanged,
+                    row: row.clone(),
+                    old_level: Some(old_row.level.clone()),
+                },
+                None => DiffEntry {
+                    status: DiffStatus::New,
+                    row: row.clone(),
+                    old_level: None,
+                },
+            });
+        }
+        old_map.insert(key, old_rows.collect());
+    }
+    for row in old_map.into_values().flatten() {
+        entries.push(DiffEntry {
+            status: DiffStatus::Resolved,
+            row: row.clone(),
+            old_level: None,
+        });
+    }
+    entries.sort_by(|a, b| {
+        a.row
+            .time
+            .cmp(&b.row.time)
+            .then_with(|| a.status.cmp(&b.status))
+            .then_with(|| a.row.key().cmp(&b.row.key()))
+    });
+    entries
+}
+
+/// Saves the differences as CSV.
+pub fn output_csv(entries: &[DiffEntry], path: &Path) -> io::Result<()> {
+    let mut writer = WriterBuilder::new()
+        .quote_style(QuoteStyle::NonNumeric)
+        .from_writer(BufWriter::new(File::create(path)?));
+    writer.write_record([
+        "Status",
+        "Timestamp",
+        "Computer",
+        "RuleTitle",
+        "RuleID",
+        "RecordID",
+        "Level",
+        "OldLevel",
+    ])?;
+    for entry in entries {
+        writer.write_record([
+            entry.status.as_str(),
+            &entry.row.timestamp,
+            &entry.row.computer,
+            &entry.row.rule_title,
+            &entry.row.rule_id,
+            &entry.row.record_id,
+            entry.row.level.to_full(),
+            entry.old_level.as_ref().map(|l| l.to_full()).unwrap_or("-"),
+        ])?;
+    }
+    writer.flush()
+}
+
+fn new_table() -> Table {
+    let mut tb = Table::new();
+    tb.load_preset(UTF8_FULL).apply_modifier(UTF8_ROUND_CORNERS);
+    tb
+}
+
+/// Prints the number of new, resolved and changed detections per level, then the rules with the
+/// most new and resolved detections and the level changes.
+pub fn print_summary(entries: &[DiffEntry], old_count: usize, new_count: usize) {
+    let count = |status: DiffStatus, level: &LEVEL| {
+        entries
+            .iter()
+            .filter(|e| e.status == status && e.row.level == *level)
+            .count()
+    };
+    let mut tb = new_table();
+    tb.set_header(vec!["Level", "New", "Resolved", "Level changed"]);
+    for level in LEVEL::iter().rev() {
+        let counts = [
+            DiffStatus::New,
+            DiffStatus::Resolved,
+            DiffStatus::LevelChanged,
+        ]
+        .map(|status| count(status, &level));
+        if level == LEVEL::UNDEFINED && counts.iter().all(|c| *c == 0) {
+            continue;
+        }
+        let mut row = vec![Cell::new(level.to_full())];
+        row.extend(
+            counts
+                .iter()
+                .map(|c| Cell::new(c.to_formatted_string(&Locale::en))),
+        );
+        tb.add_row(row);
+    }
+    let mut total = vec![Cell::new("Total")];
+    total.extend(
+        [
+            DiffStatus::New,
+            DiffStatus::Resolved,
+            DiffStatus::LevelChanged,
+        ]
+        .iter()
+        .map(|status| {
+            Cell::new(
+                entries
+                    .iter()
+                    .filter(|e| e.status == *status)
+                    .count()
+                    .to_formatted_string(&Locale::en),
+            )
+        }),
+    );
+    tb.add_row(total);
+    println!(
+        "Old results: {} detections / New results: {} detections",
+        old_count.to_formatted_string(&Locale::en),
+        new_count.to_formatted_string(&Locale::en)
+    );
+    println!("{tb}");
+    println!();
+
+    let top_rules = |status: DiffStatus| {
+        entries
+            .iter()
+            .filter(|e| e.status == status)
+            .counts_by(|e| e.row.rule_title.clone())
+            .into_iter()
+            .sorted_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)))
+            .take(10)
+            .map(|(title, count)| format!("{title} ({})", count.to_formatted_string(&Locale::en)))
+            .collect_vec()
+    };
+    let (new_rules, resolved_rules) = (top_rules(DiffStatus::New), top_rules(DiffStatus::Resolved));
+    if !new_rules.is_empty() || !resolved_rules.is_empty() {
+        let mut tb = new_table();
+        tb.set_header(vec!["Top new detections", "Top resolved detections"]);
+        tb.add_row(vec![
+            if new_rules.is_empty() {
+                "n/a".to_string()
+            } else {
+                new_rules.join("\n")
+            },
+            if resolved_rules.is_empty() {
+                "n/a".to_string()
+            } else {
+                resolved_rules.join("\n")
+            },
+        ]);
+        println!("{tb}");
+        println!();
+    }
+
+    let changes = entries
+        .iter()
+        .filter(|e| e.status == DiffStatus::LevelChanged)
+        .counts_by(|e| {
+            (
+                e.row.rule_title.clone(),
+                e.old_level.as_ref().unwrap_or(&LEVEL::UNDEFINED).to_full(),
+                e.row.level.to_full(),
+            )
+        });
+    if !changes.is_empty() {
+        let mut tb = new_table();
+        tb.set_header(vec!["Rule", "Old level", "New level", "Detections"]);
+        for ((title, old_level, new_level), count) in changes
+            .into_iter()
+            .sorted_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)))
+        {
+            tb.add_row(vec![
+                title.to_string(),
+                old_level.to_string(),
+                new_level.to_string(),
+                count.to_formatted_string(&Locale::en),
+            ]);
+        }
+        println!("{tb}");
+        println!();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DiffStatus, diff, rows_from_csv, rows_from_json};
+    use crate::level::LEVEL;
+
+    const OLD_CSV: &str = "Timestamp,RuleTitle,Level,Computer,Channel,EventID,RecordID,Details,ExtraFieldInfo,RuleID
+\"2026-03-01 10:00:00.000 +00:00\",\"Mimikatz\",\"crit\",\"PC01\",\"Sec\",4688,100,\"-\",\"-\",\"r1\"
+\"2026-03-01 11:00:00.000 +00:00\",\"Suspicious Logon\",\"med\",\"PC01\",\"Sec\",4624,200,\"-\",\"-\",\"r2\"
+\"2026-03-01 12:00:00.000 +00:00\",\"PowerShell\",\"low\",\"PC02\",\"PwSh\",4104,300,\"-\",\"-\",\"r3\"
+";
+
+    const NEW_JSONL: &str = r#"{"Timestamp":"2026-03-01 11:00:00.000 +00:00","RuleTitle":"Suspicious Logon","Level":"high","Computer":"PC01","RecordID":200,"RuleID":"r2"}
+{"Timestamp":"2026-03-01 12:00:00.000 +00:00","RuleTitle":"PowerShell","Level":"low","Computer":"PC02","RecordID":300,"RuleID":"r3"}
+{"Timestamp":"2026-03-02 09:00:00.000 +00:00","RuleTitle":"New Service","Level":"medium","Computer":"PC03","RecordID":400,"RuleID":"r4"}"#;
+
+    #[test]
+    fn test_diff_csv_and_jsonl() {
+        let old = rows_from_csv(OLD_CSV).unwrap();
+        let new = rows_from_json(NEW_JSONL).unwrap();
+        assert_eq!(old[0].level, LEVEL::CRITICAL);
+        assert_eq!(new[0].record_id, "200");
+
+        let entries = diff(&old, &new);
+        let summary: Vec<(DiffStatus, &str)> = entries
+            .iter()
+            .map(|e| (e.status, e.row.rule_id.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (DiffStatus::Resolved, "r1"),
+                (DiffStatus::LevelChanged, "r2"),
+                (DiffStatus::New, "r4"),
+            ]
+        );
+        assert_eq!(entries[1].old_level, Some(LEVEL::MEDIUM));
+        assert_eq!(entries[1].row.level, LEVEL::HIGH);
+    }
+
+    #[test]
+    fn test_diff_duplicate_keys() {
+        let old = rows_from_json(
+            r#"{"Timestamp":"2026-03-01 10:00:00.000 +00:00","RuleTitle":"Mimikatz","Level":"high","Computer":"PC01","RecordID":100,"RuleID":"r1"}
+{"Timestamp":"2026-03-01 11:00:00.000 +00:00","RuleTitle":"Logon","Level":"low","Computer":"PC01","RecordID":200,"RuleID":"r2"}
+{"Timestamp":"2026-03-01 11:00:00.000 +00:00","RuleTitle":"Logon","Level":"low","Computer":"PC01","RecordID":200,"RuleID":"r2"}"#,
+        )
+        .unwrap();
+        let new = rows_from_json(
+            r#"{"Timestamp":"2026-03-01 10:00:00.000 +00:00","RuleTitle":"Mimikatz","Level":"critical","Computer":"PC01","RecordID":100,"RuleID":"r1"}
+{"Timestamp":"2026-03-01 10:00:00.000 +00:00","RuleTitle":"Mimikatz","Level":"high","Computer":"PC01","RecordID":100,"RuleID":"r1"}
+{"Timestamp":"2026-03-01 11:00:00.000 +00:00","RuleTitle":"Logon","Level":"low","Computer":"PC01","RecordID":200,"RuleID":"r2"}"#,
+        )
+        .unwrap();
+
+        let entries = diff(&old, &new);
+        let summary: Vec<(DiffStatus, &str, &LEVEL)> = entries
+            .iter()
+            .map(|e| (e.status, e.row.rule_id.as_str(), &e.row.level))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (DiffStatus::New, "r1", &LEVEL::CRITICAL),
+                (DiffStatus::Resolved, "r2", &LEVEL::LOW),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_ecs_fields() {
+        let new = rows_from_json(
+            r#"{"@timestamp":"2026-03-01T10:00:00.000Z","log":{"level":"critical"},"host":{"name":["PC01","PC02"]},"rule":{"id":"r1","name":"Mimikatz"},"winlog":{"record_id":"100"}}"#,
+        )
+        .unwrap();
+        assert_eq!(new[0].rule_id, "r1");
+        assert_eq!(new[0].computer, "PC01 ¦ PC02");
+        assert_eq!(new[0].level, LEVEL::CRITICAL);
+    }
+
+    #[test]
+    fn test_diff_time_formats() {
+        // The same detections written with the default format in UTC+09:00, and with --iso-8601.
+        let old = rows_from_csv(
+            "Timestamp,RuleTitle,Level,Computer,RecordID,RuleID
+\"2026-03-01 19:00:00.123 +09:00\",\"Mimikatz\",\"crit\",\"PC01\",100,\"r1\"
+\"2026-03-01 20:00:00.000 +09:00\",\"Logon\",\"low\",\"PC01\",200,\"r2\"
+",
+        )
+        .unwrap();
+        let new = rows_from_json(
+            r#"{"Timestamp":"2026-03-01T10:00:00.1234567Z","RuleTitle":"Mimikatz","Level":"critical","Computer":"PC01","RecordID":100,"RuleID":"r1"}
+{"Timestamp":"2026-03-01T11:00:00.0000000Z","RuleTitle":"Logon","Level":"high","Computer":"PC01","RecordID":200,"RuleID":"r2"}"#,
+        )
+        .unwrap();
+        let entries = diff(&old, &new);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].status, DiffStatus::LevelChanged);
+        assert_eq!(entries[0].row.rule_id, "r2");
+
+        // Day-first dates are read as such when a day is above 12.
+        let rows = rows_from_csv(
+            "Timestamp,RuleTitle,Level,Computer
+\"03-01-2026 10:00:00.000 +00:00\",\"Logon\",\"low\",\"PC01\"
+\"13-01-2026 10:00:00.000 +00:00\",\"Logon\",\"low\",\"PC01\"
+",
+        )
+        .unwrap();
+        assert_eq!(rows[0].time.to_rfc3339(), "2026-01-03T10:00:00+00:00");
+
+        // A timestamp in a custom --time-format cannot be compared.
+        assert!(
+            rows_from_csv("Timestamp,RuleTitle,Level,Computer\n2026/03/01 10:00,Logon,low,PC01\n")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_csv_missing_columns() {
+        assert!(rows_from_csv("Timestamp,Details\n2026,-\n").is_err());
+    }
+}
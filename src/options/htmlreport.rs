@@ -35,6 +35,9 @@ pub struct HtmlReporter {
     pub section_order: Nested<String>,
     /// Markdown lines per section name.
     pub section_markdown: HashMap<String, Nested<String>>,
+    /// Detections embedded with `--html-interactive`, as the JSON read by
+    /// detections_table.js. Rendered after the markdown sections.
+    pub detection_table: Option<String>,
 }
 
 impl HtmlReporter {
@@ -43,6 +46,7 @@ impl HtmlReporter {
         HtmlReporter {
             section_order: init_section_order,
             section_markdown: init_data,
+            detection_table: None,
         }
     }
 
@@ -81,8 +85,17 @@ impl HtmlReporter {
 
         let mut ret = String::new();
         html::push_html(&mut ret, parser);
+        if let Some(table_json) = &self.detection_table {
+            ret.push_str(&create_detection_table_html(table_json));
+        }
         ret
     }
+
+    /// Stores the detections to embed in the report. `table_json` is embedded inside a `<script>`
+    /// element, so every `<` is escaped to keep values such as `</script>` from closing it.
+    pub fn set_detection_table(&mut self, table_json: &str) {
+        self.detection_table = Some(table_json.replace('<', "\\u003c"));
+    }
 }
 
 impl Default for HtmlReporter {
@@ -103,6 +116,30 @@ pub fn check_html_flag(config: &Config) -> bool {
     }
 }
 
+/// Returns true when the dfir-timeline action was invoked with --html-interactive.
+pub fn check_html_interactive_flag(config: &Config) -> bool {
+    match config.action.as_ref() {
+        Some(Action::DfirTimeline(option)) => option.output_options.html_interactive,
+        _ => false,
+    }
+}
+
+/// Builds the Detections section: a container filled client-side by the embedded
+/// detections_table.js from the JSON data block, so the report stays a single offline file.
+fn create_detection_table_html(table_json: &str) -> String {
+    let embedded = |name: &str| {
+        HtmlReportsConfig::get(name)
+            .map(|f| String::from_utf8(f.data.to_vec()).unwrap_or_default())
+            .unwrap_or_default()
+    };
+    format!(
+        "<h2 id=\"detections\">Detections</h2>\n<style>{}</style>\n<div id=\"detections-app\"></div>\n<script type=\"application/json\" id=\"detections-data\">{}</script>\n<script>{}</script>\n",
+        embedded("detections_table.css"),
+        table_json,
+        embedded("detections_table.js")
+    )
+}
+
 /// Builds the initial section order and the empty per-section markdown map. The `{#id}` suffixes
 /// in the section names become HTML heading ids via the heading-attributes markdown extension.
 fn get_init_md_data_map() -> (Nested<String>, HashMap<String, Nested<String>>) {
@@ -354,6 +391,42 @@ mod tests {
         assert!(!htmlreport::check_html_flag(&json_html_flag_disable.config));
     }
 
+    #[test]
+    fn test_check_html_interactive_flag() {
+        let interactive_action = Action::DfirTimeline(DfirTimelineOption {
+            output_options: OutputOption {
+                min_level: "informational".to_string(),
+                no_wizard: true,
+                html_report: Some(Path::new("./dummy").to_path_buf()),
+                html_interactive: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let stored_static = create_dummy_stored_static(Some(interactive_action));
+        assert!(htmlreport::check_html_interactive_flag(
+            &stored_static.config
+        ));
+        assert!(!htmlreport::check_html_interactive_flag(
+            &create_dummy_stored_static(None).config
+        ));
+    }
+
+    #[test]
+    fn test_create_html_with_detection_table() {
+        let mut html_reporter = HtmlReporter::new();
+        assert!(!html_reporter.create_html().contains("detections-app"));
+
+        html_reporter.set_detection_table(
+            r#"{"columns":["Details"],"rows":[{"values":["</script><b>x</b>"]}]}"#,
+        );
+        let html = html_reporter.create_html();
+        assert!(html.contains("<div id=\"detections-app\"></div>"));
+        // Field values must not be able to close the embedded data block.
+        assert!(html.contains(r#"["\u003c/script>\u003cb>x\u003c/b>"]"#));
+        assert_eq!(html.matches("</script>").count(), 2);
+    }
+
     #[test]
     fn test_create_html_file() {
         let output_tmp_dir = tempfile::tempdir().unwrap();
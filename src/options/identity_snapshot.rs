@@ -0,0 +1,584 @@
+use crate::options::profile::Profile;
+use compact_str::CompactString;
+use hashbrown::{HashMap, HashSet};
+use itertools::Itertools;
+use serde_json::{Map, Value};
+use std::collections::VecDeque;
+use std::fs;
+use std::path::Path;
+
+// Event fields that can hold the account an event is about, in output order. SIDs are matched
+// against the account SIDs and names against the sAMAccountNames of the snapshot.
+const ACCOUNT_FIELDS: [&str; 9] = [
+    "SubjectUserSid",
+    "SubjectUserName",
+    "TargetUserSid",
+    "TargetUserName",
+    "TargetSid",
+    "MemberSid",
+    "User",
+    "AccountName",
+    "TargetAccount",
+];
+
+// Groups whose members are treated as privileged, by name and by well-known SID. Domain group
+// SIDs are matched by their relative ID suffix since the domain part differs per environment.
+const PRIVILEGED_GROUP_NAMES: [&str; 12] = [
+    "DOMAIN ADMINS",
+    "ENTERPRISE ADMINS",
+    "SCHEMA ADMINS",
+    "ADMINISTRATORS",
+    "ACCOUNT OPERATORS",
+    "BACKUP OPERATORS",
+    "SERVER OPERATORS",
+    "PRINT OPERATORS",
+    "DNSADMINS",
+    "GROUP POLICY CREATOR OWNERS",
+    "KEY ADMINS",
+    "ENTERPRISE KEY ADMINS",
+];
+const PRIVILEGED_DOMAIN_RIDS: [&str; 6] = ["-512", "-518", "-519", "-520", "-526", "-527"];
+const PRIVILEGED_BUILTIN_SIDS: [&str; 5] = [
+    "S-1-5-32-544",
+    "S-1-5-32-548",
+    "S-1-5-32-549",
+    "S-1-5-32-550",
+    "S-1-5-32-551",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AccountType {
+    Admin,
+    Service,
+    #[default]
+    User,
+    Computer,
+}
+
+impl AccountType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccountType::Admin => "admin",
+            AccountType::Service => "service",
+            AccountType::User => "user",
+            AccountType::Computer => "computer",
+        }
+    }
+
+    fn from(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "admin" => Some(AccountType::Admin),
+            "service" => Some(AccountType::Service),
+            "user" => Some(AccountType::User),
+            "computer" => Some(AccountType::Computer),
+            _ => None,
+        }
+    }
+}
+
+/// One directory account with the attributes output in the `%Identity*%` profile columns.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Identity {
+    pub account: CompactString,
+    pub sid: CompactString,
+    pub enabled: Option<bool>,
+    pub account_type: AccountType,
+    pub privileged_groups: Vec<CompactString>,
+}
+
+impl Identity {
+    pub fn is_privileged(&self) -> bool {
+        self.account_type == AccountType::Admin
+    }
+}
+
+// An account as read from a snapshot file, before group nesting is resolved.
+#[derive(Debug, Default)]
+struct RawAccount {
+    account: CompactString,
+    sid: CompactString,
+    enabled: Option<bool>,
+    member_of: Vec<CompactString>,
+    admin_count: bool,
+    has_spn: bool,
+    account_type: Option<AccountType>,
+}
+
+#[derive(Debug, Default)]
+struct RawGroup {
+    name: CompactString,
+    members: Vec<CompactString>,
+}
+
+/// Accounts from an offline Active Directory snapshot: CSV/JSON exports of users and groups, or
+/// the users/groups/computers JSON files collected by SharpHound for BloodHound. Used to add the
+/// `%Identity*%` profile columns and, with `--escalate-privileged`, to raise the level of alerts
+/// involving privileged accounts.
+#[derive(Debug, Default)]
+pub struct IdentitySnapshot {
+    identities: Vec<Identity>,
+    by_sid: HashMap<CompactString, usize>,
+    by_name: HashMap<CompactString, usize>,
+}
+
+impl IdentitySnapshot {
+    /// Loads a snapshot file, or every .csv and .json file directly under a directory.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let files = if path.is_dir() {
+            fs::read_dir(path)
+                .map_err(|e| format!("Failed to read the identity snapshot directory. {e}"))?
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|p| {
+                    p.extension().is_some_and(|ext| {
+                        ext.eq_ignore_ascii_case("csv") || ext.eq_ignore_ascii_case("json")
+                    })
+                })
+                .sorted()
+                .collect_vec()
+        } else {
+            vec![path.to_path_buf()]
+        };
+        let mut accounts = vec![];
+        let mut groups = HashMap::new();
+        for file in files {
+            let contents = fs::read_to_string(&file).map_err(|e| {
+                format!(
+                    "Failed to read the identity snapshot file: {}. {e}",
+                    file.display()
+                )
+            })?;
+            let is_csv = file
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+            let parsed = if is_csv {
+                parse_csv_snapshot(&contents, &mut accounts)
+            } else {
+                parse_json_snapshot(&contents, &mut accounts, &mut groups)
+            };
+            parsed.map_err(|e| format!("{}: {e}", file.display()))?;
+        }
+        Ok(Self::build(accounts, &groups))
+    }
+
+    fn build(accounts: Vec<RawAccount>, groups: &HashMap<CompactString, RawGroup>) -> Self {
+        // Reverse the group membership lists so nested groups can be walked upwards from an
+        // account's SID.
+        let mut parents: HashMap<CompactString, Vec<&CompactString>> = HashMap::new();
+        for (group_sid, group) in groups {
+            for member in &group.members {
+                parents.entry(member.clone()).or_default().push(group_sid);
+            }
+        }
+        let mut ret = IdentitySnapshot::default();
+        for raw in accounts {
+            let mut privileged_groups: Vec<CompactString> = raw
+                .member_of
+                .iter()
+                .filter(|name| is_privileged_group("", name))
+                .cloned()
+                .collect();
+            let mut seen = HashSet::new();
+            let mut queue = VecDeque::from([raw.sid.clone()]);
+            while let Some(sid) = queue.pop_front() {
+                for group_sid in parents.get(&sid).into_iter().flatten() {
+                    if !seen.insert(*group_sid) {
+                        continue;
+                    }
+                    let name = &groups[*group_sid].name;
+                    if is_privileged_group(group_sid, name) {
+                        privileged_groups.push(name.clone());
+                    }
+                    queue.push_back((*group_sid).clone());
+                }
+            }
+            let privileged_groups = privileged_groups.into_iter().unique().collect_vec();
+            let account_type = raw.account_type.unwrap_or(if raw.account.ends_with('$') {
+                AccountType::Computer
+            } else if raw.admin_count || !privileged_groups.is_empty() {
+                AccountType::Admin
+            } else if raw.has_spn {
+                AccountType::Service
+            } else {
+                AccountType::User
+            });
+            let index = ret.identities.len();
+            if !raw.sid.is_empty() {
+                ret.by_sid.insert(raw.sid.clone(), index);
+            }
+            ret.by_name.insert(raw.account.to_uppercase().into(), index);
+            ret.identities.push(Identity {
+                account: raw.account,
+                sid: raw.sid,
+                enabled: raw.enabled,
+                account_type,
+                privileged_groups,
+            });
+        }
+        ret
+    }
+
+    pub fn len(&self) -> usize {
+        self.identities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.identities.is_empty()
+    }
+
+    /// Returns the snapshot accounts referenced by the account fields of an event record, in
+    /// field order and without duplicates.
+    pub fn find_accounts(&self, record: &Value) -> Vec<&Identity> {
+        let event_data = &record["Event"]["EventData"];
+        let user_id = &record["Event"]["System"]["Security_attributes"]["UserID"];
+        let mut indices = vec![];
+        for value in ACCOUNT_FIELDS
+            .iter()
+            .map(|field| &event_data[*field])
+            .chain([user_id])
+        {
+            let Some(value) = value.as_str() else {
+                continue;
+            };
+            let value = value.trim();
+            let index = if value.to_uppercase().starts_with("S-1-") {
+                self.by_sid.get(value.to_uppercase().as_str())
+            } else {
+                self.by_name.get(normalize_account(value).as_str())
+            };
+            if let Some(index) = index
+                && !indices.contains(index)
+            {
+                indices.push(*index);
+            }
+        }
+        indices.into_iter().map(|i| &self.identities[i]).collect()
+    }
+
+    /// Returns the value of an `%Identity*%` profile column: one entry per account joined with
+    /// " ¦ ", so the entries of the different columns line up.
+    pub fn profile_value(profile: &Profile, identities: &[&Identity]) -> CompactString {
+        identities
+            .iter()
+            .map(|identity| match profile {
+                Profile::IdentityAccount(_) => identity.account.to_string(),
+                Profile::IdentityType(_) => identity.account_type.as_str().to_string(),
+                Profile::IdentityStatus(_) => match identity.enabled {
+                    Some(true) => "enabled".to_string(),
+                    Some(false) => "disabled".to_string(),
+                    None => "-".to_string(),
+                },
+                Profile::IdentityPrivilegedGroups(_) if identity.privileged_groups.is_empty() => {
+                    "-".to_string()
+                }
+                Profile::IdentityPrivilegedGroups(_) => identity.privileged_groups.join(", "),
+                _ => String::default(),
+            })
+            .join(" ¦ ")
+            .into()
+    }
+}
+
+// Strips a `DOMAIN\` prefix or `@domain` suffix and uppercases the account name.
+fn normalize_account(name: &str) -> String {
+    let name = name.rsplit('\\').next().unwrap_or(name);
+    let name = name.split('@').next().unwrap_or(name);
+    name.trim().to_uppercase()
+}
+
+// Reduces a group reference (plain name, `NAME@DOMAIN` or a distinguished name) to its name.
+fn group_name(group: &str) -> CompactString {
+    let group = group.trim();
+    let group = if group.len() > 3 && group[..3].eq_ignore_ascii_case("CN=") {
+        group[3..].split(',').next().unwrap_or_default()
+    } else {
+        group.split('@').next().unwrap_or(group)
+    };
+    group.trim().into()
+}
+
+fn is_privileged_group(sid: &str, name: &str) -> bool {
+    let sid = sid.to_uppercase();
+    PRIVILEGED_GROUP_NAMES.contains(&name.to_uppercase().as_str())
+        || PRIVILEGED_BUILTIN_SIDS.contains(&sid.as_str())
+        || (sid.starts_with("S-1-5-21-")
+            && PRIVILEGED_DOMAIN_RIDS.iter().any(|rid| sid.ends_with(rid)))
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "true" | "1" | "yes" | "enabled" => Some(true),
+        "false" | "0" | "no" | "disabled" => Some(false),
+        _ => None,
+    }
+}
+
+fn split_list(value: &str) -> Vec<CompactString> {
+    value
+        .split(';')
+        .map(group_name)
+        .filter(|g| !g.is_empty())
+        .collect()
+}
+
+// CSV exports need a SamAccountName column. The optional columns are SID (or ObjectSid),
+// Enabled, MemberOf (group names or distinguished names separated by `;`), AdminCount,
+// ServicePrincipalName and AccountType (admin, service, user or computer).
+fn parse_csv_snapshot(contents: &str, accounts: &mut Vec<RawAccount>) -> Result<(), String> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(contents.as_bytes());
+    let headers = rdr.headers().map_err(|e| e.to_string())?.clone();
+    let column = |names: &[&str]| {
+        headers
+            .iter()
+            .position(|h| names.iter().any(|n| h.eq_ignore_ascii_case(n)))
+    };
+    let Some(name_col) = column(&["SamAccountName"]) else {
+        return Err("The SamAccountName column was not found.".to_string());
+    };
+    let sid_col = column(&["SID", "ObjectSid"]);
+    let enabled_col = column(&["Enabled"]);
+    let member_of_col = column(&["MemberOf"]);
+    let admin_count_col = column(&["AdminCount"]);
+    let spn_col = column(&["ServicePrincipalName", "ServicePrincipalNames"]);
+    let type_col = column(&["AccountType"]);
+    for record in rdr.records() {
+        let record = record.map_err(|e| e.to_string())?;
+        let get = |col: Option<usize>| col.and_then(|c| record.get(c)).unwrap_or_default();
+        if get(Some(name_col)).is_empty() {
+            continue;
+        }
+        accounts.push(RawAccount {
+            account: get(Some(name_col)).into(),
+            sid: get(sid_col).to_uppercase().into(),
+            enabled: parse_bool(get(enabled_col)),
+            member_of: split_list(get(member_of_col)),
+            admin_count: parse_bool(get(admin_count_col)).unwrap_or(false),
+            has_spn: !get(spn_col).is_empty(),
+            account_type: AccountType::from(get(type_col)),
+        });
+    }
+    Ok(())
+}
+
+// Looks up an object key case-insensitively, as AD exports and SharpHound versions differ in
+// their capitalization.
+fn get_field<'a>(obj: &'a Map<String, Value>, name: &str) -> Option<&'a Value> {
+    obj.iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v)
+}
+
+fn value_to_bool(value: Option<&Value>) -> Option<bool> {
+    match value? {
+        Value::Bool(b) => Some(*b),
+        Value::Number(n) => Some(n.as_i64().unwrap_or_default() != 0),
+        Value::String(s) => parse_bool(s),
+        _ => None,
+    }
+}
+
+fn value_to_list(value: Option<&Value>) -> Vec<CompactString> {
+    match value {
+        Some(Value::Array(values)) => values
+            .iter()
+            .filter_map(|v| v.as_str())
+            .map(group_name)
+            .filter(|g| !g.is_empty())
+            .collect(),
+        Some(Value::String(s)) => split_list(s),
+        _ => vec![],
+    }
+}
+
+// JSON snapshots are either SharpHound output (`{"data": [...], "meta": {"type": "users"}}`) or
+// an array of account objects, optionally under a "users" key, with the same fields as the CSV.
+fn parse_json_snapshot(
+    contents: &str,
+    accounts: &mut Vec<RawAccount>,
+    groups: &mut HashMap<CompactString, RawGroup>,
+) -> Result<(), String> {
+    let json: Value = serde_json::from_str(contents.trim_start_matches('\u{feff}'))
+        .map_err(|e| format!("Failed to parse JSON. {e}"))?;
+    if let Some(kind) = json["meta"]["type"].as_str() {
+        let data = json["data"].as_array().cloned().unwrap_or_default();
+        for object in data.iter().filter_map(|d| d.as_object()) {
+            let sid = get_field(object, "ObjectIdentifier")
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+                .to_uppercase();
+            let empty = Map::new();
+            let props = get_field(object, "Properties")
+                .and_then(|v| v.as_object())
+                .unwrap_or(&empty);
+            let name = get_field(props, "samaccountname")
+                .or_else(|| get_field(props, "name"))
+                .and_then(|v| v.as_str())
+                .unwrap_or_default();
+            match kind.to_lowercase().as_str() {
+                "users" | "computers" => {
+                    if name.is_empty() {
+                        continue;
+                    }
+                    accounts.push(RawAccount {
+                        account: group_name(name),
+                        sid: sid.into(),
+                        enabled: value_to_bool(get_field(props, "enabled")),
+                        admin_count: value_to_bool(get_field(props, "admincount")).unwrap_or(false),
+                        has_spn: value_to_bool(get_field(props, "hasspn")).unwrap_or(false),
+                        ..Default::default()
+                    });
+                }
+                "groups" => {
+                    let members = get_field(object, "Members")
+                        .and_then(|v| v.as_array())
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|m| m.as_object())
+                        .filter_map(|m| get_field(m, "ObjectIdentifier"))
+                        .filter_map(|v| v.as_str())
+                        .map(|s| CompactString::from(s.to_uppercase()))
+                        .collect();
+                    groups.insert(
+                        sid.into(),
+                        RawGroup {
+                            name: group_name(name),
+                            members,
+                        },
+                    );
+                }
+                _ => {}
+            }
+        }
+        return Ok(());
+    }
+    let objects = json
+        .as_array()
+        .or_else(|| json["users"].as_array())
+        .ok_or("Expected an array of accounts or SharpHound output.")?;
+    for object in objects.iter().filter_map(|o| o.as_object()) {
+        let Some(name) = get_field(object, "SamAccountName").and_then(|v| v.as_str()) else {
+            continue;
+        };
+        let sid = get_field(object, "SID")
+            .or_else(|| get_field(object, "ObjectSid"))
+            .and_then(|v| v.as_str())
+            .unwrap_or_default();
+        accounts.push(RawAccount {
+            account: name.trim().into(),
+            sid: sid.trim().to_uppercase().into(),
+            enabled: value_to_bool(get_field(object, "Enabled")),
+            member_of: value_to_list(get_field(object, "MemberOf")),
+            admin_count: value_to_bool(get_field(object, "AdminCount")).unwrap_or(false),
+            has_spn: !value_to_list(get_field(object, "ServicePrincipalName")).is_empty(),
+            account_type: get_field(object, "AccountType")
+                .and_then(|v| v.as_str())
+                .and_then(AccountType::from),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AccountType, IdentitySnapshot, parse_csv_snapshot, parse_json_snapshot};
+    use crate::options::profile::Profile;
+    use hashbrown::HashMap;
+    use serde_json::json;
+
+    #[test]
+    fn test_csv_snapshot() {
+        let csv = r#"SamAccountName,SID,Enabled,MemberOf,ServicePrincipalName
+jdoe,S-1-5-21-1-2-3-1105,True,Domain Users,
+admin.bob,S-1-5-21-1-2-3-1106,True,"CN=Domain Admins,CN=Users,DC=corp,DC=local;Staff",
+svc_sql,S-1-5-21-1-2-3-1107,False,,MSSQLSvc/db01.corp.local:1433
+WS01$,S-1-5-21-1-2-3-1108,True,,
+"#;
+        let mut accounts = vec![];
+        parse_csv_snapshot(csv, &mut accounts).unwrap();
+        let snapshot = IdentitySnapshot::build(accounts, &HashMap::new());
+        assert_eq!(snapshot.len(), 4);
+        let record = json!({"Event": {"EventData": {
+            "SubjectUserSid": "S-1-5-21-1-2-3-1105",
+            "SubjectUserName": "jdoe",
+            "TargetUserName": "CORP\\ADMIN.BOB",
+            "User": "svc_sql@corp.local"
+        }}});
+        let found = snapshot.find_accounts(&record);
+        assert_eq!(
+            found.iter().map(|i| i.account.as_str()).collect::<Vec<_>>(),
+            vec!["jdoe", "admin.bob", "svc_sql"]
+        );
+        assert_eq!(found[1].account_type, AccountType::Admin);
+        assert!(found[1].is_privileged());
+        assert_eq!(found[2].account_type, AccountType::Service);
+        assert_eq!(
+            IdentitySnapshot::profile_value(&Profile::from("%IdentityType%"), &found),
+            "user ¦ admin ¦ service"
+        );
+        assert_eq!(
+            IdentitySnapshot::profile_value(&Profile::from("%IdentityStatus%"), &found),
+            "enabled ¦ enabled ¦ disabled"
+        );
+        assert_eq!(
+            IdentitySnapshot::profile_value(&Profile::from("%IdentityPrivilegedGroups%"), &found),
+            "- ¦ Domain Admins ¦ -"
+        );
+        let computer = json!({"Event": {"EventData": {"TargetUserName": "ws01$"}}});
+        assert_eq!(
+            snapshot.find_accounts(&computer)[0].account_type,
+            AccountType::Computer
+        );
+    }
+
+    #[test]
+    fn test_bloodhound_snapshot_resolves_nested_groups() {
+        let users = r#"{"data": [
+            {"ObjectIdentifier": "S-1-5-21-9-1001", "Properties": {"name": "ALICE@CORP.LOCAL", "samaccountname": "alice", "enabled": true}},
+            {"ObjectIdentifier": "S-1-5-21-9-1002", "Properties": {"name": "CAROL@CORP.LOCAL", "samaccountname": "carol", "enabled": false, "hasspn": true}}
+        ], "meta": {"type": "users", "count": 2}}"#;
+        let groups = r#"{"data": [
+            {"ObjectIdentifier": "S-1-5-21-9-512", "Properties": {"name": "DOMAIN ADMINS@CORP.LOCAL"},
+             "Members": [{"ObjectIdentifier": "S-1-5-21-9-2000", "ObjectType": "Group"}]},
+            {"ObjectIdentifier": "S-1-5-21-9-2000", "Properties": {"name": "TIER0 OPS@CORP.LOCAL"},
+             "Members": [{"ObjectIdentifier": "S-1-5-21-9-1001", "ObjectType": "User"}]}
+        ], "meta": {"type": "groups", "count": 2}}"#;
+        let mut accounts = vec![];
+        let mut group_map = HashMap::new();
+        parse_json_snapshot(users, &mut accounts, &mut group_map).unwrap();
+        parse_json_snapshot(groups, &mut accounts, &mut group_map).unwrap();
+        let snapshot = IdentitySnapshot::build(accounts, &group_map);
+        let record = json!({"Event": {"EventData": {"TargetUserSid": "S-1-5-21-9-1001"}}});
+        let found = snapshot.find_accounts(&record);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].privileged_groups, vec!["DOMAIN ADMINS"]);
+        assert_eq!(found[0].account_type, AccountType::Admin);
+        let record = json!({"Event": {"EventData": {"TargetUserName": "carol"}}});
+        let found = snapshot.find_accounts(&record);
+        assert_eq!(found[0].account_type, AccountType::Service);
+        assert_eq!(found[0].enabled, Some(false));
+    }
+
+    #[test]
+    fn test_json_snapshot_and_unknown_accounts() {
+        let users = r#"[{"SamAccountName": "ops", "Enabled": "False", "MemberOf": ["Backup Operators"], "AccountType": "service"}]"#;
+        let mut accounts = vec![];
+        parse_json_snapshot(users, &mut accounts, &mut HashMap::new()).unwrap();
+        let snapshot = IdentitySnapshot::build(accounts, &HashMap::new());
+        let record =
+            json!({"Event": {"EventData": {"SubjectUserName": "OPS", "TargetUserName": "nobody"}}});
+        let found = snapshot.find_accounts(&record);
+        assert_eq!(found.len(), 1);
+        // An explicit account type wins over the type inferred from group membership.
+        assert_eq!(found[0].account_type, AccountType::Service);
+        assert_eq!(found[0].privileged_groups, vec!["Backup Operators"]);
+        let none = json!({"Event": {"EventData": {"TargetUserName": "nobody"}}});
+        assert!(snapshot.find_accounts(&none).is_empty());
+        assert_eq!(
+            IdentitySnapshot::profile_value(&Profile::from("%IdentityAccount%"), &[]),
+            ""
+        );
+        assert!(parse_json_snapshot("{\"foo\": 1}", &mut vec![], &mut HashMap::new()).is_err());
+    }
+}
@@ -0,0 +1,305 @@
+use compact_str::CompactString;
+use hashbrown::HashMap;
+use itertools::Itertools;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Clone, Default)]
+struct MessageTemplate {
+    message: String,
+    // The EventData/UserData field inserted for %1, %2, ... in the order of the event template.
+    // Empty for classic events, whose insertion strings are the unnamed `Data` values.
+    fields: Vec<CompactString>,
+}
+
+/// Event message templates extracted offline from the message tables and manifests of provider
+/// DLLs, used to render `%RenderedMessage%` for events without `RenderingInfo`.
+///
+/// The database is a JSON array (or JSONL file) of objects such as
+/// `{"provider": "Microsoft-Windows-Security-Auditing", "event_id": 4624, "version": 2,
+/// "message": "An account was successfully logged on.%n%nSubject:%n%tSecurity ID:%t%t%1 ...",
+/// "fields": ["SubjectUserSid", ...]}`. `version` and `fields` are optional. Objects with a
+/// `parameter_id` instead of an `event_id` hold the strings that replace `%%<id>` values (ex:
+/// `%%1833` in Security events).
+#[derive(Debug, Default)]
+pub struct MessageTemplates {
+    // Keyed by lowercase provider name and event ID, with one template per event version.
+    // Templates without a version are stored under None.
+    templates: HashMap<(CompactString, u32), Vec<(Option<u32>, MessageTemplate)>>,
+    parameters: HashMap<u32, String>,
+}
+
+impl MessageTemplates {
+    /// Loads a database file, or every .json and .jsonl file directly under a directory.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let files = if path.is_dir() {
+            fs::read_dir(path)
+                .map_err(|e| format!("Failed to read the message database directory. {e}"))?
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|p| {
+                    p.extension().is_some_and(|ext| {
+                        ext.eq_ignore_ascii_case("json") || ext.eq_ignore_ascii_case("jsonl")
+                    })
+                })
+                .sorted()
+                .collect_vec()
+        } else {
+            vec![path.to_path_buf()]
+        };
+        let mut templates = MessageTemplates::default();
+        for file in files {
+            let contents = fs::read_to_string(&file).map_err(|e| {
+                format!(
+                    "Failed to read the message database file: {}. {e}",
+                    file.display()
+                )
+            })?;
+            templates
+                .add_entries(contents.trim_start_matches('\u{feff}'))
+                .map_err(|e| format!("{}: {e}", file.display()))?;
+        }
+        Ok(templates)
+    }
+
+    fn add_entries(&mut self, contents: &str) -> Result<(), String> {
+        let entries = match serde_json::from_str::<Value>(contents) {
+            Ok(Value::Array(entries)) => entries,
+            Ok(entry) => vec![entry],
+            // JSONL: one object per line.
+            Err(_) => contents
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(serde_json::from_str)
+                .collect::<Result<Vec<Value>, _>>()
+                .map_err(|e| format!("Failed to parse JSON. {e}"))?,
+        };
+        for entry in entries {
+            let Some(message) = entry["message"].as_str() else {
+                continue;
+            };
+            if let Some(id) = value_to_u32(&entry["parameter_id"]) {
+                self.parameters.insert(id, message.to_string());
+                continue;
+            }
+            let (Some(provider), Some(event_id)) =
+                (entry["provider"].as_str(), value_to_u32(&entry["event_id"]))
+            else {
+                continue;
+            };
+            let template = MessageTemplate {
+                message: message.to_string(),
+                fields: entry["fields"]
+                    .as_array()
+                    .map(|fields| {
+                        fields
+                            .iter()
+                            .map(|f| f.as_str().unwrap_or_default().into())
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            };
+            let versions = self
+                .templates
+                .entry((provider.to_lowercase().into(), event_id))
+                .or_default();
+            let version = value_to_u32(&entry["version"]);
+            versions.retain(|(v, _)| *v != version);
+            versions.push((version, template));
+        }
+        Ok(())
+    }
+
+    /// Returns the template for the event version, falling back to the template without a
+    /// version and then to the latest version.
+    fn find(&self, provider: &str, event_id: u32, version: u32) -> Option<&MessageTemplate> {
+        let versions = self
+            .templates
+            .get(&(CompactString::from(provider.to_lowercase()), event_id))?;
+        versions
+            .iter()
+            .find(|(v, _)| *v == Some(version))
+            .or_else(|| versions.iter().find(|(v, _)| v.is_none()))
+            .or_else(|| versions.iter().max_by_key(|(v, _)| *v))
+            .map(|(_, template)| template)
+    }
+
+    /// Renders the message of an evtx record by inserting its EventData (or UserData) values into
+    /// the template of its provider, event ID and version. Lines are separated by CRLF like the
+    /// `RenderingInfo` messages of forwarded events.
+    pub fn render(&self, record: &Value) -> Option<String> {
+        let system = &record["Event"]["System"];
+        let provider = system["Provider_attributes"]["Name"].as_str()?;
+        let event_id = value_to_u32(&system["EventID"])?;
+        let version = value_to_u32(&system["Version"]).unwrap_or(0);
+        let template = self.find(provider, event_id, version)?;
+        let inserts = if template.fields.is_empty() {
+            match &record["Event"]["EventData"]["Data"] {
+                Value::Array(values) => values.iter().map(value_to_string).collect(),
+                Value::Null => vec![],
+                value => vec![value_to_string(value)],
+            }
+        } else {
+            let event_data = &record["Event"]["EventData"];
+            // UserData has a single element named after the event, holding the fields.
+            let user_data = record["Event"]["UserData"]
+                .as_object()
+                .and_then(|data| data.values().next());
+            template
+                .fields
+                .iter()
+                .map(|field| {
+                    let value = match &event_data[field.as_str()] {
+                        Value::Null => user_data.map_or(&Value::Null, |data| &data[field.as_str()]),
+                        value => value,
+                    };
+                    value_to_string(value)
+                })
+                .collect()
+        };
+        let inserts = inserts
+            .iter()
+            .map(|value| self.resolve_parameters(value))
+            .collect_vec();
+        let message = format_message(&template.message, &inserts);
+        Some(message.replace("\r\n", "\n").replace('\n', "\r\n"))
+    }
+
+    /// Replaces the `%%<id>` parameter references in an insertion string.
+    fn resolve_parameters(&self, value: &str) -> String {
+        if !value.contains("%%") {
+            return value.to_string();
+        }
+        let mut resolved = String::with_capacity(value.len());
+        let mut rest = value;
+        while let Some(pos) = rest.find("%%") {
+            resolved.push_str(&rest[..pos]);
+            let after = &rest[pos + 2..];
+            let digits = after.len() - after.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            match after[..digits]
+                .parse::<u32>()
+                .ok()
+                .and_then(|id| self.parameters.get(&id))
+            {
+                Some(parameter) => resolved.push_str(parameter.trim_end()),
+                None => resolved.push_str(&rest[pos..pos + 2 + digits]),
+            }
+            rest = &after[digits..];
+        }
+        resolved.push_str(rest);
+        resolved
+    }
+}
+
+/// Formats a message table string like FormatMessage: `%1` to `%99` (with an optional `!format!`
+/// that is ignored as the values are already strings) are replaced with the insertion strings,
+/// and `%n`, `%r`, `%t`, `%b`, `%%`, `%.`, `%!` are unescaped. `%0` ends the message.
+fn format_message(template: &str, inserts: &[String]) -> String {
+    let mut message = String::with_capacity(template.len());
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            message.push(c);
+            continue;
+        }
+        match chars.peek().copied() {
+            Some(d) if d.is_ascii_digit() => {
+                let mut index = 0;
+                while let Some(d) = chars.peek().and_then(|d| d.to_digit(10)) {
+                    index = index * 10 + d as usize;
+                    chars.next();
+                }
+                if index == 0 {
+                    break;
+                }
+                if chars.peek() == Some(&'!') {
+                    chars.next();
+                    for f in chars.by_ref() {
+                        if f == '!' {
+                            break;
+                        }
+                    }
+                }
+                message.push_str(inserts.get(index - 1).map_or("-", |v| v.as_str()));
+            }
+            Some(escaped) => {
+                chars.next();
+                match escaped {
+                    'n' => message.push('\n'),
+                    'r' => message.push('\r'),
+                    't' => message.push('\t'),
+                    'b' => message.push(' '),
+                    _ => message.push(escaped),
+                }
+            }
+            None => message.push('%'),
+        }
+    }
+    message
+}
+
+fn value_to_u32(value: &Value) -> Option<u32> {
+    match value {
+        Value::Number(n) => n.as_u64().and_then(|n| u32::try_from(n).ok()),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.to_string(),
+        Value::Null => String::new(),
+        Value::Array(values) => values.iter().map(value_to_string).join(", "),
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_render_message() {
+        let mut templates = MessageTemplates::default();
+        templates
+            .add_entries(
+                r#"[
+                {"provider": "Microsoft-Windows-Security-Auditing", "event_id": 4688, "version": 2,
+                 "message": "A new process has been created.%n%nNew Process Name:%t%2!s!%nToken Elevation Type:%t%3%n%0ignored",
+                 "fields": ["SubjectUserName", "NewProcessName", "TokenElevationType"]},
+                {"provider": "Microsoft-Windows-Security-Auditing", "event_id": 4688, "version": 1,
+                 "message": "Old version", "fields": []},
+                {"parameter_id": 1936, "message": "Type 1\r\n"},
+                {"provider": "Application Error", "event_id": 1000,
+                 "message": "Faulting application name: %1, 100%% sure%."}
+            ]"#,
+            )
+            .unwrap();
+        let record = json!({"Event": {
+            "System": {"Provider_attributes": {"Name": "Microsoft-Windows-Security-Auditing"},
+                       "EventID": 4688, "Version": 2},
+            "EventData": {"SubjectUserName": "admin", "NewProcessName": "C:\\cmd.exe",
+                          "TokenElevationType": "%%1936"}
+        }});
+        assert_eq!(
+            templates.render(&record).unwrap(),
+            "A new process has been created.\r\n\r\nNew Process Name:\tC:\\cmd.exe\r\nToken Elevation Type:\tType 1\r\n"
+        );
+
+        let record = json!({"Event": {
+            "System": {"Provider_attributes": {"Name": "application error"}, "EventID": "1000"},
+            "EventData": {"Data": ["notepad.exe", "10.0"]}
+        }});
+        assert_eq!(
+            templates.render(&record).unwrap(),
+            "Faulting application name: notepad.exe, 100% sure."
+        );
+
+        let record = json!({"Event": {
+            "System": {"Provider_attributes": {"Name": "Unknown"}, "EventID": 1}
+        }});
+        assert!(templates.render(&record).is_none());
+    }
+}
@@ -1,7 +1,14 @@
+pub mod asset_inventory;
+pub mod attack_navigator;
+pub mod diff_results;
 pub mod expand_list;
 pub mod geoip_search;
 pub mod htmlreport;
+pub mod identity_snapshot;
 pub mod level_tuning;
+pub mod message_templates;
 pub mod pivot;
 pub mod profile;
+pub mod profile_template;
+pub mod risk_score;
 pub mod update;
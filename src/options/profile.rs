@@ -1,12 +1,16 @@
 use crate::detections::configs::{Action, CURRENT_EXE_PATH, StoredStatic};
 use crate::detections::message::AlertMessage;
-use crate::detections::utils::check_setting_path;
+use crate::detections::utils::{check_setting_path, is_valid_timezone};
 use crate::options::profile::Profile::{
-    AllFieldInfo, Channel, Computer, Details, EventID, EvtxFile, ExtraFieldInfo, Level, Literal,
-    MitreTactics, MitreTags, OtherTags, Provider, RecordID, RecoveredRecord, RenderedMessage,
-    RuleAuthor, RuleCreationDate, RuleFile, RuleID, RuleModifiedDate, RuleTitle, SrcASN, SrcCity,
-    SrcCountry, Status, TgtASN, TgtCity, TgtCountry, Timestamp,
+    AllFieldInfo, AssetOwner, AssetRole, AssetTags, AssetTier, CarveOffset, Channel, Computer,
+    Details, EventID, EvtxFile, ExtraFieldInfo, IdentityAccount, IdentityPrivilegedGroups,
+    IdentityStatus, IdentityType, Level, Literal, MitreTactics, MitreTags, OriginalOffset,
+    OtherTags, ProcessAncestry, Provider, RecordID, RecoveredRecord, RenderedMessage, RuleAuthor,
+    RuleCreationDate, RuleFile, RuleID, RuleModifiedDate, RuleTitle, SessionDuration,
+    SessionLogonTime, SessionLogonType, SessionSourceIP, SrcASN, SrcCity, SrcCountry, Status,
+    Template, TgtASN, TgtCity, TgtCountry, Timestamp,
 };
+use crate::options::profile_template::ProfileTemplate;
 use crate::yaml;
 use compact_str::CompactString;
 use itertools::Itertools;
@@ -31,7 +35,10 @@ struct DefaultProfile;
 /// replaced via `convert()` with the value rendered for each detection.
 #[derive(Eq, PartialEq, Hash, Clone, Debug)]
 pub enum Profile {
+    /// Parsed from `%Timestamp%`, or from `%Timestamp|tz:<IANA zone>%` in which case the value
+    /// holds the zone name until the column is rendered in that zone.
     Timestamp(Cow<'static, str>),
+    OriginalOffset(Cow<'static, str>),
     Computer(Cow<'static, str>),
     Channel(Cow<'static, str>),
     Level(Cow<'static, str>),
@@ -60,6 +67,28 @@ pub enum Profile {
     TgtCity(Cow<'static, str>),
     ExtraFieldInfo(Cow<'static, str>),
     RecoveredRecord(Cow<'static, str>),
+    /// The offset in the raw file of the chunk a record was carved from with --carve.
+    CarveOffset(Cow<'static, str>),
+    AssetRole(Cow<'static, str>),
+    AssetOwner(Cow<'static, str>),
+    AssetTier(Cow<'static, str>),
+    AssetTags(Cow<'static, str>),
+    IdentityAccount(Cow<'static, str>),
+    IdentityType(Cow<'static, str>),
+    IdentityStatus(Cow<'static, str>),
+    IdentityPrivilegedGroups(Cow<'static, str>),
+    /// The parent/child chain of the process the detection was logged for, e.g.
+    /// `explorer.exe > cmd.exe > powershell.exe` (see process_tree.rs).
+    ProcessAncestry(Cow<'static, str>),
+    SessionLogonType(Cow<'static, str>),
+    SessionSourceIP(Cow<'static, str>),
+    SessionLogonTime(Cow<'static, str>),
+    /// Holds the index of the logon session until the results are written, as the logoff is
+    /// usually logged after the detection (see logon_sessions.rs).
+    SessionDuration(Cow<'static, str>),
+    /// A computed column such as `%Computer% / %TargetUserName|lower%`. Holds the template until
+    /// it is rendered for each detection (see profile_template.rs).
+    Template(Cow<'static, str>),
     Literal(Cow<'static, str>), // For outputting fixed strings from profiles.yaml without conversion.
 }
 
@@ -72,9 +101,12 @@ impl Profile {
             | MitreTags(v) | OtherTags(v) | RuleAuthor(v) | RuleCreationDate(v)
             | RuleModifiedDate(v) | Status(v) | RuleID(v) | Provider(v) | Details(v)
             | RenderedMessage(v) | SrcASN(v) | SrcCountry(v) | SrcCity(v) | TgtASN(v)
-            | TgtCountry(v) | TgtCity(v) | RecoveredRecord(v) | ExtraFieldInfo(v) | Literal(v) => {
-                v.to_string()
-            }
+            | TgtCountry(v) | TgtCity(v) | RecoveredRecord(v) | ExtraFieldInfo(v) | Literal(v)
+            | OriginalOffset(v) | CarveOffset(v) | AssetRole(v) | AssetOwner(v) | AssetTier(v)
+            | AssetTags(v) | IdentityAccount(v) | IdentityType(v) | IdentityStatus(v)
+            | ProcessAncestry(v) | SessionLogonType(v) | SessionSourceIP(v)
+            | SessionLogonTime(v) | SessionDuration(v) | Template(v) => v.to_string(),
+            IdentityPrivilegedGroups(v) => v.to_string(),
         }
     }
 
@@ -83,6 +115,7 @@ impl Profile {
     pub fn convert(&self, converted_string: &CompactString) -> Self {
         match self {
             Timestamp(_) => Timestamp(converted_string.to_owned().into()),
+            OriginalOffset(_) => OriginalOffset(converted_string.to_owned().into()),
             Computer(_) => Computer(converted_string.to_owned().into()),
             Channel(_) => Channel(converted_string.to_owned().into()),
             Level(_) => Level(converted_string.to_owned().into()),
@@ -109,20 +142,39 @@ impl Profile {
             TgtCity(_) => TgtCity(converted_string.to_owned().into()),
             ExtraFieldInfo(_) => ExtraFieldInfo(converted_string.to_owned().into()),
             RecoveredRecord(_) => RecoveredRecord(converted_string.to_owned().into()),
+            CarveOffset(_) => CarveOffset(converted_string.to_owned().into()),
             Details(_) => Details(converted_string.to_owned().into()),
             AllFieldInfo(_) => AllFieldInfo(converted_string.to_owned().into()),
+            AssetRole(_) => AssetRole(converted_string.to_owned().into()),
+            AssetOwner(_) => AssetOwner(converted_string.to_owned().into()),
+            AssetTier(_) => AssetTier(converted_string.to_owned().into()),
+            AssetTags(_) => AssetTags(converted_string.to_owned().into()),
+            IdentityAccount(_) => IdentityAccount(converted_string.to_owned().into()),
+            IdentityType(_) => IdentityType(converted_string.to_owned().into()),
+            IdentityStatus(_) => IdentityStatus(converted_string.to_owned().into()),
+            IdentityPrivilegedGroups(_) => {
+                IdentityPrivilegedGroups(converted_string.to_owned().into())
+            }
+            ProcessAncestry(_) => ProcessAncestry(converted_string.to_owned().into()),
+            SessionLogonType(_) => SessionLogonType(converted_string.to_owned().into()),
+            SessionSourceIP(_) => SessionSourceIP(converted_string.to_owned().into()),
+            SessionLogonTime(_) => SessionLogonTime(converted_string.to_owned().into()),
+            SessionDuration(_) => SessionDuration(converted_string.to_owned().into()),
+            Template(_) => Template(converted_string.to_owned().into()),
             // Literal is the only variant left: fixed strings are never converted per record.
             p => p.to_owned(),
         }
     }
 }
 
-/// Maps a `%Alias%` placeholder from profiles.yaml to its `Profile` variant. Any string that is
-/// not a known alias becomes a `Literal` and is output verbatim.
+/// Maps a `%Alias%` placeholder from profiles.yaml to its `Profile` variant. Any other string
+/// containing `%...%` placeholders becomes a `Template`, and the rest become a `Literal` that is
+/// output verbatim.
 impl From<&str> for Profile {
     fn from(alias: &str) -> Self {
         match alias {
             "%Timestamp%" => Timestamp(Default::default()),
+            "%OriginalOffset%" => OriginalOffset(Default::default()),
             "%Computer%" => Computer(Default::default()),
             "%Channel%" => Channel(Default::default()),
             "%Level%" => Level(Default::default()),
@@ -145,11 +197,37 @@ impl From<&str> for Profile {
             "%RenderedMessage%" => RenderedMessage(Default::default()),
             "%ExtraFieldInfo%" => ExtraFieldInfo(Default::default()),
             "%RecoveredRecord%" => RecoveredRecord(Default::default()),
+            "%CarveOffset%" => CarveOffset(Default::default()),
+            "%AssetRole%" => AssetRole(Default::default()),
+            "%AssetOwner%" => AssetOwner(Default::default()),
+            "%AssetTier%" => AssetTier(Default::default()),
+            "%AssetTags%" => AssetTags(Default::default()),
+            "%IdentityAccount%" => IdentityAccount(Default::default()),
+            "%IdentityType%" => IdentityType(Default::default()),
+            "%IdentityStatus%" => IdentityStatus(Default::default()),
+            "%IdentityPrivilegedGroups%" => IdentityPrivilegedGroups(Default::default()),
+            "%ProcessAncestry%" => ProcessAncestry(Default::default()),
+            "%SessionLogonType%" => SessionLogonType(Default::default()),
+            "%SessionSourceIP%" => SessionSourceIP(Default::default()),
+            "%SessionLogonTime%" => SessionLogonTime(Default::default()),
+            "%SessionDuration%" => SessionDuration(Default::default()),
+            s if timestamp_zone(s).is_some() => {
+                Timestamp(timestamp_zone(s).unwrap().to_string().into())
+            }
+            s if ProfileTemplate::is_template(s) => Template(s.to_string().into()),
             s => Literal(s.to_string().into()), // For outputting fixed strings from profiles.yaml without conversion.
         }
     }
 }
 
+/// Returns the zone name of a `%Timestamp|tz:<IANA zone>%` column.
+fn timestamp_zone(alias: &str) -> Option<&str> {
+    alias
+        .strip_prefix("%Timestamp|tz:")?
+        .strip_suffix('%')
+        .map(str::trim)
+}
+
 // Loads the profile YAML at the specified path, falling back to the copy embedded in the binary
 // (see DefaultProfile) when the file does not exist on disk.
 fn read_profile_data(profile_path: &str) -> Result<Vec<Yaml>, String> {
@@ -187,9 +265,9 @@ fn read_profile_data(profile_path: &str) -> Result<Vec<Yaml>, String> {
 /// Loads the output profile as an ordered list of (column name, field kind) pairs. The profile
 /// named by the --profile option is used if one was given; otherwise the default profile is
 /// loaded. Reserved GeoIP columns are appended when a GeoIP database has been loaded, and a
-/// RecoveredRecord column is appended when record recovery is enabled. Returns None if
-/// `opt_stored_static` is None or the profile cannot be loaded (in the latter case an alert has
-/// already been printed).
+/// RecoveredRecord column is appended when record recovery is enabled (and a CarveOffset column
+/// when carving). Returns None if `opt_stored_static` is None or the profile cannot be loaded
+/// (in the latter case an alert has already been printed).
 pub fn load_profile(
     default_profile_path: &str,
     profile_path: &str,
@@ -269,6 +347,25 @@ pub fn load_profile(
                 ));
             });
     }
+    // Check the templates now so that a typo in a filter stops the scan before it starts.
+    for (column_name, profile) in ret.iter() {
+        if let Template(source) = profile
+            && let Err(e) = ProfileTemplate::cached(source)
+        {
+            AlertMessage::alert(&format!("Invalid profile column {column_name}: {e}")).ok();
+            return None;
+        }
+        if let Timestamp(zone) = profile
+            && !zone.is_empty()
+            && !is_valid_timezone(zone)
+        {
+            AlertMessage::alert(&format!(
+                "Invalid profile column {column_name}: Unknown time zone: {zone}"
+            ))
+            .ok();
+            return None;
+        }
+    }
     // Append the reserved GeoIP output columns when the GeoIP option was specified (i.e. a GeoIP
     // database has been loaded).
     if opt_stored_static.unwrap().geo_ip_search.is_some() {
@@ -285,14 +382,52 @@ pub fn load_profile(
         ));
         ret.push((CompactString::from("TgtCity"), TgtCity(Cow::default())));
     }
+    // Append the account columns when an identity snapshot was loaded, unless the profile already
+    // places them itself.
+    if opt_stored_static.unwrap().identity_snapshot.is_some()
+        && !ret.iter().any(|(_, p)| {
+            matches!(
+                p,
+                IdentityAccount(_)
+                    | IdentityType(_)
+                    | IdentityStatus(_)
+                    | IdentityPrivilegedGroups(_)
+            )
+        })
+    {
+        ret.push((
+            CompactString::from("IdentityAccount"),
+            IdentityAccount(Cow::default()),
+        ));
+        ret.push((
+            CompactString::from("IdentityType"),
+            IdentityType(Cow::default()),
+        ));
+        ret.push((
+            CompactString::from("IdentityStatus"),
+            IdentityStatus(Cow::default()),
+        ));
+        ret.push((
+            CompactString::from("IdentityPrivilegedGroups"),
+            IdentityPrivilegedGroups(Cow::default()),
+        ));
+    }
     if let Some(opt) = &opt_stored_static.as_ref().unwrap().output_option
-        && opt.input_args.recover_records
+        && (opt.input_args.recover_records || opt.input_args.carve.is_some())
     {
         ret.push((
             CompactString::from("RecoveredRecord"),
             RecoveredRecord(Cow::default()),
         ));
     }
+    if opt_stored_static.unwrap().carve_flag
+        && !ret.iter().any(|(_, p)| matches!(p, CarveOffset(_)))
+    {
+        ret.push((
+            CompactString::from("CarveOffset"),
+            CarveOffset(Cow::default()),
+        ));
+    }
     Some(ret)
 }
 
@@ -436,6 +571,29 @@ mod tests {
         assert_eq!("a", profile_enum.to_value())
     }
 
+    #[test]
+    fn test_profile_from_template() {
+        assert!(matches!(Profile::from("%Computer%"), Profile::Computer(_)));
+        assert!(matches!(
+            Profile::from("%Computer% / %TargetUserName|lower%"),
+            Profile::Template(_)
+        ));
+        assert!(matches!(Profile::from("100% sure"), Profile::Literal(_)));
+    }
+
+    #[test]
+    fn test_profile_from_timestamp_zone() {
+        assert_eq!(Profile::from("%Timestamp%"), Profile::Timestamp("".into()));
+        assert_eq!(
+            Profile::from("%Timestamp|tz:Asia/Tokyo%"),
+            Profile::Timestamp("Asia/Tokyo".into())
+        );
+        assert!(matches!(
+            Profile::from("%OriginalOffset%"),
+            Profile::OriginalOffset(_)
+        ));
+    }
+
     #[test]
     /// The profile-loading assertions below are grouped into a single test and run sequentially.
     fn test_load_profile() {
@@ -0,0 +1,203 @@
+use hashbrown::HashMap;
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::sync::{Arc, RwLock};
+
+lazy_static! {
+    // A %...% placeholder of a profile template: a field name or path followed by |filters.
+    static ref PLACEHOLDER_REGEX: Regex = Regex::new(r"%([^%\s|][^%]*)%").unwrap();
+    // Parsed templates keyed by their source, so the filter regexes are compiled only once
+    // instead of for every detection.
+    static ref TEMPLATE_CACHE: RwLock<HashMap<String, Arc<ProfileTemplate>>> =
+        RwLock::new(HashMap::new());
+}
+
+/// A transform applied to a placeholder value, written after the field name with `|`
+/// (ex: `%TargetUserName|lower|default:unknown%`).
+#[derive(Debug, Clone)]
+pub enum TemplateFilter {
+    Lower,
+    Upper,
+    /// Keeps the first N characters.
+    Truncate(usize),
+    /// Keeps the first capture group of the first match (or the whole match when the pattern has
+    /// no group). Empty when the pattern does not match.
+    Regex(Regex),
+    /// Used when the value is empty or could not be found.
+    Default(String),
+}
+
+impl TemplateFilter {
+    fn parse(filter: &str) -> Result<Self, String> {
+        let (name, arg) = match filter.split_once(':') {
+            Some((name, arg)) => (name.trim(), Some(arg)),
+            None => (filter.trim(), None),
+        };
+        match (name, arg) {
+            ("lower", None) => Ok(TemplateFilter::Lower),
+            ("upper", None) => Ok(TemplateFilter::Upper),
+            ("truncate", Some(len)) => len
+                .trim()
+                .parse()
+                .map(TemplateFilter::Truncate)
+                .map_err(|_| format!("Invalid length for truncate: {len}")),
+            ("regex", Some(pattern)) => Regex::new(pattern)
+                .map(TemplateFilter::Regex)
+                .map_err(|e| format!("Invalid regular expression: {pattern}. {e}")),
+            ("default", Some(text)) => Ok(TemplateFilter::Default(text.to_string())),
+            _ => Err(format!(
+                "Unknown filter: {filter}. Please use lower, upper, truncate:N, regex:PATTERN or default:TEXT."
+            )),
+        }
+    }
+
+    fn apply(&self, value: String) -> String {
+        match self {
+            TemplateFilter::Lower => value.to_lowercase(),
+            TemplateFilter::Upper => value.to_uppercase(),
+            TemplateFilter::Truncate(len) => value.chars().take(*len).collect(),
+            TemplateFilter::Regex(regex) => regex
+                .captures(&value)
+                .and_then(|caps| caps.get(1).or_else(|| caps.get(0)))
+                .map(|m| m.as_str().to_string())
+                .unwrap_or_default(),
+            TemplateFilter::Default(text) => {
+                if is_empty_value(&value) {
+                    text.clone()
+                } else {
+                    value
+                }
+            }
+        }
+    }
+}
+
+/// Values treated as missing by the `default` filter: empty strings and the "-" and "n/a"
+/// placeholders used for fields that do not exist in a record.
+fn is_empty_value(value: &str) -> bool {
+    let value = value.trim();
+    value.is_empty() || value == "-" || value == "n/a"
+}
+
+#[derive(Debug, Clone)]
+enum TemplatePart {
+    Text(String),
+    Placeholder {
+        name: String,
+        filters: Vec<TemplateFilter>,
+    },
+}
+
+/// A computed profile column such as `%Computer% / %TargetUserName|lower%`. Placeholders name
+/// another profile alias, a field (resolved like the `details` of rules) or a raw record path
+/// such as `Event.EventData.TargetUserName`.
+#[derive(Debug, Clone)]
+pub struct ProfileTemplate {
+    parts: Vec<TemplatePart>,
+}
+
+impl ProfileTemplate {
+    /// Returns true when a profile value contains placeholders and therefore has to be rendered
+    /// per detection instead of being output as a literal string.
+    pub fn is_template(source: &str) -> bool {
+        PLACEHOLDER_REGEX.is_match(source)
+    }
+
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut parts = vec![];
+        let mut last = 0;
+        for caps in PLACEHOLDER_REGEX.captures_iter(source) {
+            let whole = caps.get(0).unwrap();
+            if whole.start() > last {
+                parts.push(TemplatePart::Text(source[last..whole.start()].to_string()));
+            }
+            let mut sections = caps[1].split('|');
+            let name = sections.next().unwrap_or_default().trim().to_string();
+            let filters = sections
+                .map(TemplateFilter::parse)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("{e} (in {source})"))?;
+            parts.push(TemplatePart::Placeholder { name, filters });
+            last = whole.end();
+        }
+        if last < source.len() {
+            parts.push(TemplatePart::Text(source[last..].to_string()));
+        }
+        Ok(ProfileTemplate { parts })
+    }
+
+    /// Returns the parsed template for `source`, parsing it on first use.
+    pub fn cached(source: &str) -> Result<Arc<Self>, String> {
+        if let Some(template) = TEMPLATE_CACHE.read().unwrap().get(source) {
+            return Ok(template.clone());
+        }
+        let template = Arc::new(ProfileTemplate::parse(source)?);
+        TEMPLATE_CACHE
+            .write()
+            .unwrap()
+            .insert(source.to_string(), template.clone());
+        Ok(template)
+    }
+
+    /// Renders the template. `resolve` returns the value of a placeholder name, or None when it
+    /// cannot be found, in which case "n/a" is used before the filters are applied.
+    pub fn render(&self, resolve: impl Fn(&str) -> Option<String>) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                TemplatePart::Text(text) => text.clone(),
+                TemplatePart::Placeholder { name, filters } => filters.iter().fold(
+                    resolve(name).unwrap_or_else(|| "n/a".to_string()),
+                    |value, filter| filter.apply(value),
+                ),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ProfileTemplate;
+
+    fn resolve(name: &str) -> Option<String> {
+        match name {
+            "Computer" => Some("DC01.corp.local".to_string()),
+            "TargetUserName" => Some("Administrator".to_string()),
+            "Event.EventData.CommandLine" => Some("powershell.exe -enc SQBFAFgA".to_string()),
+            "Empty" => Some("-".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_is_template() {
+        assert!(ProfileTemplate::is_template("%TargetUserName%"));
+        assert!(ProfileTemplate::is_template("User: %TargetUserName|lower%"));
+        assert!(!ProfileTemplate::is_template("100% sure"));
+        assert!(!ProfileTemplate::is_template("Hayabusa"));
+    }
+
+    #[test]
+    fn test_render_with_filters() {
+        let render = |source: &str| ProfileTemplate::parse(source).unwrap().render(resolve);
+        assert_eq!(
+            render("%Computer|regex:^([^.]+)|upper%\\%TargetUserName|lower%"),
+            "DC01\\administrator"
+        );
+        assert_eq!(
+            render("%Event.EventData.CommandLine|truncate:14%"),
+            "powershell.exe"
+        );
+        assert_eq!(render("%Empty|default:none%"), "none");
+        assert_eq!(render("%NoSuchField%"), "n/a");
+        assert_eq!(render("%NoSuchField|default:-%"), "-");
+        assert_eq!(render("%Computer|regex:^WEB%"), "");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(ProfileTemplate::parse("%Computer|reverse%").is_err());
+        assert!(ProfileTemplate::parse("%Computer|truncate:abc%").is_err());
+        assert!(ProfileTemplate::parse("%Computer|regex:(%").is_err());
+    }
+}
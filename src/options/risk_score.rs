@@ -0,0 +1,428 @@
+use crate::detections::configs::{CURRENT_EXE_PATH, ONE_CONFIG_MAP};
+use crate::detections::message::AlertMessage;
+use crate::detections::utils::{check_setting_path, get_serde_number_to_string};
+use crate::level::LEVEL;
+use crate::options::asset_inventory::ASSET_INVENTORY;
+use compact_str::CompactString;
+use dashmap::DashMap;
+use hashbrown::{HashMap, HashSet};
+use itertools::Itertools;
+use rust_embed::Embed;
+use serde_json::{Value, json};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use yaml_rust2::{Yaml, YamlLoader};
+
+/// Embedded copy of config/risk_scoring.yaml, used when the file is not found on disk. Sections
+/// missing from the file are also taken from here.
+#[derive(Embed)]
+#[folder = "config"]
+#[include = "risk_scoring.yaml"]
+struct DefaultRiskWeights;
+
+fn default_risk_weights() -> String {
+    let file = DefaultRiskWeights::get("risk_scoring.yaml").unwrap();
+    String::from_utf8_lossy(file.data.as_ref()).into_owned()
+}
+
+/// Weights of the risk score, loaded from config/risk_scoring.yaml.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RiskWeights {
+    /// Points of one detection per level.
+    pub levels: HashMap<LEVEL, f64>,
+    /// Multipliers for rules with these tags (ex: detection.threat-hunting). When several tags
+    /// match, the multipliers are multiplied together.
+    pub tags: HashMap<CompactString, f64>,
+    /// Points added for each distinct ATT&CK tactic detected for an entity.
+    pub tactic_diversity: f64,
+    /// Multipliers for detections on computers in these asset inventory tiers.
+    pub asset_tiers: HashMap<CompactString, f64>,
+    /// EventData fields whose values are scored as accounts.
+    pub account_fields: Vec<CompactString>,
+    /// Upper-cased account names that are never scored.
+    pub ignored_accounts: HashSet<CompactString>,
+}
+
+impl Default for RiskWeights {
+    fn default() -> Self {
+        RiskWeights::from_yaml(&default_risk_weights()).unwrap()
+    }
+}
+
+impl RiskWeights {
+    /// Parses the weights file. Sections that are not in `contents` keep their default value.
+    pub fn from_yaml(contents: &str) -> Result<Self, String> {
+        let docs = YamlLoader::load_from_str(contents).map_err(|e| e.to_string())?;
+        let user = docs.first().cloned().unwrap_or(Yaml::Null);
+        let defaults = YamlLoader::load_from_str(&default_risk_weights()).unwrap();
+        let section = |name: &str| {
+            if user[name].is_badvalue() || user[name].is_null() {
+                defaults[0][name].clone()
+            } else {
+                user[name].clone()
+            }
+        };
+        let number = |value: &Yaml, name: &str| -> Result<f64, String> {
+            value
+                .as_f64()
+                .or_else(|| value.as_i64().map(|v| v as f64))
+                .ok_or_else(|| format!("{name} must be a number."))
+        };
+        let number_map = |name: &str| -> Result<HashMap<CompactString, f64>, String> {
+            let mut ret = HashMap::new();
+            for (key, value) in section(name).as_hash().into_iter().flatten() {
+                let key = key
+                    .as_str()
+                    .ok_or_else(|| format!("The keys of {name} must be strings."))?;
+                ret.insert(
+                    CompactString::from(key.to_lowercase()),
+                    number(value, &format!("{name}.{key}"))?,
+                );
+            }
+            Ok(ret)
+        };
+        let string_list = |name: &str| -> Vec<CompactString> {
+            section(name)
+                .as_vec()
+                .into_iter()
+                .flatten()
+                .filter_map(|value| value.as_str().map(CompactString::from))
+                .collect()
+        };
+
+        let mut levels = HashMap::new();
+        for (level, points) in number_map("levels")? {
+            match LEVEL::from(&level) {
+                LEVEL::UNDEFINED => return Err(format!("Unknown level in levels: {level}")),
+                level => levels.insert(level, points),
+            };
+        }
+        Ok(RiskWeights {
+            levels,
+            tags: number_map("tags")?,
+            tactic_diversity: number(&section("tactic_diversity"), "tactic_diversity")?,
+            asset_tiers: number_map("asset_tiers")?,
+            account_fields: string_list("account_fields"),
+            ignored_accounts: string_list("ignored_accounts")
+                .iter()
+                .map(|name| name.to_uppercase().into())
+                .collect(),
+        })
+    }
+
+    /// Loads risk_scoring.yaml from the rules config directory when one is there, and otherwise
+    /// from config/risk_scoring.yaml. The embedded default weights are used when neither file
+    /// exists or the file cannot be parsed.
+    pub fn load(config_path: &Path) -> Self {
+        let contents = if let Some(contents) = ONE_CONFIG_MAP.get("risk_scoring.yaml") {
+            contents.clone()
+        } else {
+            let path = check_setting_path(config_path, "risk_scoring.yaml", false).or_else(|| {
+                check_setting_path(
+                    &CURRENT_EXE_PATH.to_path_buf(),
+                    "config/risk_scoring.yaml",
+                    false,
+                )
+            });
+            match path.map(fs::read_to_string) {
+                Some(Ok(contents)) => contents,
+                _ => return RiskWeights::default(),
+            }
+        };
+        RiskWeights::from_yaml(&contents).unwrap_or_else(|e| {
+            AlertMessage::alert(&format!(
+                "Failed to parse risk_scoring.yaml. The default weights will be used. {e}"
+            ))
+            .ok();
+            RiskWeights::default()
+        })
+    }
+
+    /// Points of one detection before the tactic bonus: the level weight multiplied by the
+    /// weights of the rule's tags and of the computer's asset tier.
+    fn detection_points(&self, level: &LEVEL, tags: &[&str], computer: &str) -> f64 {
+        let tag_multiplier: f64 = tags
+            .iter()
+            .filter_map(|tag| self.tags.get(tag.to_lowercase().as_str()))
+            .product();
+        let tier_multiplier = ASSET_INVENTORY
+            .lookup(computer)
+            .and_then(|asset| self.asset_tiers.get(asset.tier.to_lowercase().as_str()))
+            .copied()
+            .unwrap_or(1.0);
+        self.levels.get(level).copied().unwrap_or_default() * tag_multiplier * tier_multiplier
+    }
+}
+
+/// Kind of entity a risk score belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum EntityKind {
+    Computer,
+    Account,
+}
+
+impl fmt::Display for EntityKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EntityKind::Computer => write!(f, "Computer"),
+            EntityKind::Account => write!(f, "Account"),
+        }
+    }
+}
+
+/// Accumulated risk of one computer or account.
+#[derive(Debug, Clone, Default)]
+pub struct EntityRisk {
+    pub points: f64,
+    pub detections: usize,
+    pub max_level: LEVEL,
+    pub tactics: HashSet<CompactString>,
+}
+
+impl EntityRisk {
+    /// Total score: the detection points plus the bonus for each distinct tactic.
+    pub fn score(&self, weights: &RiskWeights) -> f64 {
+        self.points + weights.tactic_diversity * self.tactics.len() as f64
+    }
+}
+
+/// Risk of each entity. Filled when the detections are output.
+pub type RiskScoreMap = DashMap<(EntityKind, CompactString), EntityRisk>;
+
+/// Returns the ATT&CK tactic names in a rule's tags (ex: execution for attack.execution).
+/// Technique, group and software tags (ex: attack.t1059, attack.g0032) are not tactics.
+fn tactics<'a>(tags: &[&'a str]) -> Vec<&'a str> {
+    tags.iter()
+        .copied()
+        .filter_map(|tag| tag.strip_prefix("attack."))
+        .filter(|name| {
+            let mut chars = name.chars();
+            !(matches!(chars.next(), Some('t' | 'g' | 's'))
+                && chars.next().is_some_and(|c| c.is_ascii_digit()))
+        })
+        .unique()
+        .collect()
+}
+
+/// Returns the account names in the configured EventData fields of a record.
+pub fn record_accounts(record: &Value, weights: &RiskWeights) -> Vec<CompactString> {
+    weights
+        .account_fields
+        .iter()
+        .filter_map(|field| {
+            get_serde_number_to_string(&record["Event"]["EventData"][field.as_str()], false)
+        })
+        .map(|name| CompactString::from(name.trim()))
+        .filter(|name| {
+            !name.is_empty()
+                && !name.ends_with('$')
+                && !weights
+                    .ignored_accounts
+                    .contains(name.to_uppercase().as_str())
+        })
+        .unique()
+        .collect()
+}
+
+/// Adds one detection to the risk of its computers and accounts. `computer` may hold several
+/// names joined with " ¦ " (count/correlation results).
+pub fn add_detection(
+    map: &RiskScoreMap,
+    weights: &RiskWeights,
+    tags: &[&str],
+    computer: &str,
+    accounts: &[CompactString],
+    level: &LEVEL,
+) {
+    let computers = computer
+        .split(" ¦ ")
+        .map(str::trim)
+        .filter(|name| !name.is_empty() && *name != "-")
+        .unique()
+        .collect_vec();
+    let tactics = tactics(tags);
+    let points = computers
+        .iter()
+        .map(|name| weights.detection_points(level, tags, name))
+        .fold(0.0, f64::max);
+    let entities = computers
+        .iter()
+        .map(|name| (EntityKind::Computer, CompactString::from(*name)))
+        .chain(
+            accounts
+                .iter()
+                .map(|name| (EntityKind::Account, name.clone())),
+        );
+    for entity in entities {
+        let mut risk = map.entry(entity).or_default();
+        risk.points += points;
+        risk.detections += 1;
+        if level.index() > risk.max_level.index() {
+            risk.max_level = level.clone();
+        }
+        risk.tactics
+            .extend(tactics.iter().map(|t| CompactString::from(*t)));
+    }
+}
+
+/// One row of the ranked entity table.
+#[derive(Debug, Clone)]
+pub struct RankedEntity {
+    pub kind: EntityKind,
+    pub name: CompactString,
+    pub score: f64,
+    pub risk: EntityRisk,
+}
+
+/// Returns every entity ordered by score, highest first. Ties are ordered by kind and name.
+pub fn ranked_entities(map: &RiskScoreMap, weights: &RiskWeights) -> Vec<RankedEntity> {
+    map.iter()
+        .map(|entry| RankedEntity {
+            kind: entry.key().0,
+            name: entry.key().1.clone(),
+            score: entry.value().score(weights),
+            risk: entry.value().clone(),
+        })
+        .sorted_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then(a.kind.cmp(&b.kind))
+                .then(a.name.cmp(&b.name))
+        })
+        .collect()
+}
+
+/// Returns the tactics detected for an entity, sorted by name.
+pub fn tactic_names(risk: &EntityRisk) -> Vec<&str> {
+    risk.tactics.iter().map(|t| t.as_str()).sorted().collect()
+}
+
+/// Writes the ranked entities to `path` as a JSON array.
+pub fn output_json(entities: &[RankedEntity], path: &Path) -> io::Result<()> {
+    let rows = entities
+        .iter()
+        .enumerate()
+        .map(|(i, entity)| {
+            json!({
+                "rank": i + 1,
+                "type": entity.kind.to_string(),
+                "name": entity.name.as_str(),
+                "score": (entity.score * 100.0).round() / 100.0,
+                "detections": entity.risk.detections,
+                "max_level": entity.risk.max_level.to_full(),
+                "tactics": tactic_names(&entity.risk),
+            })
+        })
+        .collect_vec();
+    let mut out = serde_json::to_string_pretty(&rows).unwrap();
+    out.push('\n');
+    fs::write(path, out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        EntityKind, RiskScoreMap, RiskWeights, add_detection, ranked_entities, record_accounts,
+        tactics,
+    };
+    use crate::level::LEVEL;
+    use compact_str::CompactString;
+    use serde_json::json;
+
+    #[test]
+    fn test_from_yaml_keeps_default_sections() {
+        let weights = RiskWeights::from_yaml("levels:\n  high: 10\ntactic_diversity: 2\n").unwrap();
+        assert_eq!(weights.levels[&LEVEL::HIGH], 10.0);
+        assert!(!weights.levels.contains_key(&LEVEL::LOW));
+        assert_eq!(weights.tactic_diversity, 2.0);
+        assert_eq!(weights.tags["detection.threat-hunting"], 0.5);
+        assert!(weights.ignored_accounts.contains("SYSTEM"));
+
+        assert!(RiskWeights::from_yaml("levels:\n  severe: 1\n").is_err());
+        assert!(RiskWeights::from_yaml("tactic_diversity: many\n").is_err());
+    }
+
+    #[test]
+    fn test_load() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(RiskWeights::load(dir.path()), RiskWeights::default());
+
+        std::fs::write(
+            dir.path().join("risk_scoring.yaml"),
+            "tactic_diversity: 1\n",
+        )
+        .unwrap();
+        let weights = RiskWeights::load(dir.path());
+        assert_eq!(weights.tactic_diversity, 1.0);
+        assert_eq!(weights.levels[&LEVEL::CRITICAL], 15.0);
+    }
+
+    #[test]
+    fn test_tactics() {
+        let tags = [
+            "attack.execution",
+            "attack.t1059.001",
+            "attack.g0032",
+            "attack.s0002",
+            "attack.defense-evasion",
+            "detection.threat-hunting",
+        ];
+        assert_eq!(tactics(&tags), vec!["execution", "defense-evasion"]);
+    }
+
+    #[test]
+    fn test_record_accounts() {
+        let record = json!({"Event": {"EventData": {
+            "TargetUserName": "admin",
+            "SubjectUserName": "DC01$",
+            "User": "NT AUTHORITY\\SYSTEM",
+            "AccountName": "system",
+        }}});
+        let weights = RiskWeights::default();
+        assert_eq!(
+            record_accounts(&record, &weights),
+            vec!["admin", "NT AUTHORITY\\SYSTEM"]
+        );
+    }
+
+    #[test]
+    fn test_ranked_entities() {
+        let map = RiskScoreMap::new();
+        let weights = RiskWeights::default();
+        let admin = vec![CompactString::from("admin")];
+        add_detection(
+            &map,
+            &weights,
+            &["attack.execution"],
+            "PC-01",
+            &admin,
+            &LEVEL::HIGH,
+        );
+        add_detection(
+            &map,
+            &weights,
+            &["attack.persistence", "detection.threat-hunting"],
+            "PC-01 ¦ PC-02",
+            &[],
+            &LEVEL::MEDIUM,
+        );
+
+        let ranked = ranked_entities(&map, &weights);
+        let rows = ranked
+            .iter()
+            .map(|e| (e.kind, e.name.as_str(), e.score, e.risk.detections))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            vec![
+                // 8 + 3 * 0.5 points and two tactics.
+                (EntityKind::Computer, "PC-01", 19.5, 2),
+                (EntityKind::Account, "admin", 13.0, 1),
+                (EntityKind::Computer, "PC-02", 6.5, 1),
+            ]
+        );
+        assert_eq!(ranked[0].risk.max_level, LEVEL::HIGH);
+    }
+}
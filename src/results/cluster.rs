@@ -0,0 +1,247 @@
+use chrono::{DateTime, Duration, Utc};
+use compact_str::CompactString;
+use hashbrown::{HashMap, HashSet};
+
+use crate::detections::configs::TimeFormatOptions;
+use crate::detections::message::DetectInfo;
+use crate::detections::utils::format_time;
+use crate::options::profile::Profile;
+
+/// Columns used to group detections when `--cluster-keys` is not specified.
+pub const DEFAULT_CLUSTER_KEYS: [&str; 2] = ["RuleTitle", "Computer"];
+
+/// Converts a cluster window such as "30m", "1h" or "1d12h" into a duration. Returns `None` for
+/// an unknown unit, a missing number or a total length of zero.
+pub fn parse_cluster_window(window: &str) -> Option<Duration> {
+    let mut total = Duration::zero();
+    let mut num = String::new();
+    for c in window.trim().chars() {
+        if c.is_ascii_digit() {
+            num.push(c);
+            continue;
+        }
+        let value: i64 = num.parse().ok()?;
+        num.clear();
+        let part = match c {
+            'd' => Duration::try_days(value),
+            'h' => Duration::try_hours(value),
+            'm' => Duration::try_minutes(value),
+            's' => Duration::try_seconds(value),
+            _ => None,
+        }?;
+        total = total.checked_add(&part)?;
+    }
+    if !num.is_empty() || total.is_zero() {
+        return None;
+    }
+    Some(total)
+}
+
+/// Returns the value a detection has for a cluster key. Output profile columns are looked up
+/// first, then the rule and event fields kept on every detection, and finally the `Details`
+/// and `AllFieldInfo` field names (ex: TgtUser).
+fn cluster_key_value(detect_info: &DetectInfo, key: &str) -> CompactString {
+    if let Some((_, profile)) = detect_info
+        .output_fields
+        .iter()
+        .find(|(column, _)| column.eq_ignore_ascii_case(key))
+    {
+        return profile.to_value().into();
+    }
+    match key.to_ascii_lowercase().as_str() {
+        "ruletitle" => return detect_info.ruletitle.clone(),
+        "ruleid" => return detect_info.ruleid.clone(),
+        "computer" => return detect_info.computername.clone(),
+        "eventid" => return detect_info.eventid.clone(),
+        "level" => return detect_info.level.to_full().into(),
+        _ => {}
+    }
+    let prefix = format!("{key}: ");
+    ["#Details", "#AllFieldInfo"]
+        .iter()
+        .filter_map(|stock| detect_info.details_convert_map.get(*stock))
+        .flatten()
+        .find_map(|entry| entry.strip_prefix(prefix.as_str()))
+        .map(CompactString::from)
+        .unwrap_or_default()
+}
+
+/// Groups detections that share the same cluster key values and fall within `window` of the
+/// first detection of the group into a single incident. The first detection of each cluster is
+/// kept as its sample record and gets `Count`, `FirstSeen` and `LastSeen` columns appended, and
+/// the indexes of the other members are returned so they can be skipped like duplicates.
+/// Assumes `detect_infos` is already sorted by detected time; indexes in `skip_indices` (removed
+/// duplicates) are ignored.
+pub fn get_cluster_indices(
+    detect_infos: &mut [DetectInfo],
+    skip_indices: &HashSet<usize>,
+    keys: &[String],
+    window: Duration,
+    time_format: &TimeFormatOptions,
+) -> HashSet<usize> {
+    struct Cluster {
+        sample: usize,
+        first: DateTime<Utc>,
+        last: DateTime<Utc>,
+        count: usize,
+    }
+
+    let mut member_indices = HashSet::new();
+    let mut open_clusters: HashMap<Vec<CompactString>, Cluster> = HashMap::new();
+    let mut clusters = vec![];
+    for (i, detect_info) in detect_infos.iter().enumerate() {
+        if skip_indices.contains(&i) {
+            continue;
+        }
+        let key: Vec<CompactString> = keys
+            .iter()
+            .map(|k| cluster_key_value(detect_info, k))
+            .collect();
+        let time = detect_info.detected_time;
+        if let Some(cluster) = open_clusters.get_mut(&key)
+            && time - cluster.first <= window
+        {
+            cluster.last = time;
+            cluster.count += 1;
+            member_indices.insert(i);
+            continue;
+        }
+        // Either the first detection for this key or outside the window of the open cluster,
+        // so start a new one and close the previous cluster for this key.
+        let new_cluster = Cluster {
+            sample: i,
+            first: time,
+            last: time,
+            count: 1,
+        };
+        if let Some(closed) = open_clusters.insert(key, new_cluster) {
+            clusters.push(closed);
+        }
+    }
+    clusters.extend(open_clusters.into_values());
+
+    for cluster in clusters {
+        let output_fields = &mut detect_infos[cluster.sample].output_fields;
+        output_fields.push((
+            CompactString::from("Count"),
+            Profile::Literal(cluster.count.to_string().into()),
+        ));
+        output_fields.push((
+            CompactString::from("FirstSeen"),
+            Profile::Literal(
+                format_time(&cluster.first, false, time_format)
+                    .to_string()
+                    .into(),
+            ),
+        ));
+        output_fields.push((
+            CompactString::from("LastSeen"),
+            Profile::Literal(
+                format_time(&cluster.last, false, time_format)
+                    .to_string()
+                    .into(),
+            ),
+        ));
+    }
+    member_indices
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone, Utc};
+    use compact_str::CompactString;
+    use hashbrown::{HashMap, HashSet};
+
+    use crate::detections::configs::TimeFormatOptions;
+    use crate::detections::message::DetectInfo;
+    use crate::level::LEVEL;
+    use crate::options::profile::Profile;
+
+    use super::{get_cluster_indices, parse_cluster_window};
+
+    fn make(minute: u32, title: &str, computer: &str) -> DetectInfo {
+        DetectInfo {
+            detected_time: Utc.with_ymd_and_hms(2020, 1, 1, 0, minute, 0).unwrap(),
+            rule_path: CompactString::from("rule.yml"),
+            ruleid: CompactString::from("id"),
+            ruletitle: CompactString::from(title),
+            ruleauthor: CompactString::from("author"),
+            level: LEVEL::HIGH,
+            computername: CompactString::from(computer),
+            rec_id: CompactString::from("1"),
+            eventid: CompactString::from("1"),
+            detail: CompactString::default(),
+            output_fields: vec![(
+                CompactString::from("RuleTitle"),
+                Profile::RuleTitle(title.to_string().into()),
+            )],
+            agg_result: None,
+            details_convert_map: HashMap::default(),
+            channel: CompactString::default(),
+            tags: vec![],
+            risk_accounts: vec![],
+            process_key: None,
+            session_key: None,
+        }
+    }
+
+    fn column(detect_info: &DetectInfo, name: &str) -> Option<String> {
+        detect_info
+            .output_fields
+            .iter()
+            .find(|(column, _)| column == name)
+            .map(|(_, profile)| profile.to_value())
+    }
+
+    #[test]
+    fn test_parse_cluster_window() {
+        assert_eq!(parse_cluster_window("30m"), Duration::try_minutes(30));
+        assert_eq!(parse_cluster_window("1d12h"), Duration::try_hours(36));
+        assert_eq!(parse_cluster_window("90s"), Duration::try_seconds(90));
+        assert_eq!(parse_cluster_window("1h30"), None);
+        assert_eq!(parse_cluster_window("1w"), None);
+        assert_eq!(parse_cluster_window("0m"), None);
+    }
+
+    #[test]
+    fn test_get_cluster_indices() {
+        // Sorted by time, as `get_cluster_indices` requires.
+        let mut detect_infos = vec![
+            make(0, "A", "PC1"),  // 0: starts cluster A/PC1
+            make(5, "A", "PC2"),  // 1: different computer -> own cluster
+            make(10, "A", "PC1"), // 2: joins cluster 0
+            make(20, "B", "PC1"), // 3: different rule -> own cluster
+            make(30, "A", "PC1"), // 4: duplicate that is skipped
+            make(40, "A", "PC1"), // 5: more than 30m after the first record -> new cluster
+        ];
+        let keys = vec!["RuleTitle".to_string(), "Computer".to_string()];
+        let skip = HashSet::from([4]);
+        let members = get_cluster_indices(
+            &mut detect_infos,
+            &skip,
+            &keys,
+            Duration::try_minutes(30).unwrap(),
+            &TimeFormatOptions {
+                utc: true,
+                ..Default::default()
+            },
+        );
+        let mut got: Vec<usize> = members.into_iter().collect();
+        got.sort_unstable();
+        assert_eq!(got, vec![2]);
+
+        assert_eq!(column(&detect_infos[0], "Count").as_deref(), Some("2"));
+        assert_eq!(
+            column(&detect_infos[0], "FirstSeen").as_deref(),
+            Some("2020-01-01 00:00:00.000 +00:00")
+        );
+        assert_eq!(
+            column(&detect_infos[0], "LastSeen").as_deref(),
+            Some("2020-01-01 00:10:00.000 +00:00")
+        );
+        for i in [1, 3, 5] {
+            assert_eq!(column(&detect_infos[i], "Count").as_deref(), Some("1"));
+        }
+        assert_eq!(column(&detect_infos[4], "Count"), None);
+    }
+}
@@ -11,9 +11,13 @@ use crate::level::{_get_output_color, create_output_color_map};
 use crate::options::profile::Profile;
 
 use super::display::_get_serialized_disp_output;
+use super::export::{ecs_record, timesketch_record};
+use super::html_stock::_stock_html_detection_row;
 use super::json::output_json_str;
 use super::summary::calc_statistic_info;
-use super::{OutputWriter, ResultOutputState, ResultWriter, handle_output_error};
+use super::{
+    OutputWriter, ResultOutputState, ResultWriter, handle_output_error, new_result_writer,
+};
 
 /// Writes one batch of detections and folds it into the summary statistics. This is the
 /// streaming output path used in low-memory mode, where results are emitted per batch instead
@@ -98,18 +102,74 @@ pub(crate) fn emit_csv_inner(
             _ => (false, false, false),
         };
 
+    // Timesketch and ECS records are built from the detection itself instead of the profile layout.
+    let export_record: Option<fn(&DetectInfo) -> serde_json::Value> =
+        match &stored_static.config.action.as_ref().unwrap() {
+            Action::DfirTimeline(option) => match option.output_type {
+                OutputType::Timesketch => Some(timesketch_record),
+                OutputType::Ecs => Some(ecs_record),
+                _ => None,
+            },
+            _ => None,
+        };
+
     let profile = stored_static.profiles.as_ref().unwrap();
+    let cluster_flag = stored_static
+        .output_option
+        .as_ref()
+        .is_some_and(|option| option.cluster_window.is_some());
+    let mut prev_shard_key = None;
     for (i, detect_info) in detect_infos.iter().enumerate() {
         if duplicate_indices.contains(&i) {
             continue;
         }
-        if output_writer.display_flag && !(json_output_flag || jsonl_output_flag) {
+        if stored_static.html_interactive_flag {
+            _stock_html_detection_row(detect_info, result_state);
+        }
+        // With --split-by, write to the shard file of the detection. Each shard gets its own CSV
+        // header, and "DUP" must only refer to the previous row of the same file.
+        let (result_writer, write_header) = match output_writer.shards.as_mut() {
+            Some(shards) => {
+                let key = shards.shard_key(detect_info);
+                if prev_shard_key.as_ref() != Some(&key) {
+                    result_state.prev_message.clear();
+                    result_state.prev_details_convert_map.clear();
+                }
+                let (writer, rows) =
+                    shards.shard(&key, |target| new_result_writer(stored_static, target))?;
+                *rows += 1;
+                prev_shard_key = Some(key);
+                (writer, *rows == 1)
+            }
+            None => (
+                &mut output_writer.result_writer,
+                !result_state.has_displayed_header,
+            ),
+        };
+        if let Some(export_record) = export_record {
+            let line = serde_json::to_string(&export_record(detect_info)).unwrap();
+            if output_writer.display_flag {
+                write_color_buffer(&output_writer.display_writer, None, &line, true).ok();
+            } else if let ResultWriter::Json { writer, first } = result_writer {
+                if !*first {
+                    writer.write_all(b"\n")?;
+                }
+                *first = false;
+                writer.write_all(line.as_bytes())?;
+            }
+        } else if output_writer.display_flag && !(json_output_flag || jsonl_output_flag) {
             // Terminal display output.
             if !result_state.has_displayed_header {
-                // Print the header row only once.
+                // Print the header row only once. Clustered output appends columns that are not
+                // in the profile, so take the header from the record itself.
+                let header = if cluster_flag {
+                    &detect_info.output_fields
+                } else {
+                    profile
+                };
                 _get_serialized_disp_output(
                     &output_writer.display_writer,
-                    profile,
+                    header,
                     true,
                     (&output_remover, &remover_vals),
                     multiline_flag || tab_separator_flag,
@@ -154,7 +214,7 @@ pub(crate) fn emit_csv_inner(
                     true,
                 )
                 .ok();
-            } else if let ResultWriter::Json { writer, first } = &mut output_writer.result_writer {
+            } else if let ResultWriter::Json { writer, first } = result_writer {
                 // Separate records with a newline (the previous csv-writer delimiter), without a
                 // leading or trailing one.
                 if !*first {
@@ -184,7 +244,7 @@ pub(crate) fn emit_csv_inner(
                     true,
                 )
                 .ok();
-            } else if let ResultWriter::Json { writer, first } = &mut output_writer.result_writer {
+            } else if let ResultWriter::Json { writer, first } = result_writer {
                 // The previous csv writer joined the "{", body and "}" fields with its `\n`
                 // delimiter; reproduce that layout, with records separated by a newline.
                 if !*first {
@@ -193,9 +253,9 @@ pub(crate) fn emit_csv_inner(
                 *first = false;
                 write!(writer, "{{\n{}\n}}", result.0)?;
             }
-        } else if let ResultWriter::Csv(csv_writer) = &mut output_writer.result_writer {
+        } else if let ResultWriter::Csv(csv_writer) = result_writer {
             // CSV output format
-            if !result_state.has_displayed_header {
+            if write_header {
                 csv_writer
                     .write_record(detect_info.output_fields.iter().map(|field| field.0.trim()))?;
                 result_state.has_displayed_header = true;
@@ -244,6 +304,9 @@ pub(crate) fn emit_csv_inner(
             ResultWriter::Csv(csv_writer) => csv_writer.flush()?,
             ResultWriter::Json { writer, .. } => writer.flush()?,
         }
+        if let Some(shards) = output_writer.shards.as_mut() {
+            shards.flush()?;
+        }
     }
     if json_output_flag && stored_static.output_path.is_none() {
         println!()
@@ -0,0 +1,366 @@
+use chrono::SecondsFormat;
+use compact_str::CompactString;
+use itertools::Itertools;
+use serde_json::{Map, Value, json};
+
+use crate::detections::message::{DetectInfo, TAGS_CONFIG};
+use crate::detections::utils::make_ascii_titlecase;
+use crate::options::attack_navigator::technique_ids;
+use crate::options::profile::Profile;
+
+use super::json::{group_details_raw, json_string};
+
+/// ECS version the `-t ecs` records follow.
+const ECS_VERSION: &str = "8.11.0";
+
+/// Returns the "key: value" entries of a Details/AllFieldInfo/ExtraFieldInfo column as an object.
+/// Keys that appear more than once (ex: Data[1], Data[2]) become arrays.
+fn details_object(detect_info: &DetectInfo, profile: &Profile) -> Map<String, Value> {
+    let details_key = match profile {
+        Profile::Details(_) => "#Details",
+        Profile::AllFieldInfo(_) => "#AllFieldInfo",
+        _ => "#ExtraFieldInfo",
+    };
+    let Some(stock) = detect_info.details_convert_map.get(details_key) else {
+        return Map::new();
+    };
+    // Count/correlation results keep all of their details in a single " ¦ "-joined entry.
+    let entries: Vec<CompactString> = if detect_info.agg_result.is_some() {
+        stock
+            .iter()
+            .flat_map(|entry| entry.split(" ¦ ").map(CompactString::from))
+            .collect()
+    } else {
+        stock.clone()
+    };
+    group_details_raw(&entries)
+        .into_iter()
+        .filter(|(key, values)| !key.is_empty() && values.iter().any(|v| v != "-"))
+        .map(|(key, mut values)| {
+            let value = if values.len() == 1 {
+                json_string(&values.remove(0))
+            } else {
+                Value::Array(values.iter().map(|v| json_string(v)).collect())
+            };
+            (key, value)
+        })
+        .collect()
+}
+
+/// Returns the profile columns as attributes: detail columns become objects and the other columns
+/// strings. Timestamp is left out because each format has its own time fields, as are empty
+/// values.
+fn profile_attributes(detect_info: &DetectInfo) -> Map<String, Value> {
+    let mut attributes = Map::new();
+    for (column, profile) in detect_info.output_fields.iter() {
+        match profile {
+            Profile::Timestamp(_) => {}
+            Profile::Details(_) | Profile::AllFieldInfo(_) | Profile::ExtraFieldInfo(_) => {
+                let details = details_object(detect_info, profile);
+                if !details.is_empty() {
+                    attributes.insert(column.to_string(), Value::Object(details));
+                }
+            }
+            _ => {
+                let value = profile.to_value();
+                let value = value.trim();
+                if !value.is_empty() && value != "-" {
+                    attributes.insert(column.to_string(), json_string(value));
+                }
+            }
+        }
+    }
+    attributes
+}
+
+/// Returns the values of the first profile column of the given kind, split on " ¦ ".
+fn profile_values(detect_info: &DetectInfo, is_kind: fn(&Profile) -> bool) -> Vec<String> {
+    detect_info
+        .output_fields
+        .iter()
+        .find(|(_, profile)| is_kind(profile))
+        .map(|(_, profile)| {
+            profile
+                .to_value()
+                .split(" ¦ ")
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty() && v != "-")
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// "[level] Rule title: details", the one-line summary used as the message of both formats.
+fn summary_message(detect_info: &DetectInfo) -> String {
+    let details = detect_info
+        .details_convert_map
+        .get("#Details")
+        .map(|details| details.join(" ¦ "))
+        .unwrap_or_default();
+    let mut message = format!(
+        "[{}] {}",
+        detect_info.level.to_full(),
+        detect_info.ruletitle
+    );
+    if !details.is_empty() && details != "-" {
+        message.push_str(": ");
+        message.push_str(&details);
+    }
+    message
+}
+
+/// Returns the ATT&CK names of the tactic tags listed in mitre_tactics.txt
+/// (ex: attack.command-and-control -> Command and Control).
+fn tactic_names(tags: &[CompactString]) -> Vec<String> {
+    tags.iter()
+        .map(|tag| tag.to_lowercase())
+        .filter(|tag| TAGS_CONFIG.contains_key(tag.as_str()))
+        .map(|tag| {
+            tag.trim_start_matches("attack.")
+                .split('-')
+                .map(|word| match word {
+                    "and" => CompactString::from(word),
+                    _ => make_ascii_titlecase(word),
+                })
+                .join(" ")
+        })
+        .unique()
+        .collect()
+}
+
+/// Inserts `value` unless it is empty or "-".
+fn insert_value(map: &mut Map<String, Value>, key: &str, value: &str) {
+    if !value.is_empty() && value != "-" {
+        map.insert(key.to_string(), json_string(value));
+    }
+}
+
+/// Builds one Timesketch JSONL event (`-t timesketch`). Besides the `message`, `datetime` and
+/// `timestamp_desc` fields Timesketch requires, the profile columns are added as attributes.
+pub fn timesketch_record(detect_info: &DetectInfo) -> Value {
+    let mut record = profile_attributes(detect_info);
+    record.insert(
+        "message".to_string(),
+        json_string(&summary_message(detect_info)),
+    );
+    record.insert(
+        "datetime".to_string(),
+        json!(
+            detect_info
+                .detected_time
+                .to_rfc3339_opts(SecondsFormat::Micros, true)
+        ),
+    );
+    record.insert(
+        "timestamp".to_string(),
+        json!(detect_info.detected_time.timestamp_micros()),
+    );
+    record.insert("timestamp_desc".to_string(), json!("Event Time"));
+    record.insert("data_type".to_string(), json!("hayabusa:detection"));
+    record.insert("level".to_string(), json!(detect_info.level.to_full()));
+    insert_value(&mut record, "rule_title", &detect_info.ruletitle);
+    insert_value(&mut record, "rule_id", &detect_info.ruleid);
+    insert_value(&mut record, "rule_path", &detect_info.rule_path);
+    insert_value(&mut record, "computer", &detect_info.computername);
+    insert_value(&mut record, "event_id", &detect_info.eventid);
+    insert_value(&mut record, "record_id", &detect_info.rec_id);
+    Value::Object(record)
+}
+
+/// Builds one Elastic Common Schema document (`-t ecs`). Detection data goes to the ECS `event`,
+/// `host`, `rule` and `threat` fields, the event log data to `winlog`, and the profile columns to
+/// `hayabusa`.
+pub fn ecs_record(detect_info: &DetectInfo) -> Value {
+    let mut event = Map::new();
+    event.insert("kind".to_string(), json!("alert"));
+    event.insert("module".to_string(), json!("hayabusa"));
+    event.insert("dataset".to_string(), json!("hayabusa.detection"));
+    event.insert("severity".to_string(), json!(detect_info.level.index()));
+    insert_value(&mut event, "code", &detect_info.eventid);
+    if let Some(provider) =
+        profile_values(detect_info, |p| matches!(p, Profile::Provider(_))).first()
+    {
+        insert_value(&mut event, "provider", provider);
+    }
+
+    let mut rule = Map::new();
+    insert_value(&mut rule, "id", &detect_info.ruleid);
+    insert_value(&mut rule, "name", &detect_info.ruletitle);
+    insert_value(&mut rule, "author", &detect_info.ruleauthor);
+    rule.insert("ruleset".to_string(), json!("hayabusa-rules"));
+
+    let mut winlog = Map::new();
+    insert_value(&mut winlog, "computer_name", &detect_info.computername);
+    insert_value(&mut winlog, "event_id", &detect_info.eventid);
+    insert_value(&mut winlog, "record_id", &detect_info.rec_id);
+    insert_value(&mut winlog, "channel", &detect_info.channel);
+
+    let mut hayabusa = profile_attributes(detect_info);
+    insert_value(&mut hayabusa, "rule_path", &detect_info.rule_path);
+
+    let mut record = Map::new();
+    record.insert(
+        "@timestamp".to_string(),
+        json!(
+            detect_info
+                .detected_time
+                .to_rfc3339_opts(SecondsFormat::Millis, true)
+        ),
+    );
+    record.insert("ecs".to_string(), json!({ "version": ECS_VERSION }));
+    record.insert(
+        "message".to_string(),
+        json_string(&summary_message(detect_info)),
+    );
+    record.insert("event".to_string(), Value::Object(event));
+    record.insert(
+        "log".to_string(),
+        json!({ "level": detect_info.level.to_full() }),
+    );
+    if !detect_info.computername.is_empty() && detect_info.computername != "-" {
+        // Count/correlation results can span several computers.
+        let hosts: Vec<&str> = detect_info.computername.split(" ¦ ").collect();
+        let name = if hosts.len() == 1 {
+            json!(hosts[0])
+        } else {
+            json!(hosts)
+        };
+        record.insert("host".to_string(), json!({ "name": name }));
+    }
+    record.insert("rule".to_string(), Value::Object(rule));
+    let tactics = tactic_names(&detect_info.tags);
+    let techniques = technique_ids(detect_info.tags.iter().map(|tag| tag.as_str()));
+    if !tactics.is_empty() || !techniques.is_empty() {
+        let mut threat = Map::new();
+        threat.insert("framework".to_string(), json!("MITRE ATT&CK"));
+        if !tactics.is_empty() {
+            threat.insert("tactic".to_string(), json!({ "name": tactics }));
+        }
+        if !techniques.is_empty() {
+            threat.insert("technique".to_string(), json!({ "id": techniques }));
+        }
+        record.insert("threat".to_string(), Value::Object(threat));
+    }
+    record.insert("winlog".to_string(), Value::Object(winlog));
+    record.insert("hayabusa".to_string(), Value::Object(hayabusa));
+    Value::Object(record)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ecs_record, timesketch_record};
+    use crate::detections::message::DetectInfo;
+    use crate::level::LEVEL;
+    use crate::options::profile::Profile;
+    use chrono::{TimeZone, Utc};
+    use compact_str::CompactString;
+    use hashbrown::HashMap;
+    use serde_json::Value;
+    use std::fs;
+
+    fn detect_infos() -> Vec<DetectInfo> {
+        let output_fields = |fields: &[(&str, Profile)]| {
+            fields
+                .iter()
+                .map(|(k, v)| (CompactString::from(*k), v.clone()))
+                .collect::<Vec<_>>()
+        };
+        let details = |entries: &[&str]| {
+            HashMap::from([(
+                CompactString::from("#Details"),
+                entries.iter().map(|e| CompactString::from(*e)).collect(),
+            )])
+        };
+        vec![
+            DetectInfo {
+                detected_time: Utc.with_ymd_and_hms(2026, 3, 1, 10, 15, 30).unwrap()
+                    + chrono::Duration::milliseconds(123),
+                rule_path: "rules/hayabusa/builtin/Security/4688_Encoded_PowerShell.yml".into(),
+                ruleid: "1f2a3b4c-0000-4000-8000-000000000001".into(),
+                ruletitle: "Encoded PowerShell".into(),
+                ruleauthor: "Zach Mathis".into(),
+                level: LEVEL::HIGH,
+                computername: "DC01.corp.local".into(),
+                rec_id: "1234".into(),
+                eventid: "4688".into(),
+                output_fields: output_fields(&[
+                    (
+                        "Timestamp",
+                        Profile::Timestamp("2026-03-01 10:15:30.123".into()),
+                    ),
+                    ("RuleTitle", Profile::RuleTitle("Encoded PowerShell".into())),
+                    ("Level", Profile::Level("high".into())),
+                    ("Computer", Profile::Computer("DC01.corp.local".into())),
+                    ("Channel", Profile::Channel("Sec".into())),
+                    ("EventID", Profile::EventID("4688".into())),
+                    (
+                        "Provider",
+                        Profile::Provider("Microsoft-Windows-Security-Auditing".into()),
+                    ),
+                    ("MitreTactics", Profile::MitreTactics("Exec".into())),
+                    ("MitreTags", Profile::MitreTags("T1059.001 ¦ G0032".into())),
+                    ("RecordID", Profile::RecordID("1234".into())),
+                    ("Details", Profile::Details("".into())),
+                ]),
+                details_convert_map: details(&[
+                    "Cmdline: powershell.exe -enc SQBFAFgA",
+                    "Proc: C:\\Windows\\System32\\WindowsPowerShell\\v1.0\\powershell.exe",
+                    "User: admin",
+                ]),
+                channel: "Security".into(),
+                tags: vec![
+                    "attack.execution".into(),
+                    "attack.t1059.001".into(),
+                    "attack.g0032".into(),
+                ],
+                ..Default::default()
+            },
+            DetectInfo {
+                detected_time: Utc.with_ymd_and_hms(2026, 3, 1, 11, 0, 0).unwrap(),
+                rule_path: "rules/hayabusa/builtin/Security/4625_Password_Spray.yml".into(),
+                ruleid: "1f2a3b4c-0000-4000-8000-000000000002".into(),
+                ruletitle: "Password Spray".into(),
+                ruleauthor: "-".into(),
+                level: LEVEL::MEDIUM,
+                computername: "PC01 ¦ PC02".into(),
+                rec_id: "-".into(),
+                eventid: "-".into(),
+                output_fields: output_fields(&[
+                    (
+                        "Timestamp",
+                        Profile::Timestamp("2026-03-01 11:00:00.000".into()),
+                    ),
+                    ("RuleTitle", Profile::RuleTitle("Password Spray".into())),
+                    ("Level", Profile::Level("med".into())),
+                    ("Computer", Profile::Computer("PC01 ¦ PC02".into())),
+                    ("MitreTactics", Profile::MitreTactics("-".into())),
+                    ("Details", Profile::Details("".into())),
+                ]),
+                details_convert_map: details(&["Count: 25 ¦ TgtUser: admin ¦ TgtUser: guest"]),
+                agg_result: Some(Default::default()),
+                channel: "-".into(),
+                ..Default::default()
+            },
+        ]
+    }
+
+    fn assert_golden(path: &str, build: fn(&DetectInfo) -> Value) {
+        let golden = fs::read_to_string(path).unwrap();
+        let expected: Vec<Value> = golden
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let actual: Vec<Value> = detect_infos().iter().map(build).collect();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_timesketch_golden() {
+        assert_golden("test_files/export/timesketch.jsonl", timesketch_record);
+    }
+
+    #[test]
+    fn test_ecs_golden() {
+        assert_golden("test_files/export/ecs.jsonl", ecs_record);
+    }
+}
@@ -1,9 +1,216 @@
+use chrono::{DateTime, Utc};
+use compact_str::CompactString;
+use hashbrown::HashMap;
 use itertools::Itertools;
 use nested::Nested;
+use serde_json::json;
 
 use crate::detections::configs::StoredStatic;
+use crate::detections::message::{DetectInfo, TAGS_CONFIG};
+use crate::detections::utils::format_time;
+use crate::level::LEVEL;
+use crate::options::htmlreport::HtmlReporter;
+use crate::options::profile::Profile;
 
-use super::html_escape_value;
+use super::{ResultOutputState, html_escape_value};
+
+/// Returns the text of one profile column for the interactive detection table. In JSON output
+/// the Details/AllFieldInfo/ExtraFieldInfo values live in `details_convert_map`, so they are
+/// taken from there when present.
+fn html_column_value(detect_info: &DetectInfo, profile: &Profile) -> String {
+    let details_key = match profile {
+        Profile::Details(_) => "#Details",
+        Profile::AllFieldInfo(_) => "#AllFieldInfo",
+        Profile::ExtraFieldInfo(_) => "#ExtraFieldInfo",
+        _ => return profile.to_value(),
+    };
+    match detect_info.details_convert_map.get(details_key) {
+        Some(values) if !values.is_empty() => values.iter().join(" ¦ "),
+        _ => profile.to_value(),
+    }
+}
+
+/// Stocks one detection for the interactive detection table of the HTML report: the profile
+/// column values, plus the full level name and computer used by the facet sidebar and the
+/// record's AllFieldInfo shown when a row is clicked.
+pub(crate) fn _stock_html_detection_row(
+    detect_info: &DetectInfo,
+    result_state: &mut ResultOutputState,
+) {
+    let mut computer = detect_info.computername.to_string();
+    let mut all_field_info = None;
+    let values = detect_info
+        .output_fields
+        .iter()
+        .map(|(_, profile)| {
+            let value = html_column_value(detect_info, profile);
+            match profile {
+                Profile::Computer(_) => computer.clone_from(&value),
+                Profile::AllFieldInfo(_) => all_field_info = Some(value.clone()),
+                _ => {}
+            }
+            value
+        })
+        .collect_vec();
+    let all_field_info = all_field_info.unwrap_or_else(|| {
+        detect_info
+            .details_convert_map
+            .get("#AllFieldInfo")
+            .map(|values| values.iter().join(" ¦ "))
+            .unwrap_or_else(|| "-".to_string())
+    });
+    result_state.html_detection_rows.push(json!({
+        "values": values,
+        "level": detect_info.level.to_full(),
+        "computer": computer,
+        "allFieldInfo": all_field_info,
+    }));
+}
+
+/// Hands the stocked detections and the profile column names to the HTML reporter.
+pub(crate) fn _output_html_detection_table(
+    stored_static: &StoredStatic,
+    result_state: &ResultOutputState,
+    html_reporter: &mut HtmlReporter,
+) {
+    let columns = stored_static
+        .profiles
+        .as_ref()
+        .map(|profiles| profiles.iter().map(|(name, _)| name.as_str()).collect_vec())
+        .unwrap_or_default();
+    let table = json!({
+        "columns": columns,
+        "rows": result_state.html_detection_rows,
+    });
+    html_reporter.set_detection_table(&table.to_string());
+}
+
+/// One high or above detection in a computer's attack story.
+#[derive(Debug, Clone)]
+pub struct StoryEvent {
+    pub time: DateTime<Utc>,
+    pub rule_path: CompactString,
+    pub rule_title: CompactString,
+    pub level: LEVEL,
+    pub tactics: CompactString,
+}
+
+/// Detections of one computer for the attack story section of the HTML report.
+#[derive(Debug, Clone, Default)]
+pub struct HostStory {
+    /// All detections on the computer, used to pick the noisiest computers.
+    pub detections: usize,
+    /// High, critical and emergency detections only.
+    pub events: Vec<StoryEvent>,
+}
+
+/// Stocks one detection in the attack story of each computer it was detected on. Aggregated
+/// (count/correlation) detections are added to every computer of their records.
+pub(crate) fn _stock_html_host_story(
+    detect_info: &DetectInfo,
+    computer_names: &[CompactString],
+    result_state: &mut ResultOutputState,
+) {
+    let in_story = detect_info.level.index() >= LEVEL::HIGH.index();
+    // Taken from the rule tags so that the story does not depend on the profile columns.
+    let tactics = detect_info
+        .tags
+        .iter()
+        .filter_map(|tag| TAGS_CONFIG.get(tag.to_lowercase().as_str()))
+        .filter_map(|tactic| tactic.split(',').nth(1))
+        .unique()
+        .join(" ¦ ");
+    let tactics = if tactics.is_empty() {
+        "-".to_string()
+    } else {
+        tactics
+    };
+    for computer in computer_names {
+        let story = result_state
+            .host_stories
+            .entry(computer.clone())
+            .or_default();
+        story.detections += 1;
+        if in_story {
+            story.events.push(StoryEvent {
+                time: detect_info.detected_time,
+                rule_path: detect_info.rule_path.clone(),
+                rule_title: detect_info.ruletitle.clone(),
+                level: detect_info.level.clone(),
+                tactics: tactics.as_str().into(),
+            });
+        }
+    }
+}
+
+/// Collapses the detections of a story into one entry per rule with the total count, ordered by
+/// time. The entry keeps the first-seen detection of the rule.
+fn collapse_story_events(events: &[StoryEvent]) -> Vec<(&StoryEvent, usize)> {
+    let mut collapsed: Vec<(&StoryEvent, usize)> = vec![];
+    let mut positions: HashMap<&str, usize> = HashMap::new();
+    for event in events.iter().sorted_by_key(|event| event.time) {
+        match positions.get(event.rule_path.as_str()) {
+            Some(&pos) => collapsed[pos].1 += 1,
+            None => {
+                positions.insert(event.rule_path.as_str(), collapsed.len());
+                collapsed.push((event, 1));
+            }
+        }
+    }
+    collapsed
+}
+
+/// Appends to `html_output_stock` the attack story of the noisiest computers: the ordered chain
+/// of their high, critical and emergency detections with the first-seen time and the MITRE
+/// ATT&CK tactics of each.
+pub(crate) fn _output_html_attack_story(
+    html_output_stock: &mut Nested<String>,
+    stored_static: &StoredStatic,
+    result_state: &ResultOutputState,
+) {
+    let output_option = stored_static.output_option.as_ref().unwrap();
+    html_output_stock.push("### Attack Story:{#attack_story}");
+    let top_hosts = result_state
+        .host_stories
+        .iter()
+        .sorted_by(|a, b| b.1.detections.cmp(&a.1.detections).then(a.0.cmp(b.0)))
+        .take(output_option.attack_story_hosts.unwrap_or(5))
+        .collect_vec();
+    if top_hosts.is_empty() {
+        html_output_stock.push("- No detections.");
+        return;
+    }
+    for (computer, story) in top_hosts {
+        html_output_stock.push(format!(
+            "#### {} ({} detections)",
+            html_escape_value(computer),
+            story.detections
+        ));
+        if story.events.is_empty() {
+            html_output_stock.push("- No high or critical detections.");
+            html_output_stock.push("");
+            continue;
+        }
+        html_output_stock.push("|First Seen|Level|Detection|MITRE ATT&CK Tactics|");
+        html_output_stock.push("|---|---|---|---|");
+        for (event, count) in collapse_story_events(&story.events) {
+            let repeats = if count > 1 {
+                format!(" x {count}")
+            } else {
+                String::default()
+            };
+            html_output_stock.push(format!(
+                "|{}|{}|{}{}|{}|",
+                format_time(&event.time, false, &output_option.time_format_options),
+                event.level.to_full(),
+                html_escape_value(&event.rule_title),
+                repeats,
+                html_escape_value(&event.tactics).replace(" ¦ ", "<br>")
+            ));
+        }
+        html_output_stock.push("");
+    }
+}
 
 /// Appends to `html_output_stock` a Markdown table of computer names and the MITRE ATT&CK
 /// tactics detected on them (with unique and total counts), for the HTML report.
@@ -46,3 +253,65 @@ pub(crate) fn _output_html_computer_by_mitre_attck(
     stored_static.computer_mitre_attck_map.clear();
     stored_static.computer_mitre_attck_unique_keys.clear();
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::{_stock_html_host_story, StoryEvent, collapse_story_events};
+    use crate::detections::message::DetectInfo;
+    use crate::level::LEVEL;
+    use crate::results::ResultOutputState;
+
+    fn story_event(minute: u32, rule: &str) -> StoryEvent {
+        StoryEvent {
+            time: Utc.with_ymd_and_hms(2024, 1, 1, 0, minute, 0).unwrap(),
+            rule_path: rule.into(),
+            rule_title: rule.into(),
+            level: LEVEL::HIGH,
+            tactics: "-".into(),
+        }
+    }
+
+    #[test]
+    fn test_collapse_story_events() {
+        let events = vec![
+            story_event(3, "rule-b"),
+            story_event(0, "rule-a"),
+            story_event(1, "rule-a"),
+            story_event(2, "rule-a"),
+            story_event(4, "rule-a"),
+        ];
+        let collapsed = collapse_story_events(&events)
+            .into_iter()
+            .map(|(event, count)| {
+                (
+                    event.rule_title.as_str(),
+                    event.time.format("%M").to_string(),
+                    count,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            collapsed,
+            vec![
+                ("rule-a", "00".to_string(), 4),
+                ("rule-b", "03".to_string(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stock_html_host_story_tactics() {
+        let detect_info = DetectInfo {
+            level: LEVEL::HIGH,
+            tags: vec!["attack.execution".into(), "attack.t1059.001".into()],
+            ..Default::default()
+        };
+        let mut result_state = ResultOutputState::default();
+        _stock_html_host_story(&detect_info, &["PC01".into()], &mut result_state);
+        let story = &result_state.host_stories["PC01"];
+        assert_eq!(story.detections, 1);
+        assert_eq!(story.events[0].tactics, "04. Execution");
+    }
+}
@@ -86,7 +86,7 @@ pub(crate) fn close_unterminated_quote(value: &str) -> String {
 /// Splits each "key: value" detail entry (on the first colon), trims both sides, and groups
 /// the values by key preserving first-seen key order. A key that appears more than once
 /// (e.g. Data[1]/Data[2]) collects multiple values, later emitted as a JSON array.
-fn group_details_raw(stock: &[CompactString]) -> IndexMap<String, Vec<String>> {
+pub(super) fn group_details_raw(stock: &[CompactString]) -> IndexMap<String, Vec<String>> {
     let mut map: IndexMap<String, Vec<String>> = IndexMap::new();
     for contents in stock {
         let (key, value) = contents.split_once(':').unwrap_or_default();
@@ -2,6 +2,7 @@ use std::cmp::Ordering;
 use std::error::Error;
 use std::fs::File;
 use std::io::{self, BufWriter};
+use std::path::PathBuf;
 use std::process;
 
 use ::csv::{QuoteStyle, Writer, WriterBuilder};
@@ -11,6 +12,7 @@ use hashbrown::{HashMap, HashSet};
 use strum::IntoEnumIterator;
 use termcolor::{Buffer, BufferWriter, ColorChoice, ColorSpec, WriteColor};
 
+use crate::carve::CarveStats;
 use crate::detections::configs::{Action, OutputType, StoredStatic, TimeFormatOptions};
 use crate::detections::message::{AlertMessage, DetectInfo};
 
@@ -56,15 +58,22 @@ use crate::level::LEVEL;
 use crate::options::htmlreport::HtmlReporter;
 use crate::options::profile::Profile;
 
+mod cluster;
 mod csv;
 mod display;
+mod export;
 mod html_stock;
 mod json;
+mod shard;
 mod summary;
 
+use cluster::{DEFAULT_CLUSTER_KEYS, get_cluster_indices};
 use csv::emit_csv_inner;
+use html_stock::HostStory;
+use shard::ShardedOutput;
 use summary::calc_statistic_info;
 
+pub use cluster::parse_cluster_window;
 pub use csv::emit_csv;
 pub use json::output_json_str;
 pub use summary::output_result_summary;
@@ -110,6 +119,13 @@ pub struct ResultOutputState {
     pub detected_computer_and_rule_names: HashSet<CompactString>,
     pub prev_message: HashMap<CompactString, Profile>,
     pub prev_details_convert_map: HashMap<CompactString, Vec<CompactString>>,
+    /// Detections collected for the interactive HTML report (`--html-interactive`).
+    pub html_detection_rows: Vec<serde_json::Value>,
+    /// Detection counts and high or above detections per computer for the attack story section
+    /// of the HTML report.
+    pub host_stories: HashMap<CompactString, HostStory>,
+    /// Chunks and record signatures found with `--carve`.
+    pub carve_stats: CarveStats,
 }
 
 /// The three per-level count maps (by date, by computer, by rule) created together in
@@ -172,6 +188,9 @@ impl Default for ResultOutputState {
             detected_computer_and_rule_names: HashSet::new(),
             prev_message: HashMap::new(),
             prev_details_convert_map: HashMap::new(),
+            html_detection_rows: vec![],
+            host_stories: HashMap::new(),
+            carve_stats: CarveStats::default(),
         }
     }
 }
@@ -197,9 +216,19 @@ pub struct OutputWriter {
     pub(crate) display_writer: BufferWriter,
     pub(crate) disp_wtr_buf: Buffer,
     pub(crate) result_writer: ResultWriter,
+    /// Set with `--split-by`. Detections are then written to the shard files instead of
+    /// `result_writer`.
+    pub(crate) shards: Option<ShardedOutput>,
     pub display_flag: bool,
 }
 
+impl OutputWriter {
+    /// Paths of the files written with `--split-by`, or `None` when the output is not split.
+    pub fn shard_paths(&self) -> Option<Vec<PathBuf>> {
+        self.shards.as_ref().map(ShardedOutput::paths)
+    }
+}
+
 /// Creates the result writer, targeting the file given with the output option if one was
 /// specified, otherwise stdout (the pivot-keywords-list and logon-summary commands write their
 /// own files elsewhere, so their writer stays on stdout). CSV output goes through a `csv::Writer`;
@@ -211,12 +240,36 @@ pub fn init_writer(stored_static: &StoredStatic) -> OutputWriter {
     disp_wtr_buf.set_color(ColorSpec::new().set_fg(None)).ok();
 
     let mut display_flag = false;
+    let mut shards = None;
     let target: Box<dyn io::Write> = if let Some(path) = &stored_static.output_path {
+        // These commands write their own files. For convert the output path is a directory.
         if matches!(
             stored_static.config.action.as_ref().unwrap(),
-            Action::PivotKeywordsList(_) | Action::LogonSummary(_)
+            Action::PivotKeywordsList(_) | Action::LogonSummary(_) | Action::Convert(_)
         ) {
             Box::new(BufWriter::new(io::stdout()))
+        } else if let Some(split_by) = stored_static
+            .output_option
+            .as_ref()
+            .and_then(|option| option.split_by)
+        {
+            // The output path is a directory of shard files, so nothing is written to the target.
+            let extension = match stored_static.config.action.as_ref().unwrap() {
+                Action::DfirTimeline(option) => match option.output_type {
+                    OutputType::Csv => "csv",
+                    OutputType::Json => "json",
+                    _ => "jsonl",
+                },
+                _ => "csv",
+            };
+            match ShardedOutput::new(path, split_by, extension) {
+                Ok(sharded_output) => shards = Some(sharded_output),
+                Err(err) => {
+                    AlertMessage::alert(&format!("Failed to create directory. {err}")).ok();
+                    process::exit(1);
+                }
+            }
+            Box::new(io::sink())
         } else {
             // Write the results to the specified output file.
             match File::create(path) {
@@ -234,7 +287,24 @@ pub fn init_writer(stored_static: &StoredStatic) -> OutputWriter {
         Box::new(BufWriter::new(io::stdout()))
     };
 
-    let writer = match &stored_static.config.action.as_ref().unwrap() {
+    // Bundle the display writer (colored terminal display and the results summary) and the
+    // result writer (the CSV/JSON timeline output).
+    OutputWriter {
+        display_writer,
+        disp_wtr_buf,
+        result_writer: new_result_writer(stored_static, target),
+        shards,
+        display_flag,
+    }
+}
+
+/// Wraps the target in the result writer for the command's output format. Also used for each
+/// shard file with `--split-by`.
+pub(crate) fn new_result_writer(
+    stored_static: &StoredStatic,
+    target: Box<dyn io::Write>,
+) -> ResultWriter {
+    match &stored_static.config.action.as_ref().unwrap() {
         // JSON/JSONL records serialize themselves; write them straight to the target. CSV goes
         // through the csv::Writer. The dfir-timeline format is chosen by -t, --output-type.
         Action::DfirTimeline(option) if !matches!(option.output_type, OutputType::Csv) => {
@@ -249,15 +319,6 @@ pub fn init_writer(stored_static: &StoredStatic) -> OutputWriter {
                 .from_writer(target),
         )),
         _ => ResultWriter::Csv(Box::new(WriterBuilder::new().from_writer(target))),
-    };
-
-    // Bundle the display writer (colored terminal display and the results summary) and the
-    // result writer (the CSV/JSON timeline output).
-    OutputWriter {
-        display_writer,
-        disp_wtr_buf,
-        result_writer: writer,
-        display_flag,
     }
 }
 
@@ -298,23 +359,61 @@ fn output_results_inner(
         println!();
     }
 
+    // Every logon, logoff and process creation has been scanned by now, so the sessions and
+    // process ancestries can be filled in before the duplicate detections are compared.
+    if let Some(logon_sessions) = &stored_static.logon_sessions {
+        logon_sessions.read().unwrap().resolve_sessions(
+            detect_infos,
+            &stored_static
+                .output_option
+                .as_ref()
+                .unwrap()
+                .time_format_options,
+        );
+    }
+    if let Some(process_tree) = &stored_static.process_tree {
+        process_tree
+            .read()
+            .unwrap()
+            .resolve_ancestries(detect_infos);
+    }
+
     // Sort the detections, then determine which ones to drop as duplicates if the
     // remove-duplicate-detections option is enabled.
     sort_detect_info(detect_infos);
-    let duplicate_indices = if stored_static
-        .output_option
-        .as_ref()
-        .unwrap()
-        .remove_duplicate_detections
-    {
+    let output_option = stored_static.output_option.as_ref().unwrap();
+    let duplicate_indices = if output_option.remove_duplicate_detections {
         get_duplicate_indices(detect_infos)
     } else {
         HashSet::new()
     };
 
+    // With the cluster-window option, only the sample record of each cluster is written. The
+    // results summary still counts every detection.
+    let mut skipped_indices = duplicate_indices.clone();
+    if let Some(window) = output_option
+        .cluster_window
+        .as_deref()
+        .and_then(parse_cluster_window)
+    {
+        let keys = output_option.cluster_keys.clone().unwrap_or_else(|| {
+            DEFAULT_CLUSTER_KEYS
+                .iter()
+                .map(|key| key.to_string())
+                .collect()
+        });
+        skipped_indices.extend(get_cluster_indices(
+            detect_infos,
+            &duplicate_indices,
+            &keys,
+            window,
+            &output_option.time_format_options,
+        ));
+    }
+
     emit_csv_inner(
         detect_infos,
-        &duplicate_indices,
+        &skipped_indices,
         stored_static,
         output_writer,
         result_state,
@@ -593,6 +692,11 @@ mod tests {
             )],
             agg_result: None,
             details_convert_map: HashMap::default(),
+            channel: CompactString::default(),
+            tags: vec![],
+            risk_accounts: vec![],
+            process_key: None,
+            session_key: None,
         };
         let evtx_of = |detect_info: &DetectInfo| detect_info.output_fields[0].1.to_value();
 
@@ -729,6 +833,11 @@ mod tests {
                     output_fields: output_profile.to_owned(),
                     agg_result: None,
                     details_convert_map: HashMap::default(),
+                    channel: CompactString::default(),
+                    tags: vec![],
+                    risk_accounts: vec![],
+                    process_key: None,
+                    session_key: None,
                     rec_id: CompactString::default(),
                 },
                 &profile_converter,
@@ -755,6 +864,11 @@ mod tests {
                     output_fields: output_profile.to_owned(),
                     agg_result: None,
                     details_convert_map: HashMap::default(),
+                    channel: CompactString::default(),
+                    tags: vec![],
+                    risk_accounts: vec![],
+                    process_key: None,
+                    session_key: None,
                     rec_id: CompactString::default(),
                 },
                 &profile_converter,
@@ -978,6 +1092,11 @@ mod tests {
                     output_fields: output_profile.to_owned(),
                     agg_result: None,
                     details_convert_map: HashMap::default(),
+                    channel: CompactString::default(),
+                    tags: vec![],
+                    risk_accounts: vec![],
+                    process_key: None,
+                    session_key: None,
                     rec_id: CompactString::default(),
                 },
                 &profile_converter,
@@ -1004,6 +1123,11 @@ mod tests {
                     output_fields: output_profile.to_owned(),
                     agg_result: None,
                     details_convert_map: HashMap::default(),
+                    channel: CompactString::default(),
+                    tags: vec![],
+                    risk_accounts: vec![],
+                    process_key: None,
+                    session_key: None,
                     rec_id: CompactString::default(),
                 },
                 &profile_converter,
@@ -1150,6 +1274,11 @@ mod tests {
                 ],
                 agg_result: None,
                 details_convert_map: HashMap::default(),
+                channel: CompactString::default(),
+                tags: vec![],
+                risk_accounts: vec![],
+                process_key: None,
+                session_key: None,
             }
         }
 
@@ -1295,6 +1424,11 @@ mod tests {
                     output_fields: output_profile.to_owned(),
                     agg_result: None,
                     details_convert_map: HashMap::default(),
+                    channel: CompactString::default(),
+                    tags: vec![],
+                    risk_accounts: vec![],
+                    process_key: None,
+                    session_key: None,
                     rec_id: CompactString::default(),
                 },
                 &profile_converter,
@@ -1321,6 +1455,11 @@ mod tests {
                     output_fields: output_profile.to_owned(),
                     agg_result: None,
                     details_convert_map: HashMap::default(),
+                    channel: CompactString::default(),
+                    tags: vec![],
+                    risk_accounts: vec![],
+                    process_key: None,
+                    session_key: None,
                     rec_id: CompactString::default(),
                 },
                 &profile_converter,
@@ -1534,6 +1673,11 @@ mod tests {
                     output_fields: output_profile.to_owned(),
                     agg_result: None,
                     details_convert_map,
+                    channel: CompactString::default(),
+                    tags: vec![],
+                    risk_accounts: vec![],
+                    process_key: None,
+                    session_key: None,
                     rec_id: CompactString::default(),
                 },
                 &profile_converter,
@@ -1560,6 +1704,11 @@ mod tests {
                     output_fields: output_profile.to_owned(),
                     agg_result: None,
                     details_convert_map: HashMap::default(),
+                    channel: CompactString::default(),
+                    tags: vec![],
+                    risk_accounts: vec![],
+                    process_key: None,
+                    session_key: None,
                     rec_id: CompactString::default(),
                 },
                 &profile_converter,
@@ -1852,6 +2001,11 @@ mod tests {
                     output_fields: output_profile.to_owned(),
                     agg_result: None,
                     details_convert_map,
+                    channel: CompactString::default(),
+                    tags: vec![],
+                    risk_accounts: vec![],
+                    process_key: None,
+                    session_key: None,
                     rec_id: CompactString::default(),
                 },
                 &profile_converter,
@@ -2091,6 +2245,11 @@ mod tests {
                     output_fields: output_profile.to_owned(),
                     agg_result: None,
                     details_convert_map,
+                    channel: CompactString::default(),
+                    tags: vec![],
+                    risk_accounts: vec![],
+                    process_key: None,
+                    session_key: None,
                     rec_id: CompactString::default(),
                 },
                 &profile_converter,
@@ -2272,6 +2431,11 @@ mod tests {
                     output_fields: output_profile.to_owned(),
                     agg_result: None,
                     details_convert_map,
+                    channel: CompactString::default(),
+                    tags: vec![],
+                    risk_accounts: vec![],
+                    process_key: None,
+                    session_key: None,
                     rec_id: CompactString::default(),
                 },
                 &profile_converter,
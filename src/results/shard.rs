@@ -0,0 +1,324 @@
+use std::ffi::OsStr;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use compact_str::{CompactString, ToCompactString};
+use hashbrown::{HashMap, HashSet};
+use itertools::Itertools;
+
+use crate::detections::configs::SplitBy;
+use crate::detections::message::DetectInfo;
+
+use super::ResultWriter;
+
+/// File in the output directory listing every shard and its row count.
+pub const SHARD_INDEX_FILE: &str = "index.csv";
+
+/// Maximum number of shard files kept open at once. Splitting by computer can create thousands of
+/// shards, which would otherwise exceed the open file limit (256 by default on macOS).
+const MAX_OPEN_SHARDS: usize = 64;
+
+/// One output file of a split timeline.
+struct Shard {
+    file_name: String,
+    rows: u64,
+}
+
+/// The writer of a shard file that is currently open.
+struct OpenShard {
+    writer: ResultWriter,
+    last_used: u64,
+}
+
+/// The timeline output split into one file per computer, UTC date or level (`--split-by`) under
+/// the output directory. A shard's file is created when its first detection is written. Only the
+/// most recently used shards are kept open, and the others are reopened in append mode.
+pub(crate) struct ShardedOutput {
+    dir: PathBuf,
+    split_by: SplitBy,
+    extension: &'static str,
+    shards: HashMap<CompactString, Shard>,
+    open_shards: HashMap<CompactString, OpenShard>,
+    use_count: u64,
+    // Lowercased file stems already taken, so that keys which only differ by case or by
+    // characters replaced in the file name do not overwrite each other.
+    file_stems: HashSet<String>,
+}
+
+impl ShardedOutput {
+    /// Creates the output directory. The shard files listed in the index of a previous run are
+    /// deleted, so that the directory only contains the shards of this run.
+    pub(crate) fn new(dir: &Path, split_by: SplitBy, extension: &'static str) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        remove_previous_shards(dir)?;
+        Ok(ShardedOutput {
+            dir: dir.to_path_buf(),
+            split_by,
+            extension,
+            shards: HashMap::new(),
+            open_shards: HashMap::new(),
+            use_count: 0,
+            file_stems: HashSet::from([SHARD_INDEX_FILE.trim_end_matches(".csv").to_string()]),
+        })
+    }
+
+    /// Returns the value the detection is split by.
+    pub(crate) fn shard_key(&self, detect_info: &DetectInfo) -> CompactString {
+        match self.split_by {
+            SplitBy::Computer => detect_info.computername.clone(),
+            SplitBy::Date => detect_info
+                .detected_time
+                .format("%Y-%m-%d")
+                .to_compact_string(),
+            SplitBy::Level => detect_info.level.to_full().into(),
+        }
+    }
+
+    /// Returns the writer and row count of the key's shard. The file is created with `new_writer`
+    /// on first use, and reopened in append mode if it was closed to stay under
+    /// `MAX_OPEN_SHARDS`.
+    pub(crate) fn shard(
+        &mut self,
+        key: &CompactString,
+        new_writer: impl FnOnce(Box<dyn io::Write>) -> ResultWriter,
+    ) -> io::Result<(&mut ResultWriter, &mut u64)> {
+        self.use_count += 1;
+        if !self.open_shards.contains_key(key) {
+            if self.open_shards.len() >= MAX_OPEN_SHARDS {
+                self.close_least_recently_used()?;
+            }
+            let file = match self.shards.get(key) {
+                Some(shard) => OpenOptions::new()
+                    .append(true)
+                    .open(self.dir.join(&shard.file_name))?,
+                None => {
+                    let file_name = self.unique_file_name(key);
+                    let file = File::create(self.dir.join(&file_name))?;
+                    self.shards
+                        .insert(key.clone(), Shard { file_name, rows: 0 });
+                    file
+                }
+            };
+            let mut writer = new_writer(Box::new(BufWriter::new(file)));
+            if let ResultWriter::Json { first, .. } = &mut writer {
+                *first = self.shards[key].rows == 0;
+            }
+            self.open_shards.insert(
+                key.clone(),
+                OpenShard {
+                    writer,
+                    last_used: 0,
+                },
+            );
+        }
+        let open_shard = self.open_shards.get_mut(key).unwrap();
+        open_shard.last_used = self.use_count;
+        Ok((
+            &mut open_shard.writer,
+            &mut self.shards.get_mut(key).unwrap().rows,
+        ))
+    }
+
+    fn close_least_recently_used(&mut self) -> io::Result<()> {
+        let Some(key) = self
+            .open_shards
+            .iter()
+            .min_by_key(|(_, open_shard)| open_shard.last_used)
+            .map(|(key, _)| key.clone())
+        else {
+            return Ok(());
+        };
+        let mut open_shard = self.open_shards.remove(&key).unwrap();
+        flush_writer(&mut open_shard.writer)
+    }
+
+    fn unique_file_name(&mut self, key: &str) -> String {
+        let stem = file_stem(key);
+        let mut candidate = stem.clone();
+        let mut suffix = 2;
+        while !self.file_stems.insert(candidate.to_lowercase()) {
+            candidate = format!("{stem}_{suffix}");
+            suffix += 1;
+        }
+        format!("{candidate}.{}", self.extension)
+    }
+
+    /// Paths of all shard files written so far.
+    pub(crate) fn paths(&self) -> Vec<PathBuf> {
+        self.shards
+            .values()
+            .map(|shard| self.dir.join(&shard.file_name))
+            .sorted()
+            .collect()
+    }
+
+    /// Flushes every shard and rewrites the index file with the current row counts.
+    pub(crate) fn flush(&mut self) -> io::Result<()> {
+        for open_shard in self.open_shards.values_mut() {
+            flush_writer(&mut open_shard.writer)?;
+        }
+        let key_header = match self.split_by {
+            SplitBy::Computer => "Computer",
+            SplitBy::Date => "Date",
+            SplitBy::Level => "Level",
+        };
+        let mut index = ::csv::Writer::from_path(self.dir.join(SHARD_INDEX_FILE))?;
+        index.write_record([key_header, "File", "Rows"])?;
+        for (key, shard) in self.shards.iter().sorted_by(|a, b| a.0.cmp(b.0)) {
+            index.write_record([
+                key.as_str(),
+                shard.file_name.as_str(),
+                shard.rows.to_string().as_str(),
+            ])?;
+        }
+        index.flush()
+    }
+}
+
+fn flush_writer(writer: &mut ResultWriter) -> io::Result<()> {
+    match writer {
+        ResultWriter::Csv(csv_writer) => csv_writer.flush(),
+        ResultWriter::Json { writer, .. } => writer.flush(),
+    }
+}
+
+/// Deletes the shard files listed in the index file of a previous run, and the index itself.
+fn remove_previous_shards(dir: &Path) -> io::Result<()> {
+    let index_path = dir.join(SHARD_INDEX_FILE);
+    if !index_path.is_file() {
+        return Ok(());
+    }
+    let mut index = ::csv::Reader::from_path(&index_path)?;
+    for record in index.records() {
+        let record = record?;
+        // Only plain file names are written to the index, so anything else is left alone.
+        if let Some(file_name) = record.get(1)
+            && Path::new(file_name).file_name() == Some(OsStr::new(file_name))
+        {
+            let path = dir.join(file_name);
+            if path.is_file() {
+                fs::remove_file(path)?;
+            }
+        }
+    }
+    fs::remove_file(index_path)
+}
+
+/// Converts a shard key into a file stem that is safe on all platforms.
+fn file_stem(key: &str) -> String {
+    let stem: String = key
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let stem = stem.trim_start_matches('.');
+    if stem.is_empty() {
+        "unknown".to_string()
+    } else {
+        stem.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io;
+
+    use chrono::{TimeZone, Utc};
+    use compact_str::CompactString;
+
+    use super::{MAX_OPEN_SHARDS, SHARD_INDEX_FILE, ShardedOutput, file_stem};
+    use crate::detections::configs::SplitBy;
+    use crate::detections::message::DetectInfo;
+    use crate::level::LEVEL;
+    use crate::results::ResultWriter;
+
+    fn write_row(output: &mut ShardedOutput, key: &CompactString) {
+        let (writer, rows) = output
+            .shard(key, |writer| ResultWriter::Json {
+                writer,
+                first: true,
+            })
+            .unwrap();
+        *rows += 1;
+        if let ResultWriter::Json { writer, first } = writer {
+            if !*first {
+                io::Write::write_all(writer, b"\n").unwrap();
+            }
+            *first = false;
+            io::Write::write_all(writer, b"{}").unwrap();
+        }
+    }
+
+    #[test]
+    fn test_file_stem() {
+        assert_eq!(file_stem("DC01.corp.local"), "DC01.corp.local");
+        assert_eq!(file_stem("PC 01/x:y"), "PC_01_x_y");
+        assert_eq!(file_stem("..\\..\\etc"), "_.._etc");
+        assert_eq!(file_stem(""), "unknown");
+    }
+
+    #[test]
+    fn test_sharded_output_writes_index() {
+        let dir = std::env::temp_dir().join("hayabusa-test-sharded-output");
+        let _ = fs::remove_dir_all(&dir);
+        let mut output = ShardedOutput::new(&dir, SplitBy::Computer, "jsonl").unwrap();
+        for computer in ["PC01", "pc01", "PC01", "index"] {
+            let detect_info = DetectInfo {
+                detected_time: Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap(),
+                computername: CompactString::from(computer),
+                level: LEVEL::HIGH,
+                ..Default::default()
+            };
+            let key = output.shard_key(&detect_info);
+            write_row(&mut output, &key);
+        }
+        output.flush().unwrap();
+        let index = fs::read_to_string(dir.join(SHARD_INDEX_FILE)).unwrap();
+        assert_eq!(
+            index,
+            "Computer,File,Rows\nPC01,PC01.jsonl,2\nindex,index_2.jsonl,1\npc01,pc01_2.jsonl,1\n"
+        );
+        assert_eq!(output.paths().len(), 3);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_sharded_output_reopens_closed_shards() {
+        let dir = std::env::temp_dir().join("hayabusa-test-sharded-output-reopen");
+        let _ = fs::remove_dir_all(&dir);
+        let mut output = ShardedOutput::new(&dir, SplitBy::Computer, "jsonl").unwrap();
+        for i in 0..=MAX_OPEN_SHARDS {
+            write_row(&mut output, &CompactString::from(format!("PC{i:03}")));
+        }
+        assert_eq!(output.open_shards.len(), MAX_OPEN_SHARDS);
+        // PC000 was closed to open the last shard, so it is appended to after reopening.
+        write_row(&mut output, &CompactString::from("PC000"));
+        output.flush().unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("PC000.jsonl")).unwrap(),
+            "{}\n{}"
+        );
+        assert_eq!(output.paths().len(), MAX_OPEN_SHARDS + 1);
+
+        // The shards of the previous run are deleted when the directory is reused.
+        fs::write(dir.join("notes.txt"), "keep").unwrap();
+        let mut output = ShardedOutput::new(&dir, SplitBy::Computer, "jsonl").unwrap();
+        write_row(&mut output, &CompactString::from("PC999"));
+        output.flush().unwrap();
+        let mut files: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+        assert_eq!(files, vec!["PC999.jsonl", SHARD_INDEX_FILE, "notes.txt"]);
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -23,14 +23,20 @@ use crate::detections::utils::{
     write_color_buffer,
 };
 use crate::level::{_get_output_color, LEVEL, create_output_color_map};
+use crate::options::attack_navigator;
 use crate::options::htmlreport::{self, RESULTS_SUMMARY_SECTION};
+use crate::options::risk_score::{self, ranked_entities, tactic_names};
 
-use super::html_stock::_output_html_computer_by_mitre_attck;
+use super::html_stock::{
+    _output_html_attack_story, _output_html_computer_by_mitre_attck, _output_html_detection_table,
+    _stock_html_host_story,
+};
 use super::{Colors, OutputWriter, ResultOutputState, html_escape_value};
 
 /// Folds a batch of detections into `result_state`: records the detection timestamps and the
-/// IDs of detected records, and (unless no-summary is set) updates the per-level counts by
-/// date, computer, and rule, plus the rule author statistics used by the results summary.
+/// IDs of detected records, adds the detections to the ATT&CK Navigator and risk scores, and
+/// (unless no-summary is set) updates the per-level counts by date, computer, and rule, plus the
+/// rule author statistics used by the results summary.
 pub(crate) fn calc_statistic_info(
     detect_infos: &[DetectInfo],
     duplicate_indices: &HashSet<usize>,
@@ -68,6 +74,41 @@ pub(crate) fn calc_statistic_info(
                     });
             }
         }
+        let computer_names = match &detect_info.agg_result {
+            None => vec![detect_info.computername.clone()],
+            Some(agg) => agg
+                .agg_record_time_info
+                .iter()
+                .map(|record_time_info| CompactString::from(record_time_info.computer.clone()))
+                .collect::<std::collections::HashSet<_>>() // Convert to HashSet to remove duplicates
+                .into_iter()
+                .sorted()
+                .collect(),
+        };
+        // Counted here rather than when the detection is created, so that the layers and risk
+        // scores do not include the detections dropped by -X, --remove-duplicate-detections.
+        if stored_static.attack_navigator_flag {
+            attack_navigator::add_detection(
+                &stored_static.attack_navigator_map,
+                &attack_navigator::technique_ids(detect_info.tags.iter().map(|tag| tag.as_str())),
+                &computer_names.join(" ¦ "),
+                &detect_info.level,
+            );
+        }
+        if let Some(weights) = &stored_static.risk_weights {
+            risk_score::add_detection(
+                &stored_static.risk_score_map,
+                weights,
+                &detect_info
+                    .tags
+                    .iter()
+                    .map(|tag| tag.as_str())
+                    .collect_vec(),
+                &computer_names.join(" ¦ "),
+                &detect_info.risk_accounts,
+                &detect_info.level,
+            );
+        }
         if !output_option.no_summary {
             let level_index = detect_info.level.index();
             let author_list = extract_author_name(&detect_info.ruleauthor);
@@ -98,17 +139,9 @@ pub(crate) fn calc_statistic_info(
                     .insert(detect_info.ruleid.to_owned());
                 result_state.unique_detect_counts_by_level[level_index] += 1;
             }
-            let computer_names = match &detect_info.agg_result {
-                None => vec![detect_info.computername.clone()],
-                Some(agg) => agg
-                    .agg_record_time_info
-                    .iter()
-                    .map(|record_time_info| CompactString::from(record_time_info.computer.clone()))
-                    .collect::<std::collections::HashSet<_>>() // Convert to HashSet to remove duplicates
-                    .into_iter()
-                    .sorted()
-                    .collect(),
-            };
+            if stored_static.html_report_flag {
+                _stock_html_host_story(detect_info, &computer_names, result_state);
+            }
             for computername in &computer_names {
                 let computer_rule_check_key =
                     CompactString::from(format!("{}|{}", computername, detect_info.rule_path));
@@ -593,11 +626,21 @@ pub fn output_result_summary(
         if stored_static.html_report_flag {
             html_output_stock.push("");
         }
+
+        if output_option.risk_scoring {
+            _print_risk_scores(stored_static, &color_map, &mut html_output_stock);
+            println!();
+        }
     }
     if stored_static.html_report_flag {
         _output_html_computer_by_mitre_attck(&mut html_output_stock, stored_static);
+        html_output_stock.push("");
+        _output_html_attack_story(&mut html_output_stock, stored_static, result_state);
         html_reporter.add_md_data(RESULTS_SUMMARY_SECTION, html_output_stock);
     }
+    if stored_static.html_interactive_flag {
+        _output_html_detection_table(stored_static, result_state, html_reporter);
+    }
 }
 fn _get_table_color(
     color_map: &HashMap<LEVEL, Colors>,
@@ -1065,6 +1108,75 @@ fn _print_detection_summary_tables(
     }
     println!("{tb}");
 }
+/// Prints the computers and accounts with the highest risk scores as a table. The HTML report
+/// gets the same ranking.
+fn _print_risk_scores(
+    stored_static: &StoredStatic,
+    color_map: &HashMap<LEVEL, Colors>,
+    html_output_stock: &mut Nested<String>,
+) {
+    let take_cnt = 10;
+    let ranked = ranked_entities(
+        &stored_static.risk_score_map,
+        stored_static.risk_weights.as_ref().unwrap(),
+    );
+    if stored_static.html_report_flag {
+        html_output_stock.push("### Risk Scores:{#risk_scores}");
+        if ranked.is_empty() {
+            html_output_stock.push("- No detections.");
+        } else {
+            html_output_stock
+                .push("|Rank|Type|Name|Score|Detections|Max Level|MITRE ATT&CK Tactics|");
+            html_output_stock.push("|---|---|---|---|---|---|---|");
+        }
+        for (i, entity) in ranked.iter().take(take_cnt).enumerate() {
+            html_output_stock.push(format!(
+                "|{}|{}|{}|{:.1}|{}|{}|{}|",
+                i + 1,
+                entity.kind,
+                html_escape_value(&entity.name),
+                entity.score,
+                entity.risk.detections.to_formatted_string(&Locale::en),
+                entity.risk.max_level.to_full(),
+                tactic_names(&entity.risk).join("<br>")
+            ));
+        }
+        html_output_stock.push("");
+    }
+
+    println!("Top {take_cnt} computers and accounts by risk score:");
+    if ranked.is_empty() {
+        println!("n/a");
+        return;
+    }
+    let mut tb = Table::new();
+    tb.load_preset(UTF8_FULL)
+        .apply_modifier(UTF8_ROUND_CORNERS)
+        .set_header(vec![
+            "Rank",
+            "Type",
+            "Name",
+            "Score",
+            "Detections",
+            "Max Level",
+            "MITRE ATT&CK Tactics",
+        ]);
+    for (i, entity) in ranked.iter().take(take_cnt).enumerate() {
+        let color = _get_table_color(color_map, &entity.risk.max_level)
+            .unwrap_or(comfy_table::Color::Reset);
+        tb.add_row(vec![
+            Cell::new(i + 1),
+            Cell::new(entity.kind),
+            Cell::new(&entity.name).fg(color),
+            Cell::new(format!("{:.1}", entity.score)).fg(color),
+            Cell::new(entity.risk.detections.to_formatted_string(&Locale::en)),
+            Cell::new(entity.risk.max_level.to_full()).fg(color),
+            Cell::new(tactic_names(&entity.risk).join(", ")),
+        ]);
+    }
+    println!("{tb}");
+}
+
 /// Prints a table of the detected rule authors and the number of their rules that produced
 /// detections, laid out over `table_column_num` columns.
 fn output_detected_rule_authors(
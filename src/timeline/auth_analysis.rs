@@ -0,0 +1,477 @@
+use crate::detections::configs::StoredStatic;
+use crate::detections::detection::EvtxRecordInfo;
+use crate::timeline::lateral_movement::normalize_host;
+use crate::timeline::metrics::{get_event_value_as_string, get_record_timestamp};
+use chrono::{DateTime, Utc};
+use compact_str::CompactString;
+use hashbrown::{HashMap, HashSet};
+use std::collections::BTreeSet;
+
+/// Default number of distinct service names one account has to request tickets for before it is
+/// reported as a possible Kerberoasting sweep.
+pub const DEFAULT_SPN_THRESHOLD: usize = 10;
+
+/// Kinds of suspicious authentication reported by `logon-summary`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum AuthFindingKind {
+    /// 4769 service ticket issued with RC4 encryption (Kerberoasting).
+    Rc4ServiceTicket,
+    /// 4769 service tickets requested for many different services by one account.
+    ManySpnRequests,
+    /// 4768 TGT issued without Kerberos pre-authentication (AS-REP roasting).
+    AsRepWithoutPreauth,
+    /// 4771 Kerberos pre-authentication failure (password guessing).
+    KerberosPreauthFailure,
+    /// 4624/4776 NTLM authentication involving a host that otherwise uses Kerberos.
+    NtlmWhereKerberosExpected,
+}
+
+impl AuthFindingKind {
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            AuthFindingKind::Rc4ServiceTicket => "RC4 Service Ticket",
+            AuthFindingKind::ManySpnRequests => "Many SPN Requests",
+            AuthFindingKind::AsRepWithoutPreauth => "AS-REP Without Pre-Auth",
+            AuthFindingKind::KerberosPreauthFailure => "Kerberos Pre-Auth Failure",
+            AuthFindingKind::NtlmWhereKerberosExpected => "NTLM Where Kerberos Expected",
+        }
+    }
+}
+
+/// Count and time range of the events behind one finding, plus the distinct field values that
+/// are shown in the Details column (encryption types, status codes, NTLM versions).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AuthOccurrence {
+    pub count: usize,
+    pub first: Option<DateTime<Utc>>,
+    pub last: Option<DateTime<Utc>>,
+    pub details: BTreeSet<CompactString>,
+}
+
+impl AuthOccurrence {
+    fn add(&mut self, timestamp: Option<DateTime<Utc>>, detail: Option<CompactString>) {
+        self.count += 1;
+        if let Some(ts) = timestamp {
+            if self.first.is_none_or(|first| ts < first) {
+                self.first = Some(ts);
+            }
+            if self.last.is_none_or(|last| ts > last) {
+                self.last = Some(ts);
+            }
+        }
+        if let Some(detail) = detail {
+            self.details.insert(detail);
+        }
+    }
+
+    fn merge(&mut self, other: &AuthOccurrence) {
+        self.count += other.count;
+        for ts in [other.first, other.last].into_iter().flatten() {
+            if self.first.is_none_or(|first| ts < first) {
+                self.first = Some(ts);
+            }
+            if self.last.is_none_or(|last| ts > last) {
+                self.last = Some(ts);
+            }
+        }
+        self.details.extend(other.details.iter().cloned());
+    }
+}
+
+/// Grouping key of a finding: (kind, account, target, source, computer that logged the event).
+pub type AuthFindingKey = (
+    AuthFindingKind,
+    CompactString,
+    CompactString,
+    CompactString,
+    CompactString,
+);
+
+/// Kerberos and NTLM authentication statistics collected by the logon-summary command from
+/// Security 4624 (authentication package), 4768/4769/4771 (Kerberos tickets) and 4776 (NTLM
+/// credential validation).
+#[derive(Debug, Clone, Default)]
+pub struct AuthMetrics {
+    // Findings that can be decided from a single event (RC4 tickets, AS-REP, 4771).
+    pub findings: HashMap<AuthFindingKey, AuthOccurrence>,
+    // 4769: services requested per (account, domain controller).
+    pub spn_requests:
+        HashMap<(CompactString, CompactString), (HashSet<CompactString>, AuthOccurrence)>,
+    // 4624 NTLM network logons and 4776 validations. Whether Kerberos was expected is only known
+    // once all logs have been read, so these are judged in `findings()`.
+    pub ntlm_auths:
+        HashMap<(CompactString, CompactString, CompactString, CompactString), AuthOccurrence>,
+    // Hosts that were seen using Kerberos: targets of 4624 Kerberos logons and the domain
+    // controllers that issued tickets.
+    pub kerberos_hosts: HashSet<CompactString>,
+    // (EventRecordID, timestamp) pairs already counted when -X/--remove-duplicate-records is used.
+    counted_rec: HashSet<(String, String)>,
+}
+
+impl AuthMetrics {
+    pub fn update(&mut self, records: &[EvtxRecordInfo], stored_static: &StoredStatic) {
+        let alias = &stored_static.eventkey_alias;
+        for record in records.iter() {
+            let rec = &record.record;
+            if rec["Event"]["System"]["Channel"].as_str() != Some("Security") {
+                continue;
+            }
+            let event_id = &rec["Event"]["System"]["EventID"];
+            let Some(event_id) = event_id
+                .as_i64()
+                .or_else(|| event_id.as_str().and_then(|id| id.parse().ok()))
+            else {
+                continue;
+            };
+            if ![4624, 4768, 4769, 4771, 4776].contains(&event_id) {
+                continue;
+            }
+            if stored_static.metrics_remove_duplication {
+                let event = &rec["Event"]["System"];
+                let counted = (
+                    event["EventRecordID"].to_string(),
+                    event["TimeCreated_attributes"]["SystemTime"].to_string(),
+                );
+                if !self.counted_rec.insert(counted) {
+                    continue;
+                }
+            }
+            let timestamp = get_record_timestamp(rec, alias);
+            let computer = get_event_value_as_string("Computer", rec, alias);
+            let account = get_event_value_as_string("TargetUserName", rec, alias);
+            let status = get_event_value_as_string("Status", rec, alias).to_lowercase();
+            let source_ip = get_event_value_as_string("IpAddress", rec, alias);
+            let source_ip = CompactString::from(source_ip.trim_start_matches("::ffff:"));
+            match event_id {
+                4624 => {
+                    let package =
+                        get_event_value_as_string("AuthenticationPackageName", rec, alias);
+                    if package.eq_ignore_ascii_case("Kerberos") {
+                        if let Some(host) = normalize_host(&computer) {
+                            self.kerberos_hosts.insert(host);
+                        }
+                    } else if package.eq_ignore_ascii_case("NTLM")
+                        && get_event_value_as_string("LogonType", rec, alias) == "3"
+                        && !account.eq_ignore_ascii_case("ANONYMOUS LOGON")
+                    {
+                        let workstation = get_event_value_as_string("WorkstationName", rec, alias);
+                        let source = if workstation == "-" {
+                            source_ip
+                        } else {
+                            workstation
+                        };
+                        let lm_package = get_event_value_as_string("LmPackageName", rec, alias);
+                        self.ntlm_auths
+                            .entry((
+                                CompactString::from("4624"),
+                                account,
+                                source,
+                                computer.clone(),
+                            ))
+                            .or_default()
+                            .add(timestamp, Some(lm_package));
+                    }
+                }
+                4768 => {
+                    if let Some(host) = normalize_host(&computer) {
+                        self.kerberos_hosts.insert(host);
+                    }
+                    let preauth = get_event_value_as_string("PreAuthType", rec, alias);
+                    if preauth == "0" && status == "0x0" {
+                        self.findings
+                            .entry((
+                                AuthFindingKind::AsRepWithoutPreauth,
+                                account,
+                                CompactString::from("krbtgt"),
+                                source_ip,
+                                computer,
+                            ))
+                            .or_default()
+                            .add(timestamp, None);
+                    }
+                }
+                4769 => {
+                    if let Some(host) = normalize_host(&computer) {
+                        self.kerberos_hosts.insert(host);
+                    }
+                    let service = get_event_value_as_string("ServiceName", rec, alias);
+                    // Tickets for computer accounts and krbtgt are requested constantly by normal
+                    // domain traffic and cannot be cracked offline, so only user services count.
+                    if status != "0x0"
+                        || service.ends_with('$')
+                        || service.eq_ignore_ascii_case("krbtgt")
+                    {
+                        continue;
+                    }
+                    let encryption = get_event_value_as_string("TicketEncryptionType", rec, alias)
+                        .to_lowercase();
+                    if encryption == "0x17" || encryption == "0x18" {
+                        self.findings
+                            .entry((
+                                AuthFindingKind::Rc4ServiceTicket,
+                                account.clone(),
+                                service.clone(),
+                                source_ip,
+                                computer.clone(),
+                            ))
+                            .or_default()
+                            .add(timestamp, Some(format!("Encryption: {encryption}").into()));
+                    }
+                    let (services, occurrence) =
+                        self.spn_requests.entry((account, computer)).or_default();
+                    services.insert(service);
+                    occurrence.add(timestamp, None);
+                }
+                4771 => {
+                    self.findings
+                        .entry((
+                            AuthFindingKind::KerberosPreauthFailure,
+                            account,
+                            CompactString::from("krbtgt"),
+                            source_ip,
+                            computer,
+                        ))
+                        .or_default()
+                        .add(timestamp, Some(format!("Status: {status}").into()));
+                }
+                4776 => {
+                    let workstation = get_event_value_as_string("Workstation", rec, alias);
+                    self.ntlm_auths
+                        .entry((CompactString::from("4776"), account, workstation, computer))
+                        .or_default()
+                        .add(timestamp, Some(format!("Status: {status}").into()));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Returns all findings sorted by kind and then by descending count. `spn_threshold` is the
+    /// number of distinct services an account must request to be reported as a SPN sweep.
+    pub fn findings(&self, spn_threshold: usize) -> Vec<(AuthFindingKey, AuthOccurrence)> {
+        let mut findings: HashMap<AuthFindingKey, AuthOccurrence> = self.findings.clone();
+        for ((account, computer), (services, occurrence)) in self.spn_requests.iter() {
+            if services.len() < spn_threshold {
+                continue;
+            }
+            let mut occurrence = occurrence.clone();
+            occurrence.details = services.iter().cloned().collect();
+            findings.insert(
+                (
+                    AuthFindingKind::ManySpnRequests,
+                    account.clone(),
+                    format!("{} services", services.len()).into(),
+                    CompactString::from("-"),
+                    computer.clone(),
+                ),
+                occurrence,
+            );
+        }
+        for ((event_id, account, source, computer), occurrence) in self.ntlm_auths.iter() {
+            // 4624 is logged on the target host, so that host is checked. 4776 is logged on the
+            // domain controller that validated the credentials, so the client workstation is.
+            let host = if event_id == "4624" { computer } else { source };
+            let Some(host) = normalize_host(host) else {
+                continue;
+            };
+            if !self.kerberos_hosts.contains(&host) {
+                continue;
+            }
+            let finding = findings
+                .entry((
+                    AuthFindingKind::NtlmWhereKerberosExpected,
+                    account.clone(),
+                    host,
+                    source.clone(),
+                    computer.clone(),
+                ))
+                .or_default();
+            finding.merge(occurrence);
+            finding.details.insert(format!("Sec {event_id}").into());
+        }
+        let mut findings: Vec<_> = findings.into_iter().collect();
+        findings.sort_by(|(x_key, x_occ), (y_key, y_occ)| {
+            x_key
+                .0
+                .cmp(&y_key.0)
+                .then_with(|| y_occ.count.cmp(&x_occ.count))
+                .then_with(|| x_key.cmp(y_key))
+        });
+        findings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AuthFindingKind, AuthMetrics};
+    use crate::detections::configs::{
+        Action, Config, DetectCommonOption, LogonSummaryOption, StoredStatic,
+    };
+    use crate::detections::detection::EvtxRecordInfo;
+    use crate::detections::utils::create_rec_info;
+    use nested::Nested;
+
+    fn create_records(stored_static: &StoredStatic, records: &[&str]) -> Vec<EvtxRecordInfo> {
+        records
+            .iter()
+            .map(|record| {
+                create_rec_info(
+                    serde_json::from_str(record).unwrap(),
+                    "testpath".to_string(),
+                    &Nested::<String>::new(),
+                    &false,
+                    &false,
+                    &stored_static.eventkey_alias,
+                )
+            })
+            .collect()
+    }
+
+    fn security_event(event_id: i64, computer: &str, event_data: &str) -> String {
+        format!(
+            r#"{{
+                "Event": {{
+                    "System": {{
+                        "EventID": {event_id},
+                        "Channel": "Security",
+                        "Computer": "{computer}",
+                        "TimeCreated_attributes": {{"SystemTime": "2024-01-01T00:00:00.000Z"}}
+                    }},
+                    "EventData": {event_data}
+                }}
+            }}"#
+        )
+    }
+
+    #[test]
+    fn test_auth_findings() {
+        let stored_static = StoredStatic::create_static_data(Config {
+            action: Some(Action::LogonSummary(LogonSummaryOption {
+                detect_common_options: DetectCommonOption {
+                    json_input: true,
+                    ..Default::default()
+                },
+                ..Default::default()
+            })),
+            debug: false,
+        });
+        let mut events = vec![
+            // Kerberoasting: RC4 service ticket for a user service.
+            security_event(
+                4769,
+                "DC01.corp.local",
+                r#"{"TargetUserName": "alice@CORP.LOCAL", "ServiceName": "svc_sql", "TicketEncryptionType": "0x17", "Status": "0x0", "IpAddress": "::ffff:10.0.0.5"}"#,
+            ),
+            // AES ticket and a computer account ticket are not RC4 findings.
+            security_event(
+                4769,
+                "DC01.corp.local",
+                r#"{"TargetUserName": "alice@CORP.LOCAL", "ServiceName": "svc_web", "TicketEncryptionType": "0x12", "Status": "0x0", "IpAddress": "::ffff:10.0.0.5"}"#,
+            ),
+            security_event(
+                4769,
+                "DC01.corp.local",
+                r#"{"TargetUserName": "alice@CORP.LOCAL", "ServiceName": "FS01$", "TicketEncryptionType": "0x17", "Status": "0x0", "IpAddress": "::ffff:10.0.0.5"}"#,
+            ),
+            // AS-REP roasting.
+            security_event(
+                4768,
+                "DC01.corp.local",
+                r#"{"TargetUserName": "bob", "PreAuthType": "0", "Status": "0x0", "IpAddress": "::ffff:10.0.0.6"}"#,
+            ),
+            security_event(
+                4771,
+                "DC01.corp.local",
+                r#"{"TargetUserName": "carol", "Status": "0x18", "IpAddress": "::ffff:10.0.0.7"}"#,
+            ),
+            // NTLM network logon to a host that also has Kerberos logons.
+            security_event(
+                4624,
+                "FS01.corp.local",
+                r#"{"TargetUserName": "dave", "LogonType": "3", "AuthenticationPackageName": "Kerberos", "WorkstationName": "-", "IpAddress": "10.0.0.8"}"#,
+            ),
+            security_event(
+                4624,
+                "FS01.corp.local",
+                r#"{"TargetUserName": "dave", "LogonType": "3", "AuthenticationPackageName": "NTLM", "LmPackageName": "NTLM V1", "WorkstationName": "WS01", "IpAddress": "10.0.0.8"}"#,
+            ),
+            // NTLM to a host never seen using Kerberos is not reported.
+            security_event(
+                4624,
+                "STANDALONE",
+                r#"{"TargetUserName": "erin", "LogonType": "3", "AuthenticationPackageName": "NTLM", "WorkstationName": "WS02", "IpAddress": "10.0.0.9"}"#,
+            ),
+        ];
+        // One account requesting tickets for many services.
+        for i in 0..3 {
+            events.push(security_event(
+                4769,
+                "DC01.corp.local",
+                &format!(
+                    r#"{{"TargetUserName": "frank@CORP.LOCAL", "ServiceName": "svc_{i}", "TicketEncryptionType": "0x12", "Status": "0x0", "IpAddress": "::ffff:10.0.0.10"}}"#
+                ),
+            ));
+        }
+        let events: Vec<&str> = events.iter().map(|e| e.as_str()).collect();
+        let records = create_records(&stored_static, &events);
+
+        let mut auth_metrics = AuthMetrics::default();
+        auth_metrics.update(&records, &stored_static);
+        let findings = auth_metrics.findings(3);
+        let summary: Vec<_> = findings
+            .iter()
+            .map(|(key, occ)| {
+                (
+                    key.0,
+                    key.1.as_str(),
+                    key.2.as_str(),
+                    key.3.as_str(),
+                    occ.count,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    AuthFindingKind::Rc4ServiceTicket,
+                    "alice@CORP.LOCAL",
+                    "svc_sql",
+                    "10.0.0.5",
+                    1
+                ),
+                (
+                    AuthFindingKind::ManySpnRequests,
+                    "frank@CORP.LOCAL",
+                    "3 services",
+                    "-",
+                    3
+                ),
+                (
+                    AuthFindingKind::AsRepWithoutPreauth,
+                    "bob",
+                    "krbtgt",
+                    "10.0.0.6",
+                    1
+                ),
+                (
+                    AuthFindingKind::KerberosPreauthFailure,
+                    "carol",
+                    "krbtgt",
+                    "10.0.0.7",
+                    1
+                ),
+                (
+                    AuthFindingKind::NtlmWhereKerberosExpected,
+                    "dave",
+                    "FS01",
+                    "WS01",
+                    1
+                ),
+            ]
+        );
+        let ntlm = &findings.last().unwrap().1;
+        assert!(ntlm.details.contains("NTLM V1"));
+        assert!(ntlm.details.contains("Sec 4624"));
+    }
+}
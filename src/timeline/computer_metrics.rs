@@ -1,115 +1,295 @@
-use crate::detections::configs::{EventKeyAliasConfig, WIN_VERSIONS};
+use crate::detections::configs::{ComputerMetricsFormat, EventKeyAliasConfig, WIN_VERSIONS};
 use crate::detections::message::AlertMessage;
 use crate::detections::utils;
+use crate::timeline::metrics::get_record_timestamp;
 use crate::timeline::timelines::Timeline;
-use chrono::DateTime;
-use comfy_table::{Table, modifiers::UTF8_ROUND_CORNERS, presets::UTF8_FULL};
+use chrono::{DateTime, Utc};
+use comfy_table::{
+    Cell, ContentArrangement, Table, modifiers::UTF8_ROUND_CORNERS, presets::UTF8_FULL,
+};
 use compact_str::CompactString;
 use csv::{QuoteStyle, WriterBuilder};
 use downcast_rs::__std::process;
-use hashbrown::HashMap;
+use hashbrown::{HashMap, HashSet};
 use itertools::Itertools;
-use num::FromPrimitive;
 use num_format::{Locale, ToFormattedString};
-use serde_json::Value;
-use std::cmp::Ordering;
-use std::fs::File;
+use serde_json::{Value, json};
+use std::fs::{self, File};
 use std::io::BufWriter;
 use std::path::PathBuf;
 
-/// Aggregates a single record into the per-computer statistics used by the computer-metrics
-/// command. Every record increments the event count for its `Computer` name. In addition,
-/// System-channel events are mined for host details:
-/// - EID 6009 (logged at every boot): OS version and build number, resolved to a product name.
-/// - EID 6013 (system uptime): the host's timezone.
-/// - EID 12 / 6005 / 6009 (all logged at startup): the most recent boot time, from which the
+const SYSMON_CHANNEL: &str = "Microsoft-Windows-Sysmon/Operational";
+const DEFENDER_CHANNEL: &str = "Microsoft-Windows-Windows Defender/Operational";
+
+/// Security event IDs that show which audit policy subcategories are enabled on a host.
+const AUDIT_CATEGORIES: &[(&str, &[i64])] = &[
+    ("Logon", &[4624, 4625, 4634, 4647, 4648, 4672]),
+    ("Process Creation", &[4688]),
+    ("Process Termination", &[4689]),
+    ("Object Access", &[4656, 4658, 4660, 4663]),
+    (
+        "Account Management",
+        &[4720, 4722, 4724, 4725, 4726, 4728, 4732, 4738, 4740, 4756],
+    ),
+    ("Kerberos", &[4768, 4769, 4771]),
+    ("Credential Validation", &[4776]),
+    ("Scheduled Tasks", &[4698, 4699, 4700, 4701, 4702]),
+    ("Service Installation", &[4697]),
+    ("Policy Change", &[4719, 4739, 4907]),
+    ("Filtering Platform", &[5156, 5157, 5158]),
+    ("Log Cleared", &[1102]),
+];
+
+/// Accounts that log on for every Windows host and say nothing about who used it.
+const BUILTIN_ACCOUNTS: &[&str] = &[
+    "SYSTEM",
+    "LOCAL SERVICE",
+    "NETWORK SERVICE",
+    "ANONYMOUS LOGON",
+];
+
+/// Host inventory of one computer, collected by the computer-metrics command.
+#[derive(Debug, Clone, Default)]
+pub struct ComputerInventory {
+    pub os_info: CompactString,
+    /// Most recent boot time, as the ISO 8601 string of the event.
+    pub last_boot: CompactString,
+    pub timezone: CompactString,
+    /// Newest System-channel event timestamp, used with `last_boot` to calculate the uptime.
+    pub last_system_timestamp: CompactString,
+    pub event_count: usize,
+    pub domain: CompactString,
+    pub ip_addresses: HashSet<CompactString>,
+    pub users: HashSet<CompactString>,
+    /// Services installed with System 7045, keyed by service name with the image path.
+    pub services: HashMap<CompactString, CompactString>,
+    /// `Some` when Sysmon events were found, with the version when a Sysmon 4 event logged it.
+    pub sysmon: Option<CompactString>,
+    /// `Some` when Defender events were found, with the version from a 1150/1151 health report.
+    pub defender: Option<CompactString>,
+    pub security_event_ids: HashSet<i64>,
+    /// Oldest and newest event timestamp per channel.
+    pub retention: HashMap<CompactString, (DateTime<Utc>, DateTime<Utc>)>,
+}
+
+impl ComputerInventory {
+    /// Returns the audit policy categories with at least one Security event in the logs.
+    pub fn audit_coverage(&self) -> Vec<&'static str> {
+        AUDIT_CATEGORIES
+            .iter()
+            .filter(|(_, ids)| ids.iter().any(|id| self.security_event_ids.contains(id)))
+            .map(|(name, _)| *name)
+            .collect()
+    }
+
+    /// Returns the oldest and newest event timestamps over all channels.
+    pub fn retention_span(&self) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        let first = self.retention.values().map(|(first, _)| *first).min()?;
+        let last = self.retention.values().map(|(_, last)| *last).max()?;
+        Some((first, last))
+    }
+}
+
+/// Aggregates a single record into the per-computer inventory used by the computer-metrics
+/// command. Every record increments the event count and the retention span of its channel for
+/// its `Computer` name. In addition, events are mined for host details:
+/// - System 6009 (logged at every boot): OS version and build number, resolved to a product name.
+/// - System 6013 (system uptime): the host's timezone.
+/// - System 12 / 6005 / 6009 (all logged at startup): the most recent boot time, from which the
 ///   uptime is later derived as (newest event timestamp - boot time).
+/// - System 7045: installed services.
+/// - Security 4624: logged-on users, and the domain from the logons of the computer account.
+/// - Security 5156 / 5158 and Sysmon 3: the local IP addresses of network connections.
+/// - Sysmon and Defender events: whether they are installed, with their versions.
 pub fn countup_event_by_computer(
     record: &Value,
     eventkey_alias: &EventKeyAliasConfig,
     timeline: &mut Timeline,
 ) {
-    if let Some(computer_name) =
+    let Some(computer_name) =
         utils::get_event_value("Event.System.Computer", record, eventkey_alias)
+    else {
+        return;
+    };
+    let computer_name = computer_name.to_string().replace('\"', "");
+    let val = timeline
+        .stats
+        .stats_computer
+        .entry(computer_name.as_str().into())
+        .or_default();
+    val.event_count += 1;
+    let channel = record["Event"]["System"]["Channel"]
+        .as_str()
+        .unwrap_or_default();
+    if let Some(time) = get_record_timestamp(record, eventkey_alias) {
+        let span = val.retention.entry(channel.into()).or_insert((time, time));
+        span.0 = span.0.min(time);
+        span.1 = span.1.max(time);
+    }
+    if val.domain.is_empty()
+        && let Some((_, suffix)) = computer_name.split_once('.')
     {
-        // Tuple fields: (OS info, boot time, timezone, last event timestamp, event count).
-        let val = timeline
-            .stats
-            .stats_computer
-            .entry(computer_name.to_string().replace('\"', "").into())
-            .or_insert((
-                CompactString::default(),
-                CompactString::default(),
-                CompactString::default(),
-                CompactString::default(),
-                0,
-            ));
-        if let Some(ch) = record["Event"]["System"]["Channel"].as_str()
-            && ch == "System"
-            && let Some(id) = record["Event"]["System"]["EventID"].as_i64()
-        {
-            let os_name = &mut val.0;
-            if id == 6009 && os_name.is_empty() && !WIN_VERSIONS.is_empty() {
-                if let Some(arr) = record["Event"]["EventData"]["Data"].as_array() {
-                    // EID 6009 stores the OS version in the first Data element (e.g. "6.01.")
-                    // and the build number in the second (arr[0]/arr[1]). Zero-padded minor
-                    // versions are normalized (e.g. "6.01"
-                    // -> "6.1") to match the (version, build) keys loaded from
-                    // windows_versions.csv into WIN_VERSIONS.
-                    let ver = arr[0].as_str().unwrap_or_default().trim_matches('.');
-                    let ver = ver.replace(".01", ".1").replace(".00", ".0");
-                    let build_number = arr[1].as_str().unwrap_or_default().to_string();
-                    if let Some((win, data)) =
-                        WIN_VERSIONS.get(&(ver.clone(), build_number.clone()))
-                    {
-                        *os_name = format!("Windows {win} ({data})").into();
-                    } else {
-                        // Unknown combination: fall back to showing the raw version and build.
-                        *os_name = format!("Version: {ver} Build: {build_number}").into();
-                    }
-                }
-            } else if id == 6013 {
-                let timezone = &mut val.2;
-                if let Some(arr) = record["Event"]["EventData"]["Data"].as_array() {
-                    // EID 6013 stores the timezone in the seventh Data element (arr[6]) as
-                    // "<UTC offset in minutes> <timezone name>" (e.g. "540 Tokyo Standard Time");
-                    // drop the numeric offset prefix and keep only the name.
-                    let tz = arr[6].as_str().unwrap_or_default();
-                    let tz = match tz.find(' ') {
-                        Some(index) => &tz[index + 1..],
-                        None => tz,
-                    };
-                    let tz = tz.to_string();
-                    *timezone = tz.into();
-                }
+        val.domain = suffix.into();
+    }
+    let Some(id) = event_id(record) else {
+        return;
+    };
+    match channel {
+        "System" => countup_system_event(record, id, val),
+        "Security" => {
+            val.security_event_ids.insert(id);
+            if id == 4624 {
+                countup_logon(record, &computer_name, val);
+            } else if id == 5156 || id == 5158 {
+                add_ip_address(val, event_data(record, "SourceAddress"));
             }
-            let evt_time =
-                record["Event"]["System"]["TimeCreated_attributes"]["SystemTime"].to_string();
-            let evt_time = evt_time.trim_matches('"').to_string();
-            // EIDs 12 (the OS started), 6005 (the event log service started) and 6009 are all
-            // logged at boot, so the newest of their timestamps is the last boot time. Timestamps
-            // are ISO 8601 strings in a common format, so lexicographic comparison matches
-            // chronological order.
-            if id == 12 || id == 6005 || id == 6009 {
-                let uptime = &mut val.1;
-                let evt_time = evt_time.as_str();
-                if evt_time > uptime.as_str() {
-                    *uptime = evt_time.into();
-                }
+        }
+        SYSMON_CHANNEL => {
+            if id == 4
+                && let Some(version) = event_data(record, "Version")
+            {
+                val.sysmon = Some(version.into());
+            } else {
+                val.sysmon.get_or_insert_default();
             }
-            // Also track the newest System-channel event timestamp; the uptime shown to the user
-            // is calculated later as (last timestamp - boot time).
-            let last_timestamp = &mut val.3;
-            let evt_time = evt_time.as_str();
-            if evt_time > last_timestamp.as_str() {
-                *last_timestamp = evt_time.into();
+            if id == 3 {
+                let key = if event_data(record, "Initiated") == Some("true") {
+                    "SourceIp"
+                } else {
+                    "DestinationIp"
+                };
+                add_ip_address(val, event_data(record, key));
             }
         }
-        let count = &mut val.4;
-        *count += 1;
+        DEFENDER_CHANNEL => {
+            if (id == 1150 || id == 1151)
+                && let Some(version) = event_data(record, "Product Version")
+            {
+                val.defender = Some(version.into());
+            } else {
+                val.defender.get_or_insert_default();
+            }
+        }
+        _ => {}
+    }
+}
+
+fn countup_system_event(record: &Value, id: i64, val: &mut ComputerInventory) {
+    let os_name = &mut val.os_info;
+    if id == 6009 && os_name.is_empty() && !WIN_VERSIONS.is_empty() {
+        if let Some(arr) = record["Event"]["EventData"]["Data"].as_array() {
+            // EID 6009 stores the OS version in the first Data element (e.g. "6.01.")
+            // and the build number in the second (arr[0]/arr[1]). Zero-padded minor
+            // versions are normalized (e.g. "6.01"
+            // -> "6.1") to match the (version, build) keys loaded from
+            // windows_versions.csv into WIN_VERSIONS.
+            let ver = arr[0].as_str().unwrap_or_default().trim_matches('.');
+            let ver = ver.replace(".01", ".1").replace(".00", ".0");
+            let build_number = arr[1].as_str().unwrap_or_default().to_string();
+            if let Some((win, data)) = WIN_VERSIONS.get(&(ver.clone(), build_number.clone())) {
+                *os_name = format!("Windows {win} ({data})").into();
+            } else {
+                // Unknown combination: fall back to showing the raw version and build.
+                *os_name = format!("Version: {ver} Build: {build_number}").into();
+            }
+        }
+    } else if id == 6013 {
+        let timezone = &mut val.timezone;
+        if let Some(arr) = record["Event"]["EventData"]["Data"].as_array() {
+            // EID 6013 stores the timezone in the seventh Data element (arr[6]) as
+            // "<UTC offset in minutes> <timezone name>" (e.g. "540 Tokyo Standard Time");
+            // drop the numeric offset prefix and keep only the name.
+            let tz = arr[6].as_str().unwrap_or_default();
+            let tz = match tz.find(' ') {
+                Some(index) => &tz[index + 1..],
+                None => tz,
+            };
+            let tz = tz.to_string();
+            *timezone = tz.into();
+        }
+    } else if id == 7045
+        && let Some(name) = event_data(record, "ServiceName")
+    {
+        val.services.insert(
+            name.into(),
+            event_data(record, "ImagePath").unwrap_or("-").into(),
+        );
+    }
+    let evt_time = record["Event"]["System"]["TimeCreated_attributes"]["SystemTime"].to_string();
+    let evt_time = evt_time.trim_matches('"').to_string();
+    // EIDs 12 (the OS started), 6005 (the event log service started) and 6009 are all
+    // logged at boot, so the newest of their timestamps is the last boot time. Timestamps
+    // are ISO 8601 strings in a common format, so lexicographic comparison matches
+    // chronological order.
+    if id == 12 || id == 6005 || id == 6009 {
+        let uptime = &mut val.last_boot;
+        let evt_time = evt_time.as_str();
+        if evt_time > uptime.as_str() {
+            *uptime = evt_time.into();
+        }
+    }
+    // Also track the newest System-channel event timestamp; the uptime shown to the user
+    // is calculated later as (last timestamp - boot time).
+    let last_timestamp = &mut val.last_system_timestamp;
+    let evt_time = evt_time.as_str();
+    if evt_time > last_timestamp.as_str() {
+        *last_timestamp = evt_time.into();
+    }
+}
+
+/// Records the user of a 4624 logon. A logon of the computer's own machine account
+/// (`<COMPUTER>$`) gives the domain of the host instead.
+fn countup_logon(record: &Value, computer_name: &str, val: &mut ComputerInventory) {
+    let Some(user) = event_data(record, "TargetUserName") else {
+        return;
+    };
+    let domain = event_data(record, "TargetDomainName");
+    if let Some(account) = user.strip_suffix('$') {
+        let host = computer_name.split('.').next().unwrap_or_default();
+        if val.domain.is_empty()
+            && account.eq_ignore_ascii_case(host)
+            && let Some(domain) = domain
+        {
+            val.domain = domain.into();
+        }
+        return;
+    }
+    let upper = user.to_uppercase();
+    if BUILTIN_ACCOUNTS.contains(&upper.as_str())
+        || upper.starts_with("DWM-")
+        || upper.starts_with("UMFD-")
+    {
+        return;
+    }
+    val.users.insert(match domain {
+        Some(domain) => format!("{domain}\\{user}").into(),
+        None => user.into(),
+    });
+}
+
+/// Adds a local IP address, skipping loopback and unspecified addresses.
+fn add_ip_address(val: &mut ComputerInventory, ip: Option<&str>) {
+    if let Some(ip) = ip
+        && !matches!(ip, "::1" | "0.0.0.0" | "::")
+        && !ip.starts_with("127.")
+    {
+        val.ip_addresses.insert(ip.into());
     }
 }
 
+/// Returns the EventID, which is a number in .evtx files and can be a string in JSON logs.
+fn event_id(record: &Value) -> Option<i64> {
+    let id = &record["Event"]["System"]["EventID"];
+    id.as_i64().or_else(|| id.as_str()?.parse().ok())
+}
+
+/// Returns a non-empty EventData field, treating "-" as empty.
+fn event_data<'a>(record: &'a Value, key: &str) -> Option<&'a str> {
+    record["Event"]["EventData"][key]
+        .as_str()
+        .filter(|value| !value.is_empty() && *value != "-")
+}
+
 /// Returns the elapsed time between the last boot (`uptime`) and the newest event
 /// (`last_timestamp`) as a human-readable string, or an empty string if either timestamp is
 /// missing or unparsable, or the difference is not positive.
@@ -144,22 +324,99 @@ fn format_uptime(seconds: i64) -> String {
     format!("{years}Y {months}M {days}d {hours}h {minutes}m {seconds}s")
 }
 
-/// Outputs the per-computer metrics (OS information, uptime, timezone and event count) sorted by
-/// event count in descending order, either as a table on the terminal or to a CSV file when an
-/// output path is given.
+/// Returns the version of Sysmon or Defender for the table: "-" when not installed and "Yes" when
+/// installed with an unknown version.
+fn agent_version(agent: &Option<CompactString>) -> &str {
+    match agent {
+        None => "-",
+        Some(version) if version.is_empty() => "Yes",
+        Some(version) => version,
+    }
+}
+
+/// Returns the computers sorted by event count in descending order, breaking ties by computer name
+/// in ascending order.
+fn sorted_computers(
+    result_list: &HashMap<CompactString, ComputerInventory>,
+) -> Vec<(&CompactString, &ComputerInventory)> {
+    result_list
+        .iter()
+        .sorted_unstable_by(|a, b| {
+            b.1.event_count
+                .cmp(&a.1.event_count)
+                .then_with(|| a.0.cmp(b.0))
+        })
+        .collect()
+}
+
+/// Builds the JSON output: one object per computer with the full inventory.
+fn inventory_to_json(result_list: &HashMap<CompactString, ComputerInventory>) -> Value {
+    let computers = sorted_computers(result_list)
+        .into_iter()
+        .map(|(computer_name, inv)| {
+            let retention: serde_json::Map<String, Value> = inv
+                .retention
+                .iter()
+                .sorted_by(|a, b| a.0.cmp(b.0))
+                .map(|(channel, (first, last))| {
+                    (
+                        channel.to_string(),
+                        json!({"First": first.to_rfc3339(), "Last": last.to_rfc3339()}),
+                    )
+                })
+                .collect();
+            json!({
+                "Computer": computer_name.as_str(),
+                "OSInformation": inv.os_info.as_str(),
+                "LastBoot": inv.last_boot.as_str(),
+                "UpTime": calc_elapsed_seconds(&inv.last_boot, &inv.last_system_timestamp),
+                "Timezone": inv.timezone.as_str(),
+                "Domain": inv.domain.as_str(),
+                "IPAddresses": inv.ip_addresses.iter().map(|ip| ip.as_str()).sorted().collect_vec(),
+                "Users": inv.users.iter().map(|user| user.as_str()).sorted().collect_vec(),
+                "Services": inv
+                    .services
+                    .iter()
+                    .sorted_by(|a, b| a.0.cmp(b.0))
+                    .map(|(name, path)| json!({"Name": name.as_str(), "ImagePath": path.as_str()}))
+                    .collect_vec(),
+                "Sysmon": {
+                    "Installed": inv.sysmon.is_some(),
+                    "Version": inv.sysmon.as_deref().filter(|v| !v.is_empty()),
+                },
+                "Defender": {
+                    "Installed": inv.defender.is_some(),
+                    "Version": inv.defender.as_deref().filter(|v| !v.is_empty()),
+                },
+                "AuditCoverage": inv.audit_coverage(),
+                "SecurityEventIDs": inv.security_event_ids.iter().sorted().collect_vec(),
+                "Retention": retention,
+                "Events": inv.event_count,
+            })
+        })
+        .collect_vec();
+    Value::Array(computers)
+}
+
+/// Outputs the per-computer inventory sorted by event count in descending order, either as a
+/// table on the terminal, to a CSV file when an output path is given, or as JSON.
 pub fn computer_metrics_dsp_msg(
-    result_list: &HashMap<
-        CompactString,
-        (
-            CompactString,
-            CompactString,
-            CompactString,
-            CompactString,
-            usize,
-        ),
-    >,
+    result_list: &HashMap<CompactString, ComputerInventory>,
     output: &Option<PathBuf>,
+    output_type: ComputerMetricsFormat,
 ) {
+    if output_type == ComputerMetricsFormat::Json {
+        let json = serde_json::to_string_pretty(&inventory_to_json(result_list)).unwrap();
+        if let Some(path) = output {
+            if let Err(err) = fs::write(path, json + "\n") {
+                AlertMessage::alert(&format!("Failed to open file. {err}")).ok();
+                process::exit(1)
+            }
+        } else {
+            println!("{json}");
+        }
+        return;
+    }
     let mut file_wtr = None;
     if let Some(path) = output {
         match File::create(path) {
@@ -179,11 +436,26 @@ pub fn computer_metrics_dsp_msg(
     };
 
     // Write header
-    let header = vec!["Computer", "OS information", "UpTime", "Timezone", "Events"];
+    let header = vec![
+        "Computer",
+        "OS information",
+        "UpTime",
+        "Timezone",
+        "Domain",
+        "IP Addresses",
+        "Users",
+        "Services",
+        "Sysmon",
+        "Defender",
+        "Audit Coverage",
+        "Log Retention",
+        "Events",
+    ];
     let mut stats_tb = Table::new();
     stats_tb
         .load_preset(UTF8_FULL)
-        .apply_modifier(UTF8_ROUND_CORNERS);
+        .apply_modifier(UTF8_ROUND_CORNERS)
+        .set_content_arrangement(ContentArrangement::DynamicFullWidth);
     if output.is_some() {
         file_wtr.as_mut().unwrap().write_record(&header).ok();
     } else if output.is_none() && !result_list.is_empty() {
@@ -191,39 +463,37 @@ pub fn computer_metrics_dsp_msg(
     }
 
     // Write contents
-    for (computer_name, (os_info, uptime, timezone, last_timestamp, count)) in
-        result_list.into_iter().sorted_unstable_by(|a, b| {
-            // Sort by event count in descending order (compare negated counts), breaking ties by
-            // computer name in ascending order.
-            let count_cmp = Ord::cmp(
-                &-i64::from_usize(a.1.4).unwrap_or_default(),
-                &-i64::from_usize(b.1.4).unwrap_or_default(),
-            );
-            if count_cmp != Ordering::Equal {
-                return count_cmp;
-            }
-
-            a.0.cmp(b.0)
-        })
-    {
+    let sep = if output.is_some() { " ¦" } else { "\n" };
+    for (computer_name, inv) in sorted_computers(result_list) {
         // CSV output gets the plain number; terminal output gets thousands separators.
         let count_str = if output.is_some() {
-            format!("{count}")
+            format!("{}", inv.event_count)
         } else {
-            count.to_formatted_string(&Locale::en)
+            inv.event_count.to_formatted_string(&Locale::en)
         };
-        let elapsed_time = calc_elapsed_seconds(uptime.as_str(), last_timestamp.as_str());
+        let retention = inv
+            .retention_span()
+            .map(|(first, last)| format_uptime((last - first).num_seconds()))
+            .unwrap_or_default();
         let record_data = vec![
-            computer_name.as_str(),
-            os_info,
-            &elapsed_time,
-            timezone,
-            &count_str,
+            computer_name.to_string(),
+            inv.os_info.to_string(),
+            calc_elapsed_seconds(&inv.last_boot, &inv.last_system_timestamp),
+            inv.timezone.to_string(),
+            inv.domain.to_string(),
+            inv.ip_addresses.iter().sorted().join(sep),
+            inv.users.iter().sorted().join(sep),
+            inv.services.len().to_string(),
+            agent_version(&inv.sysmon).to_string(),
+            agent_version(&inv.defender).to_string(),
+            inv.audit_coverage().join(sep),
+            retention,
+            count_str,
         ];
         if output.is_some() {
             file_wtr.as_mut().unwrap().write_record(&record_data).ok();
         } else {
-            stats_tb.add_row(record_data);
+            stats_tb.add_row(record_data.into_iter().map(Cell::new).collect_vec());
         }
     }
     if output.is_none() {
@@ -240,8 +510,8 @@ mod tests {
 
     use crate::{
         detections::configs::{
-            Action, ClobberOption, CommonOptions, ComputerMetricsOption, Config, InputOption,
-            StoredStatic,
+            Action, ClobberOption, CommonOptions, ComputerMetricsFormat, ComputerMetricsOption,
+            Config, InputOption, StoredStatic,
         },
         timeline::{
             computer_metrics::{computer_metrics_dsp_msg, countup_event_by_computer},
@@ -266,6 +536,7 @@ mod tests {
                     directory: None,
                     filepath: Some(Path::new("./dummy.evtx").to_path_buf()),
                     live_analysis: false,
+                    carve: None,
                     recover_records: false,
                     time_offset: None,
                 },
@@ -280,6 +551,7 @@ mod tests {
                 config: Path::new("./rules/config").to_path_buf(),
                 verbose: false,
                 output: output.clone(),
+                output_type: ComputerMetricsFormat::Csv,
                 clobber_opt: ClobberOption { clobber: true },
                 validate_checksums: false,
             }));
@@ -332,18 +604,52 @@ mod tests {
             &mut timeline,
         );
 
-        computer_metrics_dsp_msg(&timeline.stats.stats_computer, &output);
+        computer_metrics_dsp_msg(
+            &timeline.stats.stats_computer,
+            &output,
+            ComputerMetricsFormat::Csv,
+        );
 
         let header = [
             "\"Computer\"",
             "\"OS information\"",
             "\"UpTime\",\"Timezone\"",
+            "\"Domain\",\"IP Addresses\",\"Users\",\"Services\"",
+            "\"Sysmon\",\"Defender\",\"Audit Coverage\",\"Log Retention\"",
             "\"Events\"",
         ];
 
         let expect = [
-            vec!["\"FALCON\"", "\"\"", "\"\"", "\"\"", "1"],
-            vec!["\"HAYABUSA-DESKTOP\"", "\"\"", "\"\"", "\"\"", "1"],
+            vec![
+                "\"FALCON\"",
+                "\"\"",
+                "\"\"",
+                "\"\"",
+                "\"\"",
+                "\"\"",
+                "\"\"",
+                "0",
+                "\"-\"",
+                "\"-\"",
+                "\"Logon\"",
+                "\"0Y 0M 0d 0h 0m 0s\"",
+                "1",
+            ],
+            vec![
+                "\"HAYABUSA-DESKTOP\"",
+                "\"\"",
+                "\"\"",
+                "\"\"",
+                "\"\"",
+                "\"\"",
+                "\"testuser\"",
+                "0",
+                "\"-\"",
+                "\"-\"",
+                "\"Logon\"",
+                "\"0Y 0M 0d 0h 0m 0s\"",
+                "1",
+            ],
         ];
         let expect_str =
             header.join(",") + "\n" + &expect.join(&"\n").join(",").replace(",\n,", "\n") + "\n";
@@ -357,4 +663,95 @@ mod tests {
         // Delete the file after the test.
         assert!(remove_file(&out_test_computer_metrics_csv).is_ok());
     }
+
+    #[test]
+    fn test_computer_inventory() {
+        let stored_static = StoredStatic::create_static_data(Config {
+            action: Some(Action::ComputerMetrics(ComputerMetricsOption::default())),
+            debug: false,
+        });
+        let mut timeline = Timeline::default();
+        let records = [
+            (
+                4624,
+                "Security",
+                "2024-01-01T00:00:00Z",
+                r#"{"TargetUserName": "WS01$", "TargetDomainName": "CORP"}"#,
+            ),
+            (
+                4624,
+                "Security",
+                "2024-01-02T00:00:00Z",
+                r#"{"TargetUserName": "alice", "TargetDomainName": "CORP"}"#,
+            ),
+            (
+                4624,
+                "Security",
+                "2024-01-02T00:00:01Z",
+                r#"{"TargetUserName": "SYSTEM", "TargetDomainName": "NT AUTHORITY"}"#,
+            ),
+            (
+                4688,
+                "Security",
+                "2024-01-03T00:00:00Z",
+                r#"{"NewProcessName": "C:\\Windows\\cmd.exe"}"#,
+            ),
+            (
+                7045,
+                "System",
+                "2024-01-03T00:00:00Z",
+                r#"{"ServiceName": "PSEXESVC", "ImagePath": "C:\\Windows\\PSEXESVC.exe"}"#,
+            ),
+            (
+                4,
+                "Microsoft-Windows-Sysmon/Operational",
+                "2024-01-04T00:00:00Z",
+                r#"{"State": "Started", "Version": "15.14"}"#,
+            ),
+            (
+                3,
+                "Microsoft-Windows-Sysmon/Operational",
+                "2024-01-04T00:00:00Z",
+                r#"{"Initiated": "true", "SourceIp": "10.0.0.5", "DestinationIp": "8.8.8.8"}"#,
+            ),
+            (
+                3,
+                "Microsoft-Windows-Sysmon/Operational",
+                "2024-01-04T00:00:00Z",
+                r#"{"Initiated": "false", "SourceIp": "10.0.0.9", "DestinationIp": "127.0.0.1"}"#,
+            ),
+        ];
+        for (event_id, channel, time, event_data) in records {
+            let record = serde_json::from_str(&format!(
+                r#"{{
+                    "Event": {{
+                        "System": {{
+                            "EventID": {event_id},
+                            "Channel": "{channel}",
+                            "Computer": "WS01",
+                            "TimeCreated_attributes": {{"SystemTime": "{time}"}}
+                        }},
+                        "EventData": {event_data}
+                    }}
+                }}"#
+            ))
+            .unwrap();
+            countup_event_by_computer(&record, &stored_static.eventkey_alias, &mut timeline);
+        }
+
+        let inv = &timeline.stats.stats_computer["WS01"];
+        assert_eq!(inv.event_count, 8);
+        assert_eq!(inv.domain, "CORP");
+        assert_eq!(inv.users.iter().collect::<Vec<_>>(), vec!["CORP\\alice"]);
+        assert_eq!(inv.services["PSEXESVC"], "C:\\Windows\\PSEXESVC.exe");
+        assert_eq!(inv.sysmon.as_deref(), Some("15.14"));
+        assert!(inv.defender.is_none());
+        assert_eq!(
+            inv.ip_addresses.iter().collect::<Vec<_>>(),
+            vec!["10.0.0.5"]
+        );
+        assert_eq!(inv.audit_coverage(), vec!["Logon", "Process Creation"]);
+        let (first, last) = inv.retention_span().unwrap();
+        assert_eq!((last - first).num_days(), 3);
+    }
 }
@@ -4,10 +4,11 @@ use crate::detections::utils::{check_setting_path, get_writable_color, write_col
 use console::{Style, style};
 use dialoguer::Confirm;
 use dialoguer::theme::ColorfulTheme;
-use serde_json::Value;
-use std::collections::{HashMap, HashSet};
+use itertools::Itertools;
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::OpenOptions;
-use std::io::{BufRead, BufReader, Write};
+use std::io::{BufRead, BufReader, BufWriter, Write};
 use std::path::{Path, PathBuf};
 use std::{fs, io};
 use strum::{EnumIter, IntoEnumIterator};
@@ -16,10 +17,18 @@ use termcolor::{BufferWriter, Color, ColorChoice};
 static CONFIG_CRITICAL_SYSTEMS: &str = "config/critical_systems.txt";
 
 // Categories of critical systems that can be detected from event logs.
-#[derive(Eq, Hash, PartialEq, Debug, Clone, EnumIter)]
+#[derive(Eq, Hash, PartialEq, Debug, Clone, Copy, EnumIter, PartialOrd, Ord)]
 enum ComputerType {
     DomainController,
     FileServer,
+    Exchange,
+    SqlServer,
+    CertificateAuthority,
+    Adfs,
+    DnsServer,
+    HyperV,
+    RdsGateway,
+    BackupServer,
 }
 
 impl ComputerType {
@@ -27,17 +36,229 @@ impl ComputerType {
         match self {
             ComputerType::DomainController => "Domain Controllers",
             ComputerType::FileServer => "File Servers",
+            ComputerType::Exchange => "Exchange Servers",
+            ComputerType::SqlServer => "SQL Servers",
+            ComputerType::CertificateAuthority => "Certificate Authorities (ADCS)",
+            ComputerType::Adfs => "ADFS Servers",
+            ComputerType::DnsServer => "DNS Servers",
+            ComputerType::HyperV => "Hyper-V Hosts",
+            ComputerType::RdsGateway => "RDS Gateways",
+            ComputerType::BackupServer => "Backup Servers",
         }
     }
+
+    /// Identifier used for the role in the JSON output.
+    fn to_key(&self) -> &str {
+        match self {
+            ComputerType::DomainController => "domain_controller",
+            ComputerType::FileServer => "file_server",
+            ComputerType::Exchange => "exchange",
+            ComputerType::SqlServer => "sql_server",
+            ComputerType::CertificateAuthority => "adcs",
+            ComputerType::Adfs => "adfs",
+            ComputerType::DnsServer => "dns",
+            ComputerType::HyperV => "hyper_v",
+            ComputerType::RdsGateway => "rds_gateway",
+            ComputerType::BackupServer => "backup_server",
+        }
+    }
+}
+
+/// One kind of evidence that a computer has a role. `weight` is how confident we are in the role
+/// when only this evidence has been seen (0.0 - 1.0). `matches` receives the channel, provider
+/// name, event ID and the whole record.
+struct Indicator {
+    role: ComputerType,
+    name: &'static str,
+    weight: f64,
+    matches: fn(&str, &str, i64, &Value) -> bool,
+}
+
+/// Returns true for share access events (5140/5145) to anything but the IPC$ named-pipe share,
+/// which every Windows host exposes and does not make the host a file server.
+fn is_file_share_access(data: &Value) -> bool {
+    let share = data["Event"]["EventData"]["ShareName"]
+        .as_str()
+        .unwrap_or_default();
+    share != r"\\*\IPC$"
 }
 
-/// Implements the config-critical-systems command: scans Security-channel events for computers
-/// that appear to be domain controllers or file servers, and interactively offers to append their
-/// names to config/critical_systems.txt. Alerts for hosts listed in that file later get their
-/// severity level raised by one (see level.rs).
+static INDICATORS: &[Indicator] = &[
+    // EID 4768/4769 (Kerberos TGT/service ticket requested) are only logged on domain controllers.
+    Indicator {
+        role: ComputerType::DomainController,
+        name: "Security 4768 (Kerberos TGT requested)",
+        weight: 0.95,
+        matches: |ch, _, id, _| ch == "Security" && id == 4768,
+    },
+    Indicator {
+        role: ComputerType::DomainController,
+        name: "Security 4769 (Kerberos service ticket requested)",
+        weight: 0.9,
+        matches: |ch, _, id, _| ch == "Security" && id == 4769,
+    },
+    Indicator {
+        role: ComputerType::DomainController,
+        name: "Directory Service log",
+        weight: 0.9,
+        matches: |ch, _, _, _| ch == "Directory Service",
+    },
+    Indicator {
+        role: ComputerType::FileServer,
+        name: "Security 5145 (network share file access)",
+        weight: 0.8,
+        matches: |ch, _, id, data| ch == "Security" && id == 5145 && is_file_share_access(data),
+    },
+    Indicator {
+        role: ComputerType::FileServer,
+        name: "Security 5140 (network share accessed)",
+        weight: 0.6,
+        matches: |ch, _, id, data| ch == "Security" && id == 5140 && is_file_share_access(data),
+    },
+    Indicator {
+        role: ComputerType::Exchange,
+        name: "MSExchange Management log",
+        weight: 0.9,
+        matches: |ch, _, _, _| ch == "MSExchange Management",
+    },
+    Indicator {
+        role: ComputerType::Exchange,
+        name: "MSExchange* provider events",
+        weight: 0.8,
+        matches: |_, provider, _, _| provider.starts_with("MSExchange"),
+    },
+    Indicator {
+        role: ComputerType::SqlServer,
+        name: "MSSQLSERVER/MSSQL$* provider events",
+        weight: 0.85,
+        matches: |_, provider, _, _| provider == "MSSQLSERVER" || provider.starts_with("MSSQL$"),
+    },
+    Indicator {
+        role: ComputerType::SqlServer,
+        name: "SQL Server Agent provider events",
+        weight: 0.6,
+        matches: |_, provider, _, _| {
+            provider == "SQLSERVERAGENT" || provider.starts_with("SQLAgent$")
+        },
+    },
+    // 4886-4889: Certificate Services received/approved/denied/set to pending a request.
+    Indicator {
+        role: ComputerType::CertificateAuthority,
+        name: "Security 4886-4889 (certificate request)",
+        weight: 0.95,
+        matches: |ch, _, id, _| ch == "Security" && (4886..=4889).contains(&id),
+    },
+    Indicator {
+        role: ComputerType::CertificateAuthority,
+        name: "CertificationAuthority provider events",
+        weight: 0.8,
+        matches: |_, provider, _, _| provider == "Microsoft-Windows-CertificationAuthority",
+    },
+    Indicator {
+        role: ComputerType::Adfs,
+        name: "AD FS/Admin log",
+        weight: 0.9,
+        matches: |ch, _, _, _| ch == "AD FS/Admin",
+    },
+    Indicator {
+        role: ComputerType::Adfs,
+        name: "AD FS Auditing provider events",
+        weight: 0.85,
+        matches: |_, provider, _, _| provider == "AD FS Auditing" || provider == "AD FS",
+    },
+    Indicator {
+        role: ComputerType::DnsServer,
+        name: "DNS Server log",
+        weight: 0.9,
+        matches: |ch, _, _, _| ch == "DNS Server",
+    },
+    Indicator {
+        role: ComputerType::DnsServer,
+        name: "DNSServer/Audit log",
+        weight: 0.9,
+        matches: |ch, _, _, _| ch == "Microsoft-Windows-DNSServer/Audit",
+    },
+    Indicator {
+        role: ComputerType::HyperV,
+        name: "Hyper-V-VMMS-Admin log",
+        weight: 0.85,
+        matches: |ch, _, _, _| ch == "Microsoft-Windows-Hyper-V-VMMS-Admin",
+    },
+    Indicator {
+        role: ComputerType::HyperV,
+        name: "Hyper-V-Worker-Admin log",
+        weight: 0.8,
+        matches: |ch, _, _, _| ch == "Microsoft-Windows-Hyper-V-Worker-Admin",
+    },
+    Indicator {
+        role: ComputerType::RdsGateway,
+        name: "TerminalServices-Gateway/Operational log",
+        weight: 0.9,
+        matches: |ch, _, _, _| ch == "Microsoft-Windows-TerminalServices-Gateway/Operational",
+    },
+    Indicator {
+        role: ComputerType::BackupServer,
+        name: "Veeam Backup log",
+        weight: 0.9,
+        matches: |ch, _, _, _| ch == "Veeam Backup",
+    },
+    Indicator {
+        role: ComputerType::BackupServer,
+        name: "Veeam* provider events",
+        weight: 0.8,
+        matches: |_, provider, _, _| provider.starts_with("Veeam"),
+    },
+    // Windows Server Backup also runs on hosts that only back themselves up, so it is weaker.
+    Indicator {
+        role: ComputerType::BackupServer,
+        name: "Microsoft-Windows-Backup events",
+        weight: 0.5,
+        matches: |ch, provider, _, _| {
+            ch == "Microsoft-Windows-Backup" || provider == "Microsoft-Windows-Backup"
+        },
+    },
+];
+
+/// Channels that contain at least one indicator, used for the channel filter in main.rs.
+pub static CRITICAL_SYSTEMS_CHANNELS: &[&str] = &[
+    "Security",
+    "Application",
+    "System",
+    "Directory Service",
+    "MSExchange Management",
+    "AD FS/Admin",
+    "DNS Server",
+    "Microsoft-Windows-DNSServer/Audit",
+    "Microsoft-Windows-Hyper-V-VMMS-Admin",
+    "Microsoft-Windows-Hyper-V-Worker-Admin",
+    "Microsoft-Windows-TerminalServices-Gateway/Operational",
+    "Veeam Backup",
+    "Microsoft-Windows-Backup",
+];
+
+/// Event counts per indicator (index into INDICATORS) for one computer and role.
+type RoleEvidence = BTreeMap<usize, usize>;
+
+/// Combines the indicators seen for a role into a confidence score. Each distinct indicator is
+/// treated as independent evidence: confidence = 1 - (1 - w1) * (1 - w2) * ... Repeated events of
+/// the same indicator do not raise the score, but are reported as counts.
+fn confidence(evidence: &RoleEvidence) -> f64 {
+    1.0 - evidence
+        .keys()
+        .map(|idx| 1.0 - INDICATORS[*idx].weight)
+        .product::<f64>()
+}
+
+/// Implements the config-critical-systems command: scans events for computers that appear to have
+/// a critical server role (domain controller, file server, Exchange, SQL Server, ADCS, ADFS, DNS,
+/// Hyper-V, RDS gateway or backup server), and either interactively offers to append their names
+/// to config/critical_systems.txt, appends the ones above a confidence threshold without asking,
+/// or saves the roles with their confidence to a JSON file. Alerts for hosts listed in
+/// config/critical_systems.txt later get their severity level raised by the `critical` tier rule
+/// (see options/asset_inventory.rs).
 #[derive(Clone, Debug)]
 pub struct ConfigCriticalSystems {
-    computers: HashMap<ComputerType, HashSet<String>>,
+    computers: HashMap<ComputerType, HashMap<String, RoleEvidence>>,
     config_txt_path: PathBuf,
 }
 
@@ -66,47 +287,64 @@ impl ConfigCriticalSystems {
         }
     }
 
-    /// Classifies the computer that recorded the event as a domain controller or file server
-    /// based on Security-channel event IDs that only those roles log.
+    /// Counts every indicator that the event matches for the computer that recorded it.
     fn find_critical_computers(&mut self, data: &Value) {
-        if let Some(ch) = data["Event"]["System"]["Channel"].as_str()
-            && let Some(id) = data["Event"]["System"]["EventID"].as_i64()
-            && ch == "Security"
-        {
-            if id == 4768 {
-                // EID 4768 (a Kerberos authentication ticket (TGT) was requested) is only logged
-                // on domain controllers, so the recording computer must be a DC.
-                let computer = data["Event"]["System"]["Computer"]
-                    .as_str()
-                    .unwrap_or_default()
-                    .to_string();
-                self.computers
-                    .entry(ComputerType::DomainController)
-                    .or_default()
-                    .insert(computer);
-            } else if id == 5145 {
-                // EID 5145 (a network share object was checked for access) indicates the
-                // recording computer is serving file shares. Ignore accesses to the IPC$
-                // named-pipe share, since every Windows host exposes it and it does not mean the
-                // host is a file server.
-                let share = data["Event"]["EventData"]["ShareName"]
-                    .as_str()
-                    .unwrap_or_default();
-                if share == r"\\*\IPC$" {
-                    return;
-                }
-                let computer = data["Event"]["System"]["Computer"]
-                    .as_str()
-                    .unwrap_or_default()
-                    .to_string();
-                self.computers
-                    .entry(ComputerType::FileServer)
-                    .or_default()
-                    .insert(computer);
+        let system = &data["Event"]["System"];
+        let Some(ch) = system["Channel"].as_str() else {
+            return;
+        };
+        let provider = system["Provider_attributes"]["Name"]
+            .as_str()
+            .unwrap_or_default();
+        let id = system["EventID"].as_i64().unwrap_or_default();
+        for (idx, indicator) in INDICATORS.iter().enumerate() {
+            if !(indicator.matches)(ch, provider, id, data) {
+                continue;
             }
+            let computer = system["Computer"].as_str().unwrap_or_default().to_string();
+            *self
+                .computers
+                .entry(indicator.role)
+                .or_default()
+                .entry(computer)
+                .or_default()
+                .entry(idx)
+                .or_default() += 1;
         }
     }
 
+    /// Saves the discovered computers to a JSON file, grouped by computer with the confidence and
+    /// supporting event counts of each role, so that no interactive prompt is needed.
+    pub fn output_json(&self, path: &Path) -> io::Result<()> {
+        let mut by_computer: BTreeMap<&str, Vec<Value>> = BTreeMap::new();
+        for computer_type in ComputerType::iter() {
+            let Some(names) = self.computers.get(&computer_type) else {
+                continue;
+            };
+            for (name, evidence) in names {
+                let evidence_json: Vec<Value> = evidence
+                    .iter()
+                    .map(|(idx, count)| json!({"indicator": INDICATORS[*idx].name, "count": count}))
+                    .collect();
+                by_computer.entry(name.as_str()).or_default().push(json!({
+                    "role": computer_type.to_key(),
+                    "name": computer_type.to_str(),
+                    "confidence": (confidence(evidence) * 100.0).round() / 100.0,
+                    "event_count": evidence.values().sum::<usize>(),
+                    "evidence": evidence_json,
+                }));
+            }
+        }
+        let computers: Vec<Value> = by_computer
+            .into_iter()
+            .map(|(name, roles)| json!({"computer": name, "roles": roles}))
+            .collect();
+        let mut file = BufWriter::new(fs::File::create(path)?);
+        serde_json::to_writer_pretty(&mut file, &json!({ "computers": computers }))?;
+        file.write_all(b"\n")?;
+        file.flush()
+    }
+
     /// For each computer type, prints the discovered host names and interactively asks the user
     /// whether to append them to the critical systems config file. Appended entries are then
     /// deduplicated and sorted together with any pre-existing ones.
@@ -122,12 +360,23 @@ impl ConfigCriticalSystems {
                     )
                     .ok();
                     let mut names: Vec<_> = names.iter().collect();
-                    names.sort();
-                    for name in &names {
+                    names.sort_by(|(x_name, x_evidence), (y_name, y_evidence)| {
+                        confidence(y_evidence)
+                            .total_cmp(&confidence(x_evidence))
+                            .then_with(|| x_name.cmp(y_name))
+                    });
+                    for (name, evidence) in &names {
+                        let evidence_msg = evidence
+                            .iter()
+                            .map(|(idx, count)| format!("{} x {count}", INDICATORS[*idx].name))
+                            .join(", ");
                         write_color_buffer(
                             &BufferWriter::stdout(ColorChoice::Always),
                             None,
-                            name,
+                            &format!(
+                                "{name} (confidence: {:.0}%, {evidence_msg})",
+                                confidence(evidence) * 100.0
+                            ),
                             true,
                         )
                         .ok();
@@ -189,16 +438,8 @@ impl ConfigCriticalSystems {
                         .interact()
                         .unwrap();
                     if config_append {
-                        let mut file = fs::OpenOptions::new()
-                            .append(true)
-                            .create(true)
-                            .open(&self.config_txt_path)
+                        self.append_to_config(names.iter().map(|(name, _)| name.as_str()))
                             .expect("Failed to open file");
-                        names.iter().for_each(|name| {
-                            file.write_all(format!("{name}\n").as_bytes()).ok();
-                        });
-                        file.flush().ok();
-                        sort_and_dedup_file(&self.config_txt_path).ok();
                         write_color_buffer(
                             &BufferWriter::stdout(ColorChoice::Always),
                             get_writable_color(Some(Color::Rgb(255, 175, 0)), no_color),
@@ -210,7 +451,7 @@ impl ConfigCriticalSystems {
                     }
                 }
                 None => {
-                    let msg = format!("No {computer_type:?} found.");
+                    let msg = format!("No {} found.", computer_type.to_str());
                     write_color_buffer(
                         &BufferWriter::stdout(ColorChoice::Always),
                         get_writable_color(Some(Color::Rgb(215, 0, 0)), no_color),
@@ -223,6 +464,59 @@ impl ConfigCriticalSystems {
             }
         }
     }
+
+    /// Appends the computers that have at least one role with a confidence of `min_confidence`
+    /// percent or more to the critical systems config file without asking, so that their alerts
+    /// get the `critical` tier level boost. Returns the appended computer names.
+    pub fn add_confident_computers(
+        &self,
+        min_confidence: u8,
+        no_color: bool,
+    ) -> io::Result<Vec<String>> {
+        let names: Vec<String> = self
+            .computers
+            .values()
+            .flatten()
+            .filter(|(_, evidence)| {
+                (confidence(evidence) * 100.0).round() >= f64::from(min_confidence)
+            })
+            .map(|(name, _)| name.to_string())
+            .sorted()
+            .dedup()
+            .collect();
+        let msg = if names.is_empty() {
+            format!("No computers found with a confidence of {min_confidence}% or more.")
+        } else {
+            self.append_to_config(names.iter().map(String::as_str))?;
+            format!(
+                "Added to the {CONFIG_CRITICAL_SYSTEMS} file ({}): {}",
+                names.len(),
+                names.join(", ")
+            )
+        };
+        write_color_buffer(
+            &BufferWriter::stdout(ColorChoice::Always),
+            get_writable_color(Some(Color::Rgb(255, 175, 0)), no_color),
+            &msg,
+            true,
+        )
+        .ok();
+        Ok(names)
+    }
+
+    /// Appends the given computer names to the critical systems config file, then deduplicates
+    /// and sorts it together with any pre-existing entries.
+    fn append_to_config<'a>(&self, names: impl Iterator<Item = &'a str>) -> io::Result<()> {
+        let mut file = fs::OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&self.config_txt_path)?;
+        for name in names {
+            writeln!(file, "{name}")?;
+        }
+        file.flush()?;
+        sort_and_dedup_file(&self.config_txt_path)
+    }
 }
 
 /// Rewrites the given file with its lines deduplicated and sorted alphabetically.
@@ -273,7 +567,7 @@ mod tests {
                 .computers
                 .get(&ComputerType::DomainController)
                 .unwrap()
-                .contains("DC1")
+                .contains_key("DC1")
         );
 
         let data = json!({
@@ -309,7 +603,150 @@ mod tests {
                 .computers
                 .get(&ComputerType::FileServer)
                 .unwrap()
-                .contains("FileServer2")
+                .contains_key("FileServer2")
+        );
+    }
+
+    #[test]
+    fn test_find_server_roles() {
+        let mut config = ConfigCriticalSystems::new();
+        let event = |channel: &str, provider: &str, id: i64, computer: &str| {
+            json!({
+                "Event": {
+                    "System": {
+                        "Channel": channel,
+                        "Provider_attributes": {"Name": provider},
+                        "EventID": id,
+                        "Computer": computer
+                    }
+                }
+            })
+        };
+        let records = [
+            event("Application", "MSExchangeTransport", 1, "EXCH01"),
+            event("Application", "MSSQL$PROD", 18453, "SQL01"),
+            event(
+                "Security",
+                "Microsoft-Windows-Security-Auditing",
+                4887,
+                "CA01",
+            ),
+            event("AD FS/Admin", "AD FS", 100, "ADFS01"),
+            event(
+                "DNS Server",
+                "Microsoft-Windows-DNS-Server-Service",
+                2,
+                "DNS01",
+            ),
+            event(
+                "Microsoft-Windows-Hyper-V-VMMS-Admin",
+                "Hyper-V-VMMS",
+                18304,
+                "HV01",
+            ),
+            event(
+                "Microsoft-Windows-TerminalServices-Gateway/Operational",
+                "Microsoft-Windows-TerminalServices-Gateway",
+                302,
+                "GW01",
+            ),
+            event("Application", "Veeam MP", 190, "BKP01"),
+            // An ordinary application event does not imply any role.
+            event("Application", "Microsoft-Windows-Winlogon", 6000, "WS01"),
+        ];
+        for record in &records {
+            config.find_critical_computers(record);
+        }
+        for (computer_type, computer) in [
+            (ComputerType::Exchange, "EXCH01"),
+            (ComputerType::SqlServer, "SQL01"),
+            (ComputerType::CertificateAuthority, "CA01"),
+            (ComputerType::Adfs, "ADFS01"),
+            (ComputerType::DnsServer, "DNS01"),
+            (ComputerType::HyperV, "HV01"),
+            (ComputerType::RdsGateway, "GW01"),
+            (ComputerType::BackupServer, "BKP01"),
+        ] {
+            assert!(
+                config.computers[&computer_type].contains_key(computer),
+                "{computer} should be a {}",
+                computer_type.to_str()
+            );
+        }
+        assert!(
+            config
+                .computers
+                .values()
+                .all(|names| !names.contains_key("WS01"))
+        );
+    }
+
+    #[test]
+    fn test_confidence_and_json_output() {
+        let mut config = ConfigCriticalSystems::new();
+        let event = |id: i64| {
+            json!({
+                "Event": {
+                    "System": {"Channel": "Security", "EventID": id, "Computer": "DC1"}
+                }
+            })
+        };
+        for _ in 0..3 {
+            config.find_critical_computers(&event(4768));
+        }
+        let evidence = &config.computers[&ComputerType::DomainController]["DC1"];
+        assert!((confidence(evidence) - 0.95).abs() < 1e-9);
+        // A second, independent indicator raises the confidence: 1 - 0.05 * 0.1
+        config.find_critical_computers(&event(4769));
+        let evidence = &config.computers[&ComputerType::DomainController]["DC1"];
+        assert!((confidence(evidence) - 0.995).abs() < 1e-9);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("critical-systems.json");
+        config.output_json(&path).unwrap();
+        let json: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        let computer = &json["computers"][0];
+        assert_eq!(computer["computer"], "DC1");
+        let role = &computer["roles"][0];
+        assert_eq!(role["role"], "domain_controller");
+        assert!(role["confidence"].as_f64().unwrap() > 0.99);
+        assert_eq!(role["event_count"], 4);
+        assert_eq!(role["evidence"][0]["count"], 3);
+        assert_eq!(
+            role["evidence"][0]["indicator"],
+            "Security 4768 (Kerberos TGT requested)"
+        );
+    }
+
+    #[test]
+    fn test_add_confident_computers() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = ConfigCriticalSystems::new();
+        config.config_txt_path = dir.path().join("critical_systems.txt");
+        fs::write(&config.config_txt_path, "SRV9\n").unwrap();
+        let event = |id: i64, computer: &str| {
+            json!({
+                "Event": {
+                    "System": {"Channel": "Security", "EventID": id, "Computer": computer}
+                }
+            })
+        };
+        // DC1: 4768 only (95%), DC2: 4769 only (90%)
+        config.find_critical_computers(&event(4768, "DC1"));
+        config.find_critical_computers(&event(4769, "DC2"));
+
+        let added = config.add_confident_computers(95, true).unwrap();
+        assert_eq!(added, vec!["DC1".to_string()]);
+        assert_eq!(
+            fs::read_to_string(&config.config_txt_path).unwrap(),
+            "DC1\nSRV9\n"
+        );
+
+        let added = config.add_confident_computers(0, true).unwrap();
+        assert_eq!(added, vec!["DC1".to_string(), "DC2".to_string()]);
+        assert_eq!(
+            fs::read_to_string(&config.config_txt_path).unwrap(),
+            "DC1\nDC2\nSRV9\n"
         );
     }
 }
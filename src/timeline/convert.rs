@@ -0,0 +1,173 @@
+use crate::detections::configs::ConvertFormat;
+use crate::detections::field_data_map::{FieldDataMap, FieldDataMapKey, convert_field_data};
+use crate::detections::utils::get_serde_number_to_string;
+use compact_str::CompactString;
+use hashbrown::{HashMap, HashSet};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// An .evtx file written out by the convert command.
+#[derive(Debug, Clone)]
+pub struct ConvertedFile {
+    pub source: PathBuf,
+    pub output: PathBuf,
+    /// Number of events that passed the filters and were written.
+    pub events: usize,
+}
+
+/// The files written by the convert command, one per .evtx file.
+#[derive(Debug, Clone, Default)]
+pub struct EventConverter {
+    pub files: Vec<ConvertedFile>,
+    // Output file of each .evtx file named so far.
+    outputs: HashMap<PathBuf, PathBuf>,
+    // Lowercase output file names used so far, so that evtx files with the same name in
+    // different directories are not written to the same file on case-insensitive file systems.
+    used_names: HashSet<String>,
+}
+
+impl EventConverter {
+    /// Returns the file in `output_dir` to convert `source` to. It is named after the evtx file,
+    /// with a number added when the name was already used (ex: Security.jsonl, Security-2.jsonl).
+    /// The same file is returned for the same `source`, so that all of the files can be named
+    /// before the scan.
+    pub fn output_path(
+        &mut self,
+        output_dir: &Path,
+        source: &Path,
+        format: ConvertFormat,
+    ) -> PathBuf {
+        if let Some(output) = self.outputs.get(source) {
+            return output.clone();
+        }
+        let stem = source
+            .file_stem()
+            .map_or_else(|| "events".to_string(), |s| s.to_string_lossy().to_string());
+        let extension = match format {
+            ConvertFormat::Jsonl => "jsonl",
+            ConvertFormat::Xml => "xml",
+        };
+        let mut name = format!("{stem}.{extension}");
+        let mut num = 2;
+        while !self.used_names.insert(name.to_lowercase()) {
+            name = format!("{stem}-{num}.{extension}");
+            num += 1;
+        }
+        let output = output_dir.join(name);
+        self.outputs.insert(source.to_path_buf(), output.clone());
+        output
+    }
+}
+
+/// Rewrites the EventData values of a record with the data_mapping rules of its channel and event
+/// ID, the same way the values are rewritten in the dfir-timeline details.
+pub fn map_field_data(record: &mut Value, field_data_map: &FieldDataMap) {
+    let system = &record["Event"]["System"];
+    let key = FieldDataMapKey {
+        channel: CompactString::from(
+            system["Channel"]
+                .as_str()
+                .unwrap_or_default()
+                .to_lowercase(),
+        ),
+        event_id: get_serde_number_to_string(&system["EventID"], false).unwrap_or_default(),
+    };
+    if !field_data_map.contains_key(&key) {
+        return;
+    }
+    // The provider name is read from the original record while EventData is being rewritten.
+    let original = record.clone();
+    let Some(event_data) = record["Event"]["EventData"].as_object_mut() else {
+        return;
+    };
+    for (field, value) in event_data.iter_mut() {
+        let Some(value_str) = value.as_str() else {
+            continue;
+        };
+        if let Some(converted) = convert_field_data(
+            field_data_map,
+            &key,
+            &field.to_lowercase(),
+            value_str,
+            &original,
+        ) {
+            *value = Value::String(converted.to_string());
+        }
+    }
+}
+
+/// Removes the XML declaration that the parser adds before each record, so that the records can
+/// be written under one `<Events>` root element.
+pub fn strip_xml_declaration(xml: &str) -> &str {
+    let xml = xml.trim();
+    xml.strip_prefix("<?xml")
+        .and_then(|rest| rest.find("?>").map(|end| rest[end + 2..].trim_start()))
+        .unwrap_or(xml)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detections::field_data_map::FieldDataConverter;
+    use hashbrown::HashMap;
+    use serde_json::json;
+
+    #[test]
+    fn test_output_path() {
+        let mut converter = EventConverter::default();
+        let dir = Path::new("out");
+        assert_eq!(
+            converter.output_path(dir, Path::new("a/Security.evtx"), ConvertFormat::Jsonl),
+            dir.join("Security.jsonl")
+        );
+        assert_eq!(
+            converter.output_path(dir, Path::new("b/security.evtx"), ConvertFormat::Jsonl),
+            dir.join("security-2.jsonl")
+        );
+        assert_eq!(
+            converter.output_path(dir, Path::new("c/Security.evtx"), ConvertFormat::Xml),
+            dir.join("Security.xml")
+        );
+        assert_eq!(
+            converter.output_path(dir, Path::new("a/Security.evtx"), ConvertFormat::Jsonl),
+            dir.join("Security.jsonl")
+        );
+    }
+
+    #[test]
+    fn test_map_field_data() {
+        let key = FieldDataMapKey {
+            channel: "security".into(),
+            event_id: "4624".into(),
+        };
+        let field_data_map: FieldDataMap = HashMap::from([(
+            key,
+            HashMap::from([("logontype".to_string(), FieldDataConverter::HexToDecimal)]),
+        )]);
+        let mut record = json!({"Event": {
+            "System": {"Channel": "Security", "EventID": 4624},
+            "EventData": {"LogonType": "0xa", "TargetUserName": "0x1"}
+        }});
+        map_field_data(&mut record, &field_data_map);
+        assert_eq!(record["Event"]["EventData"]["LogonType"], "10");
+        assert_eq!(record["Event"]["EventData"]["TargetUserName"], "0x1");
+
+        let mut record = json!({"Event": {
+            "System": {"Channel": "System", "EventID": 4624},
+            "EventData": {"LogonType": "0xa"}
+        }});
+        map_field_data(&mut record, &field_data_map);
+        assert_eq!(record["Event"]["EventData"]["LogonType"], "0xa");
+    }
+
+    #[test]
+    fn test_strip_xml_declaration() {
+        assert_eq!(
+            strip_xml_declaration(
+                "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<Event>\n  <System/>\n</Event>\n"
+            ),
+            "<Event>\n  <System/>\n</Event>"
+        );
+        assert_eq!(strip_xml_declaration("<Event/>"), "<Event/>");
+    }
+}
@@ -0,0 +1,617 @@
+use compact_str::CompactString;
+use hashbrown::{HashMap, HashSet};
+use itertools::Itertools;
+use yaml_rust2::Yaml;
+
+use crate::detections::configs::StoredStatic;
+use crate::detections::detection::EvtxRecordInfo;
+use crate::detections::rule::RuleNode;
+use crate::detections::utils;
+use crate::filter;
+use crate::timeline::metrics::get_event_value_as_string;
+
+const SYSMON_CHANNEL: &str = "Microsoft-Windows-Sysmon/Operational";
+const POWERSHELL_CHANNEL: &str = "Microsoft-Windows-PowerShell/Operational";
+
+/// A log a rule can detect from: a channel, limited to some event IDs when the rule filters on
+/// `EventID`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LogSource {
+    pub channel: CompactString,
+    /// Empty when any event of the channel can match.
+    pub event_ids: Vec<CompactString>,
+    /// The rule reads the `CommandLine` field of Security 4688, which is only logged when
+    /// command line auditing is enabled.
+    pub command_line: bool,
+}
+
+/// The log sources of a loaded rule.
+#[derive(Debug, Clone)]
+pub struct CoverageRule {
+    pub title: CompactString,
+    pub level: CompactString,
+    pub path: CompactString,
+    /// ATT&CK technique IDs from the `attack.tXXXX` tags, e.g. `T1059.001`.
+    pub techniques: Vec<CompactString>,
+    /// The rule can fire when the logs contain any of these sources.
+    pub sources: Vec<LogSource>,
+}
+
+/// The (channel, EventID) pairs found in the logs of a computer.
+#[derive(Debug, Clone, Default)]
+pub struct HostLogs {
+    pub events: HashSet<(CompactString, CompactString)>,
+    pub channels: HashSet<CompactString>,
+    /// A Security 4688 event with a command line was found.
+    pub command_lines: bool,
+}
+
+/// A rule that cannot fire on a computer, with the log sources missing from its logs.
+#[derive(Debug, Clone)]
+pub struct BlindRule<'a> {
+    pub rule: &'a CoverageRule,
+    pub missing: Vec<CompactString>,
+}
+
+/// The coverage gaps of one computer.
+#[derive(Debug, Clone)]
+pub struct HostCoverage<'a> {
+    pub computer: &'a str,
+    pub blind_rules: Vec<BlindRule<'a>>,
+    /// Techniques that only have blind rules on the computer.
+    pub blind_techniques: Vec<&'a str>,
+    /// Audit settings to check with the number of blind rules each of them would enable, from the
+    /// most to the least.
+    pub settings: Vec<(String, usize)>,
+}
+
+/// Compares the log sources that the loaded rules need with the events found in each computer's
+/// logs, for the coverage command.
+#[derive(Debug, Clone, Default)]
+pub struct RuleCoverage {
+    pub rules: Vec<CoverageRule>,
+    /// Number of rules whose log sources could not be determined, which are left out.
+    pub undetermined: usize,
+    pub hosts: HashMap<CompactString, HostLogs>,
+}
+
+impl RuleCoverage {
+    /// Collects the log sources of the rules. Correlation rules are skipped as they only refer to
+    /// other rules.
+    pub fn from_rules(rule_nodes: &[RuleNode]) -> Self {
+        let mut coverage = RuleCoverage::default();
+        for rule in rule_nodes {
+            if !rule.yaml["correlation"].is_badvalue() {
+                continue;
+            }
+            let sources = rule_sources(&rule.yaml);
+            if sources.is_empty() {
+                coverage.undetermined += 1;
+                continue;
+            }
+            let value = |key: &str| CompactString::from(rule.yaml[key].as_str().unwrap_or("-"));
+            coverage.rules.push(CoverageRule {
+                title: value("title"),
+                level: value("level"),
+                path: rule.rule_path.as_str().into(),
+                techniques: techniques(&rule.yaml),
+                sources,
+            });
+        }
+        coverage
+    }
+
+    /// Records the (channel, EventID) pairs of each computer, and whether its 4688 events log the
+    /// command line.
+    pub fn update(&mut self, records: &[EvtxRecordInfo], stored_static: &StoredStatic) {
+        let alias = &stored_static.eventkey_alias;
+        for record in records {
+            let rec = &record.record;
+            if utils::is_filtered_by_computer_name(
+                utils::get_event_value("Event.System.Computer", rec, alias),
+                (
+                    &stored_static.include_computer,
+                    &stored_static.exclude_computer,
+                ),
+            ) {
+                continue;
+            }
+            let computer = get_event_value_as_string("Computer", rec, alias);
+            let channel = get_event_value_as_string("Channel", rec, alias);
+            let event_id = get_event_value_as_string("EventID", rec, alias);
+            let host = self.hosts.entry(computer).or_default();
+            if channel == "Security"
+                && event_id == "4688"
+                && !matches!(
+                    get_event_value_as_string("CommandLine", rec, alias).as_str(),
+                    "-" | ""
+                )
+            {
+                host.command_lines = true;
+            }
+            host.channels.insert(channel.clone());
+            host.events.insert((channel, event_id));
+        }
+    }
+
+    /// Returns the coverage gaps of each computer, ordered by computer name.
+    pub fn report(&self) -> Vec<HostCoverage<'_>> {
+        self.hosts
+            .iter()
+            .sorted_by(|a, b| a.0.cmp(b.0))
+            .map(|(computer, host)| {
+                let blind_rules = self
+                    .rules
+                    .iter()
+                    .filter(|rule| !rule.sources.iter().any(|src| host.covers(src)))
+                    .map(|rule| BlindRule {
+                        rule,
+                        missing: rule
+                            .sources
+                            .iter()
+                            .flat_map(|src| host.missing(src))
+                            .unique()
+                            .collect(),
+                    })
+                    .collect_vec();
+                let mut covered_techniques = HashSet::new();
+                let mut all_techniques = HashSet::new();
+                for rule in &self.rules {
+                    let covered = rule.sources.iter().any(|src| host.covers(src));
+                    for technique in &rule.techniques {
+                        all_techniques.insert(technique.as_str());
+                        if covered {
+                            covered_techniques.insert(technique.as_str());
+                        }
+                    }
+                }
+                let blind_techniques = all_techniques
+                    .difference(&covered_techniques)
+                    .copied()
+                    .sorted()
+                    .collect();
+                let mut settings: HashMap<String, usize> = HashMap::new();
+                for blind in &blind_rules {
+                    for setting in blind
+                        .rule
+                        .sources
+                        .iter()
+                        .flat_map(|src| host.settings(src))
+                        .unique()
+                    {
+                        *settings.entry(setting).or_default() += 1;
+                    }
+                }
+                HostCoverage {
+                    computer: computer.as_str(),
+                    blind_rules,
+                    blind_techniques,
+                    settings: settings
+                        .into_iter()
+                        .sorted_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)))
+                        .collect(),
+                }
+            })
+            .collect()
+    }
+}
+
+impl HostLogs {
+    /// Returns the channels in the logs that the rule channel matches. Rule channels can have
+    /// leading and trailing wildcards.
+    fn matching_channels(&self, channel: &str) -> Vec<&str> {
+        self.channels
+            .iter()
+            .filter(|ch| filter::channel_matches(channel, ch))
+            .map(|ch| ch.as_str())
+            .collect()
+    }
+
+    fn has_event(&self, channel: &str, event_id: &str, command_line: bool) -> bool {
+        self.events
+            .contains(&(CompactString::from(channel), CompactString::from(event_id)))
+            && !(command_line && event_id == "4688" && !self.command_lines)
+    }
+
+    /// Returns true when 4688 events were found without the command line the source needs.
+    fn lacks_command_line(&self, src: &LogSource, event_id: &str) -> bool {
+        src.command_line
+            && event_id == "4688"
+            && self.has_event(&src.channel, event_id, false)
+            && !self.command_lines
+    }
+
+    fn covers(&self, src: &LogSource) -> bool {
+        self.matching_channels(&src.channel).iter().any(|ch| {
+            src.event_ids.is_empty()
+                || src
+                    .event_ids
+                    .iter()
+                    .any(|id| self.has_event(ch, id, src.command_line))
+        })
+    }
+
+    /// Returns the event IDs of the source that are not in the logs of any matching channel.
+    fn missing_event_ids<'a>(&self, src: &'a LogSource) -> Vec<&'a str> {
+        let channels = self.matching_channels(&src.channel);
+        src.event_ids
+            .iter()
+            .map(|id| id.as_str())
+            .filter(|id| {
+                !channels
+                    .iter()
+                    .any(|ch| self.has_event(ch, id, src.command_line))
+            })
+            .collect()
+    }
+
+    /// Returns the parts of the source that are not in the logs, e.g. `Security 4688`.
+    fn missing(&self, src: &LogSource) -> Vec<CompactString> {
+        if self.matching_channels(&src.channel).is_empty() {
+            return vec![src.channel.clone()];
+        }
+        self.missing_event_ids(src)
+            .into_iter()
+            .map(|id| {
+                if self.lacks_command_line(src, id) {
+                    format!("{} {id} (CommandLine)", src.channel).into()
+                } else {
+                    format!("{} {id}", src.channel).into()
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the audit settings that would log the missing parts of the source.
+    fn settings(&self, src: &LogSource) -> Vec<String> {
+        if self.matching_channels(&src.channel).is_empty() {
+            return vec![audit_setting(&src.channel, None, false)];
+        }
+        self.missing_event_ids(src)
+            .into_iter()
+            .map(|id| {
+                if self.lacks_command_line(src, id) {
+                    "Include command line in process creation events".to_string()
+                } else {
+                    audit_setting(&src.channel, Some(id), true)
+                }
+            })
+            .collect()
+    }
+}
+
+/// Returns the setting that logs the event, or a hint to check the channel when it is not a
+/// configurable event.
+fn audit_setting(channel: &str, event_id: Option<&str>, channel_found: bool) -> String {
+    let id = event_id.and_then(|id| id.parse::<u32>().ok());
+    let setting = match (channel, id) {
+        ("Security", Some(4688)) => "Audit Process Creation",
+        ("Security", Some(4689)) => "Audit Process Termination",
+        ("Security", Some(4624 | 4625 | 4648)) => "Audit Logon",
+        ("Security", Some(4634 | 4647)) => "Audit Logoff",
+        ("Security", Some(4672 | 4964)) => "Audit Special Logon",
+        ("Security", Some(4656 | 4658 | 4660 | 4663 | 4670)) => {
+            "Audit File System / Audit Registry (with SACLs)"
+        }
+        ("Security", Some(4657)) => "Audit Registry (with SACLs)",
+        ("Security", Some(4698..=4702)) => "Audit Other Object Access Events",
+        ("Security", Some(5140)) => "Audit File Share",
+        ("Security", Some(5145)) => "Audit Detailed File Share",
+        ("Security", Some(5156..=5158)) => "Audit Filtering Platform Connection",
+        ("Security", Some(4720..=4726 | 4738 | 4740 | 4741..=4743 | 4765..=4767 | 4781)) => {
+            "Audit User Account Management"
+        }
+        ("Security", Some(4727..=4737 | 4754..=4758 | 4798 | 4799)) => {
+            "Audit Security Group Management"
+        }
+        ("Security", Some(4768 | 4771 | 4772)) => "Audit Kerberos Authentication Service",
+        ("Security", Some(4769 | 4770 | 4773)) => "Audit Kerberos Service Ticket Operations",
+        ("Security", Some(4776)) => "Audit Credential Validation",
+        ("Security", Some(4697)) => "Audit Security System Extension",
+        ("Security", Some(4719 | 4902 | 4904..=4907 | 4912)) => "Audit Audit Policy Change",
+        ("Security", Some(4662)) => "Audit Directory Service Access",
+        ("Security", Some(5136 | 5137 | 5141)) => "Audit Directory Service Changes",
+        ("Security", Some(4673 | 4674)) => "Audit Sensitive Privilege Use",
+        (POWERSHELL_CHANNEL, Some(4104)) => "PowerShell Script Block Logging",
+        (POWERSHELL_CHANNEL | "Windows PowerShell", Some(4103 | 800)) => {
+            "PowerShell Module Logging"
+        }
+        (SYSMON_CHANNEL, _) if !channel_found => "Install Sysmon",
+        (SYSMON_CHANNEL, Some(id)) => return format!("Sysmon config: log event ID {id}"),
+        (_, _) if !channel_found => return format!("Enable or collect the {channel} log"),
+        (_, Some(id)) => return format!("{channel} {id} (no events found)"),
+        (_, None) => return format!("{channel} (no events found)"),
+    };
+    setting.to_string()
+}
+
+/// Returns the log sources of a rule from the `Channel` and `EventID` fields of its selections,
+/// or from the other `Channel` fields of the rule when the selections do not have one. Rules
+/// without any `Channel` (such as Sigma rules) fall back to the channels of their `logsource`.
+fn rule_sources(yaml: &Yaml) -> Vec<LogSource> {
+    let mut selections = vec![];
+    let mut command_line = false;
+    if let Some(detection) = yaml["detection"].as_hash() {
+        for (name, selection) in detection {
+            if !matches!(name.as_str(), Some("condition" | "timeframe")) {
+                collect_selection(selection, &mut selections, &mut command_line);
+            }
+        }
+    }
+    // EventIDs in a selection without a Channel (ex: `selection_eid: {EventID: 4624}` and
+    // `selection_channel: {Channel: Security}`) apply to the channel-only selections.
+    let loose_ids = selections
+        .iter()
+        .filter(|(channels, _)| channels.is_empty())
+        .flat_map(|(_, ids)| ids.iter().cloned())
+        .unique()
+        .collect_vec();
+    let mut sources = vec![];
+    for (channels, ids) in &selections {
+        if channels.is_empty() {
+            continue;
+        }
+        let ids = if ids.is_empty() { &loose_ids } else { ids };
+        for channel in channels {
+            sources.push(LogSource {
+                channel: channel.clone(),
+                event_ids: ids.clone(),
+                command_line: false,
+            });
+        }
+    }
+    if sources.is_empty() {
+        // The same Channel values as the channel filter, such as a Channel in a nested selection.
+        sources = filter::rule_channels(yaml)
+            .into_iter()
+            .map(|channel| LogSource {
+                channel: channel.into(),
+                event_ids: loose_ids.clone(),
+                command_line: false,
+            })
+            .collect();
+    }
+    if sources.is_empty() {
+        sources = logsource_sources(yaml);
+    }
+    for src in &mut sources {
+        src.command_line = command_line
+            && src.channel == "Security"
+            && src.event_ids.iter().any(|id| id == "4688");
+    }
+    sources.into_iter().unique().collect()
+}
+
+/// Adds the (channels, EventIDs) of a selection. A list of maps is OR'ed, so each map is added
+/// on its own.
+fn collect_selection(
+    selection: &Yaml,
+    selections: &mut Vec<(Vec<CompactString>, Vec<CompactString>)>,
+    command_line: &mut bool,
+) {
+    match selection {
+        Yaml::Hash(map) => {
+            let (mut channels, mut ids) = (vec![], vec![]);
+            for (key, value) in map {
+                let field = key.as_str().unwrap_or_default().split('|').next();
+                match field {
+                    Some("Channel") => channels.extend(yaml_values(value)),
+                    Some("EventID") => ids.extend(yaml_values(value)),
+                    Some("CommandLine") => *command_line = true,
+                    _ => {}
+                }
+            }
+            if !channels.is_empty() || !ids.is_empty() {
+                selections.push((channels, ids));
+            }
+        }
+        Yaml::Array(items) => {
+            for item in items {
+                collect_selection(item, selections, command_line);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn yaml_values(value: &Yaml) -> Vec<CompactString> {
+    match value {
+        Yaml::String(s) => vec![s.as_str().into()],
+        Yaml::Integer(i) => vec![i.to_string().into()],
+        Yaml::Array(items) => items.iter().flat_map(yaml_values).collect(),
+        _ => vec![],
+    }
+}
+
+/// Returns the log sources of the Sigma `logsource` of a rule, for rules without a `Channel`.
+fn logsource_sources(yaml: &Yaml) -> Vec<LogSource> {
+    let source = |channel: &str, ids: &[&str]| LogSource {
+        channel: channel.into(),
+        event_ids: ids.iter().map(|id| CompactString::from(*id)).collect(),
+        command_line: false,
+    };
+    let logsource = &yaml["logsource"];
+    let service = logsource["service"].as_str().unwrap_or_default();
+    let category = logsource["category"].as_str().unwrap_or_default();
+    match (service, category) {
+        ("security", _) => vec![source("Security", &[])],
+        ("system", _) => vec![source("System", &[])],
+        ("application", _) => vec![source("Application", &[])],
+        ("sysmon", _) => vec![source(SYSMON_CHANNEL, &[])],
+        ("powershell", _) => vec![source(POWERSHELL_CHANNEL, &[])],
+        ("powershell-classic", _) => vec![source("Windows PowerShell", &[])],
+        ("taskscheduler", _) => vec![source("Microsoft-Windows-TaskScheduler/Operational", &[])],
+        ("windefend", _) => vec![source(
+            "Microsoft-Windows-Windows Defender/Operational",
+            &[],
+        )],
+        ("wmi", _) => vec![source("Microsoft-Windows-WMI-Activity/Operational", &[])],
+        (_, "process_creation") => vec![
+            source(SYSMON_CHANNEL, &["1"]),
+            source("Security", &["4688"]),
+        ],
+        (_, "network_connection") => vec![source(SYSMON_CHANNEL, &["3"])],
+        (_, "process_termination") => vec![source(SYSMON_CHANNEL, &["5"])],
+        (_, "driver_load") => vec![source(SYSMON_CHANNEL, &["6"])],
+        (_, "image_load") => vec![source(SYSMON_CHANNEL, &["7"])],
+        (_, "create_remote_thread") => vec![source(SYSMON_CHANNEL, &["8"])],
+        (_, "raw_access_thread") => vec![source(SYSMON_CHANNEL, &["9"])],
+        (_, "process_access") => vec![source(SYSMON_CHANNEL, &["10"])],
+        (_, "file_event") => vec![source(SYSMON_CHANNEL, &["11"])],
+        (_, "registry_add" | "registry_delete") => vec![source(SYSMON_CHANNEL, &["12"])],
+        (_, "registry_set") => vec![source(SYSMON_CHANNEL, &["13"])],
+        (_, "registry_rename") => vec![source(SYSMON_CHANNEL, &["14"])],
+        (_, "registry_event") => vec![source(SYSMON_CHANNEL, &["12", "13", "14"])],
+        (_, "create_stream_hash") => vec![source(SYSMON_CHANNEL, &["15"])],
+        (_, "pipe_created") => vec![source(SYSMON_CHANNEL, &["17", "18"])],
+        (_, "wmi_event") => vec![source(SYSMON_CHANNEL, &["19", "20", "21"])],
+        (_, "dns_query") => vec![source(SYSMON_CHANNEL, &["22"])],
+        (_, "file_delete") => vec![source(SYSMON_CHANNEL, &["23", "26"])],
+        (_, "process_tampering") => vec![source(SYSMON_CHANNEL, &["25"])],
+        (_, "ps_module") => vec![source(POWERSHELL_CHANNEL, &["4103"])],
+        (_, "ps_script") => vec![source(POWERSHELL_CHANNEL, &["4104"])],
+        (_, "ps_classic_start") => vec![source("Windows PowerShell", &["400"])],
+        _ => vec![],
+    }
+}
+
+/// Returns the ATT&CK technique IDs in the `attack.tXXXX` tags of a rule.
+fn techniques(yaml: &Yaml) -> Vec<CompactString> {
+    yaml["tags"]
+        .as_vec()
+        .map(|tags| {
+            tags.iter()
+                .filter_map(|tag| tag.as_str()?.strip_prefix("attack.t"))
+                .filter(|id| id.starts_with(|c: char| c.is_ascii_digit()))
+                .map(|id| format!("T{id}").into())
+                .unique()
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use yaml_rust2::YamlLoader;
+
+    use super::{HostLogs, LogSource, RuleCoverage, rule_sources};
+    use crate::detections::rule::RuleNode;
+
+    fn rule(yaml: &str) -> RuleNode {
+        RuleNode::new(
+            "test.yml".to_string(),
+            YamlLoader::load_from_str(yaml).unwrap().remove(0),
+        )
+    }
+
+    #[test]
+    fn test_rule_sources() {
+        let node = rule(
+            r#"
+            title: Encoded PowerShell
+            detection:
+                process_creation:
+                    Channel: Security
+                    EventID: 4688
+                selection:
+                    CommandLine|contains: ' -enc '
+                condition: process_creation and selection
+            "#,
+        );
+        assert_eq!(
+            rule_sources(&node.yaml),
+            vec![LogSource {
+                channel: "Security".into(),
+                event_ids: vec!["4688".into()],
+                command_line: true,
+            }]
+        );
+
+        let node = rule(
+            r#"
+            title: Sigma logsource
+            logsource:
+                category: dns_query
+            detection:
+                selection:
+                    QueryName|endswith: '.onion'
+                condition: selection
+            "#,
+        );
+        assert_eq!(
+            rule_sources(&node.yaml),
+            vec![LogSource {
+                channel: "Microsoft-Windows-Sysmon/Operational".into(),
+                event_ids: vec!["22".into()],
+                command_line: false,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_coverage_report() {
+        let rules = [
+            rule(
+                r#"
+                title: Encoded PowerShell
+                tags:
+                    - attack.execution
+                    - attack.t1059.001
+                detection:
+                    selection:
+                        Channel: Security
+                        EventID: 4688
+                        CommandLine|contains: ' -enc '
+                    condition: selection
+                "#,
+            ),
+            rule(
+                r#"
+                title: Failed Logon
+                tags:
+                    - attack.t1110
+                detection:
+                    selection:
+                        Channel: Security
+                        EventID: 4625
+                    condition: selection
+                "#,
+            ),
+            rule(
+                r#"
+                title: Unknown Source
+                detection:
+                    selection:
+                        Foo: bar
+                    condition: selection
+                "#,
+            ),
+        ];
+        let mut coverage = RuleCoverage::from_rules(&rules);
+        assert_eq!(coverage.rules.len(), 2);
+        assert_eq!(coverage.undetermined, 1);
+
+        let mut host = HostLogs::default();
+        host.channels.insert("Security".into());
+        host.events.insert(("Security".into(), "4688".into()));
+        host.events.insert(("Security".into(), "4625".into()));
+        coverage.hosts.insert("PC01".into(), host);
+
+        let report = coverage.report();
+        let pc01 = &report[0];
+        assert_eq!(pc01.blind_rules.len(), 1);
+        assert_eq!(pc01.blind_rules[0].rule.title, "Encoded PowerShell");
+        assert_eq!(
+            pc01.blind_rules[0].missing,
+            vec!["Security 4688 (CommandLine)"]
+        );
+        assert_eq!(pc01.blind_techniques, vec!["T1059.001"]);
+        assert_eq!(
+            pc01.settings,
+            vec![(
+                "Include command line in process creation events".to_string(),
+                1
+            )]
+        );
+    }
+}
@@ -0,0 +1,373 @@
+use chrono::{DateTime, Duration, Utc};
+use compact_str::CompactString;
+use hashbrown::{HashMap, HashSet};
+use itertools::Itertools;
+
+use crate::detections::configs::{Action, BaselineMethod, StoredStatic};
+use crate::detections::detection::EvtxRecordInfo;
+use crate::detections::utils;
+use crate::results::parse_cluster_window;
+use crate::timeline::metrics::{get_event_value_as_string, get_record_timestamp};
+
+/// Default length of the time buckets that events are counted in.
+pub const DEFAULT_INTERVAL: &str = "1h";
+
+/// Default score above which a bucket is reported. 3.5 is the usual cut-off for modified z-scores.
+pub const DEFAULT_THRESHOLD: f64 = 3.5;
+
+/// Default minimum count of a spike bucket, and minimum baseline of a silent bucket.
+pub const DEFAULT_MIN_COUNT: u64 = 10;
+
+// Buckets needed before a baseline is trusted.
+const MIN_BASELINE_BUCKETS: usize = 6;
+// Weight of the newest bucket in the EWMA baseline.
+const EWMA_ALPHA: f64 = 0.3;
+// Converts the median absolute deviation and the mean absolute deviation into an estimate of the
+// standard deviation (Iglewicz and Hoaglin).
+const MAD_SCALE: f64 = 0.6745;
+const MEAN_AD_SCALE: f64 = 1.253314;
+
+/// Settings of the event-anomalies command.
+#[derive(Debug, Clone, Copy)]
+pub struct AnomalySettings {
+    pub interval: Duration,
+    pub baseline: BaselineMethod,
+    pub threshold: f64,
+    pub min_count: u64,
+}
+
+/// Returns the event-anomalies settings, or `None` when another command is running.
+pub fn anomaly_settings(stored_static: &StoredStatic) -> Option<AnomalySettings> {
+    match &stored_static.config.action {
+        Some(Action::EventAnomalies(opt)) => Some(AnomalySettings {
+            interval: opt
+                .interval
+                .as_deref()
+                .and_then(parse_cluster_window)
+                .or_else(|| parse_cluster_window(DEFAULT_INTERVAL))
+                .unwrap(),
+            baseline: opt.baseline,
+            threshold: opt.threshold.unwrap_or(DEFAULT_THRESHOLD),
+            min_count: opt.min_count.unwrap_or(DEFAULT_MIN_COUNT),
+        }),
+        _ => None,
+    }
+}
+
+/// Whether a bucket had far more or far fewer events than its baseline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnomalyKind {
+    Spike,
+    Silence,
+}
+
+impl AnomalyKind {
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            AnomalyKind::Spike => "Spike",
+            AnomalyKind::Silence => "Silence",
+        }
+    }
+}
+
+/// The series that events are counted in.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RateKey {
+    pub computer: CompactString,
+    pub channel: CompactString,
+    pub event_id: CompactString,
+}
+
+/// Consecutive anomalous buckets of one series. `end` is the end of the last bucket.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventAnomaly {
+    pub key: RateKey,
+    pub kind: AnomalyKind,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub count: u64,
+    pub expected: f64,
+    pub score: f64,
+}
+
+/// Event counts per (computer, channel, EventID) and time bucket. Buckets are numbered from the
+/// Unix epoch in units of the interval.
+#[derive(Debug, Clone, Default)]
+pub struct EventRates {
+    counts: HashMap<RateKey, HashMap<i64, u64>>,
+    // Buckets in which each computer logged any event. A series is only scored in these, so that
+    // a computer which was switched off does not make all of its series look silent.
+    active_buckets: HashMap<CompactString, HashSet<i64>>,
+}
+
+impl EventRates {
+    pub fn update(
+        &mut self,
+        records: &[EvtxRecordInfo],
+        stored_static: &StoredStatic,
+        interval: Duration,
+    ) {
+        let alias = &stored_static.eventkey_alias;
+        for record in records {
+            let rec = &record.record;
+            if utils::is_filtered_by_computer_name(
+                utils::get_event_value("Event.System.Computer", rec, alias),
+                (
+                    &stored_static.include_computer,
+                    &stored_static.exclude_computer,
+                ),
+            ) {
+                continue;
+            }
+            let Some(timestamp) = get_record_timestamp(rec, alias) else {
+                continue;
+            };
+            let key = RateKey {
+                computer: get_event_value_as_string("Computer", rec, alias),
+                channel: get_event_value_as_string("Channel", rec, alias),
+                event_id: get_event_value_as_string("EventID", rec, alias),
+            };
+            self.add(key, bucket_of(timestamp, interval), 1);
+        }
+    }
+
+    fn add(&mut self, key: RateKey, bucket: i64, count: u64) {
+        self.active_buckets
+            .entry(key.computer.clone())
+            .or_default()
+            .insert(bucket);
+        *self
+            .counts
+            .entry(key)
+            .or_default()
+            .entry(bucket)
+            .or_default() += count;
+    }
+
+    /// Scores every bucket of every series against its baseline and returns the spikes and
+    /// silences, merging consecutive buckets of the same kind. Sorted by start time.
+    pub fn anomalies(&self, settings: &AnomalySettings) -> Vec<EventAnomaly> {
+        let active_buckets: HashMap<&CompactString, Vec<i64>> = self
+            .active_buckets
+            .iter()
+            .map(|(computer, buckets)| (computer, buckets.iter().copied().sorted().collect()))
+            .collect();
+        let interval = settings.interval.num_seconds();
+        let bucket_time =
+            |bucket: i64| DateTime::from_timestamp(bucket * interval, 0).unwrap_or_default();
+        let mut anomalies = vec![];
+        for (key, counts) in &self.counts {
+            let (Some(active), Some(first)) =
+                (active_buckets.get(&key.computer), counts.keys().min())
+            else {
+                continue;
+            };
+            // From the first bucket of the series to the last bucket of the computer, so that a
+            // channel which stops logging is still scored.
+            let buckets = &active[active.partition_point(|bucket| bucket < first)..];
+            let series = buckets
+                .iter()
+                .map(|bucket| counts.get(bucket).copied().unwrap_or_default())
+                .collect_vec();
+            let scores = match settings.baseline {
+                BaselineMethod::Mad => mad_scores(&series),
+                BaselineMethod::Ewma => ewma_scores(&series),
+            };
+            let mut current: Option<(i64, EventAnomaly)> = None;
+            for ((bucket, count), score) in buckets.iter().zip(series).zip(scores) {
+                let kind = score.and_then(|(expected, score)| {
+                    if score >= settings.threshold && count >= settings.min_count {
+                        Some((AnomalyKind::Spike, expected, score))
+                    } else if score <= -settings.threshold && expected >= settings.min_count as f64
+                    {
+                        Some((AnomalyKind::Silence, expected, score))
+                    } else {
+                        None
+                    }
+                });
+                if let (Some((last, anomaly)), Some((kind, expected, score))) = (&mut current, kind)
+                    && *last + 1 == *bucket
+                    && anomaly.kind == kind
+                {
+                    *last = *bucket;
+                    anomaly.end = bucket_time(bucket + 1);
+                    anomaly.count += count;
+                    anomaly.expected += expected;
+                    if score.abs() > anomaly.score.abs() {
+                        anomaly.score = score;
+                    }
+                } else {
+                    anomalies.extend(current.take().map(|(_, anomaly)| anomaly));
+                    current = kind.map(|(kind, expected, score)| {
+                        (
+                            *bucket,
+                            EventAnomaly {
+                                key: key.clone(),
+                                kind,
+                                start: bucket_time(*bucket),
+                                end: bucket_time(bucket + 1),
+                                count,
+                                expected,
+                                score,
+                            },
+                        )
+                    });
+                }
+            }
+            anomalies.extend(current.map(|(_, anomaly)| anomaly));
+        }
+        anomalies.sort_by(|a, b| a.start.cmp(&b.start).then_with(|| a.key.cmp(&b.key)));
+        anomalies
+    }
+}
+
+fn bucket_of(timestamp: DateTime<Utc>, interval: Duration) -> i64 {
+    timestamp
+        .timestamp()
+        .div_euclid(interval.num_seconds().max(1))
+}
+
+fn median(values: &[f64]) -> f64 {
+    let sorted = values
+        .iter()
+        .copied()
+        .sorted_by(f64::total_cmp)
+        .collect_vec();
+    let mid = sorted.len() / 2;
+    if sorted.len() % 2 == 0 {
+        (sorted[mid - 1] + sorted[mid]) / 2.0
+    } else {
+        sorted[mid]
+    }
+}
+
+/// Modified z-scores against the median of the whole series. Returns the baseline and score of
+/// each bucket, or `None` when the series is too short or flat to have a baseline.
+fn mad_scores(series: &[u64]) -> Vec<Option<(f64, f64)>> {
+    if series.len() < MIN_BASELINE_BUCKETS {
+        return vec![None; series.len()];
+    }
+    let values = series.iter().map(|count| *count as f64).collect_vec();
+    let median_count = median(&values);
+    let deviations = values
+        .iter()
+        .map(|value| (value - median_count).abs())
+        .collect_vec();
+    let mad = median(&deviations);
+    // The MAD is 0 when more than half of the buckets have the same count, which is common for
+    // rare events, so fall back to the mean absolute deviation.
+    let scale = if mad > 0.0 {
+        mad / MAD_SCALE
+    } else {
+        MEAN_AD_SCALE * deviations.iter().sum::<f64>() / deviations.len() as f64
+    };
+    if scale == 0.0 {
+        return vec![None; series.len()];
+    }
+    values
+        .iter()
+        .map(|value| Some((median_count, (value - median_count) / scale)))
+        .collect()
+}
+
+/// Scores against an exponentially weighted moving average and variance of the previous
+/// buckets, so that the baseline follows slow changes in volume.
+fn ewma_scores(series: &[u64]) -> Vec<Option<(f64, f64)>> {
+    let Some(first) = series.first() else {
+        return vec![];
+    };
+    let mut mean = *first as f64;
+    let mut variance = 0.0;
+    series
+        .iter()
+        .enumerate()
+        .map(|(i, count)| {
+            let count = *count as f64;
+            let scored = (i >= MIN_BASELINE_BUCKETS).then(|| {
+                // Event counts are roughly Poisson distributed, so the deviation is never taken
+                // to be less than the square root of the mean.
+                let deviation = variance.sqrt().max(mean.sqrt()).max(1.0);
+                (mean, (count - mean) / deviation)
+            });
+            let diff = count - mean;
+            mean += EWMA_ALPHA * diff;
+            variance = (1.0 - EWMA_ALPHA) * (variance + EWMA_ALPHA * diff * diff);
+            scored
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone, Utc};
+    use compact_str::CompactString;
+
+    use super::{AnomalyKind, AnomalySettings, EventRates, RateKey};
+    use crate::detections::configs::BaselineMethod;
+
+    fn key(channel: &str, event_id: &str) -> RateKey {
+        RateKey {
+            computer: CompactString::from("PC01"),
+            channel: CompactString::from(channel),
+            event_id: CompactString::from(event_id),
+        }
+    }
+
+    // 48 hours of steady logons, a burst of failed logons in hour 30 and a Sysmon channel that
+    // stops logging after hour 40.
+    fn sample_rates() -> EventRates {
+        let mut rates = EventRates::default();
+        for hour in 0..48 {
+            rates.add(key("Security", "4624"), hour, 9 + hour as u64 % 3);
+            if hour <= 40 {
+                rates.add(key("Microsoft-Windows-Sysmon/Operational", "1"), hour, 50);
+            }
+        }
+        rates.add(key("Security", "4625"), 0, 1);
+        rates.add(key("Security", "4625"), 30, 200);
+        rates
+    }
+
+    fn settings(baseline: BaselineMethod) -> AnomalySettings {
+        AnomalySettings {
+            interval: Duration::hours(1),
+            baseline,
+            threshold: 3.5,
+            min_count: 10,
+        }
+    }
+
+    #[test]
+    fn test_mad_spike_and_silence() {
+        let anomalies = sample_rates().anomalies(&settings(BaselineMethod::Mad));
+        assert_eq!(anomalies.len(), 2);
+        assert_eq!(anomalies[0].key, key("Security", "4625"));
+        assert_eq!(anomalies[0].kind, AnomalyKind::Spike);
+        assert_eq!(anomalies[0].start, Utc.timestamp_opt(30 * 3600, 0).unwrap());
+        assert_eq!(anomalies[0].count, 200);
+        assert_eq!(
+            anomalies[1].key,
+            key("Microsoft-Windows-Sysmon/Operational", "1")
+        );
+        assert_eq!(anomalies[1].kind, AnomalyKind::Silence);
+        assert_eq!(anomalies[1].start, Utc.timestamp_opt(41 * 3600, 0).unwrap());
+        assert_eq!(anomalies[1].end, Utc.timestamp_opt(48 * 3600, 0).unwrap());
+        assert_eq!(anomalies[1].count, 0);
+        assert_eq!(anomalies[1].expected, 350.0);
+    }
+
+    #[test]
+    fn test_ewma_spike_and_silence() {
+        let anomalies = sample_rates().anomalies(&settings(BaselineMethod::Ewma));
+        assert_eq!(
+            anomalies
+                .iter()
+                .map(|anomaly| (anomaly.key.event_id.as_str(), anomaly.kind))
+                .collect::<Vec<_>>(),
+            vec![("4625", AnomalyKind::Spike), ("1", AnomalyKind::Silence)]
+        );
+        // The EWMA baseline widens as soon as the channel goes quiet, so only the start is reported.
+        assert_eq!(anomalies[1].start, Utc.timestamp_opt(41 * 3600, 0).unwrap());
+        assert!(anomalies[1].end < Utc.timestamp_opt(48 * 3600, 0).unwrap());
+    }
+}
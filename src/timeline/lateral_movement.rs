@@ -0,0 +1,511 @@
+use crate::detections::configs::GraphFormat;
+use crate::timeline::metrics::{LoginEvent, LogonStats};
+use chrono::{DateTime, SecondsFormat, Utc};
+use compact_str::CompactString;
+use hashbrown::HashMap;
+use itertools::Itertools;
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::net::IpAddr;
+use std::path::Path;
+
+/// Node type of the lateral movement graph.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum NodeKind {
+    Host,
+    User,
+}
+
+impl NodeKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            NodeKind::Host => "host",
+            NodeKind::User => "user",
+        }
+    }
+}
+
+/// Edge type of the lateral movement graph. `Logon` edges point from the source host to the
+/// host that was logged on to, and `Account` edges point from the account to that host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum EdgeKind {
+    Logon,
+    Account,
+}
+
+impl EdgeKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            EdgeKind::Logon => "logon",
+            EdgeKind::Account => "account",
+        }
+    }
+}
+
+/// Aggregated logons between two nodes by a single account.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GraphEdge {
+    pub user: CompactString,
+    pub successful: usize,
+    pub failed: usize,
+    pub first_seen: Option<DateTime<Utc>>,
+    pub last_seen: Option<DateTime<Utc>>,
+    pub logon_types: BTreeSet<CompactString>,
+    pub events: BTreeSet<CompactString>,
+    pub source_ips: BTreeSet<CompactString>,
+}
+
+impl GraphEdge {
+    pub fn weight(&self) -> usize {
+        self.successful + self.failed
+    }
+
+    fn merge(&mut self, login_event: &LoginEvent, stats: &LogonStats) {
+        self.successful += stats.counts[0];
+        self.failed += stats.counts[1];
+        for ts in stats.first.iter().flatten() {
+            if self.first_seen.is_none_or(|first| *ts < first) {
+                self.first_seen = Some(*ts);
+            }
+        }
+        for ts in stats.last.iter().flatten() {
+            if self.last_seen.is_none_or(|last| *ts > last) {
+                self.last_seen = Some(*ts);
+            }
+        }
+        if is_known_value(&login_event.logontype) {
+            self.logon_types.insert(login_event.logontype.clone());
+        }
+        self.events.insert(login_event.channel.clone());
+        if let Some(ip) = normalize_host(&login_event.source_ip)
+            && ip.parse::<IpAddr>().is_ok()
+        {
+            self.source_ips.insert(ip);
+        }
+    }
+}
+
+/// Directed host/user graph built from the logon-summary aggregates. BTreeMaps are used so that
+/// the exported files are identical between runs over the same logs.
+#[derive(Debug, Clone, Default)]
+pub struct LateralMovementGraph {
+    pub nodes: BTreeSet<(NodeKind, CompactString)>,
+    pub edges: BTreeMap<(EdgeKind, CompactString, CompactString, CompactString), GraphEdge>,
+}
+
+impl LateralMovementGraph {
+    /// Builds the graph from `EventMetrics::stats_login_list`. Logons without a usable source
+    /// (no workstation name and no remote IP address) and logons from a host to itself are local
+    /// activity rather than movement between hosts, so they are left out.
+    pub fn new(stats_login_list: &HashMap<LoginEvent, LogonStats>) -> Self {
+        let mut graph = LateralMovementGraph::default();
+        for (login_event, stats) in stats_login_list.iter() {
+            let Some(dst_host) = normalize_host(&login_event.hostname) else {
+                continue;
+            };
+            let Some(src_host) = normalize_host(&login_event.source_computer)
+                .or_else(|| normalize_host(&login_event.source_ip))
+            else {
+                continue;
+            };
+            if src_host == dst_host {
+                continue;
+            }
+            let user = account_name(&login_event.dst_domain, &login_event.dst_user);
+            graph.nodes.insert((NodeKind::Host, src_host.clone()));
+            graph.nodes.insert((NodeKind::Host, dst_host.clone()));
+            graph
+                .edges
+                .entry((
+                    EdgeKind::Logon,
+                    node_id(NodeKind::Host, &src_host),
+                    node_id(NodeKind::Host, &dst_host),
+                    user.clone(),
+                ))
+                .or_insert_with(|| GraphEdge {
+                    user: user.clone(),
+                    ..Default::default()
+                })
+                .merge(login_event, stats);
+            if is_known_value(&login_event.dst_user) {
+                graph.nodes.insert((NodeKind::User, user.clone()));
+                graph
+                    .edges
+                    .entry((
+                        EdgeKind::Account,
+                        node_id(NodeKind::User, &user),
+                        node_id(NodeKind::Host, &dst_host),
+                        user.clone(),
+                    ))
+                    .or_insert_with(|| GraphEdge {
+                        user: user.clone(),
+                        ..Default::default()
+                    })
+                    .merge(login_event, stats);
+            }
+        }
+        graph
+    }
+
+    /// Writes the graph to `path` in the requested format.
+    pub fn output(&self, path: &Path, format: GraphFormat) -> io::Result<()> {
+        let mut wtr = BufWriter::new(File::create(path)?);
+        let contents = match format {
+            GraphFormat::Graphml => self.to_graphml(),
+            GraphFormat::Dot => self.to_dot(),
+            GraphFormat::Json => self.to_json(),
+        };
+        wtr.write_all(contents.as_bytes())?;
+        wtr.flush()
+    }
+
+    /// GraphML output for Gephi, yEd and the Neo4j APOC importer.
+    pub fn to_graphml(&self) -> String {
+        let mut out = String::new();
+        out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        out.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+        for (id, target, attr_type) in [
+            ("label", "node", "string"),
+            ("kind", "node", "string"),
+            ("kind", "edge", "string"),
+            ("user", "edge", "string"),
+            ("weight", "edge", "int"),
+            ("successful", "edge", "int"),
+            ("failed", "edge", "int"),
+            ("first_seen", "edge", "string"),
+            ("last_seen", "edge", "string"),
+            ("logon_types", "edge", "string"),
+            ("events", "edge", "string"),
+            ("source_ips", "edge", "string"),
+        ] {
+            out.push_str(&format!(
+                "  <key id=\"{target}_{id}\" for=\"{target}\" attr.name=\"{id}\" attr.type=\"{attr_type}\"/>\n"
+            ));
+        }
+        out.push_str("  <graph id=\"lateral_movement\" edgedefault=\"directed\">\n");
+        for (kind, name) in self.nodes.iter() {
+            out.push_str(&format!(
+                "    <node id=\"{}\">\n      <data key=\"node_label\">{}</data>\n      <data key=\"node_kind\">{}</data>\n    </node>\n",
+                xml_escape(&node_id(*kind, name)),
+                xml_escape(name),
+                kind.as_str()
+            ));
+        }
+        for (i, ((kind, source, target, _), edge)) in self.edges.iter().enumerate() {
+            out.push_str(&format!(
+                "    <edge id=\"e{i}\" source=\"{}\" target=\"{}\">\n",
+                xml_escape(source),
+                xml_escape(target)
+            ));
+            for (key, value) in [
+                ("kind", kind.as_str().to_string()),
+                ("user", edge.user.to_string()),
+                ("weight", edge.weight().to_string()),
+                ("successful", edge.successful.to_string()),
+                ("failed", edge.failed.to_string()),
+                ("first_seen", format_graph_time(edge.first_seen)),
+                ("last_seen", format_graph_time(edge.last_seen)),
+                ("logon_types", edge.logon_types.iter().join(", ")),
+                ("events", edge.events.iter().join(", ")),
+                ("source_ips", edge.source_ips.iter().join(", ")),
+            ] {
+                out.push_str(&format!(
+                    "      <data key=\"edge_{key}\">{}</data>\n",
+                    xml_escape(&value)
+                ));
+            }
+            out.push_str("    </edge>\n");
+        }
+        out.push_str("  </graph>\n</graphml>\n");
+        out
+    }
+
+    /// Graphviz DOT output. Hosts are drawn as boxes and accounts as ellipses.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph lateral_movement {\n  rankdir=LR;\n");
+        for (kind, name) in self.nodes.iter() {
+            let shape = match kind {
+                NodeKind::Host => "box",
+                NodeKind::User => "ellipse",
+            };
+            out.push_str(&format!(
+                "  \"{}\" [label=\"{}\", kind=\"{}\", shape={shape}];\n",
+                dot_escape(&node_id(*kind, name)),
+                dot_escape(name),
+                kind.as_str()
+            ));
+        }
+        for ((kind, source, target, _), edge) in self.edges.iter() {
+            out.push_str(&format!(
+                "  \"{}\" -> \"{}\" [label=\"{} ({})\", kind=\"{}\", weight={}, successful={}, failed={}, first_seen=\"{}\", last_seen=\"{}\", logon_types=\"{}\", events=\"{}\", source_ips=\"{}\"{}];\n",
+                dot_escape(source),
+                dot_escape(target),
+                dot_escape(&edge.user),
+                edge.weight(),
+                kind.as_str(),
+                edge.weight(),
+                edge.successful,
+                edge.failed,
+                format_graph_time(edge.first_seen),
+                format_graph_time(edge.last_seen),
+                dot_escape(&edge.logon_types.iter().join(", ")),
+                dot_escape(&edge.events.iter().join(", ")),
+                dot_escape(&edge.source_ips.iter().join(", ")),
+                if *kind == EdgeKind::Account {
+                    ", style=dashed"
+                } else {
+                    ""
+                }
+            ));
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// JSON output with separate `nodes` and `edges` arrays, which maps directly onto a Neo4j
+    /// `UNWIND` import.
+    pub fn to_json(&self) -> String {
+        let nodes: Vec<_> = self
+            .nodes
+            .iter()
+            .map(|(kind, name)| {
+                json!({
+                    "id": node_id(*kind, name).as_str(),
+                    "label": name.as_str(),
+                    "kind": kind.as_str(),
+                })
+            })
+            .collect();
+        let edges: Vec<_> = self
+            .edges
+            .iter()
+            .map(|((kind, source, target, _), edge)| {
+                json!({
+                    "source": source.as_str(),
+                    "target": target.as_str(),
+                    "kind": kind.as_str(),
+                    "user": edge.user.as_str(),
+                    "weight": edge.weight(),
+                    "successful": edge.successful,
+                    "failed": edge.failed,
+                    "first_seen": format_graph_time(edge.first_seen),
+                    "last_seen": format_graph_time(edge.last_seen),
+                    "logon_types": edge.logon_types.iter().map(|s| s.as_str()).collect::<Vec<_>>(),
+                    "events": edge.events.iter().map(|s| s.as_str()).collect::<Vec<_>>(),
+                    "source_ips": edge.source_ips.iter().map(|s| s.as_str()).collect::<Vec<_>>(),
+                })
+            })
+            .collect();
+        let mut out =
+            serde_json::to_string_pretty(&json!({ "nodes": nodes, "edges": edges })).unwrap();
+        out.push('\n');
+        out
+    }
+}
+
+/// Returns false for the "-" placeholder that metrics.rs uses for missing fields.
+fn is_known_value(value: &str) -> bool {
+    let value = value.trim();
+    !value.is_empty() && value != "-"
+}
+
+/// Normalizes a computer name or IP address into a node name. Computer names are compared
+/// case-insensitively and without their DNS suffix, since the Security log records the target as
+/// an FQDN but the source workstation as a NetBIOS name. Loopback and unspecified addresses do not
+/// identify a remote host and return None.
+pub(super) fn normalize_host(name: &str) -> Option<CompactString> {
+    let name = name.trim();
+    if !is_known_value(name) {
+        return None;
+    }
+    if let Ok(ip) = name.parse::<IpAddr>() {
+        if ip.is_loopback() || ip.is_unspecified() {
+            return None;
+        }
+        return Some(ip.to_string().into());
+    }
+    let short_name = name.split('.').next().unwrap_or(name);
+    if short_name.is_empty() {
+        return None;
+    }
+    Some(short_name.to_uppercase().into())
+}
+
+/// Returns "DOMAIN\user", or just the user name when the domain is unknown.
+fn account_name(domain: &str, user: &str) -> CompactString {
+    if is_known_value(domain) {
+        CompactString::from(format!("{domain}\\{user}"))
+    } else {
+        CompactString::from(user)
+    }
+}
+
+fn node_id(kind: NodeKind, name: &str) -> CompactString {
+    CompactString::from(format!("{}:{name}", kind.as_str()))
+}
+
+fn format_graph_time(ts: Option<DateTime<Utc>>) -> String {
+    ts.map(|ts| ts.to_rfc3339_opts(SecondsFormat::Millis, true))
+        .unwrap_or_default()
+}
+
+fn xml_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+fn dot_escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EdgeKind, LateralMovementGraph, NodeKind, normalize_host};
+    use crate::timeline::metrics::{LoginEvent, LogonStats};
+    use chrono::{DateTime, Utc};
+    use compact_str::CompactString;
+    use hashbrown::HashMap;
+
+    fn login_event(hostname: &str, source_computer: &str, source_ip: &str) -> LoginEvent {
+        LoginEvent {
+            channel: "Sec 4624".into(),
+            dst_user: "admin".into(),
+            dst_domain: "CORP".into(),
+            hostname: hostname.into(),
+            logontype: "3 - Network".into(),
+            src_user: "-".into(),
+            src_domain: "-".into(),
+            source_computer: source_computer.into(),
+            source_ip: source_ip.into(),
+        }
+    }
+
+    fn stats(successful: usize, failed: usize, first: &str, last: &str) -> LogonStats {
+        let first = Some(first.parse::<DateTime<Utc>>().unwrap());
+        let last = Some(last.parse::<DateTime<Utc>>().unwrap());
+        LogonStats {
+            counts: [successful, failed],
+            first: [first, None],
+            last: [last, None],
+        }
+    }
+
+    fn create_graph() -> LateralMovementGraph {
+        let mut stats_login_list = HashMap::new();
+        // Workstation name and FQDN of the same host pair are merged into one edge.
+        stats_login_list.insert(
+            login_event("FS01.corp.local", "WS01", "10.0.0.5"),
+            stats(2, 0, "2024-01-01T00:00:00Z", "2024-01-01T01:00:00Z"),
+        );
+        stats_login_list.insert(
+            login_event("fs01", "ws01.corp.local", "10.0.0.5"),
+            stats(1, 3, "2023-12-31T00:00:00Z", "2024-01-02T00:00:00Z"),
+        );
+        // No workstation name: the source IP becomes the node.
+        stats_login_list.insert(
+            login_event("DC01.corp.local", "-", "10.0.0.9"),
+            stats(1, 0, "2024-01-03T00:00:00Z", "2024-01-03T00:00:00Z"),
+        );
+        // Local logons are not lateral movement.
+        stats_login_list.insert(
+            login_event("DC01.corp.local", "DC01", "127.0.0.1"),
+            stats(5, 0, "2024-01-03T00:00:00Z", "2024-01-03T00:00:00Z"),
+        );
+        stats_login_list.insert(
+            login_event("DC01.corp.local", "-", "::1"),
+            stats(5, 0, "2024-01-03T00:00:00Z", "2024-01-03T00:00:00Z"),
+        );
+        LateralMovementGraph::new(&stats_login_list)
+    }
+
+    #[test]
+    fn test_normalize_host() {
+        assert_eq!(normalize_host("ws01.corp.local"), Some("WS01".into()));
+        assert_eq!(normalize_host("10.0.0.5"), Some("10.0.0.5".into()));
+        assert_eq!(normalize_host("-"), None);
+        assert_eq!(normalize_host(""), None);
+        assert_eq!(normalize_host("127.0.0.1"), None);
+        assert_eq!(normalize_host("::1"), None);
+    }
+
+    #[test]
+    fn test_build_graph() {
+        let graph = create_graph();
+        let nodes: Vec<_> = graph.nodes.iter().cloned().collect();
+        assert_eq!(
+            nodes,
+            vec![
+                (NodeKind::Host, CompactString::from("10.0.0.9")),
+                (NodeKind::Host, CompactString::from("DC01")),
+                (NodeKind::Host, CompactString::from("FS01")),
+                (NodeKind::Host, CompactString::from("WS01")),
+                (NodeKind::User, CompactString::from("CORP\\admin")),
+            ]
+        );
+        let edge = graph
+            .edges
+            .get(&(
+                EdgeKind::Logon,
+                "host:WS01".into(),
+                "host:FS01".into(),
+                "CORP\\admin".into(),
+            ))
+            .unwrap();
+        assert_eq!(edge.successful, 3);
+        assert_eq!(edge.failed, 3);
+        assert_eq!(edge.weight(), 6);
+        assert_eq!(
+            edge.first_seen,
+            Some("2023-12-31T00:00:00Z".parse::<DateTime<Utc>>().unwrap())
+        );
+        assert_eq!(
+            edge.last_seen,
+            Some("2024-01-02T00:00:00Z".parse::<DateTime<Utc>>().unwrap())
+        );
+        assert!(edge.logon_types.contains("3 - Network"));
+        assert!(edge.source_ips.contains("10.0.0.5"));
+        // 2 logon edges + 2 account edges (CORP\admin -> FS01, CORP\admin -> DC01).
+        assert_eq!(graph.edges.len(), 4);
+    }
+
+    #[test]
+    fn test_graph_outputs() {
+        let graph = create_graph();
+        let graphml = graph.to_graphml();
+        assert!(graphml.contains("<graph id=\"lateral_movement\" edgedefault=\"directed\">"));
+        assert!(graphml.contains("<node id=\"host:WS01\">"));
+        assert!(graphml.contains("source=\"host:WS01\" target=\"host:FS01\""));
+        assert!(graphml.contains("<data key=\"edge_weight\">6</data>"));
+        assert!(graphml.contains("<data key=\"edge_first_seen\">2023-12-31T00:00:00.000Z</data>"));
+
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("digraph lateral_movement {"));
+        assert!(dot.contains("\"host:WS01\" -> \"host:FS01\" [label=\"CORP\\\\admin (6)\""));
+        assert!(dot.contains("\"user:CORP\\\\admin\" -> \"host:DC01\""));
+
+        let json: serde_json::Value = serde_json::from_str(&graph.to_json()).unwrap();
+        assert_eq!(json["nodes"].as_array().unwrap().len(), 5);
+        let edges = json["edges"].as_array().unwrap();
+        assert_eq!(edges.len(), 4);
+        let edge = edges
+            .iter()
+            .find(|e| e["source"] == "host:WS01" && e["kind"] == "logon")
+            .unwrap();
+        assert_eq!(edge["target"], "host:FS01");
+        assert_eq!(edge["successful"], 3);
+        assert_eq!(edge["failed"], 3);
+        assert_eq!(edge["last_seen"], "2024-01-02T00:00:00.000Z");
+    }
+}
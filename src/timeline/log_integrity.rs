@@ -0,0 +1,501 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Duration, Utc};
+use hashbrown::HashMap;
+use itertools::Itertools;
+
+use crate::detections::configs::{Action, StoredStatic};
+use crate::detections::detection::EvtxRecordInfo;
+use crate::timeline::log_metrics::LogMetrics;
+use crate::timeline::metrics::{get_event_value_as_string, get_record_timestamp};
+
+/// Default number of hours without events that is reported as a time gap.
+pub const DEFAULT_GAP_HOURS: u64 = 24;
+
+/// How far a 6006/6008 shutdown event may be from a time gap and still explain it.
+const SHUTDOWN_SLACK_MINUTES: i64 = 10;
+
+const EVTX_FILE_SIGNATURE: &[u8] = b"ElfFile\0";
+const EVTX_CHUNK_SIGNATURE: &[u8] = b"ElfChnk\0";
+const EVTX_HEADER_BLOCK_SIZE: u64 = 4096;
+const EVTX_CHUNK_SIZE: u64 = 65536;
+// Set while the log is open and the header may not have been written back yet.
+const EVTX_FLAG_DIRTY: u32 = 0x1;
+
+/// Integrity verdict of one log file, ordered from the least to the most serious.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum IntegrityVerdict {
+    #[default]
+    Ok,
+    Suspicious,
+    Tampered,
+}
+
+impl IntegrityVerdict {
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            IntegrityVerdict::Ok => "OK",
+            IntegrityVerdict::Suspicious => "Suspicious",
+            IntegrityVerdict::Tampered => "Tampered",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvidenceKind {
+    RecordIdGap,
+    RecordIdOutOfOrder,
+    TimeGap,
+    LogCleared,
+    AuditPolicyChanged,
+    ChunkCountMismatch,
+}
+
+impl EvidenceKind {
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            EvidenceKind::RecordIdGap => "Record ID Gap",
+            EvidenceKind::RecordIdOutOfOrder => "Record ID Out Of Order",
+            EvidenceKind::TimeGap => "Time Gap",
+            EvidenceKind::LogCleared => "Log Cleared",
+            EvidenceKind::AuditPolicyChanged => "Audit Policy Changed",
+            EvidenceKind::ChunkCountMismatch => "Chunk Count Mismatch",
+        }
+    }
+}
+
+/// One finding behind a file's verdict.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntegrityEvidence {
+    pub kind: EvidenceKind,
+    pub verdict: IntegrityVerdict,
+    pub timestamp: Option<DateTime<Utc>>,
+    pub details: String,
+}
+
+/// Records with consecutive EventRecordIDs, in the order they were read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RecordRun {
+    first_id: u64,
+    last_id: u64,
+    first_time: Option<DateTime<Utc>>,
+    last_time: Option<DateTime<Utc>>,
+}
+
+/// Integrity information collected from one log file for `log-metrics --integrity`.
+///
+/// Chunks are read in file order, so a log that has wrapped around starts with its newest
+/// records. Record IDs are therefore kept as runs of consecutive IDs and only compared after
+/// sorting the runs, which leaves a normal wrap-around as two runs without a gap between them.
+#[derive(Debug, Clone, Default)]
+pub struct LogIntegrity {
+    runs: Vec<RecordRun>,
+    // Time gaps inside a run. Gaps between runs are found once the runs are sorted.
+    time_gaps: Vec<(DateTime<Utc>, DateTime<Utc>)>,
+    // Times of System 6006 (clean shutdown) and 6008 (unexpected shutdown) events.
+    shutdowns: Vec<DateTime<Utc>>,
+    evidence: Vec<IntegrityEvidence>,
+}
+
+/// Returns the time gap threshold when `log-metrics --integrity` was specified.
+pub fn integrity_gap_threshold(stored_static: &StoredStatic) -> Option<Duration> {
+    match &stored_static.config.action {
+        Some(Action::LogMetrics(opt)) if opt.integrity => Some(Duration::hours(
+            opt.gap_hours
+                .unwrap_or(DEFAULT_GAP_HOURS)
+                .clamp(1, 1_000_000) as i64,
+        )),
+        _ => None,
+    }
+}
+
+/// Path of the evidence CSV saved next to the `log-metrics` output (ex: metrics-integrity.csv).
+pub fn integrity_output_path(output: &Path) -> PathBuf {
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    output.with_file_name(format!("{stem}-integrity.csv"))
+}
+
+impl LogIntegrity {
+    pub fn update(
+        &mut self,
+        record: &EvtxRecordInfo,
+        stored_static: &StoredStatic,
+        gap_threshold: Duration,
+    ) {
+        let rec = &record.record;
+        let alias = &stored_static.eventkey_alias;
+        let channel = rec["Event"]["System"]["Channel"]
+            .as_str()
+            .unwrap_or_default();
+        let event_id = &rec["Event"]["System"]["EventID"];
+        let event_id = event_id
+            .as_i64()
+            .or_else(|| event_id.as_str().and_then(|id| id.parse().ok()));
+        let timestamp = get_record_timestamp(rec, alias);
+        match (channel, event_id) {
+            ("Security", Some(1102)) | ("System", Some(104)) => {
+                let user = get_event_value_as_string(
+                    "Event.UserData.LogFileCleared.SubjectUserName",
+                    rec,
+                    alias,
+                );
+                let domain = get_event_value_as_string(
+                    "Event.UserData.LogFileCleared.SubjectDomainName",
+                    rec,
+                    alias,
+                );
+                // 104 names the cleared log, 1102 is always written to the cleared Security log.
+                let cleared = match get_event_value_as_string(
+                    "Event.UserData.LogFileCleared.Channel",
+                    rec,
+                    alias,
+                ) {
+                    cleared if cleared != "-" => cleared,
+                    _ => channel.into(),
+                };
+                self.evidence.push(IntegrityEvidence {
+                    kind: EvidenceKind::LogCleared,
+                    verdict: IntegrityVerdict::Tampered,
+                    timestamp,
+                    details: format!("{cleared} log cleared by {domain}\\{user}"),
+                });
+            }
+            ("Security", Some(4719)) => {
+                let user = get_event_value_as_string("SubjectUserName", rec, alias);
+                let subcategory = get_event_value_as_string("SubcategoryGuid", rec, alias);
+                let changes = get_event_value_as_string("AuditPolicyChanges", rec, alias);
+                self.evidence.push(IntegrityEvidence {
+                    kind: EvidenceKind::AuditPolicyChanged,
+                    verdict: IntegrityVerdict::Suspicious,
+                    timestamp,
+                    details: format!("Subcategory {subcategory} changed ({changes}) by {user}"),
+                });
+            }
+            ("System", Some(6006 | 6008)) => self.shutdowns.extend(timestamp),
+            _ => {}
+        }
+
+        // Record IDs only run in sequence inside one .evtx log. Forwarded events keep the IDs of
+        // their source computers, and carved records come from anywhere in the file.
+        if stored_static.json_input_flag
+            || record.recovered_record
+            || channel.eq_ignore_ascii_case("ForwardedEvents")
+        {
+            return;
+        }
+        let record_id = &rec["Event"]["System"]["EventRecordID"];
+        let Some(record_id) = record_id
+            .as_u64()
+            .or_else(|| record_id.as_str().and_then(|id| id.parse().ok()))
+        else {
+            return;
+        };
+        match self.runs.last_mut() {
+            Some(run) if run.last_id.checked_add(1) == Some(record_id) => {
+                if let (Some(prev), Some(time)) = (run.last_time, timestamp)
+                    && time - prev > gap_threshold
+                {
+                    self.time_gaps.push((prev, time));
+                }
+                run.last_id = record_id;
+                if timestamp.is_some() {
+                    run.last_time = timestamp;
+                }
+                if run.first_time.is_none() {
+                    run.first_time = timestamp;
+                }
+            }
+            _ => self.runs.push(RecordRun {
+                first_id: record_id,
+                last_id: record_id,
+                first_time: timestamp,
+                last_time: timestamp,
+            }),
+        }
+    }
+
+    /// Compares the chunk count in the .evtx file header with the chunks found in the file. A
+    /// file that was cut short or had chunks removed no longer matches its header.
+    pub fn check_file_header(&mut self, path: &Path) -> io::Result<()> {
+        let mut file = File::open(path)?;
+        let mut header = [0u8; 128];
+        file.read_exact(&mut header)?;
+        if &header[..8] != EVTX_FILE_SIGNATURE {
+            return Ok(());
+        }
+        let header_chunks = u16::from_le_bytes([header[42], header[43]]) as u64;
+        let flags = u32::from_le_bytes([header[120], header[121], header[122], header[123]]);
+        let file_len = file.metadata()?.len();
+        let mut signature = [0u8; 8];
+        let mut file_chunks = 0;
+        let mut offset = EVTX_HEADER_BLOCK_SIZE;
+        while offset + EVTX_CHUNK_SIZE <= file_len {
+            file.seek(SeekFrom::Start(offset))?;
+            file.read_exact(&mut signature)?;
+            if signature == EVTX_CHUNK_SIGNATURE {
+                file_chunks += 1;
+            }
+            offset += EVTX_CHUNK_SIZE;
+        }
+        if header_chunks != file_chunks {
+            // A log copied while it was open may not have its latest chunk in the header yet.
+            let dirty = flags & EVTX_FLAG_DIRTY != 0;
+            self.evidence.push(IntegrityEvidence {
+                kind: EvidenceKind::ChunkCountMismatch,
+                verdict: if dirty {
+                    IntegrityVerdict::Suspicious
+                } else {
+                    IntegrityVerdict::Tampered
+                },
+                timestamp: None,
+                details: format!(
+                    "The file header lists {header_chunks} chunks but the file has {file_chunks}{}",
+                    if dirty { " (header not flushed)" } else { "" }
+                ),
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns the evidence of this file, including the record ID and time gaps found after
+    /// sorting the record runs. Time gaps within the slack of a shutdown event are left out.
+    fn all_evidence(
+        &self,
+        shutdowns: &[DateTime<Utc>],
+        gap_threshold: Duration,
+    ) -> Vec<IntegrityEvidence> {
+        let mut evidence = self.evidence.clone();
+        let mut time_gaps = self.time_gaps.clone();
+        let runs = self
+            .runs
+            .iter()
+            .sorted_by_key(|run| (run.first_id, run.last_id))
+            .collect_vec();
+        for (prev, next) in runs.iter().tuple_windows() {
+            if next.first_id > prev.last_id.saturating_add(1) {
+                evidence.push(IntegrityEvidence {
+                    kind: EvidenceKind::RecordIdGap,
+                    verdict: IntegrityVerdict::Tampered,
+                    timestamp: next.first_time,
+                    details: format!(
+                        "EventRecordID {} -> {} ({} missing)",
+                        prev.last_id,
+                        next.first_id,
+                        next.first_id - prev.last_id - 1
+                    ),
+                });
+            } else if next.first_id <= prev.last_id {
+                evidence.push(IntegrityEvidence {
+                    kind: EvidenceKind::RecordIdOutOfOrder,
+                    verdict: IntegrityVerdict::Tampered,
+                    timestamp: next.first_time,
+                    details: format!(
+                        "EventRecordIDs {}-{} overlap {}-{}",
+                        next.first_id, next.last_id, prev.first_id, prev.last_id
+                    ),
+                });
+            }
+            if let (Some(end), Some(start)) = (prev.last_time, next.first_time)
+                && start - end > gap_threshold
+            {
+                time_gaps.push((end, start));
+            }
+        }
+        let slack = Duration::minutes(SHUTDOWN_SLACK_MINUTES);
+        for (start, end) in time_gaps {
+            if shutdowns
+                .iter()
+                .any(|shutdown| *shutdown >= start - slack && *shutdown <= end + slack)
+            {
+                continue;
+            }
+            let gap = end - start;
+            evidence.push(IntegrityEvidence {
+                kind: EvidenceKind::TimeGap,
+                verdict: IntegrityVerdict::Suspicious,
+                timestamp: Some(start),
+                details: format!(
+                    "No events for {}h {}m without a shutdown (6006/6008)",
+                    gap.num_hours(),
+                    gap.num_minutes() % 60
+                ),
+            });
+        }
+        evidence.sort_by_key(|e| e.timestamp);
+        evidence
+    }
+}
+
+/// Returns the verdict and evidence of each file, in the same order as `log_metrics`. Shutdowns
+/// from the System logs of the same computer are used to explain time gaps in every log.
+pub fn integrity_results(
+    log_metrics: &[LogMetrics],
+    gap_threshold: Duration,
+) -> Vec<(IntegrityVerdict, Vec<IntegrityEvidence>)> {
+    let mut shutdowns: HashMap<&str, Vec<DateTime<Utc>>> = HashMap::new();
+    for metrics in log_metrics {
+        for computer in metrics.computers.iter() {
+            shutdowns
+                .entry(computer.as_str())
+                .or_default()
+                .extend(metrics.integrity.shutdowns.iter().copied());
+        }
+    }
+    log_metrics
+        .iter()
+        .map(|metrics| {
+            let computer_shutdowns = metrics
+                .computers
+                .iter()
+                .filter_map(|computer| shutdowns.get(computer.as_str()))
+                .flatten()
+                .copied()
+                .collect_vec();
+            let evidence = metrics
+                .integrity
+                .all_evidence(&computer_shutdowns, gap_threshold);
+            let verdict = evidence.iter().map(|e| e.verdict).max().unwrap_or_default();
+            (verdict, evidence)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use nested::Nested;
+
+    use super::{EvidenceKind, IntegrityVerdict, LogIntegrity, integrity_results};
+    use crate::detections::configs::{Action, Config, LogMetricsOption, StoredStatic};
+    use crate::detections::detection::EvtxRecordInfo;
+    use crate::detections::utils::create_rec_info;
+    use crate::timeline::log_metrics::LogMetrics;
+
+    fn event(
+        stored_static: &StoredStatic,
+        channel: &str,
+        event_id: i64,
+        record_id: u64,
+        time: &str,
+    ) -> EvtxRecordInfo {
+        let record = format!(
+            r#"{{
+                "Event": {{
+                    "System": {{
+                        "EventID": {event_id},
+                        "EventRecordID": {record_id},
+                        "Channel": "{channel}",
+                        "Computer": "PC01",
+                        "TimeCreated_attributes": {{"SystemTime": "{time}"}}
+                    }},
+                    "UserData": {{
+                        "LogFileCleared": {{"SubjectUserName": "admin", "SubjectDomainName": "CORP"}}
+                    }}
+                }}
+            }}"#
+        );
+        create_rec_info(
+            serde_json::from_str(&record).unwrap(),
+            "testpath".to_string(),
+            &Nested::<String>::new(),
+            &false,
+            &false,
+            &stored_static.eventkey_alias,
+        )
+    }
+
+    fn stored_static() -> StoredStatic {
+        StoredStatic::create_static_data(Config {
+            action: Some(Action::LogMetrics(LogMetricsOption {
+                integrity: true,
+                ..Default::default()
+            })),
+            debug: false,
+        })
+    }
+
+    #[test]
+    fn test_wrapped_log_is_ok() {
+        let stored_static = stored_static();
+        let mut metrics = LogMetrics::default();
+        metrics.computers.insert("PC01".to_string());
+        // The newest records come first after the log wrapped around.
+        for (record_id, time) in [
+            (3, "2024-01-01T02:00:00Z"),
+            (4, "2024-01-01T03:00:00Z"),
+            (1, "2024-01-01T00:00:00Z"),
+            (2, "2024-01-01T01:00:00Z"),
+        ] {
+            let record = event(&stored_static, "Security", 4624, record_id, time);
+            metrics
+                .integrity
+                .update(&record, &stored_static, Duration::hours(24));
+        }
+        let results = integrity_results(&[metrics], Duration::hours(24));
+        assert_eq!(results[0], (IntegrityVerdict::Ok, vec![]));
+    }
+
+    #[test]
+    fn test_gaps_and_log_clear() {
+        let stored_static = stored_static();
+        let mut security = LogMetrics::default();
+        security.computers.insert("PC01".to_string());
+        for (event_id, record_id, time) in [
+            (4624, 1, "2024-01-01T00:00:00Z"),
+            (4624, 2, "2024-01-01T01:00:00Z"),
+            // Records 3-9 were removed.
+            (4624, 10, "2024-01-01T02:00:00Z"),
+            (1102, 11, "2024-01-03T02:00:00Z"),
+            (4624, 12, "2024-01-05T02:00:00Z"),
+        ] {
+            let record = event(&stored_static, "Security", event_id, record_id, time);
+            security
+                .integrity
+                .update(&record, &stored_static, Duration::hours(24));
+        }
+        // A reboot explains the first time gap but not the second one.
+        let mut system = LogMetrics::default();
+        system.computers.insert("PC01".to_string());
+        let shutdown = event(&stored_static, "System", 6006, 1, "2024-01-01T02:05:00Z");
+        system
+            .integrity
+            .update(&shutdown, &stored_static, Duration::hours(24));
+
+        let results = integrity_results(&[security, system], Duration::hours(24));
+        let (verdict, evidence) = &results[0];
+        assert_eq!(*verdict, IntegrityVerdict::Tampered);
+        let kinds: Vec<EvidenceKind> = evidence.iter().map(|e| e.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                EvidenceKind::RecordIdGap,
+                EvidenceKind::LogCleared,
+                EvidenceKind::TimeGap
+            ]
+        );
+        assert_eq!(evidence[0].details, "EventRecordID 2 -> 10 (7 missing)");
+        assert_eq!(evidence[1].details, "Security log cleared by CORP\\admin");
+        assert_eq!(results[1].0, IntegrityVerdict::Ok);
+    }
+
+    #[test]
+    fn test_overlapping_record_ids() {
+        let stored_static = stored_static();
+        let mut integrity = LogIntegrity::default();
+        for record_id in [5, 6, 7, 6] {
+            let record = event(
+                &stored_static,
+                "Security",
+                4624,
+                record_id,
+                "2024-01-01T00:00:00Z",
+            );
+            integrity.update(&record, &stored_static, Duration::hours(24));
+        }
+        let evidence = integrity.all_evidence(&[], Duration::hours(24));
+        assert_eq!(evidence.len(), 1);
+        assert_eq!(evidence[0].kind, EvidenceKind::RecordIdOutOfOrder);
+    }
+}
@@ -2,12 +2,13 @@ use crate::detections::configs::StoredStatic;
 use crate::detections::detection::EvtxRecordInfo;
 use crate::detections::message::AlertMessage;
 use crate::detections::utils;
+use crate::timeline::log_integrity::{LogIntegrity, integrity_gap_threshold};
 use chrono::{DateTime, Utc};
 use std::collections::HashSet;
 
 /// Per-log-file summary statistics collected for the log-metrics command: file path/name/size,
 /// event count, first/last event timestamps, and the distinct computer names, channels and
-/// providers seen in the file.
+/// providers seen in the file. With `--integrity`, also the evidence of tampering in the file.
 #[derive(Default, Debug, Clone)]
 pub struct LogMetrics {
     pub filepath: String,
@@ -19,6 +20,7 @@ pub struct LogMetrics {
     pub last_timestamp: Option<DateTime<Utc>>,
     pub channels: HashSet<String>,
     pub providers: HashSet<String>,
+    pub integrity: LogIntegrity,
 }
 
 impl LogMetrics {
@@ -34,7 +36,11 @@ impl LogMetrics {
     /// timestamp range, collects each record's computer, channel and provider, and increments the
     /// event count.
     pub fn update(&mut self, records: &[EvtxRecordInfo], stored_static: &StoredStatic) {
+        let gap_threshold = integrity_gap_threshold(stored_static);
         for record in records {
+            if let Some(gap_threshold) = gap_threshold {
+                self.integrity.update(record, stored_static, gap_threshold);
+            }
             if let Some(evttime) = utils::get_event_value(
                 "Event.System.TimeCreated_attributes.SystemTime",
                 &record.record,
@@ -0,0 +1,362 @@
+use chrono::{DateTime, Utc};
+use compact_str::CompactString;
+use hashbrown::HashMap;
+use itertools::Itertools;
+use serde_json::Value;
+
+use crate::detections::configs::{EventKeyAliasConfig, StoredStatic, TimeFormatOptions};
+use crate::detections::detection::EvtxRecordInfo;
+use crate::detections::message::DetectInfo;
+use crate::detections::utils;
+use crate::options::profile::Profile;
+use crate::timeline::metrics::{get_event_value_as_string, get_record_timestamp, logon_type_label};
+
+const SYSMON_CHANNEL: &str = "Microsoft-Windows-Sysmon/Operational";
+
+/// A logon session on a host, opened by a Security 4624 event and closed by a 4634 or 4647 event.
+#[derive(Debug, Clone, Default)]
+pub struct LogonSession {
+    pub computer: CompactString,
+    pub logon_id: CompactString,
+    pub user: CompactString,
+    pub logon_type: CompactString,
+    pub source_ip: CompactString,
+    pub source_computer: CompactString,
+    pub logon_time: DateTime<Utc>,
+    pub logoff_time: Option<DateTime<Utc>>,
+    /// Number of events logged by the session per "Channel EventID", e.g. `Sec 4688`.
+    pub activity: HashMap<CompactString, usize>,
+}
+
+impl LogonSession {
+    /// Returns the time from the logon to the logoff in hh:mm:ss.fff format, or None if the
+    /// logoff was not found.
+    pub fn duration(&self) -> Option<String> {
+        let duration = self.logoff_time? - self.logon_time;
+        let seconds = duration.num_seconds();
+        Some(utils::output_duration((
+            seconds,
+            duration.num_milliseconds() - seconds * 1000,
+        )))
+    }
+
+    pub fn event_count(&self) -> usize {
+        self.activity.values().sum()
+    }
+}
+
+/// Identifies the logon session that a record was logged in, so that the `%Session*%` columns can
+/// be filled in once every log has been scanned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionKey {
+    computer: CompactString,
+    /// The logon IDs of the record that can name its session, in the order they are tried.
+    logon_ids: Vec<CompactString>,
+    time: DateTime<Utc>,
+}
+
+impl SessionKey {
+    /// Returns the key of the session that the record was logged in, if it has a logon ID.
+    pub fn from_record(rec: &Value, alias: &EventKeyAliasConfig) -> Option<Self> {
+        let channel = get_event_value_as_string("Channel", rec, alias);
+        let event_id = get_event_value_as_string("EventID", rec, alias);
+        let keys: &[&str] = match (channel.as_str(), event_id.as_str()) {
+            ("Security", "4624" | "4634" | "4647") => &["TargetLogonId"],
+            ("Security", _) => &["SubjectLogonId", "TargetLogonId"],
+            (SYSMON_CHANNEL, _) => &["LogonId"],
+            _ => return None,
+        };
+        let logon_ids: Vec<CompactString> = keys
+            .iter()
+            .filter_map(|key| logon_id(rec, alias, key))
+            .collect();
+        if logon_ids.is_empty() {
+            return None;
+        }
+        Some(SessionKey {
+            computer: get_event_value_as_string("Computer", rec, alias),
+            logon_ids,
+            time: get_record_timestamp(rec, alias)?,
+        })
+    }
+}
+
+/// Logon sessions keyed by computer and logon ID (`TargetLogonId` of the 4624 event). Later events
+/// are linked to a session through their `SubjectLogonId`/`TargetLogonId` (Security) or `LogonId`
+/// (Sysmon). Logon IDs are only unique until the host restarts, so an event belongs to the latest
+/// session with its logon ID that was opened before the event and not closed yet.
+#[derive(Debug, Clone, Default)]
+pub struct LogonSessions {
+    pub sessions: Vec<LogonSession>,
+    // Indexes of the sessions opened with each logon ID, ordered by logon time.
+    by_logon_id: HashMap<(CompactString, CompactString), Vec<usize>>,
+}
+
+impl LogonSessions {
+    pub fn update(&mut self, records: &[EvtxRecordInfo], stored_static: &StoredStatic) {
+        let alias = &stored_static.eventkey_alias;
+        for record in records {
+            let rec = &record.record;
+            if utils::is_filtered_by_computer_name(
+                utils::get_event_value("Event.System.Computer", rec, alias),
+                (
+                    &stored_static.include_computer,
+                    &stored_static.exclude_computer,
+                ),
+            ) {
+                continue;
+            }
+            let Some(time) = get_record_timestamp(rec, alias) else {
+                continue;
+            };
+            let channel = get_event_value_as_string("Channel", rec, alias);
+            let event_id = get_event_value_as_string("EventID", rec, alias);
+            if channel == "Security" && event_id == "4624" {
+                self.add(rec, alias, time);
+            }
+            let Some(idx) =
+                SessionKey::from_record(rec, alias).and_then(|key| self.find_session(&key))
+            else {
+                continue;
+            };
+            let session = &mut self.sessions[idx];
+            if channel == "Security" && (event_id == "4634" || event_id == "4647") {
+                session.logoff_time.get_or_insert(time);
+            }
+            let channel_name = match channel.as_str() {
+                "Security" => "Sec",
+                SYSMON_CHANNEL => "Sysmon",
+                other => other,
+            };
+            *session
+                .activity
+                .entry(format!("{channel_name} {event_id}").into())
+                .or_default() += 1;
+        }
+    }
+
+    fn add(&mut self, rec: &Value, alias: &EventKeyAliasConfig, time: DateTime<Utc>) {
+        let Some(logon_id) = logon_id(rec, alias, "TargetLogonId") else {
+            return;
+        };
+        let computer = get_event_value_as_string("Computer", rec, alias);
+        let key = (computer.clone(), logon_id.clone());
+        // The same 4624 event can be scanned twice when a log was exported more than once.
+        if self
+            .by_logon_id
+            .get(&key)
+            .is_some_and(|indexes| indexes.iter().any(|&i| self.sessions[i].logon_time == time))
+        {
+            return;
+        }
+        let value = |key: &str| get_event_value_as_string(key, rec, alias);
+        let (domain, user) = (value("TargetDomainName"), value("TargetUserName"));
+        let session = LogonSession {
+            computer,
+            logon_id,
+            user: if domain == "-" || domain.is_empty() {
+                user
+            } else {
+                format!("{domain}\\{user}").into()
+            },
+            logon_type: logon_type_label(&value("LogonType")),
+            source_ip: value("IpAddress"),
+            source_computer: value("WorkstationName"),
+            logon_time: time,
+            ..Default::default()
+        };
+        let idx = self.sessions.len();
+        let indexes = self.by_logon_id.entry(key).or_default();
+        let pos = indexes.partition_point(|&other| self.sessions[other].logon_time <= time);
+        indexes.insert(pos, idx);
+        self.sessions.push(session);
+    }
+
+    /// Returns the session with the key's logon ID that was open at the key's time, if its logon
+    /// was found.
+    pub fn find_session(&self, key: &SessionKey) -> Option<usize> {
+        key.logon_ids.iter().find_map(|logon_id| {
+            self.by_logon_id
+                .get(&(key.computer.clone(), logon_id.clone()))?
+                .iter()
+                .rev()
+                .copied()
+                .find(|&idx| {
+                    let session = &self.sessions[idx];
+                    session.logon_time <= key.time
+                        && session.logoff_time.is_none_or(|logoff| logoff >= key.time)
+                })
+        })
+    }
+
+    /// Fills in the `%Session*%` columns of the detections from their session keys, with "-" when
+    /// the session is not found or, for `%SessionDuration%`, its logoff is not found.
+    pub fn resolve_sessions(
+        &self,
+        detect_infos: &mut [DetectInfo],
+        time_format_options: &TimeFormatOptions,
+    ) {
+        for detect_info in detect_infos {
+            let session = detect_info
+                .session_key
+                .as_ref()
+                .and_then(|key| self.find_session(key))
+                .map(|idx| &self.sessions[idx]);
+            for (_, profile) in detect_info.output_fields.iter_mut() {
+                let value = match profile {
+                    Profile::SessionLogonType(_) => session.map(|s| s.logon_type.clone()),
+                    Profile::SessionSourceIP(_) => session.map(|s| s.source_ip.clone()),
+                    Profile::SessionLogonTime(_) => session
+                        .map(|s| utils::format_time(&s.logon_time, false, time_format_options)),
+                    Profile::SessionDuration(_) => session
+                        .and_then(LogonSession::duration)
+                        .map(CompactString::from),
+                    _ => continue,
+                };
+                *profile = profile.convert(&value.unwrap_or_else(|| "-".into()));
+            }
+        }
+    }
+
+    /// Returns the sessions ordered by logon time and then computer.
+    pub fn sorted(&self) -> Vec<&LogonSession> {
+        self.sessions
+            .iter()
+            .sorted_by(|a, b| {
+                (a.logon_time, &a.computer, &a.logon_id).cmp(&(
+                    b.logon_time,
+                    &b.computer,
+                    &b.logon_id,
+                ))
+            })
+            .collect()
+    }
+}
+
+/// Returns the lowercased logon ID in the field, skipping the empty `0x0` ID.
+fn logon_id(rec: &Value, alias: &EventKeyAliasConfig, key: &str) -> Option<CompactString> {
+    let value = get_event_value_as_string(key, rec, alias).to_lowercase();
+    (value != "-" && value != "0x0" && !value.is_empty()).then(|| value.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use nested::Nested;
+
+    use super::{LogonSessions, SessionKey};
+    use crate::detections::configs::{
+        Action, Config, SessionsOption, StoredStatic, TimeFormatOptions,
+    };
+    use crate::detections::detection::EvtxRecordInfo;
+    use crate::detections::message::DetectInfo;
+    use crate::detections::utils::create_rec_info;
+    use crate::options::profile::Profile;
+
+    fn event(
+        stored_static: &StoredStatic,
+        event_id: i64,
+        time: &str,
+        event_data: &str,
+    ) -> EvtxRecordInfo {
+        let record = format!(
+            r#"{{
+                "Event": {{
+                    "System": {{
+                        "EventID": {event_id},
+                        "Channel": "Security",
+                        "Computer": "PC01",
+                        "TimeCreated_attributes": {{"SystemTime": "{time}"}}
+                    }},
+                    "EventData": {event_data}
+                }}
+            }}"#
+        );
+        create_rec_info(
+            serde_json::from_str(&record).unwrap(),
+            "testpath".to_string(),
+            &Nested::<String>::new(),
+            &false,
+            &false,
+            &stored_static.eventkey_alias,
+        )
+    }
+
+    #[test]
+    fn test_logon_sessions() {
+        let stored_static = StoredStatic::create_static_data(Config {
+            action: Some(Action::Sessions(SessionsOption::default())),
+            debug: false,
+        });
+        let logon = |time: &str, ip: &str| {
+            event(
+                &stored_static,
+                4624,
+                time,
+                &format!(
+                    r#"{{"TargetLogonId": "0x3A5F1", "TargetUserName": "alice", "TargetDomainName": "CORP", "LogonType": 3, "IpAddress": "{ip}"}}"#
+                ),
+            )
+        };
+        let records = vec![
+            logon("2024-01-01T00:00:00Z", "10.0.0.5"),
+            event(
+                &stored_static,
+                4688,
+                "2024-01-01T00:00:10Z",
+                r#"{"SubjectLogonId": "0x3a5f1", "NewProcessName": "C:\\Windows\\cmd.exe"}"#,
+            ),
+            event(
+                &stored_static,
+                4634,
+                "2024-01-01T01:02:03Z",
+                r#"{"TargetLogonId": "0x3a5f1"}"#,
+            ),
+            // The logon ID is reused after a restart.
+            logon("2024-01-02T00:00:00Z", "10.0.0.6"),
+            event(
+                &stored_static,
+                4688,
+                "2024-01-02T00:00:10Z",
+                r#"{"SubjectLogonId": "0x3a5f1", "NewProcessName": "C:\\Windows\\cmd.exe"}"#,
+            ),
+        ];
+        let mut sessions = LogonSessions::default();
+        sessions.update(&records, &stored_static);
+
+        assert_eq!(sessions.sessions.len(), 2);
+        let first = &sessions.sessions[0];
+        assert_eq!(first.user, "CORP\\alice");
+        assert_eq!(first.logon_type, "3 - Network");
+        assert_eq!(first.event_count(), 3);
+        assert_eq!(first.activity["Sec 4688"], 1);
+        assert_eq!(first.duration().unwrap(), "01:02:03.000");
+        let key = |idx: usize| {
+            SessionKey::from_record(&records[idx].record, &stored_static.eventkey_alias)
+        };
+        assert_eq!(sessions.find_session(&key(4).unwrap()), Some(1));
+        assert_eq!(sessions.sessions[1].source_ip, "10.0.0.6");
+        assert_eq!(sessions.sessions[1].duration(), None);
+
+        let detect_info = |session_key: Option<SessionKey>| DetectInfo {
+            output_fields: vec![
+                ("Type".into(), Profile::SessionLogonType("-".into())),
+                ("IP".into(), Profile::SessionSourceIP("-".into())),
+                ("Duration".into(), Profile::SessionDuration("-".into())),
+            ],
+            session_key,
+            ..Default::default()
+        };
+        let mut detect_infos = vec![detect_info(key(1)), detect_info(key(4)), detect_info(None)];
+        sessions.resolve_sessions(&mut detect_infos, &TimeFormatOptions::default());
+        let values = |idx: usize| {
+            detect_infos[idx]
+                .output_fields
+                .iter()
+                .map(|(_, profile)| profile.to_value())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(values(0), ["3 - Network", "10.0.0.5", "01:02:03.000"]);
+        assert_eq!(values(1), ["3 - Network", "10.0.0.6", "-"]);
+        assert_eq!(values(2), ["-", "-", "-"]);
+    }
+}
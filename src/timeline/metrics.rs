@@ -5,6 +5,10 @@ use crate::detections::{
     message::AlertMessage,
     utils,
 };
+use crate::timeline::auth_analysis::AuthMetrics;
+use crate::timeline::computer_metrics::ComputerInventory;
+use crate::timeline::event_anomalies::{EventRates, anomaly_settings};
+use crate::timeline::log_integrity::integrity_gap_threshold;
 use crate::timeline::log_metrics::LogMetrics;
 use crate::timeline::metrics::Channel::{RdsGtw, RdsLsm, RdsRcm, Sec, Sec4778};
 use bytesize::ByteSize;
@@ -45,11 +49,29 @@ fn parse_evtx_datetime(evttime: &str) -> Option<DateTime<Utc>> {
     parse_evtx_timestamp(evttime).ok()
 }
 
+/// Returns the record's event time, trying TimeCreated SystemTime first and then falling back to
+/// @timestamp (as in `stats_time_cnt`).
+pub(super) fn get_record_timestamp(
+    record: &serde_json::Value,
+    eventkey_alias: &EventKeyAliasConfig,
+) -> Option<DateTime<Utc>> {
+    utils::get_event_value(
+        "Event.System.TimeCreated_attributes.SystemTime",
+        record,
+        eventkey_alias,
+    )
+    .or_else(|| utils::get_event_value("Event.System.@timestamp", record, eventkey_alias))
+    .map(|evt_value| evt_value.to_string().replace("\\\"", "").replace('"', ""))
+    .as_deref()
+    .and_then(parse_evtx_datetime)
+}
+
 /// Accumulates statistics over all scanned records. Depending on the command being run, only some
 /// of the fields are populated: eid-metrics fills `stats_list`, logon-summary fills
 /// `stats_login_list`, computer-metrics fills `stats_computer` (from `computer_metrics.rs`),
-/// log-metrics fills `stats_logfile`, and csv-timeline/json-timeline only use the record count and
-/// time range kept in `total`/`start_time`/`end_time`.
+/// log-metrics fills `stats_logfile`, event-anomalies fills `stats_rates`, and
+/// csv-timeline/json-timeline only use the record count and time range kept in
+/// `total`/`start_time`/`end_time`.
 #[derive(Debug, Clone, Default)]
 pub struct EventMetrics {
     // Total number of records scanned.
@@ -61,20 +83,16 @@ pub struct EventMetrics {
     pub end_time: Option<DateTime<Utc>>,
     // eid-metrics: record count keyed by (EventID, Channel), both lowercased.
     pub stats_list: HashMap<(CompactString, CompactString), usize>,
-    pub stats_computer: HashMap<
-        CompactString, // ComputerName
-        (
-            CompactString, // OS
-            CompactString, // Uptime
-            CompactString, // TimeZone
-            CompactString, // LastTimestamp
-            usize,
-        ),
-    >,
+    // computer-metrics: host inventory keyed by computer name.
+    pub stats_computer: HashMap<CompactString, ComputerInventory>,
     // logon-summary: [successful, failed] logon counts + first/last timestamps per grouping key.
     pub stats_login_list: HashMap<LoginEvent, LogonStats>,
+    // logon-summary: Kerberos/NTLM authentication findings.
+    pub stats_auth: AuthMetrics,
     // log-metrics: per-log-file metrics (file size, event count, time range, computers, etc.).
     pub stats_logfile: Vec<LogMetrics>,
+    // event-anomalies: record count per (computer, channel, EventID) and time bucket.
+    pub stats_rates: EventRates,
     // (EventRecordID, timestamp) pairs that have already been counted. Used by the
     // -X/--remove-duplicate-records option to skip duplicate records.
     pub counted_rec: HashSet<(String, String)>,
@@ -105,7 +123,8 @@ impl EventMetrics {
     }
 
     /// Aggregation entry point for the logon-summary command: updates the overall record
-    /// count/time range and counts successful/failed logon events.
+    /// count/time range, counts successful/failed logon events and collects the Kerberos/NTLM
+    /// authentication events.
     pub fn logon_stats_start(&mut self, records: &[EvtxRecordInfo], stored_static: &StoredStatic) {
         // Only output statistics when the logon-summary option is specified as an argument.
         if !stored_static.logon_summary_flag {
@@ -113,6 +132,7 @@ impl EventMetrics {
         }
         self.stats_time_cnt(records, stored_static);
         self.stats_login_eventid(records, stored_static);
+        self.stats_auth.update(records, stored_static);
     }
 
     /// Aggregation entry point for the log-metrics command. Records arrive in batches: if an
@@ -157,11 +177,40 @@ impl EventMetrics {
             existing_lm.update(records, stored_static);
         } else {
             let mut log_metrics = LogMetrics::new(self.filepath.as_str(), file_name, file_size);
+            // Check the file header once per file, not for each computer found in it.
+            if integrity_gap_threshold(stored_static).is_some()
+                && !stored_static.json_input_flag
+                && !self
+                    .stats_logfile
+                    .iter()
+                    .any(|log_metrics| log_metrics.filepath == self.filepath.as_str())
+                && let Err(e) = log_metrics.integrity.check_file_header(path)
+                && !stored_static.quiet_errors_flag
+            {
+                stored_static.error_log_stack.lock().unwrap().push(format!(
+                    "[ERROR] Failed to read the file header of {file_name}. {e}"
+                ));
+            }
             log_metrics.update(records, stored_static);
             self.stats_logfile.push(log_metrics);
         }
     }
 
+    /// Aggregation entry point for the event-anomalies command: updates the overall record
+    /// count/time range and counts records per (computer, channel, EventID) and time bucket.
+    pub fn event_rate_stats_start(
+        &mut self,
+        records: &[EvtxRecordInfo],
+        stored_static: &StoredStatic,
+    ) {
+        let Some(settings) = anomaly_settings(stored_static) else {
+            return;
+        };
+        self.stats_time_cnt(records, stored_static);
+        self.stats_rates
+            .update(records, stored_static, settings.interval);
+    }
+
     /// Updates the total record count and widens the overall start/end time range based on the
     /// given batch of records. Used by all commands that report the scanned time range.
     pub fn stats_time_cnt(&mut self, records: &[EvtxRecordInfo], stored_static: &StoredStatic) {
@@ -300,21 +349,6 @@ impl EventMetrics {
     /// 1149 (network-level authentication) and RDS Gateway 302 (RD Gateway logon). All but 4625 are
     /// counted as successful logons.
     fn stats_login_eventid(&mut self, records: &[EvtxRecordInfo], stored_static: &StoredStatic) {
-        // Maps the LogonType number to a human-readable label for display.
-        let logontype_map: HashMap<&str, &str> = HashMap::from([
-            ("0", "0 - System"),
-            ("2", "2 - Interactive"),
-            ("3", "3 - Network"),
-            ("4", "4 - Batch"),
-            ("5", "5 - Service"),
-            ("7", "7 - Unlock"),
-            ("8", "8 - NetworkCleartext"),
-            ("9", "9 - NewInteractive"),
-            ("10", "10 - RemoteInteractive"),
-            ("11", "11 - CachedInteractive"),
-            ("12", "12 - CachedRemoteInteractive"),
-            ("13", "13 - CachedUnlock"),
-        ]);
         for record in records.iter() {
             if let Some(evtid) =
                 utils::get_event_value("EventID", &record.record, &stored_static.eventkey_alias)
@@ -521,11 +555,7 @@ impl EventMetrics {
                             dst_user,
                             dst_domain,
                             hostname,
-                            logontype: CompactString::from(
-                                *logontype_map
-                                    .get(&logontype.as_str())
-                                    .unwrap_or(&logontype.as_str()),
-                            ),
+                            logontype: logon_type_label(&logontype),
                             src_user,
                             src_domain,
                             source_computer,
@@ -535,23 +565,8 @@ impl EventMetrics {
                     let idx = if event_id == 4625 { 1 } else { 0 };
                     entry.counts[idx] += 1;
                     // Widen the first/last timestamp range for this grouping and result type.
-                    // Try TimeCreated SystemTime first, then fall back to @timestamp (as in
-                    // stats_time_cnt).
-                    if let Some(ts) = utils::get_event_value(
-                        "Event.System.TimeCreated_attributes.SystemTime",
-                        &record.record,
-                        &stored_static.eventkey_alias,
-                    )
-                    .or_else(|| {
-                        utils::get_event_value(
-                            "Event.System.@timestamp",
-                            &record.record,
-                            &stored_static.eventkey_alias,
-                        )
-                    })
-                    .map(|evt_value| evt_value.to_string().replace("\\\"", "").replace('"', ""))
-                    .as_deref()
-                    .and_then(parse_evtx_datetime)
+                    if let Some(ts) =
+                        get_record_timestamp(&record.record, &stored_static.eventkey_alias)
                     {
                         if entry.first[idx].is_none() || Some(ts) < entry.first[idx] {
                             entry.first[idx] = Some(ts);
@@ -566,9 +581,30 @@ impl EventMetrics {
     }
 }
 
+/// Maps a LogonType number to a human-readable label for display (ex: `3` to `3 - Network`).
+/// Unknown values are returned as is.
+pub(super) fn logon_type_label(logon_type: &str) -> CompactString {
+    let label = match logon_type {
+        "0" => "0 - System",
+        "2" => "2 - Interactive",
+        "3" => "3 - Network",
+        "4" => "4 - Batch",
+        "5" => "5 - Service",
+        "7" => "7 - Unlock",
+        "8" => "8 - NetworkCleartext",
+        "9" => "9 - NewInteractive",
+        "10" => "10 - RemoteInteractive",
+        "11" => "11 - CachedInteractive",
+        "12" => "12 - CachedRemoteInteractive",
+        "13" => "13 - CachedUnlock",
+        _ => logon_type,
+    };
+    CompactString::from(label)
+}
+
 /// Looks up `key` in the record (resolving it through eventkey_alias.txt) and returns the value
 /// as a string with all double/single quote characters removed, or "-" if the field is missing.
-fn get_event_value_as_string(
+pub(super) fn get_event_value_as_string(
     key: &str,
     record: &serde_json::Value,
     eventkey_alias: &EventKeyAliasConfig,
@@ -659,6 +695,7 @@ mod tests {
                     directory: None,
                     filepath: None,
                     live_analysis: false,
+                    carve: None,
                     recover_records: false,
                     time_offset: None,
                 },
@@ -686,6 +723,8 @@ mod tests {
                     us_military_time: false,
                     us_time: false,
                     utc: false,
+                    timezone: None,
+                    time_format: None,
                 },
                 output: None,
                 clobber_opt: ClobberOption { clobber: false },
@@ -1,7 +1,16 @@
+mod auth_analysis;
 pub mod computer_metrics;
-mod config_critical_systems;
+pub mod config_critical_systems;
+pub mod convert;
+pub mod coverage;
+pub mod event_anomalies;
 mod extract_base64;
+mod lateral_movement;
+pub mod log_integrity;
 mod log_metrics;
+pub mod logon_sessions;
 pub mod metrics;
+pub mod process_tree;
+pub mod rare_artifacts;
 pub mod search;
 pub mod timelines;
@@ -0,0 +1,690 @@
+use chrono::{DateTime, Utc};
+use compact_str::CompactString;
+use hashbrown::HashMap;
+use itertools::Itertools;
+use serde_json::{Map, Value, json};
+
+use crate::detections::configs::{EventKeyAliasConfig, StoredStatic};
+use crate::detections::detection::EvtxRecordInfo;
+use crate::detections::message::DetectInfo;
+use crate::detections::utils;
+use crate::options::profile::Profile;
+use crate::timeline::metrics::{get_event_value_as_string, get_record_timestamp};
+
+const SYSMON_CHANNEL: &str = "Microsoft-Windows-Sysmon/Operational";
+
+/// A 4688 and a Sysmon 1 event logged for the same process on the same host are merged when their
+/// timestamps are at most this many seconds apart.
+const MERGE_WINDOW_SECS: i64 = 2;
+
+/// A process started on a host, built from a Security 4688 or Sysmon 1 event. The end time is
+/// filled from a Security 4689 or Sysmon 5 event when one is found.
+#[derive(Debug, Clone, Default)]
+pub struct ProcessNode {
+    pub computer: CompactString,
+    pub pid: u64,
+    pub guid: Option<CompactString>,
+    pub image: CompactString,
+    pub command_line: CompactString,
+    pub user: CompactString,
+    pub start: DateTime<Utc>,
+    pub end: Option<DateTime<Utc>>,
+    pub parent_pid: Option<u64>,
+    pub parent_guid: Option<CompactString>,
+    /// The parent image as logged in the creation event, used when the parent process itself
+    /// was not found in the logs.
+    pub parent_image: CompactString,
+}
+
+impl ProcessNode {
+    /// Returns the file name of the image, e.g. `cmd.exe` for `C:\Windows\System32\cmd.exe`.
+    pub fn image_name(&self) -> &str {
+        file_name(&self.image)
+    }
+}
+
+/// Identifies the process that a record was logged for, so that the `%ProcessAncestry%` column
+/// can be filled in once every log has been scanned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProcessKey {
+    /// The process created by a 4688 or Sysmon 1 event.
+    Created {
+        computer: CompactString,
+        pid: u64,
+        guid: Option<CompactString>,
+        start: DateTime<Utc>,
+    },
+    /// The process of any other Sysmon event, through its ProcessGuid.
+    Guid {
+        computer: CompactString,
+        guid: CompactString,
+    },
+}
+
+impl ProcessKey {
+    /// Returns the key of the process that the record was logged for, if it names one.
+    pub fn from_record(rec: &Value, alias: &EventKeyAliasConfig) -> Option<Self> {
+        match parse_event(rec, alias) {
+            Some(ProcessEvent::Create(node)) => Some(ProcessKey::Created {
+                computer: node.computer,
+                pid: node.pid,
+                guid: node.guid,
+                start: node.start,
+            }),
+            _ => Some(ProcessKey::Guid {
+                computer: get_event_value_as_string("Computer", rec, alias),
+                guid: event_string(rec, alias, "ProcessGuid")?,
+            }),
+        }
+    }
+}
+
+/// Process creation and termination events linked into parent/child trees per host.
+///
+/// Parents are resolved when the tree is read rather than when a process is added, and the
+/// `%ProcessAncestry%` columns are filled in by `resolve_ancestries` after the scan, so the order
+/// in which the logs are scanned does not matter. A Sysmon ProcessGuid is used when both sides
+/// have one. Otherwise the parent is the latest process on the host with the parent's PID that
+/// was started before the child and had not ended yet, which keeps reused PIDs apart.
+#[derive(Debug, Clone, Default)]
+pub struct ProcessTree {
+    pub nodes: Vec<ProcessNode>,
+    by_guid: HashMap<(CompactString, CompactString), usize>,
+    // Indexes of the processes started with each PID, ordered by start time.
+    by_pid: HashMap<(CompactString, u64), Vec<usize>>,
+}
+
+enum ProcessEvent {
+    Create(ProcessNode),
+    Terminate {
+        computer: CompactString,
+        pid: u64,
+        guid: Option<CompactString>,
+        time: DateTime<Utc>,
+    },
+}
+
+impl ProcessTree {
+    pub fn update(&mut self, records: &[EvtxRecordInfo], stored_static: &StoredStatic) {
+        let alias = &stored_static.eventkey_alias;
+        for record in records {
+            let rec = &record.record;
+            if utils::is_filtered_by_computer_name(
+                utils::get_event_value("Event.System.Computer", rec, alias),
+                (
+                    &stored_static.include_computer,
+                    &stored_static.exclude_computer,
+                ),
+            ) {
+                continue;
+            }
+            match parse_event(rec, alias) {
+                Some(ProcessEvent::Create(node)) => self.add(node),
+                Some(ProcessEvent::Terminate {
+                    computer,
+                    pid,
+                    guid,
+                    time,
+                }) => {
+                    let idx = guid
+                        .and_then(|guid| self.by_guid.get(&(computer.clone(), guid)).copied())
+                        .or_else(|| self.find_by_pid(&computer, pid, &time));
+                    if let Some(idx) = idx {
+                        self.nodes[idx].end.get_or_insert(time);
+                    }
+                }
+                None => {}
+            }
+        }
+    }
+
+    fn add(&mut self, node: ProcessNode) {
+        if let Some(guid) = &node.guid
+            && let Some(&idx) = self.by_guid.get(&(node.computer.clone(), guid.clone()))
+        {
+            merge(&mut self.nodes[idx], node);
+            return;
+        }
+        let pid_key = (node.computer.clone(), node.pid);
+        let same_process = self.by_pid.get(&pid_key).and_then(|indexes| {
+            indexes.iter().copied().find(|&idx| {
+                let other = &self.nodes[idx];
+                (other.start - node.start).num_seconds().abs() <= MERGE_WINDOW_SECS
+                    && other.image.eq_ignore_ascii_case(&node.image)
+                    && (other.guid.is_none() || node.guid.is_none())
+            })
+        });
+        if let Some(idx) = same_process {
+            if let Some(guid) = &node.guid {
+                self.by_guid
+                    .insert((node.computer.clone(), guid.clone()), idx);
+            }
+            merge(&mut self.nodes[idx], node);
+            return;
+        }
+        let idx = self.nodes.len();
+        if let Some(guid) = &node.guid {
+            self.by_guid
+                .insert((node.computer.clone(), guid.clone()), idx);
+        }
+        let indexes = self.by_pid.entry(pid_key).or_default();
+        let pos = indexes.partition_point(|&other| self.nodes[other].start <= node.start);
+        indexes.insert(pos, idx);
+        self.nodes.push(node);
+    }
+
+    /// Returns the latest process with the PID that was running on the host at the given time.
+    fn find_by_pid(
+        &self,
+        computer: &CompactString,
+        pid: u64,
+        time: &DateTime<Utc>,
+    ) -> Option<usize> {
+        self.by_pid
+            .get(&(computer.clone(), pid))?
+            .iter()
+            .rev()
+            .copied()
+            .find(|&idx| {
+                let node = &self.nodes[idx];
+                node.start <= *time && node.end.is_none_or(|end| end >= *time)
+            })
+    }
+
+    /// Returns the parent of the process if it was found in the logs. A parent always sorts
+    /// before its child by start time and then insertion order, so the links cannot form a cycle.
+    pub fn parent(&self, idx: usize) -> Option<usize> {
+        let node = &self.nodes[idx];
+        let is_before = |parent: &usize| (self.nodes[*parent].start, *parent) < (node.start, idx);
+        node.parent_guid
+            .as_ref()
+            .and_then(|guid| {
+                self.by_guid
+                    .get(&(node.computer.clone(), guid.clone()))
+                    .copied()
+            })
+            .filter(is_before)
+            .or_else(|| {
+                self.find_by_pid(&node.computer, node.parent_pid?, &node.start)
+                    .filter(is_before)
+            })
+    }
+
+    /// Returns the process and its ancestors from the oldest ancestor down to the process.
+    pub fn ancestry(&self, idx: usize) -> Vec<usize> {
+        let mut chain = vec![idx];
+        while let Some(parent) = self.parent(*chain.last().unwrap()) {
+            chain.push(parent);
+        }
+        chain.reverse();
+        chain
+    }
+
+    /// Returns the ancestry of the process as image names, e.g.
+    /// `explorer.exe > cmd.exe > powershell.exe`. When the oldest ancestor's parent is not in the
+    /// logs, the parent image from its creation event starts the chain.
+    pub fn ancestry_chain(&self, idx: usize) -> String {
+        let chain = self.ancestry(idx);
+        let top = &self.nodes[chain[0]];
+        let parent_name = Some(file_name(&top.parent_image)).filter(|name| !name.is_empty());
+        parent_name
+            .into_iter()
+            .chain(chain.iter().map(|&i| self.nodes[i].image_name()))
+            .join(" > ")
+    }
+
+    /// Returns the ancestry chain of the process, or None when it is not in the tree.
+    pub fn key_ancestry(&self, key: &ProcessKey) -> Option<String> {
+        let idx = match key {
+            ProcessKey::Created {
+                computer,
+                pid,
+                guid,
+                start,
+            } => guid
+                .as_ref()
+                .and_then(|guid| self.by_guid.get(&(computer.clone(), guid.clone())).copied())
+                .or_else(|| self.find_by_pid(computer, *pid, start)),
+            ProcessKey::Guid { computer, guid } => {
+                self.by_guid.get(&(computer.clone(), guid.clone())).copied()
+            }
+        }?;
+        Some(self.ancestry_chain(idx))
+    }
+
+    /// Fills in the `%ProcessAncestry%` columns of the detections from their process keys, with
+    /// "-" when the process is not in the tree.
+    pub fn resolve_ancestries(&self, detect_infos: &mut [DetectInfo]) {
+        for detect_info in detect_infos {
+            let Some(key) = &detect_info.process_key else {
+                continue;
+            };
+            let ancestry = self.key_ancestry(key).unwrap_or_else(|| "-".to_string());
+            for (_, profile) in detect_info.output_fields.iter_mut() {
+                if let Profile::ProcessAncestry(_) = profile {
+                    *profile = Profile::ProcessAncestry(ancestry.clone().into());
+                }
+            }
+        }
+    }
+
+    /// Returns the root processes of each host, with the hosts sorted by name and the processes by
+    /// start time, and the children of every process sorted by start time.
+    pub fn roots_and_children(&self) -> (Vec<(CompactString, Vec<usize>)>, Vec<Vec<usize>>) {
+        let mut children = vec![vec![]; self.nodes.len()];
+        let mut roots: HashMap<CompactString, Vec<usize>> = HashMap::new();
+        for (idx, node) in self.nodes.iter().enumerate() {
+            match self.parent(idx) {
+                Some(parent) => children[parent].push(idx),
+                None => roots.entry(node.computer.clone()).or_default().push(idx),
+            }
+        }
+        let by_start =
+            |a: &usize, b: &usize| (self.nodes[*a].start, *a).cmp(&(self.nodes[*b].start, *b));
+        for indexes in children.iter_mut() {
+            indexes.sort_by(by_start);
+        }
+        let roots = roots
+            .into_iter()
+            .sorted_by(|a, b| a.0.cmp(&b.0))
+            .map(|(computer, mut indexes)| {
+                indexes.sort_by(by_start);
+                (computer, indexes)
+            })
+            .collect();
+        (roots, children)
+    }
+
+    /// Renders the trees as indented text, one block per host.
+    pub fn to_text(&self, format_time: impl Fn(&DateTime<Utc>) -> String) -> String {
+        let (roots, children) = self.roots_and_children();
+        let mut lines = vec![];
+        for (computer, indexes) in roots {
+            if !lines.is_empty() {
+                lines.push(String::new());
+            }
+            lines.push(format!("Computer: {computer}"));
+            for (i, &idx) in indexes.iter().enumerate() {
+                self.push_text_lines(
+                    idx,
+                    "",
+                    i + 1 == indexes.len(),
+                    &children,
+                    &format_time,
+                    &mut lines,
+                );
+            }
+        }
+        lines.join("\n")
+    }
+
+    fn push_text_lines(
+        &self,
+        idx: usize,
+        prefix: &str,
+        is_last: bool,
+        children: &[Vec<usize>],
+        format_time: &impl Fn(&DateTime<Utc>) -> String,
+        lines: &mut Vec<String>,
+    ) {
+        let node = &self.nodes[idx];
+        let mut line = format!(
+            "{prefix}{}{} (PID: {}) Start: {}",
+            if is_last { "└─ " } else { "├─ " },
+            node.image_name(),
+            node.pid,
+            format_time(&node.start)
+        );
+        if let Some(end) = &node.end {
+            line.push_str(&format!(" End: {}", format_time(end)));
+        }
+        if !node.user.is_empty() {
+            line.push_str(&format!(" User: {}", node.user));
+        }
+        if self.parent(idx).is_none() && !node.parent_image.is_empty() {
+            line.push_str(&format!(" Parent: {}", node.parent_image));
+        }
+        if !node.command_line.is_empty() {
+            line.push_str(&format!(" Cmdline: {}", node.command_line));
+        }
+        lines.push(line);
+        let child_prefix = format!("{prefix}{}", if is_last { "   " } else { "│  " });
+        for (i, &child) in children[idx].iter().enumerate() {
+            self.push_text_lines(
+                child,
+                &child_prefix,
+                i + 1 == children[idx].len(),
+                children,
+                format_time,
+                lines,
+            );
+        }
+    }
+
+    /// Renders the trees as JSON: an array with one object per host holding its root processes,
+    /// each with its children nested under `Children`.
+    pub fn to_json(&self, format_time: impl Fn(&DateTime<Utc>) -> String) -> Value {
+        let (roots, children) = self.roots_and_children();
+        Value::Array(
+            roots
+                .into_iter()
+                .map(|(computer, indexes)| {
+                    json!({
+                        "Computer": computer.as_str(),
+                        "Processes": indexes
+                            .iter()
+                            .map(|&idx| self.node_to_json(idx, &children, &format_time))
+                            .collect_vec(),
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    fn node_to_json(
+        &self,
+        idx: usize,
+        children: &[Vec<usize>],
+        format_time: &impl Fn(&DateTime<Utc>) -> String,
+    ) -> Value {
+        let node = &self.nodes[idx];
+        let mut obj = Map::new();
+        obj.insert("Image".to_string(), json!(node.image.as_str()));
+        obj.insert("ProcessId".to_string(), json!(node.pid));
+        if let Some(guid) = &node.guid {
+            obj.insert("ProcessGuid".to_string(), json!(guid.as_str()));
+        }
+        obj.insert("CommandLine".to_string(), json!(node.command_line.as_str()));
+        obj.insert("User".to_string(), json!(node.user.as_str()));
+        obj.insert("Start".to_string(), json!(format_time(&node.start)));
+        obj.insert(
+            "End".to_string(),
+            node.end
+                .as_ref()
+                .map_or(Value::Null, |end| json!(format_time(end))),
+        );
+        obj.insert("ParentImage".to_string(), json!(node.parent_image.as_str()));
+        obj.insert(
+            "Children".to_string(),
+            Value::Array(
+                children[idx]
+                    .iter()
+                    .map(|&child| self.node_to_json(child, children, format_time))
+                    .collect(),
+            ),
+        );
+        Value::Object(obj)
+    }
+}
+
+/// Fills the fields of `node` that the event already added for the same process left empty.
+fn merge(node: &mut ProcessNode, other: ProcessNode) {
+    if node.guid.is_none() {
+        node.guid = other.guid;
+    }
+    if node.parent_guid.is_none() {
+        node.parent_guid = other.parent_guid;
+    }
+    if node.parent_pid.is_none() {
+        node.parent_pid = other.parent_pid;
+    }
+    for (field, value) in [
+        (&mut node.command_line, other.command_line),
+        (&mut node.user, other.user),
+        (&mut node.parent_image, other.parent_image),
+    ] {
+        if field.is_empty() {
+            *field = value;
+        }
+    }
+    node.start = node.start.min(other.start);
+}
+
+/// Parses a Security 4688/4689 or Sysmon 1/5 event.
+fn parse_event(rec: &Value, alias: &EventKeyAliasConfig) -> Option<ProcessEvent> {
+    let value = |key: &str| event_string(rec, alias, key);
+    let pid = |key: &str| value(key).as_deref().and_then(parse_pid);
+    let channel = get_event_value_as_string("Channel", rec, alias);
+    let event_id = get_event_value_as_string("EventID", rec, alias);
+    let computer = get_event_value_as_string("Computer", rec, alias);
+    let time = get_record_timestamp(rec, alias)?;
+    let node = match (channel.as_str(), event_id.as_str()) {
+        ("Security", "4688") => ProcessNode {
+            pid: pid("NewProcessId")?,
+            image: value("NewProcessName").unwrap_or_default(),
+            command_line: value("CommandLine").unwrap_or_default(),
+            user: match (value("SubjectDomainName"), value("SubjectUserName")) {
+                (Some(domain), Some(user)) => format!("{domain}\\{user}").into(),
+                (_, user) => user.unwrap_or_default(),
+            },
+            parent_pid: pid("ProcessId"),
+            parent_image: value("ParentProcessName").unwrap_or_default(),
+            ..Default::default()
+        },
+        (SYSMON_CHANNEL, "1") => ProcessNode {
+            pid: pid("ProcessId")?,
+            guid: value("ProcessGuid"),
+            image: value("Image").unwrap_or_default(),
+            command_line: value("CommandLine").unwrap_or_default(),
+            user: value("User").unwrap_or_default(),
+            parent_pid: pid("ParentProcessId"),
+            parent_guid: value("ParentProcessGuid"),
+            parent_image: value("ParentImage").unwrap_or_default(),
+            ..Default::default()
+        },
+        ("Security", "4689") | (SYSMON_CHANNEL, "5") => {
+            return Some(ProcessEvent::Terminate {
+                pid: pid("ProcessId")?,
+                guid: value("ProcessGuid"),
+                computer,
+                time,
+            });
+        }
+        _ => return None,
+    };
+    Some(ProcessEvent::Create(ProcessNode {
+        computer,
+        start: time,
+        ..node
+    }))
+}
+
+/// Returns a non-empty event field as a string, reading numbers as their decimal text.
+fn event_string(rec: &Value, alias: &EventKeyAliasConfig, key: &str) -> Option<CompactString> {
+    utils::get_event_value(key, rec, alias)
+        .and_then(|value| utils::get_serde_number_to_string(value, false))
+        .map(|value| CompactString::from(value.trim().trim_matches('"')))
+        .filter(|value| !value.is_empty() && value.as_str() != "-")
+}
+
+/// Parses a PID logged either in hex (Security, e.g. `0x1a2c`) or in decimal (Sysmon).
+fn parse_pid(value: &str) -> Option<u64> {
+    match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit(['\\', '/']).next().unwrap_or(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use nested::Nested;
+
+    use super::{ProcessKey, ProcessTree, parse_pid};
+    use crate::detections::configs::{Action, Config, ProcessTreeOption, StoredStatic};
+    use crate::detections::detection::EvtxRecordInfo;
+    use crate::detections::message::DetectInfo;
+    use crate::detections::utils::create_rec_info;
+    use crate::options::profile::Profile;
+
+    fn event(
+        stored_static: &StoredStatic,
+        channel: &str,
+        event_id: i64,
+        time: &str,
+        event_data: &str,
+    ) -> EvtxRecordInfo {
+        let record = format!(
+            r#"{{
+                "Event": {{
+                    "System": {{
+                        "EventID": {event_id},
+                        "Channel": "{channel}",
+                        "Computer": "PC01",
+                        "TimeCreated_attributes": {{"SystemTime": "{time}"}}
+                    }},
+                    "EventData": {event_data}
+                }}
+            }}"#
+        );
+        create_rec_info(
+            serde_json::from_str(&record).unwrap(),
+            "testpath".to_string(),
+            &Nested::<String>::new(),
+            &false,
+            &false,
+            &stored_static.eventkey_alias,
+        )
+    }
+
+    #[test]
+    fn test_parse_pid() {
+        assert_eq!(parse_pid("0x1a2c"), Some(0x1a2c));
+        assert_eq!(parse_pid("6700"), Some(6700));
+        assert_eq!(parse_pid("-"), None);
+    }
+
+    #[test]
+    fn test_process_tree_handles_pid_reuse() {
+        let stored_static = StoredStatic::create_static_data(Config {
+            action: Some(Action::ProcessTree(ProcessTreeOption::default())),
+            debug: false,
+        });
+        let creation = |time: &str, pid: &str, parent_pid: &str, image: &str| {
+            event(
+                &stored_static,
+                "Security",
+                4688,
+                time,
+                &format!(
+                    r#"{{"NewProcessId": "{pid}", "ProcessId": "{parent_pid}", "NewProcessName": "C:\\Windows\\{image}", "ParentProcessName": "C:\\Windows\\explorer.exe"}}"#
+                ),
+            )
+        };
+        let records = vec![
+            creation("2024-01-01T00:00:00Z", "0x100", "0x10", "cmd.exe"),
+            creation("2024-01-01T00:01:00Z", "0x200", "0x100", "whoami.exe"),
+            event(
+                &stored_static,
+                "Security",
+                4689,
+                "2024-01-01T00:02:00Z",
+                r#"{"ProcessId": "0x100", "ProcessName": "C:\\Windows\\cmd.exe"}"#,
+            ),
+            // PID 0x100 is reused by a new process, so its child must not be linked to cmd.exe.
+            creation("2024-01-01T00:03:00Z", "0x100", "0x10", "wscript.exe"),
+            creation("2024-01-01T00:04:00Z", "0x300", "0x100", "powershell.exe"),
+        ];
+        let mut tree = ProcessTree::default();
+        tree.update(&records, &stored_static);
+
+        assert_eq!(tree.nodes.len(), 4);
+        assert!(tree.nodes[0].end.is_some());
+        assert_eq!(
+            tree.ancestry_chain(1),
+            "explorer.exe > cmd.exe > whoami.exe"
+        );
+        assert_eq!(
+            tree.ancestry_chain(3),
+            "explorer.exe > wscript.exe > powershell.exe"
+        );
+        let (roots, children) = tree.roots_and_children();
+        assert_eq!(roots.len(), 1);
+        assert_eq!(roots[0].1, vec![0, 2]);
+        assert_eq!(children[2], vec![3]);
+    }
+
+    #[test]
+    fn test_process_tree_merges_sysmon_and_security() {
+        let stored_static = StoredStatic::create_static_data(Config {
+            action: Some(Action::ProcessTree(ProcessTreeOption::default())),
+            debug: false,
+        });
+        let records = vec![
+            event(
+                &stored_static,
+                "Microsoft-Windows-Sysmon/Operational",
+                1,
+                "2024-01-01T00:00:00Z",
+                r#"{"ProcessId": 256, "ProcessGuid": "{A}", "Image": "C:\\Windows\\cmd.exe", "ParentProcessGuid": "{P}", "ParentProcessId": 16, "ParentImage": "C:\\Windows\\explorer.exe"}"#,
+            ),
+            event(
+                &stored_static,
+                "Security",
+                4688,
+                "2024-01-01T00:00:01Z",
+                r#"{"NewProcessId": "0x100", "ProcessId": "0x10", "NewProcessName": "C:\\Windows\\cmd.exe", "CommandLine": "cmd.exe /c whoami"}"#,
+            ),
+            event(
+                &stored_static,
+                "Microsoft-Windows-Sysmon/Operational",
+                1,
+                "2024-01-01T00:00:02Z",
+                r#"{"ProcessId": 512, "ProcessGuid": "{B}", "Image": "C:\\Windows\\whoami.exe", "ParentProcessGuid": "{A}", "ParentProcessId": 256}"#,
+            ),
+        ];
+        let mut tree = ProcessTree::default();
+        tree.update(&records, &stored_static);
+
+        assert_eq!(tree.nodes.len(), 2);
+        assert_eq!(tree.nodes[0].command_line, "cmd.exe /c whoami");
+        let key =
+            ProcessKey::from_record(&records[2].record, &stored_static.eventkey_alias).unwrap();
+        assert_eq!(
+            tree.key_ancestry(&key),
+            Some("explorer.exe > cmd.exe > whoami.exe".to_string())
+        );
+    }
+
+    #[test]
+    fn test_resolve_ancestries_after_scan() {
+        let stored_static = StoredStatic::create_static_data(Config {
+            action: Some(Action::ProcessTree(ProcessTreeOption::default())),
+            debug: false,
+        });
+        let child = event(
+            &stored_static,
+            "Security",
+            4688,
+            "2024-01-01T00:00:05Z",
+            r#"{"NewProcessId": "0x200", "ProcessId": "0x100", "NewProcessName": "C:\\Windows\\whoami.exe"}"#,
+        );
+        let parent = event(
+            &stored_static,
+            "Security",
+            4688,
+            "2024-01-01T00:00:00Z",
+            r#"{"NewProcessId": "0x100", "ProcessId": "0x10", "NewProcessName": "C:\\Windows\\cmd.exe", "ParentProcessName": "C:\\Windows\\explorer.exe"}"#,
+        );
+        // The detection is made on the child before the file with its parent is scanned.
+        let mut tree = ProcessTree::default();
+        tree.update(std::slice::from_ref(&child), &stored_static);
+        let mut detect_infos = vec![DetectInfo {
+            output_fields: vec![("Ancestry".into(), Profile::ProcessAncestry("-".into()))],
+            process_key: ProcessKey::from_record(&child.record, &stored_static.eventkey_alias),
+            ..Default::default()
+        }];
+        tree.update(&[parent], &stored_static);
+        tree.resolve_ancestries(&mut detect_infos);
+        assert_eq!(
+            detect_infos[0].output_fields[0].1.to_value(),
+            "explorer.exe > cmd.exe > whoami.exe"
+        );
+    }
+}
@@ -0,0 +1,303 @@
+use chrono::{DateTime, Utc};
+use compact_str::CompactString;
+use hashbrown::{HashMap, HashSet};
+use itertools::Itertools;
+use serde_json::Value;
+
+use crate::detections::configs::{EventKeyAliasConfig, StoredStatic};
+use crate::detections::detection::EvtxRecordInfo;
+use crate::detections::utils;
+use crate::timeline::metrics::{get_event_value_as_string, get_record_timestamp};
+
+const SYSMON_CHANNEL: &str = "Microsoft-Windows-Sysmon/Operational";
+
+/// Kinds of artifacts that show up when something new runs or is installed on a host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ArtifactKind {
+    Process,
+    Service,
+    Task,
+    Driver,
+}
+
+impl ArtifactKind {
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            ArtifactKind::Process => "Process",
+            ArtifactKind::Service => "Service",
+            ArtifactKind::Task => "Scheduled Task",
+            ArtifactKind::Driver => "Driver",
+        }
+    }
+}
+
+/// Identifies an artifact across hosts. `name` and `details` are lowercased so that the same
+/// path logged with different casing is counted once.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ArtifactKey {
+    pub kind: ArtifactKind,
+    pub name: CompactString,
+    pub details: CompactString,
+}
+
+/// Where and when an artifact was seen. `name` and `details` keep the casing of the first event.
+/// `details` is the binary of a service, the command of a scheduled task and the signer of a
+/// driver, and is empty for processes.
+#[derive(Debug, Clone, Default)]
+pub struct ArtifactStats {
+    pub name: CompactString,
+    pub details: CompactString,
+    pub first_seen: Option<DateTime<Utc>>,
+    pub first_host: CompactString,
+    pub hosts: HashSet<CompactString>,
+    pub hashes: HashSet<CompactString>,
+    pub count: usize,
+}
+
+/// Process images (4688, Sysmon 1), service installs (7045, 4697), scheduled tasks (4698) and
+/// driver loads (Sysmon 6) collected for the rare-artifacts command, together with every computer
+/// seen in the logs so that the prevalence of an artifact can be given for the whole fleet.
+#[derive(Debug, Clone, Default)]
+pub struct RareArtifacts {
+    pub artifacts: HashMap<ArtifactKey, ArtifactStats>,
+    pub computers: HashSet<CompactString>,
+}
+
+impl RareArtifacts {
+    pub fn update(&mut self, records: &[EvtxRecordInfo], stored_static: &StoredStatic) {
+        let alias = &stored_static.eventkey_alias;
+        for record in records {
+            let rec = &record.record;
+            if utils::is_filtered_by_computer_name(
+                utils::get_event_value("Event.System.Computer", rec, alias),
+                (
+                    &stored_static.include_computer,
+                    &stored_static.exclude_computer,
+                ),
+            ) {
+                continue;
+            }
+            let computer = get_event_value_as_string("Computer", rec, alias);
+            self.computers.insert(computer.clone());
+            let Some((kind, name, details, hashes)) = extract_artifact(rec, alias) else {
+                continue;
+            };
+            let key = ArtifactKey {
+                kind,
+                name: name.to_lowercase().into(),
+                details: details.to_lowercase().into(),
+            };
+            let stats = self.artifacts.entry(key).or_insert_with(|| ArtifactStats {
+                name,
+                details,
+                ..Default::default()
+            });
+            let timestamp = get_record_timestamp(rec, alias);
+            if stats.first_host.is_empty()
+                || timestamp.is_some_and(|time| stats.first_seen.is_none_or(|first| time < first))
+            {
+                stats.first_seen = timestamp;
+                stats.first_host = computer.clone();
+            }
+            stats.hosts.insert(computer);
+            stats.hashes.extend(hashes);
+            stats.count += 1;
+        }
+    }
+
+    /// Returns the artifacts from the rarest to the most common across the fleet: by the number
+    /// of hosts, then the number of events, then the first-seen time.
+    pub fn sorted_by_rarity(&self) -> Vec<(&ArtifactKey, &ArtifactStats)> {
+        self.artifacts
+            .iter()
+            .sorted_by(|(a_key, a), (b_key, b)| {
+                a.hosts
+                    .len()
+                    .cmp(&b.hosts.len())
+                    .then_with(|| a.count.cmp(&b.count))
+                    .then_with(|| match (a.first_seen, b.first_seen) {
+                        (Some(a_time), Some(b_time)) => a_time.cmp(&b_time),
+                        (a_time, b_time) => b_time.is_some().cmp(&a_time.is_some()),
+                    })
+                    .then_with(|| a_key.cmp(b_key))
+            })
+            .collect()
+    }
+}
+
+/// Returns the kind, name, details and hashes of the artifact that the record logs, if any.
+fn extract_artifact(
+    rec: &Value,
+    alias: &EventKeyAliasConfig,
+) -> Option<(
+    ArtifactKind,
+    CompactString,
+    CompactString,
+    Option<CompactString>,
+)> {
+    let value = |key: &str| {
+        utils::get_event_value(key, rec, alias)
+            .and_then(Value::as_str)
+            .map(str::trim)
+            .filter(|value| !value.is_empty() && *value != "-")
+            .map(CompactString::from)
+    };
+    let channel = get_event_value_as_string("Channel", rec, alias);
+    let event_id = get_event_value_as_string("EventID", rec, alias);
+    let (kind, name, details, hashes) = match (channel.as_str(), event_id.as_str()) {
+        ("Security", "4688") => (ArtifactKind::Process, value("NewProcessName")?, None, None),
+        (SYSMON_CHANNEL, "1") => (
+            ArtifactKind::Process,
+            value("Image")?,
+            None,
+            value("Hashes"),
+        ),
+        ("System", "7045") => (
+            ArtifactKind::Service,
+            value("ServiceName")?,
+            value("ImagePath"),
+            None,
+        ),
+        ("Security", "4697") => (
+            ArtifactKind::Service,
+            value("ServiceName")?,
+            value("ServiceFileName"),
+            None,
+        ),
+        ("Security", "4698") => (
+            ArtifactKind::Task,
+            value("TaskName")?,
+            value("TaskContent").and_then(|content| task_command(&content)),
+            None,
+        ),
+        (SYSMON_CHANNEL, "6") => (
+            ArtifactKind::Driver,
+            value("ImageLoaded")?,
+            value("Signature"),
+            value("Hashes"),
+        ),
+        _ => return None,
+    };
+    Some((kind, name, details.unwrap_or_default(), hashes))
+}
+
+/// Returns the command and arguments of the first exec action in the XML of a 4698 event.
+fn task_command(content: &str) -> Option<CompactString> {
+    let element = |name: &str| {
+        let start = content.find(&format!("<{name}>"))? + name.len() + 2;
+        let end = start + content[start..].find(&format!("</{name}>"))?;
+        Some(content[start..end].trim())
+    };
+    let command = element("Command")?;
+    match element("Arguments") {
+        Some(arguments) if !arguments.is_empty() => Some(format!("{command} {arguments}").into()),
+        _ => Some(command.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nested::Nested;
+
+    use super::{ArtifactKind, RareArtifacts, task_command};
+    use crate::detections::configs::{Action, Config, RareArtifactsOption, StoredStatic};
+    use crate::detections::detection::EvtxRecordInfo;
+    use crate::detections::utils::create_rec_info;
+
+    fn event(
+        stored_static: &StoredStatic,
+        computer: &str,
+        channel: &str,
+        event_id: i64,
+        time: &str,
+        event_data: &str,
+    ) -> EvtxRecordInfo {
+        let record = format!(
+            r#"{{
+                "Event": {{
+                    "System": {{
+                        "EventID": {event_id},
+                        "Channel": "{channel}",
+                        "Computer": "{computer}",
+                        "TimeCreated_attributes": {{"SystemTime": "{time}"}}
+                    }},
+                    "EventData": {event_data}
+                }}
+            }}"#
+        );
+        create_rec_info(
+            serde_json::from_str(&record).unwrap(),
+            "testpath".to_string(),
+            &Nested::<String>::new(),
+            &false,
+            &false,
+            &stored_static.eventkey_alias,
+        )
+    }
+
+    #[test]
+    fn test_rare_artifacts_sorted_by_rarity() {
+        let stored_static = StoredStatic::create_static_data(Config {
+            action: Some(Action::RareArtifacts(RareArtifactsOption::default())),
+            debug: false,
+        });
+        let mut records = vec![];
+        for (computer, time) in [
+            ("PC01", "2024-01-01T00:00:00Z"),
+            ("PC02", "2024-01-01T01:00:00Z"),
+        ] {
+            records.push(event(
+                &stored_static,
+                computer,
+                "Security",
+                4688,
+                time,
+                r#"{"NewProcessName": "C:\\Windows\\System32\\cmd.exe"}"#,
+            ));
+        }
+        records.push(event(
+            &stored_static,
+            "PC02",
+            "Microsoft-Windows-Sysmon/Operational",
+            1,
+            "2024-01-01T03:00:00Z",
+            r#"{"Image": "C:\\WINDOWS\\System32\\CMD.EXE", "Hashes": "SHA256=ABCD"}"#,
+        ));
+        records.push(event(
+            &stored_static,
+            "PC02",
+            "System",
+            7045,
+            "2024-01-01T02:00:00Z",
+            r#"{"ServiceName": "PSEXESVC", "ImagePath": "%SystemRoot%\\PSEXESVC.exe"}"#,
+        ));
+        let mut artifacts = RareArtifacts::default();
+        artifacts.update(&records, &stored_static);
+
+        assert_eq!(artifacts.computers.len(), 2);
+        let sorted = artifacts.sorted_by_rarity();
+        assert_eq!(sorted.len(), 2);
+        let (key, stats) = sorted[0];
+        assert_eq!(key.kind, ArtifactKind::Service);
+        assert_eq!(stats.name, "PSEXESVC");
+        assert_eq!(stats.details, "%SystemRoot%\\PSEXESVC.exe");
+        assert_eq!(stats.hosts.len(), 1);
+        let (key, stats) = sorted[1];
+        assert_eq!(key.kind, ArtifactKind::Process);
+        assert_eq!(stats.name, "C:\\Windows\\System32\\cmd.exe");
+        assert_eq!(stats.hosts.len(), 2);
+        assert_eq!(stats.count, 3);
+        assert_eq!(stats.first_host, "PC01");
+        assert!(stats.hashes.contains("SHA256=ABCD"));
+    }
+
+    #[test]
+    fn test_task_command() {
+        let content = "<Task><Actions Context=\"Author\"><Exec><Command>C:\\Users\\Public\\a.exe</Command><Arguments>-q</Arguments></Exec></Actions></Task>";
+        assert_eq!(
+            task_command(content).unwrap(),
+            "C:\\Users\\Public\\a.exe -q"
+        );
+        assert_eq!(task_command("<Task></Task>"), None);
+    }
+}
@@ -1,10 +1,11 @@
-use crate::detections::configs::{Action, EventInfoConfig, StoredStatic};
+use crate::detections::configs::{Action, EventInfoConfig, ProcessTreeFormat, StoredStatic};
 use crate::detections::detection::EvtxRecordInfo;
 use crate::detections::message::AlertMessage;
 use crate::detections::utils::{
     self, get_writable_color, make_ascii_titlecase, write_color_buffer,
 };
 use crate::timeline::search::search_result_dsp_msg;
+use chrono::{DateTime, Utc};
 use comfy_table::ColumnConstraint::LowerBoundary;
 use comfy_table::ColumnConstraint::UpperBoundary;
 use comfy_table::Width::Fixed;
@@ -17,19 +18,30 @@ use downcast_rs::__std::process;
 use nested::Nested;
 use num_format::{Locale, ToFormattedString};
 use std::cmp;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::BufWriter;
 use std::path::PathBuf;
 use termcolor::{BufferWriter, Color, ColorChoice};
 use terminal_size::Width;
 use terminal_size::terminal_size;
 
+use super::auth_analysis::DEFAULT_SPN_THRESHOLD;
 use super::computer_metrics;
 use super::metrics::{EventMetrics, LoginEvent, LogonStats};
 use super::search::EventSearch;
 use crate::timeline::config_critical_systems::ConfigCriticalSystems;
+use crate::timeline::convert::EventConverter;
+use crate::timeline::coverage::{HostCoverage, RuleCoverage};
+use crate::timeline::event_anomalies::anomaly_settings;
 use crate::timeline::extract_base64::{output_all, process_evtx_record_infos};
+use crate::timeline::lateral_movement::LateralMovementGraph;
+use crate::timeline::log_integrity::{
+    IntegrityEvidence, integrity_gap_threshold, integrity_output_path, integrity_results,
+};
 use crate::timeline::log_metrics::LogMetrics;
+use crate::timeline::logon_sessions::LogonSessions;
+use crate::timeline::process_tree::ProcessTree;
+use crate::timeline::rare_artifacts::RareArtifacts;
 use hashbrown::HashSet;
 use itertools::Itertools;
 
@@ -76,12 +88,13 @@ fn logon_summary_row_order(
         .then_with(|| x_key.cmp(y_key))
 }
 
-/// Aggregated state for the non-detection commands (eid-metrics, logon-summary, log-metrics,
-/// search, extract-base64, config-critical-systems, computer-metrics). Records are fed in
-/// incrementally via `start()` (except for computer-metrics, which fills `stats.stats_computer`
-/// via `computer_metrics::countup_event_by_computer()`) and the collected results are rendered
-/// later by the `*_dsp_msg` methods. The detection commands csv-timeline/json-timeline also use
-/// this struct to track the total record count and the first/last event timestamps.
+/// Aggregated state for the non-detection commands (eid-metrics, event-anomalies, logon-summary,
+/// log-metrics, process-tree, rare-artifacts, search, sessions, coverage, convert, extract-base64,
+/// config-critical-systems, computer-metrics). Records are fed in incrementally via `start()`
+/// (except for computer-metrics, which fills `stats.stats_computer` via
+/// `computer_metrics::countup_event_by_computer()`) and the collected results are rendered later by
+/// the `*_dsp_msg` methods. The detection commands csv-timeline/json-timeline also use this struct
+/// to track the total record count and the first/last event timestamps.
 #[derive(Debug, Clone)]
 pub struct Timeline {
     pub total_record_cnt: usize,
@@ -89,6 +102,11 @@ pub struct Timeline {
     pub event_search: EventSearch,
     pub extracted_base64_records: Vec<Vec<String>>,
     pub config_critical_systems: ConfigCriticalSystems,
+    pub rare_artifacts: RareArtifacts,
+    pub process_tree: ProcessTree,
+    pub logon_sessions: LogonSessions,
+    pub coverage: RuleCoverage,
+    pub converter: EventConverter,
 }
 
 impl Default for Timeline {
@@ -108,6 +126,11 @@ impl Timeline {
             event_search: search,
             extracted_base64_records: vec![],
             config_critical_systems,
+            rare_artifacts: RareArtifacts::default(),
+            process_tree: ProcessTree::default(),
+            logon_sessions: LogonSessions::default(),
+            coverage: RuleCoverage::default(),
+            converter: EventConverter::default(),
         }
     }
 
@@ -128,8 +151,18 @@ impl Timeline {
             self.stats.logon_stats_start(records, stored_static);
         } else if stored_static.log_metrics_flag {
             self.stats.logfile_stats_start(records, stored_static);
+        } else if stored_static.event_anomalies_flag {
+            self.stats.event_rate_stats_start(records, stored_static);
+        } else if stored_static.rare_artifacts_flag {
+            self.rare_artifacts.update(records, stored_static);
+        } else if stored_static.process_tree_flag {
+            self.process_tree.update(records, stored_static);
         } else if stored_static.search_flag {
             self.event_search.search_start(records, stored_static);
+        } else if stored_static.sessions_flag {
+            self.logon_sessions.update(records, stored_static);
+        } else if stored_static.coverage_flag {
+            self.coverage.update(records, stored_static);
         } else if stored_static.extract_base64_flag {
             if let Action::ExtractBase64(opt) = &stored_static.config.action.as_ref().unwrap() {
                 let records = process_evtx_record_infos(records, &opt.time_format_options);
@@ -144,12 +177,46 @@ impl Timeline {
             Action::DfirTimeline(_)
         ) {
             self.stats.stats_time_cnt(records, stored_static);
+            // The %ProcessAncestry% and %Session*% columns are filled in from these after the scan,
+            // so processes, logons and logoffs in later files are also found.
+            if let Some(process_tree) = &stored_static.process_tree {
+                process_tree.write().unwrap().update(records, stored_static);
+            }
+            if let Some(logon_sessions) = &stored_static.logon_sessions {
+                logon_sessions
+                    .write()
+                    .unwrap()
+                    .update(records, stored_static);
+            }
         }
     }
 
     /// Output the computers found by the config-critical-systems command, grouped by system type.
-    pub fn config_critical_systems_dsp_msg(&mut self, no_color: bool) {
-        self.config_critical_systems.output_computers(no_color);
+    /// With -o they are saved to a JSON file instead of prompting to update critical_systems.txt,
+    /// and with --min-confidence the confident ones are added to critical_systems.txt directly.
+    pub fn config_critical_systems_dsp_msg(&mut self, stored_static: &StoredStatic) {
+        if let Some(path) = &stored_static.output_path
+            && let Err(err) = self.config_critical_systems.output_json(path)
+        {
+            AlertMessage::alert(&format!("Failed to open file. {err}")).ok();
+            process::exit(1);
+        }
+        let min_confidence = match &stored_static.config.action {
+            Some(Action::ConfigCriticalSystems(opt)) => opt.min_confidence,
+            _ => None,
+        };
+        if let Some(min_confidence) = min_confidence {
+            if let Err(err) = self
+                .config_critical_systems
+                .add_confident_computers(min_confidence, stored_static.common_options.no_color)
+            {
+                AlertMessage::alert(&format!("Failed to open file. {err}")).ok();
+                process::exit(1);
+            }
+        } else if stored_static.output_path.is_none() {
+            self.config_critical_systems
+                .output_computers(stored_static.common_options.no_color);
+        }
     }
 
     /// Function to output the statistics message for the eid-metrics command.
@@ -369,6 +436,21 @@ impl Timeline {
                 stored_static.common_options.no_color,
                 stored_static,
             );
+            self.tm_auth_findings_dsp_msg(
+                &logon_summary_option.output,
+                logon_summary_option
+                    .spn_threshold
+                    .unwrap_or(DEFAULT_SPN_THRESHOLD),
+                stored_static,
+            );
+
+            if let Some(graph_path) = &logon_summary_option.graph_output {
+                let graph = LateralMovementGraph::new(&self.stats.stats_login_list);
+                if let Err(err) = graph.output(graph_path, logon_summary_option.graph_format) {
+                    AlertMessage::alert(&format!("Failed to open file. {err}")).ok();
+                    process::exit(1);
+                }
+            }
         }
     }
 
@@ -595,6 +677,95 @@ impl Timeline {
         }
     }
 
+    /// Output the Kerberos/NTLM authentication findings of the logon-summary command. With -o they
+    /// are saved to `<prefix>-authentication.csv` instead of being printed.
+    fn tm_auth_findings_dsp_msg(
+        &self,
+        output: &Option<PathBuf>,
+        spn_threshold: usize,
+        stored_static: &StoredStatic,
+    ) {
+        let no_color = stored_static.common_options.no_color;
+        let header = vec![
+            "Finding", "Count", "First", "Last", "Account", "Target", "Source", "Computer",
+            "Details",
+        ];
+        let csv_target;
+        let mut wtr = if let Some(csv_path) = output {
+            let file_name = csv_path.as_path().display().to_string() + "-authentication.csv";
+            match File::create(file_name) {
+                Ok(file) => {
+                    csv_target = Box::new(BufWriter::new(file));
+                    Some(WriterBuilder::new().from_writer(csv_target))
+                }
+                Err(err) => {
+                    AlertMessage::alert(&format!("Failed to open file. {err}")).ok();
+                    process::exit(1);
+                }
+            }
+        } else {
+            println!("\n\n");
+            write_color_buffer(
+                &BufferWriter::stdout(ColorChoice::Always),
+                get_writable_color(Some(Color::Rgb(0, 255, 0)), no_color),
+                "Authentication Findings:",
+                true,
+            )
+            .ok();
+            write_color_buffer(&BufferWriter::stdout(ColorChoice::Always), None, "", false).ok();
+            None
+        };
+        if let Some(ref mut writer) = wtr {
+            writer.write_record(&header).ok();
+        }
+
+        let mut auth_tb = Table::new();
+        auth_tb
+            .load_preset(UTF8_FULL)
+            .apply_modifier(UTF8_ROUND_CORNERS);
+        // The Details column can list many service names, so it is only written to the CSV.
+        auth_tb.set_header(&header[..8]);
+        let tfo = &stored_static
+            .output_option
+            .as_ref()
+            .unwrap()
+            .time_format_options;
+        let format_ts = |ts: Option<DateTime<Utc>>| match ts {
+            Some(timestamp) => utils::format_time(&timestamp, false, tfo).to_string(),
+            None => "-".to_string(),
+        };
+        for ((kind, account, target, source, computer), occurrence) in
+            self.stats.stats_auth.findings(spn_threshold)
+        {
+            let count = occurrence.count.to_string();
+            let first = format_ts(occurrence.first);
+            let last = format_ts(occurrence.last);
+            let details = occurrence.details.iter().join(" ¦ ");
+            let record_data = vec![
+                kind.to_str(),
+                count.as_str(),
+                first.as_str(),
+                last.as_str(),
+                account.as_str(),
+                target.as_str(),
+                source.as_str(),
+                computer.as_str(),
+                details.as_str(),
+            ];
+            if let Some(ref mut writer) = wtr {
+                writer.write_record(&record_data).ok();
+            }
+            auth_tb.add_row(&record_data[..8]);
+        }
+        if output.is_none() {
+            if auth_tb.row_iter().len() == 0 {
+                println!(" No suspicious Kerberos or NTLM authentication was detected.");
+            } else {
+                println!("{auth_tb}");
+            }
+        }
+    }
+
     /// Output search results.
     pub fn search_dsp_msg(&mut self, stored_static: &StoredStatic) {
         if let Action::Search(search_summary_option) =
@@ -622,6 +793,7 @@ impl Timeline {
                 computer_metrics::computer_metrics_dsp_msg(
                     &self.stats.stats_computer,
                     &computer_metrics_option.output,
+                    computer_metrics_option.output_type,
                 );
                 write_color_buffer(
                     &BufferWriter::stdout(ColorChoice::Always),
@@ -654,7 +826,9 @@ impl Timeline {
         if let Action::LogMetrics(opt) = &stored_static.config.action.as_ref().unwrap() {
             let log_metrics = &mut self.stats.stats_logfile;
             log_metrics.sort_by(|a, b| a.event_count.cmp(&b.event_count).reverse());
-            let header = vec![
+            let integrity = integrity_gap_threshold(stored_static)
+                .map(|gap_threshold| integrity_results(log_metrics, gap_threshold));
+            let mut header = vec![
                 "Filename",
                 "Computers",
                 "Events",
@@ -664,12 +838,41 @@ impl Timeline {
                 "Providers",
                 "Size",
             ];
+            if integrity.is_some() {
+                header.push("Integrity");
+            }
+            let sep = if opt.output.is_some() { " ¦" } else { "\n" };
+            let mut rows = vec![];
+            let mut evidence_rows = vec![];
+            for (i, rec) in log_metrics.iter().enumerate() {
+                let Some(row) = Self::create_record_array(rec, stored_static, sep) else {
+                    continue;
+                };
+                let mut row = row.to_vec();
+                if let Some((verdict, evidence)) = integrity.as_ref().map(|results| &results[i]) {
+                    row.push(verdict.to_str().to_string());
+                    evidence_rows.extend(
+                        evidence.iter().map(|ev| {
+                            Self::create_evidence_array(&rec.filename, ev, stored_static)
+                        }),
+                    );
+                }
+                rows.push(row);
+            }
+            let evidence_header = vec!["Filename", "Verdict", "Type", "Timestamp", "Details"];
             if let Some(path) = &opt.output {
                 let file = File::create(path).expect("Failed to create output file");
                 let mut wrt = WriterBuilder::new().from_writer(file);
-                let _ = wrt.write_record(header);
-                for rec in &mut *log_metrics {
-                    if let Some(row) = Self::create_record_array(rec, stored_static, " ¦") {
+                let _ = wrt.write_record(&header);
+                for row in &rows {
+                    let _ = wrt.write_record(row);
+                }
+                if integrity.is_some() {
+                    let file = File::create(integrity_output_path(path))
+                        .expect("Failed to create output file");
+                    let mut wrt = WriterBuilder::new().from_writer(file);
+                    let _ = wrt.write_record(&evidence_header);
+                    for row in &evidence_rows {
                         let _ = wrt.write_record(row);
                     }
                 }
@@ -679,26 +882,448 @@ impl Timeline {
                     .apply_modifier(UTF8_ROUND_CORNERS)
                     .set_content_arrangement(ContentArrangement::DynamicFullWidth)
                     .set_header(&header);
-                for rec in &mut *log_metrics {
-                    if let Some(row) = Self::create_record_array(rec, stored_static, "\n") {
-                        tb.add_row(vec![
-                            Cell::new(row[0].to_string()),
-                            Cell::new(row[1].to_string()),
-                            Cell::new(row[2].to_string()),
-                            Cell::new(row[3].to_string()),
-                            Cell::new(row[4].to_string()),
-                            Cell::new(row[5].to_string()),
-                            Cell::new(row[6].to_string()),
-                            Cell::new(row[7].to_string()),
-                        ]);
-                    }
+                for row in rows {
+                    tb.add_row(row.into_iter().map(Cell::new).collect::<Vec<_>>());
                 }
                 if log_metrics.is_empty() {
                     println!("No matches found.");
                 } else {
                     println!("{tb}");
                 }
+                if integrity.is_some() && !log_metrics.is_empty() {
+                    if evidence_rows.is_empty() {
+                        println!("No integrity issues found.");
+                    } else {
+                        let mut tb = Table::new();
+                        tb.load_preset(UTF8_FULL)
+                            .apply_modifier(UTF8_ROUND_CORNERS)
+                            .set_content_arrangement(ContentArrangement::DynamicFullWidth)
+                            .set_header(&evidence_header);
+                        for row in evidence_rows {
+                            tb.add_row(row.into_iter().map(Cell::new).collect::<Vec<_>>());
+                        }
+                        println!();
+                        println!("Integrity evidence:");
+                        println!("{tb}");
+                    }
+                }
+            }
+        }
+    }
+
+    /// Builds one row of the log-metrics integrity evidence table.
+    fn create_evidence_array(
+        filename: &str,
+        evidence: &IntegrityEvidence,
+        stored_static: &StoredStatic,
+    ) -> [String; 5] {
+        let timestamp = evidence.timestamp.map_or("-".to_string(), |ts| {
+            utils::format_time(
+                &ts,
+                false,
+                &stored_static
+                    .output_option
+                    .as_ref()
+                    .unwrap()
+                    .time_format_options,
+            )
+            .into()
+        });
+        [
+            filename.to_string(),
+            evidence.verdict.to_str().to_string(),
+            evidence.kind.to_str().to_string(),
+            timestamp,
+            evidence.details.clone(),
+        ]
+    }
+
+    /// Output the spikes and silences found by the event-anomalies command, in start time order.
+    /// With -o they are saved to a CSV file instead of printed as a table.
+    pub fn event_anomalies_dsp_msg(
+        &mut self,
+        event_timeline_config: &EventInfoConfig,
+        stored_static: &StoredStatic,
+    ) {
+        let Some(settings) = anomaly_settings(stored_static) else {
+            return;
+        };
+        let time_format_options = &stored_static
+            .output_option
+            .as_ref()
+            .unwrap()
+            .time_format_options;
+        let header = vec![
+            "Start", "End", "Computer", "Channel", "EventID", "Event", "Type", "Count", "Expected",
+            "Score",
+        ];
+        let rows = self
+            .stats
+            .stats_rates
+            .anomalies(&settings)
+            .into_iter()
+            .map(|anomaly| {
+                let event_title = event_timeline_config
+                    .get_event_id(&anomaly.key.channel.to_lowercase(), &anomaly.key.event_id)
+                    .map_or("Unknown".to_string(), |info| info.event_title.clone());
+                vec![
+                    utils::format_time(&anomaly.start, false, time_format_options).to_string(),
+                    utils::format_time(&anomaly.end, false, time_format_options).to_string(),
+                    anomaly.key.computer.to_string(),
+                    replace_channel_abbr(stored_static, &anomaly.key.channel)
+                        .trim()
+                        .to_string(),
+                    anomaly.key.event_id.to_string(),
+                    event_title,
+                    anomaly.kind.to_str().to_string(),
+                    anomaly.count.to_string(),
+                    format!("{:.1}", anomaly.expected),
+                    format!("{:.1}", anomaly.score),
+                ]
+            })
+            .collect_vec();
+        if let Some(path) = &stored_static.output_path {
+            let file = File::create(path).expect("Failed to create output file");
+            let mut wrt = WriterBuilder::new().from_writer(file);
+            let _ = wrt.write_record(&header);
+            for row in &rows {
+                let _ = wrt.write_record(row);
+            }
+        } else if rows.is_empty() {
+            println!("No anomalies found.");
+        } else {
+            let mut tb = Table::new();
+            tb.load_preset(UTF8_FULL)
+                .apply_modifier(UTF8_ROUND_CORNERS)
+                .set_content_arrangement(ContentArrangement::DynamicFullWidth)
+                .set_header(&header);
+            for row in rows {
+                tb.add_row(row.into_iter().map(Cell::new).collect::<Vec<_>>());
+            }
+            println!("{tb}");
+        }
+    }
+
+    /// Output the artifacts collected by the rare-artifacts command from the rarest to the most
+    /// common, with the number of computers seen in the logs as the fleet size.
+    pub fn rare_artifacts_dsp_msg(&mut self, stored_static: &StoredStatic) {
+        let Action::RareArtifacts(opt) = &stored_static.config.action.as_ref().unwrap() else {
+            return;
+        };
+        let time_format_options = &stored_static
+            .output_option
+            .as_ref()
+            .unwrap()
+            .time_format_options;
+        let fleet_size = self.rare_artifacts.computers.len();
+        let sep = if opt.output.is_some() { " ¦" } else { "\n" };
+        let header = vec![
+            "Type",
+            "Artifact",
+            "Details",
+            "Hosts",
+            "Prevalence",
+            "Events",
+            "First Seen",
+            "First Host",
+            "Hashes",
+        ];
+        let rows = self
+            .rare_artifacts
+            .sorted_by_rarity()
+            .into_iter()
+            .filter(|(_, stats)| opt.max_hosts.is_none_or(|max| stats.hosts.len() <= max))
+            .map(|(key, stats)| {
+                vec![
+                    key.kind.to_str().to_string(),
+                    stats.name.to_string(),
+                    stats.details.to_string(),
+                    stats.hosts.len().to_string(),
+                    format!(
+                        "{:.1}%",
+                        stats.hosts.len() as f64 * 100.0 / fleet_size.max(1) as f64
+                    ),
+                    stats.count.to_string(),
+                    stats.first_seen.map_or("-".to_string(), |time| {
+                        utils::format_time(&time, false, time_format_options).to_string()
+                    }),
+                    stats.first_host.to_string(),
+                    stats.hashes.iter().sorted().join(sep),
+                ]
+            })
+            .collect_vec();
+        if let Some(path) = &opt.output {
+            let file = File::create(path).expect("Failed to create output file");
+            let mut wrt = WriterBuilder::new().from_writer(file);
+            let _ = wrt.write_record(&header);
+            for row in &rows {
+                let _ = wrt.write_record(row);
+            }
+        } else if rows.is_empty() {
+            println!("No artifacts found.");
+        } else {
+            println!("Total computers: {fleet_size}");
+            let mut tb = Table::new();
+            tb.load_preset(UTF8_FULL)
+                .apply_modifier(UTF8_ROUND_CORNERS)
+                .set_content_arrangement(ContentArrangement::DynamicFullWidth)
+                .set_header(&header);
+            for row in rows {
+                tb.add_row(row.into_iter().map(Cell::new).collect::<Vec<_>>());
+            }
+            println!("{tb}");
+        }
+    }
+
+    /// Output the trees built by the process-tree command as text or JSON, to the -o file or to
+    /// the terminal.
+    pub fn process_tree_dsp_msg(&mut self, stored_static: &StoredStatic) {
+        let Action::ProcessTree(opt) = &stored_static.config.action.as_ref().unwrap() else {
+            return;
+        };
+        let time_format_options = &stored_static
+            .output_option
+            .as_ref()
+            .unwrap()
+            .time_format_options;
+        let format_time =
+            |time: &DateTime<Utc>| utils::format_time(time, false, time_format_options).to_string();
+        let output = match opt.output_type {
+            ProcessTreeFormat::Text if self.process_tree.nodes.is_empty() => {
+                "No process creation events found.".to_string()
+            }
+            ProcessTreeFormat::Text => self.process_tree.to_text(format_time),
+            ProcessTreeFormat::Json => {
+                serde_json::to_string_pretty(&self.process_tree.to_json(format_time)).unwrap()
+            }
+        };
+        if let Some(path) = &opt.output {
+            if let Err(err) = fs::write(path, output + "\n") {
+                AlertMessage::alert(&format!("Failed to write the process tree. {err}")).ok();
+                process::exit(1);
+            }
+        } else {
+            println!("{output}");
+        }
+    }
+
+    /// Output the logon sessions found by the sessions command in logon order, with the number of
+    /// events logged in each session per channel and event ID.
+    pub fn sessions_dsp_msg(&mut self, stored_static: &StoredStatic) {
+        let Action::Sessions(opt) = &stored_static.config.action.as_ref().unwrap() else {
+            return;
+        };
+        let time_format_options = &stored_static
+            .output_option
+            .as_ref()
+            .unwrap()
+            .time_format_options;
+        let sep = if opt.output.is_some() { " ¦" } else { "\n" };
+        let header = vec![
+            "Computer",
+            "User",
+            "Logon ID",
+            "Logon Type",
+            "Source IP",
+            "Source Computer",
+            "Logon Time",
+            "Logoff Time",
+            "Duration",
+            "Events",
+            "Activity",
+        ];
+        let rows = self
+            .logon_sessions
+            .sorted()
+            .into_iter()
+            .map(|session| {
+                vec![
+                    session.computer.to_string(),
+                    session.user.to_string(),
+                    session.logon_id.to_string(),
+                    session.logon_type.to_string(),
+                    session.source_ip.to_string(),
+                    session.source_computer.to_string(),
+                    utils::format_time(&session.logon_time, false, time_format_options).to_string(),
+                    session.logoff_time.map_or("-".to_string(), |time| {
+                        utils::format_time(&time, false, time_format_options).to_string()
+                    }),
+                    session.duration().unwrap_or_else(|| "-".to_string()),
+                    session.event_count().to_string(),
+                    session
+                        .activity
+                        .iter()
+                        .sorted_by(|(x_key, x_count), (y_key, y_count)| {
+                            y_count.cmp(x_count).then_with(|| x_key.cmp(y_key))
+                        })
+                        .map(|(key, count)| format!("{key}: {count}"))
+                        .join(sep),
+                ]
+            })
+            .collect_vec();
+        if let Some(path) = &opt.output {
+            let file = File::create(path).expect("Failed to create output file");
+            let mut wrt = WriterBuilder::new().from_writer(file);
+            let _ = wrt.write_record(&header);
+            for row in &rows {
+                let _ = wrt.write_record(row);
+            }
+        } else if rows.is_empty() {
+            println!("No sessions found.");
+        } else {
+            let mut tb = Table::new();
+            tb.load_preset(UTF8_FULL)
+                .apply_modifier(UTF8_ROUND_CORNERS)
+                .set_content_arrangement(ContentArrangement::DynamicFullWidth)
+                .set_header(&header);
+            for row in rows {
+                tb.add_row(row.into_iter().map(Cell::new).collect::<Vec<_>>());
+            }
+            println!("{tb}");
+        }
+    }
+
+    /// Output the files written by the convert command with the number of events in each.
+    pub fn convert_dsp_msg(&mut self, stored_static: &StoredStatic) {
+        let files = &self.converter.files;
+        let total = files.iter().map(|file| file.events).sum::<usize>();
+        write_color_buffer(
+            &BufferWriter::stdout(ColorChoice::Always),
+            get_writable_color(
+                Some(Color::Rgb(0, 255, 0)),
+                stored_static.common_options.no_color,
+            ),
+            "Converted events: ",
+            false,
+        )
+        .ok();
+        write_color_buffer(
+            &BufferWriter::stdout(ColorChoice::Always),
+            None,
+            &total.to_formatted_string(&Locale::en),
+            true,
+        )
+        .ok();
+        if files.is_empty() {
+            println!("No files were converted.");
+            return;
+        }
+        let mut tb = Table::new();
+        tb.load_preset(UTF8_FULL)
+            .apply_modifier(UTF8_ROUND_CORNERS)
+            .set_content_arrangement(ContentArrangement::DynamicFullWidth)
+            .set_header(["Event Log File", "Output File", "Events"]);
+        for file in files {
+            tb.add_row([
+                Cell::new(file.source.display()),
+                Cell::new(file.output.display()),
+                Cell::new(file.events.to_formatted_string(&Locale::en)),
+            ]);
+        }
+        println!("{tb}");
+    }
+
+    /// Output the rules, ATT&CK techniques and audit settings that each computer's logs leave
+    /// uncovered. With -o the report is saved to `<prefix>-hosts.csv` and
+    /// `<prefix>-blind-rules.csv` instead of being printed.
+    pub fn coverage_dsp_msg(&mut self, stored_static: &StoredStatic) {
+        let Action::Coverage(opt) = &stored_static.config.action.as_ref().unwrap() else {
+            return;
+        };
+        let report = self.coverage.report();
+        let rule_cnt = self.coverage.rules.len();
+        let header = vec![
+            "Computer",
+            "Rules",
+            "Blind Rules",
+            "Blind %",
+            "Blind Techniques",
+            "Audit Settings to Check",
+        ];
+        let host_row = |host: &HostCoverage, sep: &str, settings_limit: usize| {
+            let blind_percent = if rule_cnt == 0 {
+                0.0
+            } else {
+                host.blind_rules.len() as f64 / rule_cnt as f64 * 100.0
+            };
+            vec![
+                host.computer.to_string(),
+                rule_cnt.to_string(),
+                host.blind_rules.len().to_string(),
+                format!("{blind_percent:.1}%"),
+                host.blind_techniques.len().to_string(),
+                host.settings
+                    .iter()
+                    .take(settings_limit)
+                    .map(|(setting, cnt)| format!("{setting} ({cnt} rules)"))
+                    .join(sep),
+            ]
+        };
+        if let Some(path) = &opt.output {
+            let hosts_path = path.as_path().display().to_string() + "-hosts.csv";
+            let blind_rules_path = path.as_path().display().to_string() + "-blind-rules.csv";
+            let (Ok(hosts_file), Ok(blind_rules_file)) =
+                (File::create(&hosts_path), File::create(&blind_rules_path))
+            else {
+                AlertMessage::alert("Failed to open the coverage output files.").ok();
+                process::exit(1);
+            };
+            let mut hosts_wtr = WriterBuilder::new().from_writer(BufWriter::new(hosts_file));
+            hosts_wtr.write_record(&header).ok();
+            for host in &report {
+                hosts_wtr
+                    .write_record(host_row(host, " ¦ ", usize::MAX))
+                    .ok();
+            }
+            let mut rules_wtr = WriterBuilder::new().from_writer(BufWriter::new(blind_rules_file));
+            rules_wtr
+                .write_record([
+                    "Computer",
+                    "Rule Title",
+                    "Level",
+                    "Techniques",
+                    "Rule Path",
+                    "Missing Log Sources",
+                ])
+                .ok();
+            for host in &report {
+                for blind in &host.blind_rules {
+                    rules_wtr
+                        .write_record([
+                            host.computer,
+                            blind.rule.title.as_str(),
+                            blind.rule.level.as_str(),
+                            blind.rule.techniques.iter().join(" ¦ ").as_str(),
+                            blind.rule.path.as_str(),
+                            blind.missing.iter().join(" ¦ ").as_str(),
+                        ])
+                        .ok();
+                }
             }
+        } else if report.is_empty() {
+            println!("No events found.");
+        } else {
+            let mut tb = Table::new();
+            tb.load_preset(UTF8_FULL)
+                .apply_modifier(UTF8_ROUND_CORNERS)
+                .set_content_arrangement(ContentArrangement::DynamicFullWidth)
+                .set_header(&header);
+            for host in &report {
+                tb.add_row(
+                    host_row(host, "\n", 5)
+                        .into_iter()
+                        .map(Cell::new)
+                        .collect::<Vec<_>>(),
+                );
+            }
+            println!("{tb}");
+        }
+        if self.coverage.undetermined > 0 {
+            println!();
+            println!(
+                "{} rules were left out as their log sources could not be determined.",
+                self.coverage.undetermined.to_formatted_string(&Locale::en)
+            );
         }
     }
 
@@ -960,7 +1585,7 @@ mod tests {
         detections::{
             configs::{
                 Action, ClobberOption, CommonOptions, Config, DetectCommonOption, EidMetricsOption,
-                InputOption, LogonSummaryOption, StoredStatic, TimeRangeOption,
+                GraphFormat, InputOption, LogonSummaryOption, StoredStatic, TimeRangeOption,
             },
             utils::create_rec_info,
         },
@@ -983,6 +1608,7 @@ mod tests {
                     directory: None,
                     filepath: None,
                     live_analysis: false,
+                    carve: None,
                     recover_records: false,
                     time_offset: None,
                 },
@@ -1010,6 +1636,8 @@ mod tests {
                     us_military_time: false,
                     us_time: false,
                     utc: false,
+                    timezone: None,
+                    time_format: None,
                 },
                 output: None,
                 clobber_opt: ClobberOption { clobber: false },
@@ -1018,6 +1646,9 @@ mod tests {
                     start_timeline: None,
                 },
                 remove_duplicate_detections: false,
+                graph_output: None,
+                graph_format: GraphFormat::default(),
+                spn_threshold: None,
             }));
         dummy_stored_static.logon_summary_flag = true;
         let mut timeline = Timeline::default();
@@ -1242,6 +1873,7 @@ mod tests {
                     directory: None,
                     filepath: None,
                     live_analysis: false,
+                    carve: None,
                     recover_records: false,
                     time_offset: None,
                 },
@@ -1269,6 +1901,8 @@ mod tests {
                     us_military_time: false,
                     us_time: false,
                     utc: false,
+                    timezone: None,
+                    time_format: None,
                 },
                 output: Some(out_test_tm_stats_csv.clone()),
                 clobber_opt: ClobberOption { clobber: false },
@@ -1344,6 +1978,7 @@ mod tests {
                     directory: None,
                     filepath: Some(Path::new("./dummy.evtx").to_path_buf()),
                     live_analysis: false,
+                    carve: None,
                     recover_records: false,
                     time_offset: None,
                 },
@@ -1371,6 +2006,8 @@ mod tests {
                     us_military_time: false,
                     us_time: false,
                     utc: false,
+                    timezone: None,
+                    time_format: None,
                 },
                 output: Some(out_test_tm_logon_stats.clone()),
                 clobber_opt: ClobberOption { clobber: false },
@@ -1379,6 +2016,9 @@ mod tests {
                     start_timeline: None,
                 },
                 remove_duplicate_detections: false,
+                graph_output: None,
+                graph_format: GraphFormat::default(),
+                spn_threshold: None,
             }));
         dummy_stored_static.logon_summary_flag = true;
         let mut timeline = Timeline::default();